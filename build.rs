@@ -10,15 +10,60 @@ fn main() {
             buffer.extend_from_slice(b"  ")
         }
     }
+    // Only values with backslashes or newlines go through the slower _dump
+    // to be escaped, the others are printed as-is. Arrays are expanded in a
+    // way that an unset one, e.g. an arch-specific one, is fine with set -u
+    fn buffer_extend_dump_value(
+        buffer: &mut Vec<u8>, key: &[u8], var: &[u8], array: bool
+    ) {
+        let mut all = Vec::new();
+        if array {
+            all.extend_from_slice(b"${");
+            all.extend_from_slice(var);
+            all.extend_from_slice(b"[@]+\"${");
+            all.extend_from_slice(var);
+            all.extend_from_slice(b"[@]}\"}");
+        } else {
+            all.extend_from_slice(b"\"${");
+            all.extend_from_slice(var);
+            all.extend_from_slice(b"}\"");
+        }
+        buffer.extend_from_slice(b"if [[ \"${");
+        buffer.extend_from_slice(var);
+        buffer.extend_from_slice(if array { b"[*]-}" } else { b"}" });
+        buffer.extend_from_slice(b"\" == *[$'\\\\\\n']* ]]; then _dump ");
+        buffer.extend_from_slice(key);
+        buffer.push(b' ');
+        buffer.extend_from_slice(&all);
+        buffer.extend_from_slice(b"; else printf '");
+        buffer.extend_from_slice(key);
+        buffer.extend_from_slice(b":%s\\n' ");
+        buffer.extend_from_slice(&all);
+        buffer.extend_from_slice(b"; fi\n");
+    }
+    // Start every PKGBUILD from a clean slate, so nothing leaks in from the
+    // environment and all the dumped variables are set even with set -u
+    fn buffer_extend_init(
+        buffer: &mut Vec<u8>, prefix: &[u8], names: &[&[u8]], value: &[u8], 
+        indent_level: usize
+    ) {
+        buffer_extend_indent(buffer, indent_level);
+        for (id, name) in names.iter().enumerate() {
+            if id > 0 {
+                buffer.push(b' ');
+            }
+            buffer.extend_from_slice(prefix);
+            buffer.extend_from_slice(name);
+            buffer.push(b'=');
+            buffer.extend_from_slice(value);
+        }
+        buffer.push(b'\n');
+    }
     fn buffer_extend_dump_plain(
         buffer: &mut Vec<u8>, name: &[u8], indent_level: usize
     ) {
         buffer_extend_indent(buffer, indent_level);
-        buffer.extend_from_slice(b"echo ");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b":\"${");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b"}\"\n");
+        buffer_extend_dump_value(buffer, name, name, false);
     }
     fn buffer_extend_multi_dump_plain(
         buffer: &mut Vec<u8>, names: &[&[u8]], indent_level: usize
@@ -26,21 +71,11 @@ fn main() {
         names.iter().for_each(|name|
             buffer_extend_dump_plain(buffer, name, indent_level))
     }
-    fn buffer_extend_dump_array_license_workaround(
-        buffer: &mut Vec<u8>, indent_level: usize
-    ) {
-        buffer_extend_indent(buffer, indent_level);
-        buffer.extend_from_slice(b"license=(\"${license[@]//\n/ }\")\n");
-    }
     fn buffer_extend_dump_array(
         buffer: &mut Vec<u8>, name: &[u8], indent_level: usize
     ) {
         buffer_extend_indent(buffer, indent_level);
-        buffer.extend_from_slice(b"printf '");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b":%s\\n' \"${");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b"[@]}\"\n");
+        buffer_extend_dump_value(buffer, name, name, true);
     }
     fn buffer_extend_multi_dump_array(
         buffer: &mut Vec<u8>, names: &[&[u8]], indent_level: usize
@@ -63,11 +98,16 @@ fn main() {
         buffer.push(b'\n');
         for items in names.iter() {
             buffer_extend_indent(buffer, indent_level);
-            buffer.extend_from_slice(b"printf '");
-            buffer.extend_from_slice(items);
-            buffer.extend_from_slice(b":%s\\n' \"${_arch_");
-            buffer.extend_from_slice(items);
-            buffer.extend_from_slice(b"[@]}\"\n");
+            if ! unset {
+                // Those of the PKGBUILD were unset, so only the ones set in
+                // the package function are declared
+                buffer.extend_from_slice(b"declare -p ");
+                buffer.extend_from_slice(items);
+                buffer.extend_from_slice(b"_\"${_arch}\" &>/dev/null && ");
+            }
+            let mut var = b"_arch_".to_vec();
+            var.extend_from_slice(items);
+            buffer_extend_dump_value(buffer, items, &var, true);
         }
         if unset {
             buffer_extend_indent(buffer, indent_level);
@@ -89,7 +129,13 @@ fn main() {
             buffer.extend_from_slice(b"if [[ \"${_line}\" == *');' || \"${_line}\" == *')' ]]; then\n");
             buffer_extend_indent(buffer, indent_level + 2);
         }
-        buffer.extend_from_slice(b"eval \"${_line}\"\n");
+        if wait_line {
+            buffer.extend_from_slice(b"eval \"${_line}\"\n");
+        } else {
+            // Unmatched quotes fail the evaluation with 2, the value then
+            // spans multiple lines
+            buffer.extend_from_slice(b"eval \"${_line}\" 2>/dev/null || (( $? != 2 )) || _buffer=\"${_line}\"\n");
+        }
         if wait_line {
             buffer_extend_indent(buffer, indent_level + 1);
             buffer.extend_from_slice(b"else\n");
@@ -98,8 +144,11 @@ fn main() {
             buffer_extend_indent(buffer, indent_level + 1);
             buffer.extend_from_slice(b"fi\n");
         }
+        // The pattern also matches the arch-specific ones, e.g. depends_x86_64
         buffer_extend_indent(buffer, indent_level + 1);
-        buffer.extend_from_slice(b"_pkg_");
+        buffer.extend_from_slice(b"[[ \"${_line%%[+=]*}\" == ");
+        buffer.extend_from_slice(name);
+        buffer.extend_from_slice(b" ]] && _pkg_");
         buffer.extend_from_slice(name);
         buffer.extend_from_slice(b"='y'\n");
         buffer_extend_indent(buffer, indent_level + 1);
@@ -114,11 +163,8 @@ fn main() {
         buffer_extend_indent(buffer, indent_level);
         buffer.extend_from_slice(b"[[ \"${_pkg_");
         buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b"}\" ]] && echo ");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b":\"${");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b"}\"\n");
+        buffer.extend_from_slice(b"}\" ]] && ");
+        buffer_extend_dump_value(buffer, name, name, false);
     }
     fn buffer_extend_multi_dump_pkg_plain(
         buffer: &mut Vec<u8>, names: &[&[u8]], indent_level: usize
@@ -132,11 +178,8 @@ fn main() {
         buffer_extend_indent(buffer, indent_level);
         buffer.extend_from_slice(b"[[ \"${_pkg_");
         buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b"}\" ]] && printf '");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b":%s\\n' \"${");
-        buffer.extend_from_slice(name);
-        buffer.extend_from_slice(b"[@]}\"\n");
+        buffer.extend_from_slice(b"}\" ]] && ");
+        buffer_extend_dump_value(buffer, name, name, true);
     }
     fn buffer_extend_multi_dump_pkg_array(
         buffer: &mut Vec<u8>, names: &[&[u8]], indent_level: usize
@@ -166,9 +209,15 @@ fn main() {
     let mut buffer = Vec::with_capacity(8192);
     buffer.extend_from_slice(include_bytes!(
         "src/script/10_source_config_and_start_loop.bash"));
+    buffer_extend_init(&mut buffer, b"", PKGBUILD_PLAIN_ITEMS, b"''", 1);
+    buffer_extend_init(&mut buffer, b"", &[b"pkgname", b"arch"], b"()", 1);
+    buffer_extend_init(&mut buffer, b"", PKGBUILD_ARRAY_ITEMS, b"()", 1);
+    buffer_extend_init(&mut buffer, b"", 
+        PKGBUILD_ARCH_SPECIFIC_ARRAY_ITEMS, b"()", 1);
+    buffer.extend_from_slice(include_bytes!(
+        "src/script/15_source_pkgbuild.bash"));
     buffer_extend_multi_dump_plain(&mut buffer, 
         PKGBUILD_PLAIN_ITEMS, 1);
-    buffer_extend_dump_array_license_workaround(&mut buffer, 1);
     buffer_extend_multi_dump_array(&mut buffer, 
         PKGBUILD_ARRAY_ITEMS, 1);
     buffer.extend_from_slice(include_bytes!(
@@ -181,6 +230,11 @@ fn main() {
         PKGBUILD_ARCH_SPECIFIC_ARRAY_ITEMS, 3, true);
     buffer.extend_from_slice(include_bytes!(
         "src/script/40_arch_end_other_package_start.bash"));
+    buffer_extend_init(&mut buffer, b"_pkg_", PACKAGE_PLAIN_ITEMS, b"''", 2);
+    buffer_extend_init(&mut buffer, b"_pkg_", &[b"arch"], b"''", 2);
+    buffer_extend_init(&mut buffer, b"_pkg_", PACKAGE_ARRAY_ITEMS, b"''", 2);
+    buffer_extend_init(&mut buffer, b"_pkg_", 
+        PACKAGE_ARCH_SPECIFIC_ARRAY_ITEMS, b"''", 2);
     buffer.extend_from_slice(include_bytes!(
         "src/script/50_pkg_until_cases.bash"));
     buffer_extend_cases_flags(&mut buffer, PACKAGE_PLAIN_ITEMS, 5, false);
@@ -189,7 +243,6 @@ fn main() {
     buffer_extend_cases_flags(&mut buffer, PACKAGE_ARCH_SPECIFIC_ARRAY_ITEMS, 5, true);
     buffer.extend_from_slice(include_bytes!(
         "src/script/60_pkg_end_cases.bash"));
-    buffer_extend_dump_array_license_workaround(&mut buffer, 2);
     buffer_extend_multi_dump_pkg_plain(&mut buffer, 
         PACKAGE_PLAIN_ITEMS, 2);
     buffer_extend_multi_dump_pkg_array(&mut buffer, 
@@ -207,6 +260,8 @@ fn main() {
     buffer.extend_from_slice(include_bytes!(
         "src/script/90_pkg_end_other.bash"));
     buffer_extend_indent(&mut buffer, 1);
-    buffer.extend_from_slice(b"echo END\n) || exit $?\ndone\n");
+    buffer.extend_from_slice(b"echo END\n) || exit $?\n");
+    buffer.extend_from_slice(include_bytes!(
+        "src/script/99_end_loop.bash"));
     file.write_all(&buffer).expect("Failed to write to script");
 }
\ No newline at end of file
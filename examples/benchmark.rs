@@ -1,5 +1,5 @@
-use std::{fs::read_dir, time::Instant,ffi::OsString, thread::{spawn, JoinHandle}};
-use pkgbuild::{self, Pkgbuild};
+use std::{fs::read_dir, time::Instant};
+use pkgbuild::Parser;
 
 fn main() {
     let mut list = Vec::new();
@@ -8,33 +8,20 @@ fn main() {
         list.push(entry.file_name())
     }
     list.sort_unstable();
-    let list_backup = list.clone();
     println!("Count of PKGBUILDs: {}", list.len());
-    let mut step: usize = list.len() / std::thread::available_parallelism().unwrap();
-    let mut lists: Vec<Vec<OsString>> = Vec::new();
-    while ! list.is_empty() {
-        let len = list.len();
-        if step > len {
-            step = len
-        }
-        lists.push(list.drain((len - step)..len).collect())
+    let parser = Parser::new().unwrap();
+    #[cfg(not(feature = "nothread"))]
+    {
+        let jobs = std::thread::available_parallelism().unwrap().get();
+        println!("Testing {}-job reading...", jobs);
+        let time_start = Instant::now();
+        let pkgbuilds = parser.parse_multi_par(&list, jobs).unwrap();
+        println!("Multi-job reading took {} seconds", (Instant::now() - time_start).as_secs_f64());
+        println!("First PKGBUILD is {}, last is {}", pkgbuilds.first().unwrap().pkgbase, pkgbuilds.last().unwrap().pkgbase);
     }
-    println!("Testing {}-thread reading...", lists.len());
-    let mut time_start = Instant::now();
-    let threads: Vec<JoinHandle<Vec<Pkgbuild>>> = 
-        lists.into_iter().map(|list|spawn(
-            move ||pkgbuild::parse_multi(list).unwrap())).collect();
-    let mut chunks: Vec<Vec<Pkgbuild>> = threads.into_iter().map(
-        |thread|thread.join().unwrap()).collect();
-    let mut pkgbuilds = Vec::new();
-    while let Some(mut chunk) = chunks.pop() {
-        pkgbuilds.append(&mut chunk);
-    }
-    println!("Multi-thread reading took {} seconds", (Instant::now() - time_start).as_secs_f64());
-    println!("First PKGBUILD is {}, last is {}", pkgbuilds.first().unwrap().pkgbase, pkgbuilds.last().unwrap().pkgbase);
-    println!("Testing single-thread reading...");
-    time_start = Instant::now();
-    pkgbuilds = pkgbuild::parse_multi(&list_backup).unwrap();
-    println!("Single-thread reading took {} seconds", (Instant::now() - time_start).as_secs_f64());
+    println!("Testing single-job reading...");
+    let time_start = Instant::now();
+    let pkgbuilds = parser.parse_multi(&list).unwrap();
+    println!("Single-job reading took {} seconds", (Instant::now() - time_start).as_secs_f64());
     println!("First PKGBUILD is {}, last is {}", pkgbuilds.first().unwrap().pkgbase, pkgbuilds.last().unwrap().pkgbase);
-}
\ No newline at end of file
+}
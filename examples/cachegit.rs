@@ -86,7 +86,7 @@ fn cache_source<S: Into<String>>(source: &Source, allrefs: bool, gmr: S) {
 
 fn print_config(pkgbuild: &Pkgbuild) {
     let mut repos = Vec::new();
-    for source_with_checksum in pkgbuild.sources_with_checksums() {
+    for source_with_checksum in pkgbuild.sources_with_checksums(None) {
         let source = &source_with_checksum.source;
         if let SourceProtocol::Git { fragment: _, signed: _ } = source.protocol {
             let mut repo = source.url.clone();
@@ -115,7 +115,7 @@ fn main() -> Result<(), &'static str> {
         eprintln!("You must set gmr url!");
         return Err("No GMR url set");
     }
-    for source_with_checksum in pkgbuild.sources_with_checksums() {
+    for source_with_checksum in pkgbuild.sources_with_checksums(None) {
         cache_source(&source_with_checksum.source, arg.allrefs, &arg.gmr);
     }
     Ok(())
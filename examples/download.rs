@@ -4,12 +4,13 @@ fn main() {
     println!("Note: This does nothing to actually download the sources, but \
         just demonstrates how a program should use pkgbuild-rs's strongly \
         typed source to determine how to download them in Rust natively");
-    for source_with_checksum in pkgbuild.sources_with_checksums() {
+    for source_with_checksum in pkgbuild.sources_with_checksums(None) {
         let source = &source_with_checksum.source;
         println!("=> Downloading '{}' from '{}'...", source.name, source.url);
         match &source.protocol {
-            pkgbuild::SourceProtocol::Unknown => 
-                println!(" -> Impossible to download, unknown protocol"),
+            pkgbuild::SourceProtocol::Unknown { scheme } => 
+                println!(" -> Impossible to download, unknown protocol {}", 
+                    scheme),
             pkgbuild::SourceProtocol::Local =>
                 println!(" -> Skipped downloading for local file"),
             pkgbuild::SourceProtocol::File => 
@@ -22,6 +23,8 @@ fn main() {
                 println!(" -> HTTPS downloading..."),
             pkgbuild::SourceProtocol::Rsync => 
                 println!(" -> rsync downloading..."),
+            pkgbuild::SourceProtocol::Scp => 
+                println!(" -> scp downloading..."),
             pkgbuild::SourceProtocol::Bzr { fragment } => 
                 if let Some(fragment) = fragment {
                     println!(" -> Bzr cloning ({})...", fragment)
@@ -1,11 +1,20 @@
 fn main() {
-    let mut args = std::env::args_os();
+    let mut args = std::env::args_os().peekable();
     let _ = args.next();
+    if args.peek().is_some_and(|arg|arg == "--version") {
+        let info = pkgbuild::build_info();
+        if args.any(|arg|arg == "--verbose") {
+            println!("{}", info)
+        } else {
+            println!("pkgbuild-rs {}", info.version)
+        }
+        return
+    }
     let pkgbuilds = pkgbuild::parse_multi(args).unwrap();
     #[cfg(feature = "format")]
     for pkgbuild in pkgbuilds {
-        println!("{}", pkgbuild);
+        println!("{:#}", pkgbuild);
     }
     #[cfg(not(feature = "format"))]
     println!("{:?}", pkgbuilds);
-}
\ No newline at end of file
+}
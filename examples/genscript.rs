@@ -2,5 +2,10 @@ use pkgbuild::ParserScriptBuilder;
 
 fn main() {
     let builder = ParserScriptBuilder::new();
-    let _ = builder.build(std::env::args_os().nth(1)).expect("Failed to generate parser script");
-}
\ No newline at end of file
+    match std::env::args_os().nth(1) {
+        Some(path) => {
+            let _ = builder.build(Some(path)).expect("Failed to generate parser script");
+        },
+        None => builder.write_to(std::io::stdout().lock()).expect("Failed to write parser script"),
+    }
+}
@@ -4,8 +4,8 @@ use pkgbuild::Pkgbuild;
 use tempfile::tempdir;
 
 fn clone_file(path_old: &Path, path_new: &Path) {
-    let mut file_in = File::open(&path_old).expect("Failed to open old PKGBUILD");
-    let mut file_out = File::create_new(&path_new).expect("Failed to create new PKGBUILD");
+    let mut file_in = File::open(path_old).expect("Failed to open old PKGBUILD");
+    let mut file_out = File::create_new(path_new).expect("Failed to create new PKGBUILD");
     copy(&mut file_in, &mut file_out).expect("Failed to clone PKGBUILD");
 }
 
@@ -5,9 +5,9 @@ use pkgbuild::PlainVersion;
 fn main() {
     let mut args = std::env::args();
     let arg_ver1 = args.nth(1).unwrap();
-    let arg_ver2 = args.nth(0).unwrap();
-    let ver1 = PlainVersion::try_from(arg_ver1.as_str()).unwrap();
-    let ver2 = PlainVersion::try_from(arg_ver2.as_str()).unwrap();
+    let arg_ver2 = args.next().unwrap();
+    let ver1 = PlainVersion::from(arg_ver1.as_str());
+    let ver2 = PlainVersion::from(arg_ver2.as_str());
     let order = ver1.cmp(&ver2);
     eprintln!("Comparing version '{}' as '{:?}' and version '{}' as '{:?}': {:?}", 
         arg_ver1, ver1, arg_ver2, ver2, order);
@@ -0,0 +1,140 @@
+//! Conversions between the dependency and version types here and those of the
+//! `alpm-types` crate, for code mixing this with the ALPM ecosystem
+//!
+//! Those from `alpm-types` always succeed, as its types are stricter, while
+//! those to it are `TryFrom` failing with `alpm_types::Error` on what it does
+//! not accept, e.g. an invalid name or an empty `pkgver`.
+
+use alpm_types::{PackageRelation, VersionComparison, VersionRequirement};
+
+use crate::{Dependency, DependencyOrder, OrderedVersion, PlainVersion,
+    Provide};
+
+impl From<&alpm_types::Version> for PlainVersion {
+    fn from(value: &alpm_types::Version) -> Self {
+        Self {
+            epoch: value.epoch.map(|epoch|epoch.to_string())
+                .unwrap_or_default(),
+            pkgver: value.pkgver.to_string(),
+            pkgrel: value.pkgrel.as_ref().map(|pkgrel|pkgrel.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<alpm_types::Version> for PlainVersion {
+    fn from(value: alpm_types::Version) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl From<&alpm_types::FullVersion> for PlainVersion {
+    fn from(value: &alpm_types::FullVersion) -> Self {
+        Self::from(alpm_types::Version::from(value))
+    }
+}
+
+impl TryFrom<&PlainVersion> for alpm_types::Version {
+    type Error = alpm_types::Error;
+
+    fn try_from(value: &PlainVersion) -> Result<Self, Self::Error> {
+        value.to_version_string().parse()
+    }
+}
+
+impl From<VersionComparison> for DependencyOrder {
+    fn from(value: VersionComparison) -> Self {
+        match value {
+            VersionComparison::Greater => Self::Greater,
+            VersionComparison::GreaterOrEqual => Self::GreaterOrEqual,
+            VersionComparison::Equal => Self::Equal,
+            VersionComparison::LessOrEqual => Self::LessOrEqual,
+            VersionComparison::Less => Self::Less,
+        }
+    }
+}
+
+impl From<DependencyOrder> for VersionComparison {
+    fn from(value: DependencyOrder) -> Self {
+        match value {
+            DependencyOrder::Greater => Self::Greater,
+            DependencyOrder::GreaterOrEqual => Self::GreaterOrEqual,
+            DependencyOrder::Equal => Self::Equal,
+            DependencyOrder::LessOrEqual => Self::LessOrEqual,
+            DependencyOrder::Less => Self::Less,
+        }
+    }
+}
+
+impl From<&VersionRequirement> for OrderedVersion {
+    fn from(value: &VersionRequirement) -> Self {
+        Self {
+            order: value.comparison.into(),
+            plain: (&value.version).into(),
+        }
+    }
+}
+
+impl TryFrom<&OrderedVersion> for VersionRequirement {
+    type Error = alpm_types::Error;
+
+    fn try_from(value: &OrderedVersion) -> Result<Self, Self::Error> {
+        Ok(Self::new(value.order.into(), (&value.plain).try_into()?))
+    }
+}
+
+impl From<&PackageRelation> for Dependency {
+    fn from(value: &PackageRelation) -> Self {
+        Self {
+            name: value.name.to_string(),
+            version: value.version_requirement.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<PackageRelation> for Dependency {
+    fn from(value: PackageRelation) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl TryFrom<&Dependency> for PackageRelation {
+    type Error = alpm_types::Error;
+
+    fn try_from(value: &Dependency) -> Result<Self, Self::Error> {
+        Ok(Self::new(value.name.parse()?,
+            value.version.as_ref().map(TryInto::try_into).transpose()?))
+    }
+}
+
+impl From<&PackageRelation> for Provide {
+    /// Like `From<&Dependency>`, the version is only kept for `=`
+    fn from(value: &PackageRelation) -> Self {
+        Self::from(&Dependency::from(value))
+    }
+}
+
+impl TryFrom<&Provide> for PackageRelation {
+    type Error = alpm_types::Error;
+
+    /// Provide exactly the version, i.e. `foo=1.2` for a versioned one
+    fn try_from(value: &Provide) -> Result<Self, Self::Error> {
+        Ok(Self::new(value.name.parse()?, match &value.version {
+            Some(version) => Some(VersionRequirement::new(
+                VersionComparison::Equal, version.try_into()?)),
+            None => None,
+        }))
+    }
+}
+
+#[cfg(feature = "vercmp")]
+impl Dependency {
+    /// Whether the installed package `pkg` satisfies this by itself, i.e. by
+    /// its name and version, see `satisfied_by_package()`
+    pub fn satisfied_by_alpm_pkg(&self, pkg: &alpm_types::InstalledPackage)
+        -> bool
+    {
+        self.satisfied_by_package(pkg.name().as_ref(),
+            &PlainVersion::from(pkg.version()))
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, ffi::{OsStr, OsString}, fmt::{Display, Formatter}, io::{Read, Write}, os::unix::ffi::OsStrExt, path::{Path, PathBuf}, process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Stdio}};
+use std::{borrow::Cow, collections::BTreeMap, ffi::{CString, OsStr, OsString}, fmt::{Display, Formatter}, io::{Read, Seek, Write}, str::FromStr, sync::Mutex, os::unix::{ffi::OsStrExt, process::CommandExt}, path::{Path, PathBuf}, process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Stdio}, time::{Duration, Instant}};
 
 use hex::FromHex;
 #[cfg(feature = "serde")]
@@ -9,10 +9,14 @@ use nix::fcntl::{fcntl, FcntlArg::F_SETFL, OFlag};
 use std::os::fd::AsRawFd;
 #[cfg(not(feature = "nothread"))]
 use std::thread::spawn;
-#[cfg(feature = "vercmp")]
 use std::cmp::Ordering;
-#[cfg(not(feature = "tempfile"))]
-use std::io::BufWriter;
+
+#[cfg(feature = "alpm-interop")]
+pub mod alpm_interop;
+pub mod lint;
+pub mod rewrite;
+#[cfg(feature = "spdx")]
+pub mod spdx;
 
 #[cfg(feature = "unsafe_str")]
 macro_rules! str_from_slice_u8 {
@@ -34,11 +38,150 @@ macro_rules! string_from_slice_u8 {
     ($l:expr) => {String::from_utf8_lossy($l).to_string()}
 }
 
+/// The max bytes of parser output that would be logged at once
+const LOG_EXCERPT_LIMIT: usize = 0x2000;
+
+/// An excerpt of some bytes for logging, truncated to at most `limit` bytes 
+/// without splitting a multi-byte character, with a notice of the total size
+struct LogExcerpt<'a> {
+    bytes: &'a [u8],
+    limit: usize,
+}
+
+fn log_excerpt(bytes: &[u8], limit: usize) -> LogExcerpt<'_> {
+    LogExcerpt { bytes, limit }
+}
+
+/// The max bytes of stderr kept in `Error::ParserScriptError`
+const STDERR_TAIL_LIMIT: usize = 0x1000;
+
+/// The last at most `limit` bytes, without splitting a multi-byte character
+fn tail_string(bytes: &[u8], limit: usize) -> String {
+    let mut start = bytes.len().saturating_sub(limit);
+    // Do not start in the middle of a character, i.e. at a continuation byte
+    while start < bytes.len() && bytes[start] & 0xc0 == 0x80 {
+        start += 1
+    }
+    String::from_utf8_lossy(&bytes[start..]).into_owned()
+}
+
+impl Display for LogExcerpt<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.bytes.len() <= self.limit {
+            return write!(f, "{}", String::from_utf8_lossy(self.bytes))
+        }
+        let mut end = self.limit;
+        // Do not end in the middle of a character, i.e. before a continuation
+        // byte
+        while end > 0 && self.bytes[end] & 0xc0 == 0x80 {
+            end -= 1
+        }
+        write!(f, "{}\n... (truncated, {} bytes in total, use \
+            Parser::parse_raw() to get the full output)", 
+            String::from_utf8_lossy(&self.bytes[..end]), self.bytes.len())
+    }
+}
+
+/// The version of the output format spoken by the embedded parser script,
+/// bumped whenever the script output changes incompatibly. The script writes
+/// it as a `PROTOCOL:<version>` line before anything else.
+pub const PARSER_SCRIPT_PROTOCOL: u32 = 5;
+
+/// The optional features this crate was compiled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BuildFeatures {
+    pub alpm_interop: bool,
+    pub r#async: bool,
+    pub format: bool,
+    pub nothread: bool,
+    pub sandbox: bool,
+    pub serde: bool,
+    pub spdx: bool,
+    pub srcinfo: bool,
+    pub tempfile: bool,
+    pub unsafe_str: bool,
+    pub url: bool,
+    pub vendored_makepkg: bool,
+    pub vercmp: bool,
+}
+
+/// Information about how this crate was built, useful in bug reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BuildInfo {
+    /// The crate version, e.g. `0.1.0`
+    pub version: &'static str,
+    /// The optional features enabled
+    pub features: BuildFeatures,
+    /// The output protocol version of the embedded parser script
+    pub protocol: u32,
+}
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pkgbuild-rs {} (protocol {}), features: [", 
+            self.version, self.protocol)?;
+        let features = [
+            ("alpm-interop", self.features.alpm_interop),
+            ("async", self.features.r#async),
+            ("format", self.features.format),
+            ("nothread", self.features.nothread),
+            ("sandbox", self.features.sandbox),
+            ("serde", self.features.serde),
+            ("spdx", self.features.spdx),
+            ("srcinfo", self.features.srcinfo),
+            ("tempfile", self.features.tempfile),
+            ("unsafe_str", self.features.unsafe_str),
+            ("url", self.features.url),
+            ("vendored-makepkg", self.features.vendored_makepkg),
+            ("vercmp", self.features.vercmp),
+        ];
+        let mut started = false;
+        for (name, enabled) in features {
+            if ! enabled { continue }
+            if started {
+                write!(f, ", ")?
+            } else {
+                started = true
+            }
+            write!(f, "{}", name)?
+        }
+        write!(f, "]")
+    }
+}
+
+/// Get the crate version, the enabled features and the parser script protocol
+/// version this crate was compiled with
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        features: BuildFeatures {
+            alpm_interop: cfg!(feature = "alpm-interop"),
+            r#async: cfg!(feature = "async"),
+            format: cfg!(feature = "format"),
+            nothread: cfg!(feature = "nothread"),
+            sandbox: cfg!(feature = "sandbox"),
+            serde: cfg!(feature = "serde"),
+            spdx: cfg!(feature = "spdx"),
+            srcinfo: cfg!(feature = "srcinfo"),
+            tempfile: cfg!(feature = "tempfile"),
+            unsafe_str: cfg!(feature = "unsafe_str"),
+            url: cfg!(feature = "url"),
+            vendored_makepkg: cfg!(feature = "vendored-makepkg"),
+            vercmp: cfg!(feature = "vercmp"),
+        },
+        protocol: PARSER_SCRIPT_PROTOCOL,
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ParserScriptError {
     PkbguildMultiArchWithAny,
     PackageFunctionNotFound,
     PackageMultiArchWithAny,
+    /// The makepkg config to source does not exist or is not readable
+    MakepkgConfigUnreadable,
     Other (Option<i32>),
 }
 
@@ -51,6 +194,8 @@ impl Display for ParserScriptError {
                 write!(f, "Package function not found, could be be expanded"),
             ParserScriptError::PackageMultiArchWithAny => 
                 write!(f, "Package multiarch with 'any' as one of the arch"),
+            ParserScriptError::MakepkgConfigUnreadable =>
+                write!(f, "Makepkg config not readable"),
             ParserScriptError::Other(value) => 
                 write!(f, "Other bash error: {:?}", value),
         }
@@ -64,6 +209,7 @@ impl From<Option<i32>> for ParserScriptError {
                 -1 => return Self::PkbguildMultiArchWithAny,
                 -2 => return Self::PackageFunctionNotFound,
                 -3 => return Self::PackageMultiArchWithAny,
+                -4 => return Self::MakepkgConfigUnreadable,
                 _ => (),
             }
         }
@@ -71,6 +217,34 @@ impl From<Option<i32>> for ParserScriptError {
     }
 }
 
+/// A `PKGBUILD` whose dumped values could not be converted, and why
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BrokenPkgbuild {
+    /// Empty if the value was not converted as part of a `PKGBUILD`
+    pub pkgbase: String,
+    pub reason: String,
+}
+
+impl BrokenPkgbuild {
+    /// The error for a single broken value, the pkgbase is filled in and the
+    /// context is added to the reason while the error bubbles up
+    fn error<S: Into<String>>(reason: S) -> Error {
+        Error::BrokenPKGBUILDs(vec![Self { 
+            pkgbase: String::new(), reason: reason.into() }])
+    }
+}
+
+impl Display for BrokenPkgbuild {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.pkgbase.is_empty() {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{}: {}", self.pkgbase, self.reason)
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Error {
     /// Some I/O error happended, possibly during the script generation,
@@ -81,11 +255,12 @@ pub enum Error {
     #[cfg(feature = "nothread")]
     NixErrno(nix::errno::Errno),
     /// The parsed result count is different from our input, but it might still
-    /// be usable
+    /// be usable, `missing` contains the input paths that got no result
     MismatchedResultCount {
         input: usize,
         output: usize,
-        result: Vec<Pkgbuild>
+        result: Vec<Pkgbuild>,
+        missing: Vec<PathBuf>,
     },
     /// The child's Stdio handles are incomplete and we can't get, this is not
     /// fixable, but intentionally not panic to reduce damage to caller
@@ -95,15 +270,155 @@ pub enum Error {
     #[cfg(not(feature = "nothread"))]
     ThreadUnjoinable,
     /// Some PKGBUILDs were broken, this contains a list of those PKGBUILDs
-    BrokenPKGBUILDs(Vec<String>),
+    /// and the reasons
+    BrokenPKGBUILDs(Vec<BrokenPkgbuild>),
     /// The parser script has errored out
-    ParserScriptError(ParserScriptError),
+    ParserScriptError {
+        error: ParserScriptError,
+        /// The last at most 4 KiB of the stderr, which usually explains the
+        /// error, e.g. the bash error when sourcing a `PKGBUILD`
+        stderr_tail: String,
+        /// Where the script is preserved, for a temporary script built with
+        /// `ParserScriptBuilder::set_keep_on_error(true)`
+        kept_script: Option<PathBuf>,
+    },
+    /// The parser script, or the subshell sourcing a `PKGBUILD`, was killed 
+    /// by a signal, e.g. `SIGXCPU` after exceeding the CPU time limit, or
+    /// `SIGKILL` from the OOM killer
+    ParserKilledBySignal(i32),
     /// The parser script has returned some unexpected, illegal output
-    ParserScriptIllegalOutput(Vec<u8>)
+    ParserScriptIllegalOutput {
+        /// The offending line, empty if the output ended unexpectedly
+        line: Vec<u8>,
+        /// The index of the input path being dumped
+        index: usize,
+        /// The pkgbase of the `PKGBUILD` being dumped, if already dumped
+        pkgbase: Option<String>,
+        /// The input path of the `PKGBUILD` being dumped, only known when 
+        /// parsing with a `Parser`
+        path: Option<PathBuf>,
+    },
+    /// The parser script wrote more than the limit to stdout, and was killed
+    OutputLimitExceeded {
+        limit: usize,
+    },
+    /// The input path could not be passed to the parser script, e.g. it 
+    /// contains the path delimiter
+    InvalidInputPath(PathBuf),
+    /// Some sources use protocols we do not know, this contains their schemes,
+    /// only returned in strict modes
+    UnknownSourceProtocols(Vec<String>),
+    /// The interpreter does not exist or is not executable, this contains the
+    /// path looked up, which is under the chroot if one is set
+    InterpreterNotFound(PathBuf),
+    /// The parser script does not exist or is not readable
+    ScriptMissing(PathBuf),
+    /// The `PKGBUILD` or one of its packages declared more architectures than
+    /// allowed
+    TooManyArchitectures {
+        count: usize,
+        max: usize,
+    },
+    /// The parser script speaks a different output protocol than this library,
+    /// e.g. an old persistent script, `found` is `None` if it does not write
+    /// the protocol header at all
+    ProtocolMismatch {
+        expected: u32,
+        found: Option<u32>,
+    },
+    /// Some chunks failed when parsing in parallel, this contains the errors
+    /// of those chunks, and one slot per input path in input order, `None`
+    /// for the paths of the failed chunks
+    #[cfg(not(feature = "nothread"))]
+    ChunksFailed {
+        errors: Vec<Error>,
+        result: Vec<Option<Pkgbuild>>
+    },
+    /// A `PKGBUILD` could not be edited in place by the functions in 
+    /// `rewrite`, as the assignment of `variable` is computed, e.g. by 
+    /// arithmetic or command substitution, at 1-based `line`, or is missing at
+    /// top level if `line` is `None`
+    UnrewritableAssignment {
+        path: PathBuf,
+        variable: String,
+        line: Option<usize>,
+    },
+    /// A file referenced by a `PKGBUILD` does not exist, `kind` is the 
+    /// variable referencing it, e.g. `install`, and `name` its value
+    ReferencedFileMissing {
+        kind: &'static str,
+        name: String,
+    },
+    /// Some names are declared by more than one `PKGBUILD` in a `Pkgbuilds`,
+    /// `kind` is either `pkgbase` or `pkgname`, and `names` the duplicated
+    /// ones, each once, in sorted order
+    DuplicatedNames {
+        kind: &'static str,
+        names: Vec<String>,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// The input paths that got no result, if this is a 
+    /// `Error::MismatchedResultCount`
+    pub fn missing_paths(&self) -> Option<&[PathBuf]> {
+        match self {
+            Error::MismatchedResultCount { missing, .. } => Some(missing),
+            _ => None
+        }
+    }
+
+    /// The `Pkgbuild`s that were parsed nevertheless, if this is a 
+    /// `Error::MismatchedResultCount`
+    pub fn partial_result(&self) -> Option<&[Pkgbuild]> {
+        match self {
+            Error::MismatchedResultCount { result, .. } => Some(result),
+            _ => None
+        }
+    }
+
+    /// Take the `Pkgbuild`s that were parsed nevertheless, if this is a
+    /// `Error::MismatchedResultCount`
+    pub fn into_partial_result(self) -> Option<Vec<Pkgbuild>> {
+        match self {
+            Error::MismatchedResultCount { result, .. } => Some(result),
+            _ => None
+        }
+    }
+}
+
+impl Error {
+    /// Prefix the reasons of `Error::BrokenPKGBUILDs` with `context`
+    fn broken_context<D: Display>(self, context: D) -> Self {
+        match self {
+            Error::BrokenPKGBUILDs(mut brokens) => {
+                for broken in brokens.iter_mut() {
+                    broken.reason = format!("{}: {}", context, broken.reason)
+                }
+                Error::BrokenPKGBUILDs(brokens)
+            },
+            e => e
+        }
+    }
+
+    /// Fill the pkgbase of `Error::BrokenPKGBUILDs` where not yet filled
+    fn broken_pkgbase(self, pkgbase: &str) -> Self {
+        match self {
+            Error::BrokenPKGBUILDs(mut brokens) => {
+                for broken in brokens.iter_mut() {
+                    if broken.pkgbase.is_empty() {
+                        broken.pkgbase = pkgbase.into()
+                    }
+                }
+                Error::BrokenPKGBUILDs(brokens)
+            },
+            e => e
+        }
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
         Self::IoError(format!("{}", value))
@@ -124,18 +439,82 @@ impl Display for Error {
             #[cfg(feature = "nothread")]
             Error::NixErrno(e) => write!(f, "Nix Errno: {}", e),
             Error::MismatchedResultCount { 
-                input, output, result: _ 
-            } => write!(f, "Result Count Mismatch: Input {}, Output {}",
-                    input, output),
+                input, output, result: _, missing
+            } => write!(f, "Result Count Mismatch: Input {}, Output {}, \
+                    Missing {:?}", input, output, missing),
             Error::ChildStdioIncomplete => write!(f, "Child StdIO incomplete"),
             #[cfg(not(feature = "nothread"))]
             Error::ThreadUnjoinable => write!(f, "Thread Not Joinable"),
-            Error::BrokenPKGBUILDs(e) => 
-                write!(f, "PKGBUILDs Broken ({})", e.len()),
-            Error::ParserScriptError(e) =>
-                write!(f, "Parser Script Error: {}", e),
-            Error::ParserScriptIllegalOutput(e) => write!(
-                f, "Parser Script Illegal Output: {}", str_from_slice_u8!(e)),
+            Error::BrokenPKGBUILDs(e) => {
+                write!(f, "PKGBUILDs Broken ({})", e.len())?;
+                for (id, broken) in e.iter().enumerate() {
+                    write!(f, "{} {}", if id == 0 { ":" } else { ";" }, 
+                        broken)?
+                }
+                Ok(())
+            },
+            Error::ParserScriptError { error, stderr_tail, kept_script } => {
+                write!(f, "Parser Script Error: {}", error)?;
+                if let Some(kept_script) = kept_script {
+                    write!(f, ", script kept at {:?}", kept_script)?
+                }
+                if ! stderr_tail.is_empty() {
+                    write!(f, ", stderr:\n{}", stderr_tail)?
+                }
+                Ok(())
+            },
+            Error::ParserKilledBySignal(signal) =>
+                write!(f, "Parser Killed By Signal: {} ({})", signal, 
+                    nix::sys::signal::Signal::try_from(*signal).map_or(
+                        "unknown", |signal|signal.as_str())),
+            Error::ParserScriptIllegalOutput { line, index, pkgbase, path } => {
+                write!(f, "Parser Script Illegal Output: '{}' in PKGBUILD #{}",
+                    str_from_slice_u8!(line), index)?;
+                if let Some(pkgbase) = pkgbase {
+                    write!(f, " ({})", pkgbase)?
+                }
+                if let Some(path) = path {
+                    write!(f, " at {:?}", path)?
+                }
+                Ok(())
+            },
+            Error::OutputLimitExceeded { limit } => write!(
+                f, "Output Limit Exceeded: {} bytes", limit),
+            Error::InvalidInputPath(path) => write!(
+                f, "Invalid Input Path: {:?}", path),
+            Error::UnknownSourceProtocols(schemes) => write!(
+                f, "Unknown Source Protocols: {}", schemes.join(", ")),
+            Error::InterpreterNotFound(path) => write!(
+                f, "Interpreter Not Found: {:?}", path),
+            Error::ScriptMissing(path) => write!(
+                f, "Script Missing: {:?}", path),
+            Error::TooManyArchitectures { count, max } => write!(
+                f, "Too Many Architectures: {} > {}", count, max),
+            Error::ProtocolMismatch { expected, found: Some(found) } => write!(
+                f, "Protocol Mismatch: expected {}, found {}", expected, found),
+            Error::ProtocolMismatch { expected, found: None } => write!(
+                f, "Protocol Mismatch: expected {}, found none", expected),
+            #[cfg(not(feature = "nothread"))]
+            Error::ChunksFailed { errors, result: _ } => {
+                write!(f, "Chunks Failed ({}): [", errors.len())?;
+                for (id, e) in errors.iter().enumerate() {
+                    if id > 0 {
+                        write!(f, ", ")?
+                    }
+                    write!(f, "{}", e)?
+                }
+                write!(f, "]")
+            },
+            Error::UnrewritableAssignment { path, variable, line: Some(line) } 
+                => write!(f, "Unrewritable Assignment: {} at {:?} line {}", 
+                    variable, path, line),
+            Error::UnrewritableAssignment { path, variable, line: None } 
+                => write!(f, "Unrewritable Assignment: {} missing in {:?}", 
+                    variable, path),
+            Error::ReferencedFileMissing { kind, name } => write!(
+                f, "Referenced File Missing: {} '{}'", kind, name),
+            Error::DuplicatedNames { kind, names } => write!(
+                f, "Duplicated Names: {} {:?}", kind, names),
         }
     }
 }
@@ -143,14 +522,53 @@ impl Display for Error {
 impl std::error::Error for Error {}
 
 /// The script builder to construct a `ParserScript` dynamically
+#[derive(Debug, Clone)]
 pub struct ParserScriptBuilder {
     /// The path to makepkg library, usually `/usr/share/makepkg` on an Arch 
     /// installation
     pub makepkg_library: OsString,
 
+    /// Whether to embed the few makepkg library routines the parser needs into
+    /// the script instead of sourcing `makepkg_library`
+    #[cfg(feature = "vendored-makepkg")]
+    pub vendored_makepkg_library: bool,
+
     /// The makepkg configuration file, usually `/etc/makepkg.conf` on an Arch
     /// installation
     pub makepkg_config: OsString,
+
+    /// The content of the makepkg config embedded into the script, which is
+    /// used instead of `makepkg_config` if set
+    pub makepkg_config_inline: Option<Vec<u8>>,
+
+    /// Whether to source the makepkg config, without it the `CARCH` should be
+    /// set by `ParserOptions::set_carch()` for architecture-specific items
+    pub use_makepkg_config: bool,
+
+    /// The names of additional variables to dump from `PKGBUILD`s, e.g. 
+    /// `_commit`, which end up in `Pkgbuild::extra`
+    pub extra_variables: Vec<String>,
+
+    /// The directory to create temporary scripts in, the system default temp
+    /// dir if `None`
+    #[cfg(feature = "tempfile")]
+    pub temp_dir: Option<PathBuf>,
+
+    /// The prefix of the names of temporary scripts
+    #[cfg(feature = "tempfile")]
+    pub temp_prefix: String,
+
+    /// Whether to keep temporary scripts when the parser fails with them
+    #[cfg(feature = "tempfile")]
+    pub keep_on_error: bool,
+
+    /// Whether to dump the bodies of the functions in `PKGBUILD`s into 
+    /// `Pkgbuild::functions`
+    pub dump_function_bodies: bool,
+
+    /// Whether to tolerate `any` mixed with other architectures in `arch`, 
+    /// instead of failing the whole parse
+    pub tolerate_any_with_arch: bool,
 }
 
 /// Get a variable from environment, or use the default value if failed
@@ -166,9 +584,36 @@ impl Default for ParserScriptBuilder {
     fn default() -> Self {
         Self { 
             makepkg_library: env_or("LIBRARY", "/usr/share/makepkg"),
+            #[cfg(feature = "vendored-makepkg")]
+            vendored_makepkg_library: false,
             makepkg_config: env_or("MAKEPKG_CONF", "/etc/makepkg.conf"),
+            makepkg_config_inline: None,
+            use_makepkg_config: true,
+            extra_variables: Vec::new(),
+            #[cfg(feature = "tempfile")]
+            temp_dir: None,
+            #[cfg(feature = "tempfile")]
+            temp_prefix: ".pkgbuild-rs".into(),
+            #[cfg(feature = "tempfile")]
+            keep_on_error: false,
+            dump_function_bodies: false,
+            tolerate_any_with_arch: false,
+        }
+    }
+}
+
+/// Quote the content in single quotes for bash, with single quotes in it
+/// closed, escaped and re-opened
+fn buffer_extend_single_quoted(buffer: &mut Vec<u8>, content: &[u8]) {
+    buffer.push(b'\'');
+    for byte in content.iter() {
+        if *byte == b'\'' {
+            buffer.extend_from_slice(b"'\\''")
+        } else {
+            buffer.push(*byte)
         }
     }
+    buffer.push(b'\'');
 }
 
 impl ParserScriptBuilder {
@@ -197,15 +642,124 @@ impl ParserScriptBuilder {
         self
     }
 
+    /// Set whether to embed the few makepkg library routines the parser needs
+    /// into the script, instead of sourcing the makepkg library set by 
+    /// `set_makepkg_library()`, so `PKGBUILD`s could be parsed on hosts
+    /// without makepkg installed. Default `false`.
+    #[cfg(feature = "vendored-makepkg")]
+    pub fn set_vendored_makepkg_library(&mut self, vendored: bool) 
+        -> &mut Self 
+    {
+        self.vendored_makepkg_library = vendored;
+        self
+    }
+
     /// Set the path to the makepkg config, usually `/etc/makepkg.conf` on an
     /// Arch installation.
     /// 
     /// If not set explicitly then the value of environmenr var `MAKEPKG_CONF` (
-    /// if set), or the default value `/etc/makepkg.conf` would be used
+    /// if set), or the default value `/etc/makepkg.conf` would be used. 
+    /// 
+    /// This drops the config set by `set_makepkg_config_inline()`.
     pub fn set_makepkg_config<O: Into<OsString>>(&mut self, config: O) 
         -> &mut Self 
     {
         self.makepkg_config = config.into();
+        self.makepkg_config_inline = None;
+        self
+    }
+
+    /// Embed the content of the makepkg config into the script, instead of 
+    /// sourcing the one at path set by `set_makepkg_config()`, e.g. for 
+    /// configs generated per target.
+    /// 
+    /// The content could contain anything other than NUL bytes, otherwise
+    /// building the script would fail.
+    pub fn set_makepkg_config_inline<S: Into<Vec<u8>>>(&mut self, content: S)
+        -> &mut Self
+    {
+        self.makepkg_config_inline = Some(content.into());
+        self
+    }
+
+    /// Set whether to source the makepkg config, default `true`. If not, no 
+    /// variables like `CARCH` and `OPTIONS` would come from the config, the
+    /// `CARCH` could then be set by `ParserOptions::set_carch()`.
+    /// 
+    /// If sourced but the config is not readable, the parser would fail with
+    /// `ParserScriptError::MakepkgConfigUnreadable`.
+    pub fn set_use_makepkg_config(&mut self, use_makepkg_config: bool) 
+        -> &mut Self 
+    {
+        self.use_makepkg_config = use_makepkg_config;
+        self
+    }
+
+    /// Set the directory to create temporary scripts in by `build(None)`, e.g.
+    /// a project-scoped runtime dir. If not set, the system default temp dir
+    /// would be used.
+    #[cfg(feature = "tempfile")]
+    pub fn set_temp_dir<P: Into<PathBuf>>(&mut self, dir: Option<P>) 
+        -> &mut Self 
+    {
+        self.temp_dir = dir.map(|dir|dir.into());
+        self
+    }
+
+    /// Set the prefix of the names of temporary scripts, default 
+    /// `.pkgbuild-rs`
+    #[cfg(feature = "tempfile")]
+    pub fn set_temp_prefix<S: Into<String>>(&mut self, prefix: S) 
+        -> &mut Self 
+    {
+        self.temp_prefix = prefix.into();
+        self
+    }
+
+    /// Set whether to keep temporary scripts when the parser fails with them,
+    /// default `false`. If set, the scripts would be built as
+    /// `ParserScript::TemporaryKeptOnError`, and a copy would be kept at the 
+    /// script path suffixed with `.kept` when `ParserScriptError` happens, 
+    /// which would be in `Error::ParserScriptError::kept_script`.
+    #[cfg(feature = "tempfile")]
+    pub fn set_keep_on_error(&mut self, keep_on_error: bool) -> &mut Self {
+        self.keep_on_error = keep_on_error;
+        self
+    }
+
+    /// Set whether to dump the bodies of `pkgver()`, `prepare()`, `build()`, 
+    /// `check()`, `package()` and `package_*()` into `Pkgbuild::functions`,
+    /// e.g. for auditing, default `false`.
+    pub fn set_dump_function_bodies(&mut self, dump_function_bodies: bool) 
+        -> &mut Self 
+    {
+        self.dump_function_bodies = dump_function_bodies;
+        self
+    }
+
+    /// Set whether to tolerate `any` mixed with other architectures, e.g.
+    /// `arch=('any' 'x86_64')`, default `false`. Such `arch` is invalid and
+    /// by default fails the whole parse with `PkgbuildMultiArchWithAny` or
+    /// `PackageMultiArchWithAny`. If set, `any` would be dropped from it so
+    /// the others are treated as arch-specific, and the anomaly would be
+    /// recorded in `Pkgbuild::warnings`.
+    pub fn set_tolerate_any_with_arch(&mut self, tolerate_any_with_arch: bool)
+        -> &mut Self 
+    {
+        self.tolerate_any_with_arch = tolerate_any_with_arch;
+        self
+    }
+
+    /// Add a variable to dump from `PKGBUILD`s besides the standard ones, e.g.
+    /// `_commit`, its value, or all of its elements if it's an array, would 
+    /// be in `Pkgbuild::extra` under its name.
+    /// 
+    /// The name must be a valid bash variable name, otherwise building the
+    /// script would fail.
+    pub fn add_extra_variable<S: Into<String>>(&mut self, name: S) 
+        -> &mut Self 
+    {
+        self.extra_variables.push(name.into());
         self
     }
 
@@ -214,25 +768,195 @@ impl ParserScriptBuilder {
     fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> 
     {
         let mut buffer = Vec::with_capacity(8192);
-        buffer.extend_from_slice(b"LIBRARY='"); 
-        buffer.extend_from_slice(self.makepkg_library.as_bytes());
-        buffer.extend_from_slice(b"'\nMAKEPKG_CONF='");
-        buffer.extend_from_slice(self.makepkg_config.as_bytes());
-        buffer.extend_from_slice(b"'\nsource \'");
-        buffer.extend_from_slice(self.makepkg_library.as_bytes());
-        buffer.extend_from_slice(b"/util.sh\'\nsource \'");
-        buffer.extend_from_slice(self.makepkg_library.as_bytes());
-        buffer.extend_from_slice(b"/source.sh\'\n");
+        #[cfg(feature = "vendored-makepkg")]
+        let vendored = self.vendored_makepkg_library;
+        #[cfg(not(feature = "vendored-makepkg"))]
+        let vendored = false;
+        for path in [&self.makepkg_library, &self.makepkg_config] {
+            if path.as_bytes().contains(&0) {
+                log::error!("Makepkg path '{}' contains NUL bytes", 
+                    path.to_string_lossy());
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput, 
+                    "makepkg path contains NUL bytes"))
+            }
+        }
+        // Sourced and evaluated things could not be followed by shellcheck,
+        // and LIBRARY is only used by the makepkg library
+        buffer.extend_from_slice(b"#!/bin/bash\n\
+            # shellcheck disable=SC1090,SC1091,SC2034\n");
+        if vendored {
+            buffer.extend_from_slice(b"MAKEPKG_CONF=");
+            buffer_extend_single_quoted(&mut buffer, 
+                self.makepkg_config.as_bytes());
+            buffer.push(b'\n');
+            #[cfg(feature = "vendored-makepkg")]
+            buffer.extend_from_slice(include_bytes!(
+                "script/vendored_makepkg.bash"));
+        } else {
+            let library = self.makepkg_library.as_bytes();
+            buffer.extend_from_slice(b"LIBRARY="); 
+            buffer_extend_single_quoted(&mut buffer, library);
+            buffer.extend_from_slice(b"\nMAKEPKG_CONF=");
+            buffer_extend_single_quoted(&mut buffer, 
+                self.makepkg_config.as_bytes());
+            buffer.extend_from_slice(b"\nsource ");
+            buffer_extend_single_quoted(&mut buffer, 
+                &[library, b"/util.sh"].concat());
+            buffer.extend_from_slice(b"\nsource ");
+            buffer_extend_single_quoted(&mut buffer, 
+                &[library, b"/source.sh"].concat());
+            buffer.push(b'\n');
+        }
+        match (self.use_makepkg_config, &self.makepkg_config_inline) {
+            (false, _) => buffer.extend_from_slice(b"_use_makepkg_config=''\n"),
+            (true, None) => 
+                buffer.extend_from_slice(b"_use_makepkg_config='file'\n"),
+            (true, Some(content)) => {
+                if content.contains(&0) {
+                    log::error!("Inline makepkg config contains NUL bytes");
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput, 
+                        "inline makepkg config contains NUL bytes"))
+                }
+                // The quoted heredoc is taken literally until the marker line,
+                // so only make sure the content has no such line
+                let mut marker = b"PKGBUILD_RS_MAKEPKG_CONF".to_vec();
+                while content.split(|byte|*byte == b'\n').any(
+                    |line|line == marker) 
+                {
+                    marker.push(b'_')
+                }
+                buffer.extend_from_slice(b"_use_makepkg_config='inline'\n\
+                    IFS= read -r -d '' _makepkg_config_inline <<'");
+                buffer.extend_from_slice(&marker);
+                buffer.extend_from_slice(b"'\n");
+                buffer.extend_from_slice(content);
+                if ! content.ends_with(b"\n") {
+                    buffer.push(b'\n')
+                }
+                buffer.extend_from_slice(&marker);
+                buffer.push(b'\n');
+            }
+        }
+        buffer.extend_from_slice(if self.dump_function_bodies {
+            b"_dump_function_bodies='y'\n"
+        } else {
+            b"_dump_function_bodies=''\n"
+        });
+        buffer.extend_from_slice(if self.tolerate_any_with_arch {
+            b"_tolerate_any_with_arch='y'\n"
+        } else {
+            b"_tolerate_any_with_arch=''\n"
+        });
+        buffer.extend_from_slice(b"_extra_variables=(");
+        for name in self.extra_variables.iter() {
+            let mut chars = name.chars();
+            if ! chars.next().is_some_and(|c|c.is_ascii_alphabetic() || c == '_') 
+                || ! chars.all(|c|c.is_ascii_alphanumeric() || c == '_')
+            {
+                log::error!("Extra variable name '{}' is not a valid bash \
+                    variable name", name);
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                    format!("invalid extra variable name '{}'", name)))
+            }
+            buffer.push(b' ');
+            buffer.extend_from_slice(name.as_bytes());
+        }
+        buffer.extend_from_slice(b" )\n");
+        buffer.extend_from_slice(
+            format!("echo PROTOCOL:{}\n", PARSER_SCRIPT_PROTOCOL).as_bytes());
         buffer.extend_from_slice(include_bytes!(
             "script/full.bash"));
         writer.write_all(&buffer)
     }
 
+    /// Write the script content into any writer without creating a file, e.g.
+    /// stdout or a `Vec<u8>`
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        if let Err(e) = self.write(writer) {
+            log::error!("Failed to write script: {}", e);
+            return Err(e.into())
+        }
+        Ok(())
+    }
+
+    /// Write the script into the file at `path`, created if not existing, 
+    /// only if its content differs from the script, with an exclusive advisory
+    /// lock held while reading and writing if `lock`. Return whether the file
+    /// was written.
+    fn write_persistent(&self, path: &Path, lock: bool) -> Result<bool> {
+        let mut buffer = Vec::with_capacity(8192);
+        self.write_to(&mut buffer)?;
+        let file = match std::fs::OpenOptions::new().read(true).write(true)
+            .create(true).truncate(false).open(path) 
+        {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Failed to open script file at '{}': {}",
+                                path.display(), e);
+                return Err(e.into())
+            },
+        };
+        let locked;
+        let unlocked;
+        let mut file: &std::fs::File = if lock {
+            locked = match nix::fcntl::Flock::lock(
+                file, nix::fcntl::FlockArg::LockExclusive) 
+            {
+                Ok(locked) => locked,
+                Err((_, e)) => {
+                    log::error!("Failed to lock script file at '{}': {}",
+                                    path.display(), e);
+                    return Err(std::io::Error::from(e).into())
+                },
+            };
+            &locked
+        } else {
+            unlocked = file;
+            &unlocked
+        };
+        let mut existing = Vec::new();
+        if let Err(e) = file.read_to_end(&mut existing) {
+            log::error!("Failed to read existing script file '{}': {}", 
+                path.display(), e);
+            return Err(e.into())
+        }
+        if existing == buffer {
+            log::debug!("Script file '{}' is up to date", path.display());
+            return Ok(false)
+        }
+        if let Err(e) = file.set_len(0).and_then(|_|
+            file.seek(std::io::SeekFrom::Start(0))).and_then(|_|
+                file.write_all(&buffer)) 
+        {
+            log::error!("Failed to write script into file '{}': {}", 
+                    path.display(), e);
+            return Err(e.into())
+        }
+        Ok(true)
+    }
+
+    /// Build a `ParserScript` at `path` like `build()`, but with an exclusive
+    /// advisory lock (`flock`) held on the file while checking and writing it,
+    /// so multiple instances could build the same script concurrently. 
+    /// 
+    /// Return the script and whether the file was refreshed, i.e. it did not
+    /// exist or had a different content.
+    pub fn build_cached<P: AsRef<Path>>(&self, path: P) 
+        -> Result<(ParserScript, bool)> 
+    {
+        let refreshed = self.write_persistent(path.as_ref(), true)?;
+        Ok((ParserScript::Persistent(path.as_ref().into()), refreshed))
+    }
+
     /// Build a `ParserScript`, would could later be used to parse `PKGBUILD`s
     /// 
     /// If `path` is `Some`, then create the file if not existing; if `path` is 
-    /// `None`, then create a `NamedTempFile`. In both cases write the script
-    /// dynamically generated into the file.
+    /// `None`, then create a `NamedTempFile` under `temp_dir` with 
+    /// `temp_prefix`. In both cases write the script
+    /// dynamically generated into the file, but an existing file with the
+    /// same content is left untouched, to keep its mtime.
     /// 
     /// Return `Ok(ParserScript)` if write was successfull, return `Err` on IO
     /// Error.
@@ -245,85 +969,154 @@ impl ParserScriptBuilder {
         -> Result<ParserScript> 
     {
         if let Some(path) = path {
-            let file = match std::fs::File::create(&path) {
-                Ok(file) => file,
-                Err(e) => {
-                    log::error!("Failed to create script file at '{}': {}",
-                                    path.as_ref().display(), e);
-                    return Err(e.into())
-                },
-            };
-            if let Err(e) = self.write(file) 
-            {
-                log::error!("Failed to write script into file '{}': {}", 
-                     path.as_ref().display(), e);
-                return Err(e.into())
-            }
+            self.write_persistent(path.as_ref(), false)?;
             Ok(ParserScript::Persistent(path.as_ref().into()))
         } else {
-            let mut temp_file = match 
-                tempfile::Builder::new().prefix(".pkgbuild-rs").tempfile() 
-            {
-                Ok(temp_file) => temp_file,
-                Err(e) => {
-                    log::error!("Failed to create tempfile for script: {}", e);
-                    return Err(e.into());
-                },
-            };
-            if let Err(e) = self.write(temp_file.as_file_mut()) 
-            {
-                log::error!("Failed to write script into temp file '{}': {}", 
-                     temp_file.path().display(), e);
-                return Err(e.into())
-            }
-            Ok(ParserScript::Temporary(temp_file))
+            let mut builder = tempfile::Builder::new();
+            builder.prefix(&self.temp_prefix);
+            self.build_temporary(match &self.temp_dir {
+                Some(dir) => builder.tempfile_in(dir),
+                None => builder.tempfile(),
+            })
         }
     }
 
-    /// Build a `ParserScript`, at given path, which would could later be used 
-    /// to parse `PKGBUILD`s
-    /// 
-    /// Return `Ok(ParserScript)` if write was successfull, return `Err` on IO
-    /// Error.
-    #[cfg(not(feature = "tempfile"))]
-    pub fn build<P: AsRef<Path>>(&self, path: P) -> Result<ParserScript> {
-        let file = match std::fs::File::create(&path) {
-            Ok(file) => file,
+    /// Build a temporary `ParserScript` under `/tmp` of the chroot `root`, for
+    /// a `Parser` with `ParserOptions::set_chroot()` set to the same `root`.
+    /// The makepkg library and config would then be looked up inside the
+    /// chroot.
+    #[cfg(feature = "tempfile")]
+    pub fn build_in_chroot<P: AsRef<Path>>(&self, root: P) 
+        -> Result<ParserScript> 
+    {
+        self.build_temporary(tempfile::Builder::new()
+            .prefix(&self.temp_prefix).tempfile_in(root.as_ref().join("tmp")))
+    }
+
+    /// Write the script into a newly created temporary file
+    #[cfg(feature = "tempfile")]
+    fn build_temporary(
+        &self, temp_file: std::io::Result<tempfile::NamedTempFile>
+    ) -> Result<ParserScript> 
+    {
+        let mut temp_file = match temp_file {
+            Ok(temp_file) => temp_file,
             Err(e) => {
-                log::error!("Failed to create script file at '{}': {}",
-                                path.as_ref().display(), e);
-                return Err(e.into())
+                log::error!("Failed to create tempfile for script: {}", e);
+                return Err(e.into());
             },
         };
-        if let Err(e) = self.write(
-            BufWriter::new(file)) 
+        if let Err(e) = self.write(temp_file.as_file_mut()) 
         {
-            log::error!("Failed to write script into file '{}': {}", 
-                    path.as_ref().display(), e);
+            log::error!("Failed to write script into temp file '{}': {}", 
+                 temp_file.path().display(), e);
             return Err(e.into())
         }
-        Ok(ParserScript::Persistent(path.as_ref().into()))
+        if self.keep_on_error {
+            Ok(ParserScript::TemporaryKeptOnError(temp_file))
+        } else {
+            Ok(ParserScript::Temporary(temp_file))
+        }
     }
-}
 
-pub enum ParserScript {
-    #[cfg(feature = "tempfile")]
-    Temporary(tempfile::NamedTempFile),
+    /// Build a `ParserScript`, at given path, which would could later be used 
+    /// to parse `PKGBUILD`s, an existing file with the same content is left
+    /// untouched, to keep its mtime.
+    /// 
+    /// Return `Ok(ParserScript)` if write was successfull, return `Err` on IO
+    /// Error.
+    #[cfg(not(feature = "tempfile"))]
+    pub fn build<P: AsRef<Path>>(&self, path: P) -> Result<ParserScript> {
+        self.write_persistent(path.as_ref(), false)?;
+        Ok(ParserScript::Persistent(path.as_ref().into()))
+    }
+}
+
+/// The on-disk parser script, to share one among multiple `Parser`s, e.g. 
+/// across threads, wrap it in an `Arc` and use `Parser::with_script()`
+#[derive(Debug)]
+pub enum ParserScript {
+    #[cfg(feature = "tempfile")]
+    Temporary(tempfile::NamedTempFile),
+    /// A temporary script kept when the parser fails with it
+    #[cfg(feature = "tempfile")]
+    TemporaryKeptOnError(tempfile::NamedTempFile),
     Persistent(PathBuf),
 }
 
 impl AsRef<OsStr> for ParserScript {
     fn as_ref(&self) -> &OsStr {
+        self.path().as_os_str()
+    }
+}
+
+impl AsRef<ParserScript> for ParserScript {
+    fn as_ref(&self) -> &ParserScript {
+        self
+    }
+}
+
+impl ParserScript {
+    /// Get the path of the script
+    pub fn path(&self) -> &Path {
         match self {
             #[cfg(feature = "tempfile")]
-            ParserScript::Temporary(temp_file) => 
-                temp_file.path().as_os_str(),
-            ParserScript::Persistent(path) => path.as_os_str(),
+            ParserScript::Temporary(temp_file) |
+            ParserScript::TemporaryKeptOnError(temp_file) => temp_file.path(),
+            ParserScript::Persistent(path) => path,
         }
     }
-}
 
-impl ParserScript {
+    /// Make the script persistent, i.e. not removed on drop for a temporary
+    /// one, return its path
+    pub fn persist(self) -> Result<PathBuf> {
+        match self {
+            #[cfg(feature = "tempfile")]
+            ParserScript::Temporary(temp_file) |
+            ParserScript::TemporaryKeptOnError(temp_file) => 
+                match temp_file.keep() {
+                    Ok((_, path)) => Ok(path),
+                    Err(e) => {
+                        log::error!("Failed to persist script: {}", e);
+                        Err(e.error.into())
+                    },
+                },
+            ParserScript::Persistent(path) => Ok(path),
+        }
+    }
+
+    /// Keep a copy of a `TemporaryKeptOnError` script, as a hard link at its
+    /// path suffixed with `.kept`, return the path of the copy
+    fn keep_on_error(&self) -> Option<PathBuf> {
+        #[cfg(feature = "tempfile")]
+        if let ParserScript::TemporaryKeptOnError(temp_file) = self {
+            let mut kept = temp_file.path().as_os_str().to_owned();
+            kept.push(".kept");
+            let kept = PathBuf::from(kept);
+            return match std::fs::hard_link(temp_file.path(), &kept) {
+                Ok(_) => Some(kept),
+                // Kept by an earlier failure
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => 
+                    Some(kept),
+                Err(e) => {
+                    log::error!("Failed to keep script at '{}': {}", 
+                        kept.display(), e);
+                    None
+                }
+            }
+        }
+        None
+    }
+
+    /// Read back the content of the script
+    pub fn content(&self) -> Result<Vec<u8>> {
+        std::fs::read(self.path()).map_err(|e| {
+            log::error!("Failed to read script '{}': {}", 
+                self.path().display(), e);
+            e.into()
+        })
+    }
+
     /// Generate a parser script at the given path, or create a named tempfile
     /// to store the script. 
     /// 
@@ -356,6 +1149,129 @@ impl ParserScript {
     }
 }
 
+/// The delimiter between paths of `PKGBUILD`s fed to the parser script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathDelimiter {
+    /// Paths are separated by `\n`, paths containing `\n` are rejected
+    #[default]
+    Newline,
+    /// Paths are separated by `\0`, this works with any path
+    Nul,
+}
+
+impl PathDelimiter {
+    fn as_byte(&self) -> u8 {
+        match self {
+            PathDelimiter::Newline => b'\n',
+            PathDelimiter::Nul => b'\0',
+        }
+    }
+}
+
+/// How the output of the parser script is handled when it has keys this 
+/// library does not know, e.g. from a newer script or lines echoed by a 
+/// `PKGBUILD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Unknown keys fail the parsing with `Error::ParserScriptIllegalOutput`
+    #[default]
+    Strict,
+    /// Unknown keys are collected into the `extra` maps of the structs, and
+    /// duplicated architecture sections are merged in first-seen order with
+    /// a warning in `Pkgbuild::warnings`, bad nesting of sections still fails
+    /// the parsing
+    Lenient,
+}
+
+/// Which environment variables of the current process the parser inherits
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum EnvPolicy {
+    /// Inherit the whole environment
+    #[default]
+    Inherit,
+    /// Start with an empty environment, note `bash` would then use its own 
+    /// default `PATH`
+    Clear,
+    /// Start with an empty environment, and only inherit these variables
+    Allowlist(Vec<OsString>),
+}
+
+/// Options to run the parser inside a `bubblewrap` sandbox
+#[cfg(feature = "sandbox")]
+#[derive(Debug, Clone)]
+pub struct SandboxOptions {
+    /// The `bwrap` binary
+    /// 
+    /// Default: `/usr/bin/bwrap`
+    pub bwrap: PathBuf,
+
+    /// Extra `(host, sandbox)` paths to bind read-only into the sandbox, e.g.
+    /// a non-standard makepkg library. `/usr` and the makepkg config under
+    /// `/etc` are always bound.
+    /// 
+    /// Default: empty
+    pub ro_binds: Vec<(PathBuf, PathBuf)>,
+
+    /// Share the network with the sandbox
+    /// 
+    /// Default: `false`
+    pub share_net: bool,
+}
+
+#[cfg(feature = "sandbox")]
+impl Default for SandboxOptions {
+    fn default() -> Self {
+        Self {
+            bwrap: "/usr/bin/bwrap".into(),
+            ro_binds: Vec::new(),
+            share_net: false,
+        }
+    }
+}
+
+#[cfg(feature = "sandbox")]
+impl SandboxOptions {
+    /// Get a `SandboxOptions` instance with default settings: no extra binds,
+    /// no network
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the `bwrap` binary
+    pub fn set_bwrap<P: Into<PathBuf>>(&mut self, bwrap: P) -> &mut Self {
+        self.bwrap = bwrap.into();
+        self
+    }
+
+    /// Add an extra path to bind read-only into the sandbox
+    pub fn add_ro_bind<P1, P2>(&mut self, host: P1, sandbox: P2) -> &mut Self
+    where
+        P1: Into<PathBuf>,
+        P2: Into<PathBuf>
+    {
+        self.ro_binds.push((host.into(), sandbox.into()));
+        self
+    }
+
+    /// Set whether to share the network with the sandbox
+    pub fn set_share_net(&mut self, share_net: bool) -> &mut Self {
+        self.share_net = share_net;
+        self
+    }
+}
+
+/// The path of the parser script inside the sandbox
+#[cfg(feature = "sandbox")]
+const SANDBOX_SCRIPT: &str = "/parser.bash";
+
+/// The dir under which the dirs of `PKGBUILD`s are bound inside the sandbox
+#[cfg(feature = "sandbox")]
+const SANDBOX_PKGBUILDS: &str = "/pkgbuilds";
+
+/// A callback reporting progress as `(done, total)`, see 
+/// `ParserOptions::set_progress()`
+pub type ProgressCallback = Box<dyn Fn(usize, usize) + Send>;
+
 /// Options used by `ParserScript` when parsing `PKGBUILD`s
 pub struct ParserOptions {
     /// The interpreter used for the parser script, changing this only makes
@@ -368,13 +1284,175 @@ pub struct ParserOptions {
     /// 
     /// Default: `None`
     pub work_dir: Option<PathBuf>,
+
+    /// The delimiter between paths fed to the parser script, set this to
+    /// `PathDelimiter::Nul` to parse `PKGBUILD`s with `\n` in their paths
+    /// 
+    /// Default: `PathDelimiter::Newline`
+    pub path_delimiter: PathDelimiter,
+
+    /// Which environment variables the parser inherits from the current 
+    /// process. The paths of the makepkg library and config are baked into
+    /// the script, so they do not need to be inherited.
+    /// 
+    /// Default: `EnvPolicy::Inherit`
+    pub env_policy: EnvPolicy,
+
+    /// The niceness of the parser
+    /// 
+    /// Default: `None`, inherit from the current process
+    pub nice: Option<i32>,
+
+    /// The CPU time limit, rounded up to seconds. As each `PKGBUILD` is 
+    /// sourced in its own subshell, this effectively limits each of them. 
+    /// Exceeding it results in `Error::ParserKilledBySignal(SIGXCPU)`.
+    /// 
+    /// Default: `None`
+    pub rlimit_cpu: Option<Duration>,
+
+    /// The address space limit in bytes
+    /// 
+    /// Default: `None`
+    pub rlimit_as: Option<u64>,
+
+    /// Drop to this `(uid, gid)` before running the parser, clearing the
+    /// supplementary groups. Failing to do so fails the spawn. The parser 
+    /// script would be made readable by everyone, and the makepkg library, 
+    /// config and `PKGBUILD`s must be readable by that user.
+    /// 
+    /// Default: `None`, keep the current ones
+    pub uid_gid: Option<(u32, u32)>,
+
+    /// Mark all file descriptors above stderr close-on-exec in the parser
+    /// before running the interpreter, so the parser does not inherit the ones
+    /// the current process opened without `O_CLOEXEC`, e.g. sockets, which
+    /// the sourced `PKGBUILD`s could otherwise use
+    /// 
+    /// Default: `true`
+    pub close_fds: bool,
+
+    /// Run the parser inside a `bubblewrap` sandbox, with a read-only view of
+    /// the system, the script and the dirs of the `PKGBUILD`s
+    /// 
+    /// Default: `None`
+    #[cfg(feature = "sandbox")]
+    pub sandbox: Option<SandboxOptions>,
+
+    /// Enter this chroot before running the parser, which needs root. The
+    /// interpreter, the makepkg library and config, `work_dir` and the paths
+    /// of `PKGBUILD`s are then all looked up inside the chroot. The script is
+    /// also, unless it's stored under the chroot, in which case it's remapped;
+    /// use `ParserScriptBuilder::build_in_chroot()` to create such a script.
+    /// 
+    /// This should not be combined with `sandbox`, as `bwrap` would then be
+    /// looked up inside the chroot.
+    /// 
+    /// Default: `None`
+    pub chroot: Option<PathBuf>,
+
+    /// Override the `CARCH` from the makepkg config, for `PKGBUILD`s that 
+    /// branch on it. Architecture-specific arrays are always dumped for all
+    /// architectures regardless of this.
+    /// 
+    /// Default: `None`
+    pub carch: Option<OsString>,
+
+    /// The max bytes read from the parser's stdout, exceeding it kills the 
+    /// parser and results in `Error::OutputLimitExceeded`, as a `PKGBUILD` 
+    /// could write endlessly to stdout when sourced
+    /// 
+    /// Default: `Some(256 MiB)`
+    pub max_output: Option<usize>,
+
+    /// The max bytes kept from the parser's stderr, the rest is read but
+    /// dropped
+    /// 
+    /// Default: `Some(16 MiB)`
+    pub max_stderr: Option<usize>,
+
+    /// How unknown keys in the output of the parser script are handled
+    /// 
+    /// Default: `Strictness::Strict`
+    pub strictness: Strictness,
+
+    /// Only parse the first of the input paths that point to the same 
+    /// `PKGBUILD`, compared after canonicalizing them under `work_dir`, the
+    /// results are then one for each unique path. Inside a chroot they're
+    /// compared without resolving symlinks.
+    /// 
+    /// Default: `true`
+    pub dedup_paths: bool,
+
+    /// Extra environment variables set for the parser, applied after 
+    /// `env_policy`, e.g. `SRCDEST` or `PACKAGER`
+    /// 
+    /// Default: empty
+    pub envs: Vec<(OsString, OsString)>,
+
+    /// A callback invoked with `(done, total)` each time a `PKGBUILD` is fully
+    /// dumped by the parser, useful to show a progress bar for long batches.
+    /// 
+    /// A panic inside the callback is caught and logged, and the callback
+    /// would then not be called again for the rest of the batch, the parsing
+    /// itself is not affected.
+    /// 
+    /// The callback is behind a lock, so it does not need to be `Sync` for the
+    /// `Parser` to be shared by threads, and it's never called concurrently,
+    /// not even by the chunks of `Parser::parse_multi_par()`.
+    /// 
+    /// Default: `None`
+    pub progress: Option<Mutex<ProgressCallback>>,
+}
+
+impl std::fmt::Debug for ParserOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("ParserOptions");
+        debug.field("intepreter", &self.intepreter)
+            .field("work_dir", &self.work_dir)
+            .field("path_delimiter", &self.path_delimiter)
+            .field("env_policy", &self.env_policy)
+            .field("nice", &self.nice)
+            .field("rlimit_cpu", &self.rlimit_cpu)
+            .field("rlimit_as", &self.rlimit_as)
+            .field("uid_gid", &self.uid_gid)
+            .field("close_fds", &self.close_fds);
+        #[cfg(feature = "sandbox")]
+        debug.field("sandbox", &self.sandbox);
+        debug.field("chroot", &self.chroot)
+            .field("carch", &self.carch)
+            .field("max_output", &self.max_output)
+            .field("max_stderr", &self.max_stderr)
+            .field("strictness", &self.strictness)
+            .field("dedup_paths", &self.dedup_paths)
+            .field("envs", &self.envs)
+            .field("progress", 
+                &self.progress.as_ref().map(|_|"Fn(usize, usize)"))
+            .finish()
+    }
 }
 
 impl Default for ParserOptions {
     fn default() -> Self {
         Self {
             intepreter: "/bin/bash".into(),
-            work_dir: None
+            work_dir: None,
+            path_delimiter: PathDelimiter::default(),
+            env_policy: EnvPolicy::default(),
+            nice: None,
+            rlimit_cpu: None,
+            rlimit_as: None,
+            uid_gid: None,
+            close_fds: true,
+            #[cfg(feature = "sandbox")]
+            sandbox: None,
+            chroot: None,
+            carch: None,
+            max_output: Some(0x1000_0000),
+            max_stderr: Some(0x100_0000),
+            strictness: Strictness::default(),
+            dedup_paths: true,
+            envs: Vec::new(),
+            progress: None,
         }
     }
 }
@@ -401,6 +1479,189 @@ impl ParserOptions {
         self.work_dir = work_dir.map(|path|path.into());
         self
     }
+
+    /// Set the delimiter between paths fed to the parser script
+    pub fn set_path_delimiter(&mut self, path_delimiter: PathDelimiter) 
+        -> &mut Self
+    {
+        self.path_delimiter = path_delimiter;
+        self
+    }
+
+    /// Set which environment variables the parser inherits
+    pub fn set_env_policy(&mut self, env_policy: EnvPolicy) -> &mut Self {
+        self.env_policy = env_policy;
+        self
+    }
+
+    /// Set the niceness of the parser
+    pub fn set_nice(&mut self, nice: Option<i32>) -> &mut Self {
+        self.nice = nice;
+        self
+    }
+
+    /// Set the CPU time limit of the parser
+    pub fn set_rlimit_cpu(&mut self, rlimit_cpu: Option<Duration>) 
+        -> &mut Self 
+    {
+        self.rlimit_cpu = rlimit_cpu;
+        self
+    }
+
+    /// Set the address space limit of the parser, in bytes
+    pub fn set_rlimit_as(&mut self, rlimit_as: Option<u64>) -> &mut Self {
+        self.rlimit_as = rlimit_as;
+        self
+    }
+
+    /// Set the `(uid, gid)` to drop to before running the parser
+    pub fn set_uid_gid(&mut self, uid_gid: Option<(u32, u32)>) -> &mut Self {
+        self.uid_gid = uid_gid;
+        self
+    }
+
+    /// Set whether to keep the file descriptors of the current process from 
+    /// the parser
+    pub fn set_close_fds(&mut self, close_fds: bool) -> &mut Self {
+        self.close_fds = close_fds;
+        self
+    }
+
+    /// Set the `bubblewrap` sandbox to run the parser in
+    #[cfg(feature = "sandbox")]
+    pub fn set_sandbox(&mut self, sandbox: Option<SandboxOptions>) 
+        -> &mut Self 
+    {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Set the max bytes read from the parser's stdout, `None` for unlimited
+    pub fn set_max_output(&mut self, max_output: Option<usize>) -> &mut Self {
+        self.max_output = max_output;
+        self
+    }
+
+    /// Set the max bytes kept from the parser's stderr, `None` for unlimited
+    pub fn set_max_stderr(&mut self, max_stderr: Option<usize>) -> &mut Self {
+        self.max_stderr = max_stderr;
+        self
+    }
+
+    /// Set how unknown keys in the output of the parser script are handled
+    pub fn set_strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Set whether to only parse the first of duplicated input paths
+    pub fn set_dedup_paths(&mut self, dedup_paths: bool) -> &mut Self {
+        self.dedup_paths = dedup_paths;
+        self
+    }
+
+    /// Set the chroot to enter before running the parser
+    pub fn set_chroot<P: Into<PathBuf>>(&mut self, chroot: Option<P>) 
+        -> &mut Self 
+    {
+        self.chroot = chroot.map(|chroot|chroot.into());
+        self
+    }
+
+    /// Set the `CARCH` to override the one from the makepkg config
+    pub fn set_carch<S: Into<OsString>>(&mut self, carch: Option<S>) 
+        -> &mut Self
+    {
+        self.carch = carch.map(|carch|carch.into());
+        self
+    }
+
+    /// Set an extra environment variable for the parser
+    pub fn set_env<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<OsString>,
+        V: Into<OsString>
+    {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Set the callback to report progress as `(done, total)` during parsing,
+    /// it could capture a `Cell` or `RefCell` as its state
+    pub fn set_progress(
+        &mut self, progress: Option<ProgressCallback>
+    ) -> &mut Self
+    {
+        self.progress = progress.map(Mutex::new);
+        self
+    }
+
+    /// Get a `Sync` wrapper calling the progress callback through its lock, 
+    /// `None` if no callback is set
+    fn progress_reporter(&self) 
+        -> Option<impl Fn(usize, usize) + Send + Sync + '_> 
+    {
+        self.progress.as_ref().map(|progress| move |done, total| {
+            // A panic in it was already logged by the tracker
+            let callback = progress.lock().unwrap_or_else(
+                std::sync::PoisonError::into_inner);
+            callback(done, total)
+        })
+    }
+}
+
+/// Scan the child stdout incrementally for the `END` markers that close
+/// top-level `PKGBUILD` blocks, and report the progress to the callback
+struct ProgressTracker<'a> {
+    callback: &'a (dyn Fn(usize, usize) + Send + Sync),
+    total: usize,
+    done: usize,
+    depth: usize,
+    scanned: usize,
+    poisoned: bool,
+}
+
+impl<'a> ProgressTracker<'a> {
+    fn new(callback: &'a (dyn Fn(usize, usize) + Send + Sync), total: usize)
+        -> Self
+    {
+        Self { callback, total, done: 0, depth: 0, scanned: 0, poisoned: false }
+    }
+
+    /// Scan the complete lines in `output` that were not scanned yet, `output`
+    /// must be the whole stdout collected so far
+    fn update(&mut self, output: &[u8]) {
+        while let Some(end) = output[self.scanned..].iter().position(
+            |byte|*byte == b'\n') 
+        {
+            let line = &output[self.scanned..self.scanned + end];
+            self.scanned += end + 1;
+            match line {
+                b"PKGBUILD" | b"PACKAGE" | b"ARCH" | b"PACKAGEARCH" => 
+                    self.depth += 1,
+                b"END" if self.depth > 0 => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        self.done += 1;
+                        self.report()
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+
+    fn report(&mut self) {
+        if self.poisoned { return }
+        let (callback, done, total) = (self.callback, self.done, self.total);
+        if std::panic::catch_unwind(std::panic::AssertUnwindSafe(
+            ||callback(done, total))).is_err() 
+        {
+            log::error!("Progress callback panicked, it would not be called \
+                again for this batch");
+            self.poisoned = true
+        }
+    }
 }
 
 fn take_child_io<I>(from: &mut Option<I>) -> Result<I> {
@@ -425,145 +1686,301 @@ fn set_nonblock<H: AsRawFd>(handle: &H) -> Result<()> {
     }
 }
 
-struct ChildIOs {
-    stdin: ChildStdin,
-    stdout: ChildStdout,
-    stderr: ChildStderr
+/// The limits on what's read from the child
+#[derive(Clone, Copy)]
+struct OutputLimits {
+    stdout: Option<usize>,
+    stderr: Option<usize>,
 }
 
-impl TryFrom<&mut Child> for ChildIOs {
-    type Error = Error;
+impl OutputLimits {
+    /// Fail if the stdout read so far is over the limit
+    fn check_stdout(&self, stdout: &[u8]) -> Result<()> {
+        match self.stdout {
+            Some(limit) if stdout.len() > limit => {
+                log::error!("Child stdout exceeded limit of {} bytes", limit);
+                Err(Error::OutputLimitExceeded { limit })
+            },
+            _ => Ok(()),
+        }
+    }
 
-    fn try_from(child: &mut Child) -> Result<Self> {
-        let stdin = take_child_io(&mut child.stdin)?;
-        let stdout = take_child_io(&mut child.stdout)?;
-        let stderr = take_child_io(&mut child.stderr)?;
-        Ok(Self { stdin, stdout, stderr })
+    /// Truncate the stderr read so far to the limit, return whether the limit
+    /// is reached so further reads could be dropped
+    fn cap_stderr(&self, stderr: &mut Vec<u8>) -> bool {
+        match self.stderr {
+            Some(limit) if stderr.len() >= limit => {
+                if stderr.len() > limit {
+                    log::warn!("Child stderr exceeded limit of {} bytes, the \
+                        rest is dropped", limit);
+                    stderr.truncate(limit)
+                }
+                true
+            },
+            _ => false,
+        }
     }
 }
 
-
-impl ChildIOs {
-    /// Set the underlying child stdin/out/err handles to non-blocking
-    #[cfg(feature = "nothread")]
-    fn set_nonblock(&mut self) -> Result<()> {   
-        set_nonblock(&self.stdin)?;
-        set_nonblock(&self.stdout)?;
-        set_nonblock(&self.stderr)
+/// Read the child stdout till EOF, while reporting progress on every read,
+/// and failing if it's over the limit
+#[cfg(not(feature = "nothread"))]
+fn read_stdout(
+    reader: &mut ChildStdout, output: &mut Vec<u8>, 
+    mut progress: Option<ProgressTracker>, limits: OutputLimits
+) -> Result<()> 
+{
+    let mut buffer = vec![0; 0x10000];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read_this) => {
+                output.extend_from_slice(&buffer[0..read_this]);
+                limits.check_stdout(output)?;
+                if let Some(progress) = &mut progress {
+                    progress.update(output)
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => {
+                log::error!("Child stdout reader encountered IO error: {}", e);
+                return Err(e.into())
+            },
+        }
     }
+}
 
-    /// This is a sub-optimal single-thread implementation, extra times would
-    /// be wasted on inefficient page-by-page try-reading to avoid jamming the
-    /// child stdin/out/err.
-    #[cfg(feature = "nothread")]
-    fn work(mut self, input: &[u8]) -> Result<(Vec<u8>, Vec<u8>)>{
-        use libc::{PIPE_BUF, EAGAIN};
+/// Write all input to the child stdin, the child quitting before reading all
+/// of it is not an error here, as that's told by its exit status instead
+#[cfg(not(feature = "nothread"))]
+fn write_stdin(writer: &mut ChildStdin, input: &[u8]) -> std::io::Result<()> {
+    match writer.write_all(input) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+            log::debug!("Child quit before reading all input");
+            Ok(())
+        },
+        r => r
+    }
+}
 
-        self.set_nonblock()?;
+/// Read the child stderr till EOF, only keeping what's under the limit
+#[cfg(not(feature = "nothread"))]
+fn read_stderr(reader: &mut ChildStderr, limits: OutputLimits) 
+    -> std::io::Result<Vec<u8>> 
+{
+    let mut buffer = vec![0; 0x10000];
+    let mut output = Vec::new();
+    let mut full = false;
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(output),
+            Ok(read_this) => if ! full {
+                output.extend_from_slice(&buffer[0..read_this]);
+                full = limits.cap_stderr(&mut output)
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Read whatever is available from a non-blocking reader, return `true` if
+/// the reader has reached EOF
+#[cfg(feature = "nothread")]
+fn read_nonblock<R: Read>(
+    reader: &mut R, buffer: &mut [u8], output: &mut Vec<u8>, name: &str
+) -> Result<bool> 
+{
+    match reader.read(buffer) {
+        Ok(0) => Ok(true),
+        Ok(read_this) => {
+            output.extend_from_slice(&buffer[0..read_this]);
+            Ok(false)
+        },
+        Err(e) => if e.raw_os_error() == Some(libc::EAGAIN) {
+            Ok(false)
+        } else {
+            log::error!("Failed to read from {}: {}", name, e);
+            Err(e.into())
+        },
+    }
+}
+
+struct ChildIOs {
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    stderr: ChildStderr
+}
+
+impl TryFrom<&mut Child> for ChildIOs {
+    type Error = Error;
+
+    fn try_from(child: &mut Child) -> Result<Self> {
+        let stdin = take_child_io(&mut child.stdin)?;
+        let stdout = take_child_io(&mut child.stdout)?;
+        let stderr = take_child_io(&mut child.stderr)?;
+        Ok(Self { stdin, stdout, stderr })
+    }
+}
+
+
+/// Kill the child early along with the subshells and commands it spawned,
+/// which are all in its process group led by the child, it's still waited by
+/// the caller
+fn kill_child(id: u32) {
+    use nix::{sys::signal::{killpg, Signal}, unistd::Pid};
+
+    if let Err(e) = killpg(Pid::from_raw(id as i32), Signal::SIGKILL) {
+        log::error!("Failed to kill child process group: {}", e)
+    }
+}
+
+/// Owns a spawned child, and kills its whole process group then reaps it if
+/// dropped before being waited, e.g. on a panic while doing IO, so neither 
+/// the parser nor anything the `PKGBUILD`s spawned is left behind
+struct ChildGuard(Option<Child>);
+
+impl ChildGuard {
+    fn child(&mut self) -> &mut Child {
+        // Only taken when consumed or dropped
+        self.0.as_mut().expect("Child taken from guard")
+    }
+
+    /// Wait for the child to finish, with `wait4()` if `usage` is set
+    fn wait(mut self, usage: Option<&mut ChildUsage>) 
+        -> std::io::Result<ExitStatus> 
+    {
+        // Disarmed first, as the ID could be reused once the child is reaped
+        let mut child = self.0.take().expect("Child taken from guard");
+        match usage {
+            Some(usage) => usage.wait(&child),
+            None => child.wait(),
+        }
+    }
+
+    /// Kill the whole process group of the child, and reap the child
+    fn kill(mut self) -> std::io::Result<ExitStatus> {
+        let mut child = self.0.take().expect("Child taken from guard");
+        kill_child(child.id());
+        child.wait()
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.0.take() {
+            kill_child(child.id());
+            if let Err(e) = child.wait() {
+                log::error!("Failed to wait for killed child: {}", e)
+            }
+        }
+    }
+}
+
+impl ChildIOs {
+    /// Set the underlying child stdin/out/err handles to non-blocking
+    #[cfg(feature = "nothread")]
+    fn set_nonblock(&mut self) -> Result<()> {   
+        set_nonblock(&self.stdin)?;
+        set_nonblock(&self.stdout)?;
+        set_nonblock(&self.stderr)
+    }
+
+    /// This is a single-thread implementation, all of child stdin/out/err are
+    /// multiplexed with `poll()`, and IO is only attempted on the ready ones,
+    /// so we neither jam the child nor spin on `EAGAIN`. The child stdin is 
+    /// closed once all input is written, and the child stdout/err are removed
+    /// from the set once they reach EOF.
+    /// 
+    /// The child is killed if its stdout is over the limit.
+    #[cfg(feature = "nothread")]
+    fn work(mut self, input: &[u8], mut progress: Option<ProgressTracker>, 
+        limits: OutputLimits, child: &mut Child
+    ) -> Result<(Vec<u8>, Vec<u8>)>
+    {
+        use libc::EAGAIN;
+        use nix::{errno::Errno, poll::{poll, PollFd, PollFlags, PollTimeout}};
+        use std::os::fd::AsFd;
+
+        self.set_nonblock()?;
+        let mut stdin = Some(self.stdin);
         let mut stdout = Vec::new();
         let mut stderr = Vec::new();
-        let mut buffer = vec![0; PIPE_BUF];
+        let mut buffer = vec![0; 0x10000];
         let buffer = buffer.as_mut_slice();
         let mut written = 0;
         let total = input.len();
         let mut stdout_finish = false;
         let mut stderr_finish = false;
-        // Rotate among stdin, stdout and stderr to avoid jamming
         loop {
-            // Try to write at most the length of a PIPE buffer
-            let mut end = written + PIPE_BUF;
-            if end > total {
-                end = total;
-            }
-            match self.stdin.write(&input[written..end]) {
-                Ok(written_this) => {
-                    written += written_this;
-                    if written >= total {
-                        drop(self.stdin);
-                        break
-                    }
-                },
-                Err(e) => 
-                    if let Some(EAGAIN) = e.raw_os_error() {
-                        log::warn!("Child stdin blocked")
-                    } else {
-                        log::error!("Failed to write to child-in: {}", e);
-                        return Err(e.into())
-                    },
+            if written >= total {
+                // Close it so the child sees EOF
+                stdin = None
             }
-            if ! stdout_finish {
-                match self.stdout.read (&mut buffer[..]) {
-                    Ok(read_this) =>
-                        if read_this > 0 {
-                            stdout.extend_from_slice(&buffer[0..read_this])
-                        } else {
-                            stdout_finish = true;
-                        },
-                    Err(e) => 
-                        if let Some(EAGAIN) = e.raw_os_error() {
-                            log::warn!("Child stdout blocked")
-                        } else {
-                            log::error!("Failed to read from child-out: {}", e);
-                            return Err(e.into())
-                        },
-                }
+            if stdin.is_none() && stdout_finish && stderr_finish {
+                break
             }
-            if ! stderr_finish {
-                match self.stderr.read (&mut buffer[..]) {
-                    Ok(read_this) =>
-                        if read_this > 0 {
-                            stderr.extend_from_slice(&buffer[0..read_this])
-                        } else {
-                            stderr_finish = true;
-                        }
-                    Err(e) => 
-                        if let Some(EAGAIN) = e.raw_os_error() {
-                            log::warn!("Child stderr blocked")
-                        } else {
-                            log::error!("Failed to read from child-err: {}", e);
-                            return Err(e.into())
-                        },
+            let mut fds = Vec::with_capacity(3);
+            let stdin_id = stdin.as_ref().map(|stdin| {
+                fds.push(PollFd::new(stdin.as_fd(), PollFlags::POLLOUT));
+                fds.len() - 1
+            });
+            let stdout_id = if stdout_finish { None } else {
+                fds.push(PollFd::new(self.stdout.as_fd(), PollFlags::POLLIN));
+                Some(fds.len() - 1)
+            };
+            let stderr_id = if stderr_finish { None } else {
+                fds.push(PollFd::new(self.stderr.as_fd(), PollFlags::POLLIN));
+                Some(fds.len() - 1)
+            };
+            match poll(&mut fds, PollTimeout::NONE) {
+                Ok(_) => (),
+                Err(Errno::EINTR) => continue,
+                Err(e) => {
+                    log::error!("Failed to poll child stdin/out/err: {}", e);
+                    return Err(e.into())
                 }
             }
-        }
-        // Rotate between stdout and stderr to avoid jamming
-        loop {
-            if ! stdout_finish {
-                match self.stdout.read (&mut buffer[..]) {
-                    Ok(read_this) =>
-                        if read_this > 0 {
-                            stdout.extend_from_slice(&buffer[0..read_this])
-                        } else {
-                            stdout_finish = true;
-                        },
-                    Err(e) => 
-                        if let Some(EAGAIN) = e.raw_os_error() {
-                            log::warn!("Child stdout blocked")
-                        } else {
-                            log::error!("Failed to read from child-out: {}", e);
-                            return Err(e.into())
-                        },
+            // Also ready on POLLHUP and POLLERR, the following IO reports them
+            let ready = |id: Option<usize>| match id {
+                Some(id) => fds[id].revents().is_some_and(
+                                |revents|! revents.is_empty()),
+                None => false,
+            };
+            let stdin_ready = ready(stdin_id);
+            let stdout_ready = ready(stdout_id);
+            let stderr_ready = ready(stderr_id);
+            drop(fds);
+            if let (true, Some(stdin)) = (stdin_ready, &mut stdin) {
+                // Write as much as the pipe takes, the child consumes the
+                // rest later
+                match stdin.write(&input[written..]) {
+                    Ok(written_this) => written += written_this,
+                    Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+                        log::debug!("Child quit before reading all input");
+                        written = total
+                    },
+                    Err(e) => if e.raw_os_error() != Some(EAGAIN) {
+                        log::error!("Failed to write to child-in: {}", e);
+                        return Err(e.into())
+                    },
                 }
             }
-            if ! stderr_finish {
-                match self.stderr.read (&mut buffer[..]) {
-                    Ok(read_this) =>
-                        if read_this > 0 {
-                            stderr.extend_from_slice(&buffer[0..read_this])
-                        } else {
-                            stderr_finish = true;
-                        }
-                    Err(e) => 
-                        if let Some(EAGAIN) = e.raw_os_error() {
-                            log::warn!("Child stderr blocked")
-                        } else {
-                            log::error!("Failed to read from child-err: {}", e);
-                            return Err(e.into())
-                        },
+            if stdout_ready {
+                stdout_finish = read_nonblock(
+                    &mut self.stdout, buffer, &mut stdout, "child-out")?;
+                if let Err(e) = limits.check_stdout(&stdout) {
+                    kill_child(child.id());
+                    return Err(e)
+                }
+                if let Some(progress) = &mut progress {
+                    progress.update(&stdout)
                 }
             }
-            if stdout_finish && stderr_finish {
-                break
+            if stderr_ready {
+                stderr_finish = read_nonblock(
+                    &mut self.stderr, buffer, &mut stderr, "child-err")?;
+                limits.cap_stderr(&mut stderr);
             }
         }
         drop(self.stdout);
@@ -571,22 +1988,35 @@ impl ChildIOs {
         Ok((stdout, stderr))
     }
 
-    /// The multi-threaded 
+    /// The multi-threaded implementation, stdin is written and stderr is read
+    /// in their own threads, while stdout is read in the current thread.
+    /// 
+    /// The child is killed if its stdout is over the limit, so the threads
+    /// would not be blocked on it.
     #[cfg(not(feature = "nothread"))]
-    fn work(mut self, mut input: Vec<u8>) 
-        -> Result<(Vec<u8>, Vec<u8>)> 
+    fn work(mut self, input: Vec<u8>, progress: Option<ProgressTracker>,
+        limits: OutputLimits, child: &mut Child
+    ) -> Result<(Vec<u8>, Vec<u8>)> 
     {
         let stdin_writer = spawn(move||
-            self.stdin.write_all(&mut input));
-        let stderr_reader = spawn(move|| {
-            let mut stderr = Vec::new();
-            self.stderr.read_to_end(&mut stderr).and(Ok(stderr))
-        });
+            write_stdin(&mut self.stdin, &input));
+        let stderr_reader = spawn(move||
+            read_stderr(&mut self.stderr, limits));
         let mut last_error = None;
         let mut stdout = Vec::new();
-        if let Err(e) = self.stdout.read_to_end(&mut stdout) {
-            log::error!("Child stdout reader encountered IO error: {}", e);
-            last_error = Some(e.into());
+        if let Err(e) = read_stdout(
+            &mut self.stdout, &mut stdout, progress, limits) 
+        {
+            if let Error::OutputLimitExceeded { limit: _ } = e {
+                kill_child(child.id());
+                // Also break the pipe for any grandchild still writing
+                drop(self.stdout);
+                // Errors from the other threads are caused by the killing
+                let _ = stdin_writer.join();
+                let _ = stderr_reader.join();
+                return Err(e)
+            }
+            last_error = Some(e);
         }
         match stdin_writer.join() {
             Ok(writer_r) => if let Err(e) = writer_r {
@@ -622,15 +2052,220 @@ impl ChildIOs {
     }
 }
 
-pub struct Parser {
+/// The parser, `S` could be anything that references a `ParserScript`, e.g.
+/// an `Arc<ParserScript>` shared by multiple `Parser`s
+/// 
+/// Each parsing runs the interpreter as a child in a new process group led 
+/// by it, so the child and everything the `PKGBUILD`s spawned could be killed
+/// together. The whole group is killed when parsing fails, e.g. on output 
+/// over `ParserOptions::set_max_output()`, or is interrupted by a panic or a
+/// dropped future. The group is not in the terminal's foreground group, so
+/// signals from the terminal, e.g. `SIGINT` on Ctrl-C, do not reach it: if 
+/// the caller is killed by one without unwinding, the child only quits once 
+/// it sees its stdin closed or its stdout broken, after the `PKGBUILD` being 
+/// sourced.
+#[derive(Debug)]
+pub struct Parser<S = ParserScript> {
     /// A on-disk or temporary file that stores the script that would be used
     /// to parse `PKGBUILD`s
-    pub script: ParserScript,
+    pub script: S,
 
     /// The options used when parsing `PKGBUILD`s
     pub options: ParserOptions,
 }
 
+/// The input fed to the parser script's stdin
+struct ParserInput {
+    bytes: Vec<u8>,
+    count: usize,
+    /// The paths fed, after deduplicating
+    paths: Vec<PathBuf>,
+    /// The dirs of the `PKGBUILD`s to bind into the sandbox, by their indices
+    /// under `SANDBOX_PKGBUILDS`
+    #[cfg(feature = "sandbox")]
+    dirs: Vec<PathBuf>,
+}
+
+/// The process attributes applied in the child before exec
+struct ChildAttributes {
+    nice: Option<i32>,
+    rlimit_cpu: Option<u64>,
+    rlimit_as: Option<u64>,
+    /// The root to chroot into, and the dir to chdir into inside it, prepared
+    /// as `CString`s so nothing is allocated in the child
+    chroot: Option<(CString, CString)>,
+    uid_gid: Option<(u32, u32)>,
+    close_fds: bool,
+}
+
+/// The time and resources used by the child, collected for `ParseReport`
+#[derive(Default)]
+struct ChildUsage {
+    spawn: Duration,
+    spawned: Option<Instant>,
+    io: Duration,
+    user: Duration,
+    sys: Duration,
+}
+
+impl ChildUsage {
+    /// Wait for the child with `wait4()` to get its resource usage, the child
+    /// must not be waited afterwards
+    fn wait(&mut self, child: &Child) -> std::io::Result<ExitStatus> {
+        use nix::libc::{rusage, timeval, wait4};
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut status = 0;
+        // SAFETY: rusage is plain data, an all-zero one is valid
+        let mut rusage: rusage = unsafe { std::mem::zeroed() };
+        // SAFETY: the pointers are valid during the call, and the child is 
+        // ours and not waited yet
+        while unsafe { wait4(child.id() as i32, &mut status, 0, &mut rusage) }
+            < 0 
+        {
+            let e = std::io::Error::last_os_error();
+            if e.kind() != std::io::ErrorKind::Interrupted {
+                return Err(e)
+            }
+        }
+        let duration = |time: timeval| Duration::new(time.tv_sec as u64, 
+            time.tv_usec as u32 * 1000);
+        if let Some(spawned) = self.spawned {
+            self.io = spawned.elapsed()
+        }
+        self.user = duration(rusage.ru_utime);
+        self.sys = duration(rusage.ru_stime);
+        Ok(ExitStatus::from_raw(status))
+    }
+}
+
+/// Mark all file descriptors above stderr close-on-exec, in the child before
+/// exec. They're not closed right away, as std still uses its own pipe to 
+/// report exec failures, the stdio pipes are already duplicated onto 0 to 2.
+/// 
+/// `close_range()` needs Linux 5.11, otherwise the ones listed in 
+/// `/proc/self/fd` are marked, or all up to `RLIMIT_NOFILE` if that's not
+/// mounted. Only async-signal-safe syscalls are used.
+fn mark_fds_cloexec() -> std::io::Result<()> {
+    use nix::libc::{c_int, c_uint, fcntl, syscall, SYS_close_range, 
+        CLOSE_RANGE_CLOEXEC, FD_CLOEXEC, F_SETFD};
+    use nix::sys::resource::{getrlimit, Resource};
+
+    // SAFETY: close_range() only takes plain values
+    if unsafe { syscall(SYS_close_range, 3 as c_uint, c_uint::MAX, 
+        CLOSE_RANGE_CLOEXEC) } == 0 
+    {
+        return Ok(())
+    }
+    if mark_fds_cloexec_in_proc().is_ok() {
+        return Ok(())
+    }
+    let (soft, _) = getrlimit(Resource::RLIMIT_NOFILE)?;
+    for fd in 3..soft.min(c_int::MAX as u64) as c_int {
+        // SAFETY: fcntl() only takes plain values, closed ones just fail
+        unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) };
+    }
+    Ok(())
+}
+
+/// Mark the file descriptors above stderr listed in `/proc/self/fd` as 
+/// close-on-exec, reading the dir with raw `getdents64()` so nothing is 
+/// allocated
+fn mark_fds_cloexec_in_proc() -> std::io::Result<()> {
+    use nix::libc::{c_int, close, fcntl, open, syscall, SYS_getdents64, 
+        FD_CLOEXEC, F_SETFD, O_CLOEXEC, O_DIRECTORY, O_RDONLY};
+
+    // SAFETY: the path is a valid C string
+    let dir = unsafe { open(c"/proc/self/fd".as_ptr(), 
+        O_RDONLY | O_DIRECTORY | O_CLOEXEC) };
+    if dir < 0 {
+        return Err(std::io::Error::last_os_error())
+    }
+    let mut buffer = [0u8; 0x1000];
+    let result = loop {
+        // SAFETY: the kernel writes at most the buffer length
+        let read = unsafe { syscall(SYS_getdents64, dir, 
+            buffer.as_mut_ptr(), buffer.len()) };
+        if read < 0 {
+            break Err(std::io::Error::last_os_error())
+        } else if read == 0 {
+            break Ok(())
+        }
+        // Each record: d_ino: u64, d_off: i64, d_reclen: u16, d_type: u8,
+        // then the NUL-terminated d_name
+        let mut offset = 0;
+        while offset + 19 < read as usize {
+            let record = &buffer[offset..];
+            let length = u16::from_ne_bytes([record[16], record[17]]) as usize;
+            if length <= 19 || offset + length > read as usize {
+                break
+            }
+            let mut fd: c_int = -1;
+            for byte in &record[19..length] {
+                match byte {
+                    b'0'..=b'9' => fd = fd.max(0).saturating_mul(10)
+                                    .saturating_add((byte - b'0') as c_int),
+                    _ => break,
+                }
+            }
+            if fd > 2 && fd != dir {
+                // SAFETY: fcntl() only takes plain values
+                unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) };
+            }
+            offset += length
+        }
+    };
+    // SAFETY: the fd was opened above and is not used afterwards
+    unsafe { close(dir) };
+    result
+}
+
+impl ChildAttributes {
+    fn is_empty(&self) -> bool {
+        self.nice.is_none() && self.rlimit_cpu.is_none() && 
+            self.rlimit_as.is_none() && self.chroot.is_none() &&
+            self.uid_gid.is_none() && ! self.close_fds
+    }
+
+    /// Apply the attributes, privileges are dropped last, as lowering niceness
+    /// and chrooting need them. The file descriptors are marked first, as 
+    /// `/proc` might not be there after chrooting.
+    fn apply(&self) -> std::io::Result<()> {
+        use nix::{sys::resource::{setrlimit, Resource}, 
+            unistd::{chdir, chroot, setgid, setgroups, setuid, Gid, Uid}};
+
+        if self.close_fds {
+            mark_fds_cloexec()?
+        }
+
+        if let Some(nice) = self.nice {
+            // SAFETY: setpriority() only takes plain values
+            if unsafe { nix::libc::setpriority(
+                nix::libc::PRIO_PROCESS, 0, nice) } != 0 
+            {
+                return Err(std::io::Error::last_os_error())
+            }
+        }
+        if let Some(secs) = self.rlimit_cpu {
+            // One more second for the hard limit, so SIGXCPU comes first
+            setrlimit(Resource::RLIMIT_CPU, secs, secs + 1)?
+        }
+        if let Some(bytes) = self.rlimit_as {
+            setrlimit(Resource::RLIMIT_AS, bytes, bytes)?
+        }
+        if let Some((root, dir)) = &self.chroot {
+            chroot(root.as_c_str())?;
+            chdir(dir.as_c_str())?
+        }
+        if let Some((uid, gid)) = self.uid_gid {
+            setgroups(&[])?;
+            setgid(Gid::from_raw(gid))?;
+            setuid(Uid::from_raw(uid))?
+        }
+        Ok(())
+    }
+}
+
 impl Parser {
     /// Create a new parser with default settings
     #[cfg(feature = "tempfile")]
@@ -654,9 +2289,25 @@ impl Parser {
             options,
         })
     }
+}
+
+impl<S: AsRef<ParserScript>> Parser<S> {
+    /// Create a new parser with default options and the given script, which
+    /// could be shared, e.g. an `Arc<ParserScript>`
+    pub fn with_script(script: S) -> Self {
+        Self {
+            script,
+            options: ParserOptions::default(),
+        }
+    }
+
+    /// Get the `ParserScript` used
+    fn script(&self) -> &ParserScript {
+        self.script.as_ref()
+    }
 
     /// Set the `ParserScript` instance used
-    pub fn set_script(&mut self, script: ParserScript) -> &mut Self {
+    pub fn set_script(&mut self, script: S) -> &mut Self {
         self.script = script;
         self
     }
@@ -668,116 +2319,722 @@ impl Parser {
     }
 
     /// Prepare a `Command` instance that could be used to spawn a `Child`
-    fn get_command(&self) -> Command {
-        let mut command = Command::new(
-            &self.options.intepreter);
+    #[cfg_attr(not(feature = "sandbox"), allow(unused_variables))]
+    fn get_command(&self, input: &ParserInput) -> Command {
+        #[cfg(feature = "sandbox")]
+        let mut command = match &self.options.sandbox {
+            Some(sandbox) => self.get_sandbox_command(sandbox, &input.dirs),
+            None => {
+                let mut command = Command::new(&self.options.intepreter);
+                command.arg(self.script_path());
+                command
+            },
+        };
+        #[cfg(not(feature = "sandbox"))]
+        let mut command = Command::new(&self.options.intepreter);
+        #[cfg(not(feature = "sandbox"))]
+        command.arg(self.script_path());
         command.stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            // .arg("-e")
-            .arg(self.script.as_ref());
-        if let Some(work_dir) = &self.options.work_dir {
-            command.current_dir(work_dir);
+            // So the whole tree could be killed on errors, see `Parser`
+            .process_group(0);
+        if self.options.path_delimiter == PathDelimiter::Nul {
+            command.arg("--nul");
+        }
+        if let Some(carch) = &self.options.carch {
+            let mut arg = OsString::from("--carch=");
+            arg.push(carch);
+            command.arg(arg);
+        }
+        let chroot = match &self.options.chroot {
+            // The work dir could only be entered after chrooting, paths with
+            // NUL bytes become empty ones and fail the spawn
+            Some(root) => Some((
+                CString::new(root.as_os_str().as_bytes()).unwrap_or_default(),
+                CString::new(self.options.work_dir.as_deref().unwrap_or(
+                    Path::new("/")).as_os_str().as_bytes()).unwrap_or_default()
+            )),
+            None => {
+                if let Some(work_dir) = &self.options.work_dir {
+                    command.current_dir(work_dir);
+                }
+                None
+            },
+        };
+        match &self.options.env_policy {
+            EnvPolicy::Inherit => (),
+            EnvPolicy::Clear => { command.env_clear(); },
+            EnvPolicy::Allowlist(keys) => {
+                command.env_clear();
+                for key in keys.iter() {
+                    if let Some(value) = std::env::var_os(key) {
+                        command.env(key, value);
+                    }
+                }
+            },
+        }
+        command.envs(self.options.envs.iter().map(
+            |(key, value)|(key, value)));
+        let attributes = ChildAttributes {
+            nice: self.options.nice,
+            rlimit_cpu: self.options.rlimit_cpu.map(|limit|
+                (limit.as_secs() + u64::from(limit.subsec_nanos() > 0)).max(1)),
+            rlimit_as: self.options.rlimit_as,
+            chroot,
+            uid_gid: self.options.uid_gid,
+            close_fds: self.options.close_fds,
+        };
+        if ! attributes.is_empty() {
+            // SAFETY: only async-signal-safe syscalls are called in the hook
+            unsafe { command.pre_exec(move ||attributes.apply()) };
         }
         command
     }
 
-    /// Spawn a `Child` that's ready to parse `PKGBUILD`s
-    fn get_child(&self) -> Result<Child> {
-        self.get_command().spawn().map_err(|e|e.into())
-    }
-
-    /// Spawn a `Child` and take its `stdin`, `stdout`, `stderr` handles
-    fn get_child_taken(&self) 
-        -> Result<(Child, ChildIOs)> 
+    /// Get a `bwrap` command that runs the interpreter with the script inside
+    /// the sandbox, the script arguments are to be appended
+    #[cfg(feature = "sandbox")]
+    fn get_sandbox_command(&self, sandbox: &SandboxOptions, dirs: &[PathBuf]) 
+        -> Command 
     {
-        let mut child = self.get_child()?;
-        let ios = ChildIOs::try_from(&mut child)?;
-        Ok((child, ios))
+        let mut command = Command::new(&sandbox.bwrap);
+        command.args(["--unshare-all", "--die-with-parent", 
+            "--cap-drop", "ALL"]);
+        if sandbox.share_net {
+            command.args(["--share-net", 
+                "--ro-bind-try", "/etc/resolv.conf", "/etc/resolv.conf"]);
+        }
+        command.args([
+            "--ro-bind", "/usr", "/usr",
+            "--symlink", "usr/lib", "/lib",
+            "--symlink", "usr/lib", "/lib64",
+            "--symlink", "usr/bin", "/bin",
+            "--symlink", "usr/bin", "/sbin",
+            "--dev", "/dev",
+            "--proc", "/proc",
+            "--tmpfs", "/tmp"]);
+        for path in ["/etc/makepkg.conf", "/etc/makepkg.conf.d", 
+            "/etc/passwd", "/etc/group", "/etc/nsswitch.conf", 
+            "/etc/localtime"] 
+        {
+            command.args(["--ro-bind-try", path, path]);
+        }
+        for (host, path) in sandbox.ro_binds.iter() {
+            command.arg("--ro-bind").arg(host).arg(path);
+        }
+        command.arg("--ro-bind").arg(self.script().path())
+            .arg(SANDBOX_SCRIPT);
+        for (id, dir) in dirs.iter().enumerate() {
+            command.arg("--ro-bind").arg(dir)
+                .arg(format!("{}/{}", SANDBOX_PKGBUILDS, id));
+        }
+        command.args(["--chdir", "/", "--"])
+            .arg(&self.options.intepreter)
+            .arg(SANDBOX_SCRIPT);
+        command
     }
 
-    /// Parse multiple PKGBUILD files
-    pub fn parse_multi<I, P>(&self, paths: I) -> Result<Vec<Pkgbuild>>
-    where
-        I: IntoIterator<Item = P>,
-        P: AsRef<Path>
-    {
-        let mut input = Vec::new();
-        let mut count = 0;
-        for path in paths {
-            count += 1;
-            let line = path.as_ref().as_os_str().as_bytes();
-            if ! line.contains(&b'/') {
-                input.extend_from_slice(b"./");
+    /// The path of the script passed to the interpreter, which is relative to
+    /// the chroot if the script is stored under it
+    fn script_path(&self) -> &OsStr {
+        let path = self.script().path();
+        if let Some(root) = &self.options.chroot {
+            if let Ok(relative) = path.strip_prefix(root) {
+                // Keep the leading '/'
+                let bytes = path.as_os_str().as_bytes();
+                return OsStr::from_bytes(
+                    &bytes[bytes.len() - relative.as_os_str().len() - 1..])
             }
-            input.extend_from_slice(line);
-            input.push(b'\n')
         }
-        if count == 0 {
-            return Ok(Vec::new())
+        path.as_os_str()
+    }
+
+    /// Spawn a `Child` from the `Command` that's ready to parse `PKGBUILD`s
+    fn get_child(&self, mut command: Command) -> Result<Child> {
+        self.prepare_spawn()?;
+        command.spawn().map_err(|e|self.spawn_error(e))
+    }
+
+    /// Make sure the script and the interpreter are usable before spawning
+    fn prepare_spawn(&self) -> Result<()> {
+        if self.options.uid_gid.is_some() {
+            self.make_script_readable()?
         }
-        let (mut child, child_ios) = self.get_child_taken()?;
-        // Do not handle the error yet, wait for the child to finish first
-        #[cfg(not(feature = "nothread"))]
-        let out_and_err = child_ios.work(input);
-        #[cfg(feature = "nothread")]
-        let out_and_err = child_ios.work(&input);
-        let (out, err) = match out_and_err {
-            Ok((out, err)) => {
-                let status = match child.wait() {
-                    Ok(status) => status,
-                    Err(e) => {
-                        log::error!("Failed to wait for child: {}", e);
-                        return Err(e.into())
-                    },
-                };
-                if ! status.success() {
-                    log::error!("Child did not execute successfully");
-                    log::debug!("Current stdout: {}", str_from_slice_u8!(&out));
-                    log::debug!("Current stderr: {}", str_from_slice_u8!(&err));
-                    return Err(Error::ParserScriptError(
-                        ParserScriptError::from(status.code())))
-                }
-                (out, err)
-            },
-            Err(e) => {
-                if let Err(e) = child.kill() {
-                    log::error!("Failed to kill child after failed parsing");
-                    return Err(e.into())
-                }
-                match child.wait() {
-                    Ok(status) =>
-                        log::warn!("Killed child return: {}", status),
-                    Err(e) => {
-                        log::error!("Failed to wait for killed child: {}", e);
-                        return Err(e.into())
-                    }
-                }
-                return Err(e)
-            },
-        };
-        if ! err.is_empty() {
-            log::warn!("Parser has written to stderr: \n{}", 
-                str_from_slice_u8!(&err));
+        self.check()
+    }
+
+    /// Map the error from spawning the interpreter
+    fn spawn_error(&self, e: std::io::Error) -> Error {
+        let interpreter = self.interpreter_in_chroot();
+        log::error!("Failed to spawn interpreter '{}': {}", 
+            interpreter.display(), e);
+        if e.kind() == std::io::ErrorKind::NotFound {
+            Error::InterpreterNotFound(interpreter)
+        } else {
+            e.into()
         }
-        if log::log_enabled!(log::Level::Debug) {
-            log::debug!("Raw output from parser:\n{}", 
-                str_from_slice_u8!(&out));
+    }
+
+    /// Check that the interpreter exists and is executable, and the script
+    /// exists and is readable, returning `Error::InterpreterNotFound` or
+    /// `Error::ScriptMissing` otherwise. This is also done before spawning
+    /// the parser, so it's only needed to fail early, e.g. when the `Parser`
+    /// is created.
+    /// 
+    /// An interpreter without `/` is searched in `PATH`, the one set by 
+    /// `set_env()` if any. Inside a chroot only its existence is checked, as
+    /// symlinks there might be absolute.
+    pub fn check(&self) -> Result<()> {
+        self.check_interpreter()?;
+        let script = self.script().path();
+        if let Err(e) = nix::unistd::faccessat(None, script, 
+            nix::unistd::AccessFlags::R_OK, nix::fcntl::AtFlags::empty()) 
+        {
+            log::error!("Parser script '{}' is not readable: {}", 
+                script.display(), e);
+            return Err(Error::ScriptMissing(script.into()))
         }
-        let pkgbuilds = Pkgbuilds::try_from(
-            &PkgbuildsParsing::from_parser_output(&out)?)?;
-        let actual_count = pkgbuilds.entries.len();
-        if actual_count != count {
-            log::error!("Parsed PKGBUILDs count {} != input count {}",
-                actual_count, count);
-            return Err(Error::MismatchedResultCount { 
-                input: count, output: actual_count, result: pkgbuilds.entries })
+        Ok(())
+    }
+
+    /// The interpreter, under the chroot if it's absolute and one is set
+    fn interpreter_in_chroot(&self) -> PathBuf {
+        let interpreter = &self.options.intepreter;
+        match &self.options.chroot {
+            Some(root) if interpreter.is_absolute() => root.join(
+                interpreter.strip_prefix("/").unwrap_or(interpreter)),
+            _ => interpreter.clone(),
         }
-        Ok(pkgbuilds.entries)
+    }
 
+    fn check_interpreter(&self) -> Result<()> {
+        let interpreter = &self.options.intepreter;
+        let is_usable = |path: &Path| if self.options.chroot.is_some() {
+            path.symlink_metadata().is_ok()
+        } else {
+            nix::unistd::faccessat(None, path, nix::unistd::AccessFlags::X_OK,
+                nix::fcntl::AtFlags::empty()).is_ok()
+        };
+        if interpreter.as_os_str().as_bytes().contains(&b'/') {
+            let path = self.interpreter_in_chroot();
+            if is_usable(&path) {
+                return Ok(())
+            }
+            log::error!("Interpreter '{}' does not exist or is not executable",
+                path.display());
+            return Err(Error::InterpreterNotFound(path))
+        }
+        let search = match self.options.envs.iter().rev().find(
+            |(key, _)|key == "PATH") 
+        {
+            Some((_, search)) => Some(search.clone()),
+            None => std::env::var_os("PATH"),
+        };
+        // Leave it to spawning if there's nowhere to search
+        let Some(search) = search else {
+            return Ok(())
+        };
+        for dir in std::env::split_paths(&search) {
+            let dir = match &self.options.chroot {
+                Some(root) => root.join(dir.strip_prefix("/").unwrap_or(&dir)),
+                None => dir,
+            };
+            if is_usable(&dir.join(interpreter)) {
+                return Ok(())
+            }
+        }
+        log::error!("Interpreter '{}' not found in PATH", interpreter.display());
+        Err(Error::InterpreterNotFound(interpreter.clone()))
     }
 
-    /// Parse only a single PKGBUILD file,
+    /// Make the script readable by everyone, as temporary scripts are only
+    /// readable by us, but the child could be running as another user
+    fn make_script_readable(&self) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = self.script().path();
+        let mut permissions = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.permissions(),
+            Err(e) => {
+                log::error!("Failed to get metadata of parser script '{}': {}",
+                    path.display(), e);
+                return Err(e.into())
+            },
+        };
+        let mode = permissions.mode();
+        if mode & 0o044 != 0o044 {
+            permissions.set_mode(mode | 0o044);
+            if let Err(e) = std::fs::set_permissions(path, permissions) {
+                log::error!("Failed to make parser script '{}' readable: {}",
+                    path.display(), e);
+                return Err(e.into())
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawn a `Child` and take its `stdin`, `stdout`, `stderr` handles, the
+    /// child is killed along with its process group if the guard is dropped
+    fn get_child_taken(&self, command: Command) 
+        -> Result<(ChildGuard, ChildIOs)> 
+    {
+        let mut guard = ChildGuard(Some(self.get_child(command)?));
+        let ios = ChildIOs::try_from(guard.child())?;
+        Ok((guard, ios))
+    }
+
+    /// Parse multiple PKGBUILD files
+    /// 
+    /// The results are returned in the same order as the input paths, one for
+    /// each unique path, or each path including duplicated ones if 
+    /// `ParserOptions::dedup_paths` is disabled, so they could be zipped with
+    /// the paths. If the count does not match, e.g. some `PKGBUILD` quit or
+    /// defined no `pkgname`, an `Error::MismatchedResultCount` listing the 
+    /// paths without results would be returned instead.
+    /// 
+    /// Empty paths and paths containing the path delimiter are rejected with
+    /// `Error::InvalidInputPath`.
+    pub fn parse_multi<I, P>(&self, paths: I) -> Result<Vec<Pkgbuild>>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let reporter = self.options.progress_reporter();
+        self.parse_multi_with_progress(paths, 
+            reporter.as_ref().map(|report|report as _), 
+            self.options.dedup_paths)
+    }
+
+    /// Parse multiple PKGBUILD files like `parse_multi()`, but collect the
+    /// results into `Pkgbuilds`, for the collection-level helpers on it
+    pub fn parse_multi_collected<I, P>(&self, paths: I) -> Result<Pkgbuilds>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        self.parse_multi(paths).map(Pkgbuilds::from)
+    }
+
+    /// Parse multiple PKGBUILD files in parallel, the paths are split into 
+    /// `jobs` chunks and each chunk is parsed by its own child, sharing the 
+    /// same `ParserScript`. If `jobs` is `0`, then the available parallelism
+    /// is used.
+    /// 
+    /// The results are returned in the same order as the input paths, just 
+    /// like `parse_multi()`, regardless of which chunk finishes first. If any
+    /// chunk failed, an `Error::ChunksFailed` would be returned, containing
+    /// the errors of the failed chunks in chunk order, and a slot for each 
+    /// input path in input order, `None` for those in the failed chunks, so 
+    /// the results could still be zipped with the input paths (after the
+    /// deduplication by `set_dedup_paths()`, if enabled).
+    #[cfg(not(feature = "nothread"))]
+    pub fn parse_multi_par<I, P>(&self, paths: I, jobs: usize) 
+        -> Result<Vec<Pkgbuild>>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path> + Sync,
+        S: Sync
+    {
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+        let paths = self.dedup_paths(paths);
+        let jobs = if jobs == 0 {
+            std::thread::available_parallelism().map(
+                |jobs|jobs.get()).unwrap_or(1)
+        } else {
+            jobs
+        };
+        let reporter = self.options.progress_reporter();
+        let callback = reporter.as_ref();
+        // Already deduplicated above, so neither the chunks nor the single
+        // child do it again
+        if jobs <= 1 || paths.len() <= 1 {
+            return self.parse_multi_with_progress(paths, 
+                callback.map(|report|report as _), false)
+        }
+        let total = paths.len();
+        let done = AtomicUsize::new(0);
+        // Chunks report to this instead, to count in the whole batch
+        let report = |_, _| if let Some(callback) = callback {
+            callback(done.fetch_add(1, Relaxed) + 1, total)
+        };
+        let chunk_size = total.div_ceil(jobs);
+        let results: Vec<(usize, Result<Vec<Pkgbuild>>)> = 
+            std::thread::scope(|scope| 
+        {
+            let report = &report;
+            let workers: Vec<_> = paths.chunks(chunk_size).map(
+                |chunk|(chunk.len(), scope.spawn(move||
+                    self.parse_multi_with_progress(chunk, 
+                        callback.and(Some(report)), false)))
+            ).collect();
+            // Joined in spawning order, so the chunks are reassembled in the
+            // input order
+            workers.into_iter().map(|(len, worker)|(len, match worker.join() {
+                Ok(result) => result,
+                // This should not happend, but still covered anyway
+                Err(_) => Err(Error::ThreadUnjoinable),
+            })).collect()
+        });
+        let mut errors = Vec::new();
+        let mut pkgbuilds = Vec::with_capacity(total);
+        for (len, result) in results {
+            match result {
+                Ok(chunk) => pkgbuilds.extend(chunk.into_iter().map(Some)),
+                Err(e) => {
+                    log::error!("Failed to parse a chunk of PKGBUILDs: {}", e);
+                    errors.push(e);
+                    pkgbuilds.extend(std::iter::repeat_n(None, len))
+                },
+            }
+        }
+        if errors.is_empty() {
+            Ok(pkgbuilds.into_iter().flatten().collect())
+        } else {
+            Err(Error::ChunksFailed { errors, result: pkgbuilds })
+        }
+    }
+
+    /// Build the input fed to the parser script's stdin, and count the paths,
+    /// skipping duplicated paths if `dedup`
+    fn input_from_paths<I, P>(&self, paths: I, dedup: bool) 
+        -> Result<ParserInput>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let mut input = Vec::new();
+        let mut count = 0;
+        let mut kept = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        #[cfg(feature = "sandbox")]
+        let mut dirs = Vec::new();
+        let delimiter = self.options.path_delimiter.as_byte();
+        for path in paths {
+            if path.as_ref().as_os_str().is_empty() {
+                log::error!("Empty path could not be parsed");
+                return Err(Error::InvalidInputPath(PathBuf::new()))
+            }
+            if dedup && ! seen.insert(self.dedup_key(path.as_ref())) {
+                log::debug!("Skipped duplicated path {:?}", path.as_ref());
+                continue
+            }
+            count += 1;
+            kept.push(path.as_ref().to_path_buf());
+            #[cfg(feature = "sandbox")]
+            let remapped;
+            #[cfg(feature = "sandbox")]
+            let path = if self.options.sandbox.is_some() {
+                remapped = self.remap_into_sandbox(path.as_ref(), &mut dirs)?;
+                remapped.as_path()
+            } else {
+                path.as_ref()
+            };
+            #[cfg(not(feature = "sandbox"))]
+            let path = path.as_ref();
+            let line = path.as_os_str().as_bytes();
+            if line.contains(&delimiter) {
+                log::error!("Path {:?} contains the path delimiter", path);
+                return Err(Error::InvalidInputPath(path.into()))
+            }
+            if ! line.contains(&b'/') {
+                input.extend_from_slice(b"./");
+            }
+            input.extend_from_slice(line);
+            input.push(delimiter)
+        }
+        Ok(ParserInput { bytes: input, count, paths: kept,
+            #[cfg(feature = "sandbox")]
+            dirs 
+        })
+    }
+
+    /// The key to find duplicated paths, the canonical path if it could be
+    /// resolved, otherwise the path under `work_dir`
+    fn dedup_key(&self, path: &Path) -> PathBuf {
+        let joined = match &self.options.work_dir {
+            Some(work_dir) => work_dir.join(path),
+            None => path.to_path_buf(),
+        };
+        if self.options.chroot.is_some() {
+            return joined
+        }
+        joined.canonicalize().unwrap_or(joined)
+    }
+
+    /// Only keep the first of duplicated paths if `dedup_paths` is set
+    #[cfg(not(feature = "nothread"))]
+    fn dedup_paths<I, P>(&self, paths: I) -> Vec<P>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        if ! self.options.dedup_paths {
+            return paths.into_iter().collect()
+        }
+        let mut seen = std::collections::HashSet::new();
+        paths.into_iter().filter(
+            |path|seen.insert(self.dedup_key(path.as_ref()))).collect()
+    }
+
+    /// Get the path of a `PKGBUILD` inside the sandbox, its dir would be added
+    /// to the dirs to bind if not yet
+    #[cfg(feature = "sandbox")]
+    fn remap_into_sandbox(&self, path: &Path, dirs: &mut Vec<PathBuf>) 
+        -> Result<PathBuf> 
+    {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            match &self.options.work_dir {
+                Some(work_dir) => work_dir.join(path),
+                None => std::env::current_dir()?.join(path),
+            }
+        };
+        let (Some(dir), Some(name)) = (absolute.parent(), absolute.file_name()) 
+        else {
+            log::error!("Path {:?} could not be bound into sandbox", path);
+            return Err(Error::InvalidInputPath(path.into()))
+        };
+        let id = match dirs.iter().position(|bound|bound == dir) {
+            Some(id) => id,
+            None => {
+                dirs.push(dir.into());
+                dirs.len() - 1
+            },
+        };
+        Ok(Path::new(SANDBOX_PKGBUILDS).join(id.to_string()).join(name))
+    }
+
+    /// Spawn the child, feed it the input and wait for it to finish, if
+    /// `usage` is set then the child is timed and waited with `wait4()`
+    fn run_child(&self, 
+        command: Command, input: Vec<u8>, progress: Option<ProgressTracker>,
+        mut usage: Option<&mut ChildUsage>
+    ) -> Result<RawParseOutput>
+    {
+        let spawning = Instant::now();
+        let (mut guard, child_ios) = self.get_child_taken(command)?;
+        if let Some(usage) = &mut usage {
+            usage.spawn = spawning.elapsed();
+            usage.spawned = Some(Instant::now())
+        }
+        let limits = OutputLimits {
+            stdout: self.options.max_output,
+            stderr: self.options.max_stderr,
+        };
+        // Do not handle the error yet, wait for the child to finish first
+        #[cfg(not(feature = "nothread"))]
+        let out_and_err = child_ios.work(
+            input, progress, limits, guard.child());
+        #[cfg(feature = "nothread")]
+        let out_and_err = child_ios.work(
+            &input, progress, limits, guard.child());
+        match out_and_err {
+            Ok((stdout, stderr)) => match guard.wait(usage) {
+                Ok(status) => Ok(RawParseOutput { stdout, stderr, status }),
+                Err(e) => {
+                    log::error!("Failed to wait for child: {}", e);
+                    Err(e.into())
+                },
+            },
+            Err(e) => {
+                // The whole group, as the child could have left subshells
+                // and commands spawned by the PKGBUILDs running
+                match guard.kill() {
+                    Ok(status) =>
+                        log::warn!("Killed child return: {}", status),
+                    Err(e) => {
+                        log::error!("Failed to wait for killed child: {}", e);
+                        return Err(e.into())
+                    }
+                }
+                Err(e)
+            },
+        }
+    }
+
+    /// Run the parser script on multiple PKGBUILD files, but only return its
+    /// raw output without parsing, useful for debugging. The raw output could
+    /// later be parsed with `Pkgbuilds::from_raw()`.
+    /// 
+    /// Unlike `parse_multi()`, this does not fail if the script errored out,
+    /// check `RawParseOutput::status` for that. The `PKGBUILD`s are dumped in
+    /// the same order as the input paths.
+    pub fn parse_raw<I, P>(&self, paths: I) -> Result<RawParseOutput>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let input = self.input_from_paths(paths, self.options.dedup_paths)?;
+        self.run_child(self.get_command(&input), input.bytes, None, None)
+    }
+
+    /// Parse the raw output, and check the result count against the input
+    /// paths, the path of the `PKGBUILD` with illegal output is filled in
+    fn entries_from_raw(&self, raw: &RawParseOutput, paths: &[PathBuf]) 
+        -> Result<Vec<Pkgbuild>>
+    {
+        let count = paths.len();
+        let pkgbuilds = match Pkgbuilds::from_raw_with_strictness(
+            raw, self.options.strictness) 
+        {
+            Ok(pkgbuilds) => pkgbuilds,
+            Err(Error::ParserScriptIllegalOutput { 
+                line, index, pkgbase, path: _ }) => 
+            {
+                let path = paths.get(index).cloned();
+                if let Some(path) = &path {
+                    log::error!("Illegal output found when parsing {:?}", path)
+                }
+                return Err(Error::ParserScriptIllegalOutput { 
+                    line, index, pkgbase, path })
+            },
+            Err(Error::ParserScriptError { error, stderr_tail, 
+                kept_script: _ }) => 
+            {
+                let kept_script = self.script.as_ref().keep_on_error();
+                if let Some(kept_script) = &kept_script {
+                    log::error!("Parser script kept at '{}'", 
+                        kept_script.display())
+                }
+                return Err(Error::ParserScriptError { error, stderr_tail, 
+                    kept_script })
+            },
+            Err(e) => return Err(e),
+        };
+        let actual_count = pkgbuilds.entries.len();
+        if actual_count != count {
+            log::error!("Parsed PKGBUILDs count {} != input count {}",
+                actual_count, count);
+            // The input indices are always ascending
+            let missing: Vec<PathBuf> = paths.iter().enumerate().filter(
+                |(index, _)|pkgbuilds.inputs.binary_search(index).is_err())
+                .map(|(_, path)|path.clone()).collect();
+            for path in missing.iter() {
+                log::error!("No result for {:?}", path)
+            }
+            return Err(Error::MismatchedResultCount { 
+                input: count, output: actual_count, result: pkgbuilds.entries,
+                missing })
+        }
+        Ok(pkgbuilds.entries)
+    }
+
+    /// Parse multiple PKGBUILD files, reporting progress to the callback, and
+    /// skipping duplicated paths if `dedup`
+    fn parse_multi_with_progress<I, P>(&self, paths: I, 
+        progress: Option<&(dyn Fn(usize, usize) + Send + Sync)>, dedup: bool
+    ) -> Result<Vec<Pkgbuild>>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let input = self.input_from_paths(paths, dedup)?;
+        let count = input.count;
+        if count == 0 {
+            return Ok(Vec::new())
+        }
+        let progress = progress.map(
+            |callback|ProgressTracker::new(callback, count));
+        let command = self.get_command(&input);
+        let raw = self.run_child(command, input.bytes, progress, None)?;
+        self.entries_from_raw(&raw, &input.paths)
+    }
+
+    /// Parse multiple PKGBUILD files like `parse_multi()`, but also return a
+    /// `ParseReport` on how long each stage took and what the parser used
+    /// 
+    /// The results are returned in the same order as the input paths, just
+    /// like `parse_multi()`, and so are the per-`PKGBUILD` timings
+    pub fn parse_multi_report<I, P>(&self, paths: I) 
+        -> Result<(Vec<Pkgbuild>, ParseReport)>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let start = Instant::now();
+        let input = self.input_from_paths(paths, self.options.dedup_paths)?;
+        let count = input.count;
+        if count == 0 {
+            return Ok((Vec::new(), ParseReport { 
+                total: start.elapsed(), ..Default::default() }))
+        }
+        // Record when each PKGBUILD is fully dumped, which the progress 
+        // tracker already detects
+        let finished = std::sync::Mutex::new(Vec::with_capacity(count));
+        let callback = self.options.progress_reporter();
+        let record = |done, total| {
+            if let Ok(mut finished) = finished.lock() {
+                finished.push(Instant::now())
+            }
+            if let Some(callback) = &callback {
+                callback(done, total)
+            }
+        };
+        let progress = ProgressTracker::new(&record, count);
+        let mut usage = ChildUsage::default();
+        let command = self.get_command(&input);
+        let raw = self.run_child(
+            command, input.bytes, Some(progress), Some(&mut usage))?;
+        let converting = Instant::now();
+        let pkgbuilds = self.entries_from_raw(&raw, &input.paths)?;
+        let convert = converting.elapsed();
+        let finished = finished.into_inner().unwrap_or_else(
+            |poisoned|poisoned.into_inner());
+        let mut last = usage.spawned.unwrap_or(start);
+        let per_pkgbuild = pkgbuilds.iter().zip(finished).map(
+            |(pkgbuild, finished)| 
+        {
+            let elapsed = finished - last;
+            last = finished;
+            (pkgbuild.pkgbase.clone(), elapsed)
+        }).collect();
+        let report = ParseReport {
+            total: start.elapsed(),
+            spawn: usage.spawn,
+            io: usage.io,
+            convert,
+            child_user: usage.user,
+            child_sys: usage.sys,
+            output_bytes: raw.stdout.len(),
+            per_pkgbuild,
+        };
+        Ok((pkgbuilds, report))
+    }
+
+    /// Parse multiple PKGBUILD files like `parse_multi()`, but also return
+    /// what the parser script wrote to stderr, split per `PKGBUILD` on a best
+    /// effort basis
+    /// 
+    /// The results are returned in the same order as the input paths, just 
+    /// like `parse_multi()`, and so are the per-`PKGBUILD` diagnostics
+    pub fn parse_multi_verbose<I, P>(&self, paths: I) 
+        -> Result<(Vec<Pkgbuild>, ParseDiagnostics)>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let input = self.input_from_paths(paths, self.options.dedup_paths)?;
+        let count = input.count;
+        if count == 0 {
+            return Ok((Vec::new(), ParseDiagnostics::default()))
+        }
+        let reporter = self.options.progress_reporter();
+        let progress = reporter.as_ref().map(
+            |report|ProgressTracker::new(report, count));
+        let mut command = self.get_command(&input);
+        command.arg("--stderr-markers");
+        let mut raw = self.run_child(command, input.bytes, progress, None)?;
+        let diagnostics = ParseDiagnostics::from_marked_stderr(
+            &raw.stderr, input.paths.clone());
+        raw.stderr.clone_from(&diagnostics.stderr);
+        let pkgbuilds = self.entries_from_raw(&raw, &input.paths)?;
+        Ok((pkgbuilds, diagnostics))
+    }
+
+    /// Parse only a single PKGBUILD file,
     /// 
     /// If `path` is `None`, defaults to `PKGBUILD`, i.e. parse the `PKGBUILD`
     /// in the work directory for parser. 
@@ -793,7 +3050,8 @@ impl Parser {
         if count != 1 {
             log::error!("Parser return PKGBUILD count is not 1, but {}", count);
             return Err(Error::MismatchedResultCount { 
-                input: 1, output: count, result: pkgbuilds })
+                input: 1, output: count, result: pkgbuilds,
+                missing: Vec::new() })
         }
         match pkgbuilds.pop() {
             Some(pkgbuild) => Ok(pkgbuild),
@@ -801,14 +3059,16 @@ impl Parser {
                 // We should not be here
                 log::error!("Parser returned no PKGBUILDs empty, it should be \
                     at least one");
-                return Err(Error::MismatchedResultCount { 
-                    input: 1, output: 0, result: pkgbuilds })
+                Err(Error::MismatchedResultCount { 
+                    input: 1, output: 0, result: pkgbuilds,
+                    missing: Vec::new() })
             },
         }
     }
 }
 
-/// A shortcut to create a `Parser` and parse multiple `PKGBUILD`s
+/// A shortcut to create a `Parser` and parse multiple `PKGBUILD`s, the results
+/// are in the same order as the input paths
 #[cfg(feature = "tempfile")]
 pub fn parse_multi<I, P>(paths: I) -> Result<Vec<Pkgbuild>>
 where
@@ -819,7 +3079,8 @@ where
 }
 
 /// A shortcut to create a `Parser` and parse multiple `PKGBUILD`s, with the
-/// parser script created at the given path
+/// parser script created at the given path, the results are in the same order
+/// as the input paths
 #[cfg(not(feature = "tempfile"))]
 pub fn parse_multi<I, P1, P2>(script_path: P1, pkgbuild_paths: I) 
 -> Result<Vec<Pkgbuild>>
@@ -852,81 +3113,376 @@ where
     Parser::new(script_path)?.parse_one(pkgbuild_path)
 }
 
-#[derive(Default, Debug)]
-struct PackageArchitectureParsing<'a> {
-    arch: &'a [u8],
-    checkdepends: Vec<&'a [u8]>,
-    depends: Vec<&'a [u8]>,
-    optdepends: Vec<&'a [u8]>,
-    provides: Vec<&'a [u8]>,
-    conflicts: Vec<&'a [u8]>,
-    replaces: Vec<&'a [u8]>,
-}
+/// Kills the whole process group of the child if dropped while armed, so a
+/// dropped parsing future does not leave the parser or its subshells behind
+#[cfg(feature = "async")]
+struct ProcessGroupGuard(Option<u32>);
 
-/// A sub-package parsed from a split-package `PKGBUILD`, borrowed variant
-/// during parsing. Library users should not used this.
-#[derive(Default, Debug)]
-struct PackageParsing<'a> {
-    pkgname: &'a [u8],
-    pkgdesc: &'a [u8],
-    url: &'a [u8],
-    license: Vec<&'a [u8]>,
-    groups: Vec<&'a [u8]>,
-    backup: Vec<&'a [u8]>,
-    options: Vec<&'a [u8]>,
-    install: &'a [u8],
-    changelog: &'a [u8],
-    arches: Vec<PackageArchitectureParsing<'a>>,
+#[cfg(feature = "async")]
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(id) = self.0.take() {
+            kill_child(id)
+        }
+    }
 }
 
-#[derive(Default, Debug)]
-struct PkgbuildArchitectureParsing<'a> {
-    arch: &'a [u8],
-    sources: Vec<&'a [u8]>,
-    cksums: Vec<&'a [u8]>,
-    md5sums: Vec<&'a [u8]>,
-    sha1sums: Vec<&'a [u8]>,
-    sha224sums: Vec<&'a [u8]>,
-    sha256sums: Vec<&'a [u8]>,
-    sha384sums: Vec<&'a [u8]>,
-    sha512sums: Vec<&'a [u8]>,
-    b2sums: Vec<&'a [u8]>,
-    depends: Vec<&'a [u8]>,
-    makedepends: Vec<&'a [u8]>,
-    checkdepends: Vec<&'a [u8]>,
-    optdepends: Vec<&'a [u8]>,
-    conflicts: Vec<&'a [u8]>,
-    provides: Vec<&'a [u8]>,
-    replaces: Vec<&'a [u8]>,
-}
+/// Read the async child stdout till EOF, while reporting progress on every 
+/// read. If it's over the limit, the child is killed so the other IO futures
+/// would not be blocked on it.
+#[cfg(feature = "async")]
+async fn read_stdout_async(
+    mut reader: tokio::process::ChildStdout, id: u32, 
+    mut progress: Option<ProgressTracker<'_>>, limits: OutputLimits
+) -> Result<Vec<u8>> 
+{
+    use tokio::io::AsyncReadExt;
 
-/// A `PKGBUILD` being parsed. Library users should
-/// not use this.
-#[derive(Default, Debug)]
-struct PkgbuildParsing<'a> {
-    pkgbase: &'a [u8],
-    pkgs: Vec<PackageParsing<'a>>,
-    pkgver: &'a [u8],
-    pkgrel: &'a [u8],
-    epoch: &'a [u8],
-    pkgdesc: &'a [u8],
-    url: &'a [u8],
-    license: Vec<&'a [u8]>,
-    install: &'a [u8],
-    changelog: &'a [u8],
-    validgpgkeys: Vec<&'a [u8]>,
-    noextract: Vec<&'a [u8]>,
-    groups: Vec<&'a [u8]>,
-    arches: Vec<PkgbuildArchitectureParsing<'a>>,
-    backups: Vec<&'a [u8]>,
-    options: Vec<&'a [u8]>,
-    pkgver_func: bool,
+    let mut buffer = vec![0; 0x10000];
+    let mut output = Vec::new();
+    loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) => return Ok(output),
+            Ok(read_this) => {
+                output.extend_from_slice(&buffer[0..read_this]);
+                if let Err(e) = limits.check_stdout(&output) {
+                    kill_child(id);
+                    return Err(e)
+                }
+                if let Some(progress) = &mut progress {
+                    progress.update(&output)
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => {
+                log::error!("Child stdout reader encountered IO error: {}", e);
+                return Err(e.into())
+            },
+        }
+    }
 }
 
-#[derive(Default, Debug)]
-struct PkgbuildsParsing<'a> {
-    entries: Vec<PkgbuildParsing<'a>>
-}
+/// Read the async child stderr till EOF, only keeping what's under the limit
+#[cfg(feature = "async")]
+async fn read_stderr_async(
+    mut reader: tokio::process::ChildStderr, limits: OutputLimits
+) -> Result<Vec<u8>> 
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut buffer = vec![0; 0x10000];
+    let mut output = Vec::new();
+    let mut full = false;
+    loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) => return Ok(output),
+            Ok(read_this) => if ! full {
+                output.extend_from_slice(&buffer[0..read_this]);
+                full = limits.cap_stderr(&mut output)
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => {
+                log::error!("Child stderr reader encountered IO error: {}", e);
+                return Err(e.into())
+            },
+        }
+    }
+}
+
+/// Write all input to the async child stdin, then close it
+#[cfg(feature = "async")]
+async fn write_stdin_async(
+    mut writer: tokio::process::ChildStdin, input: Vec<u8>
+) -> Result<()> 
+{
+    use tokio::io::AsyncWriteExt;
+
+    match writer.write_all(&input).await {
+        Ok(_) => Ok(()),
+        // The child quitting early is told by its exit status instead
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+            log::debug!("Child quit before reading all input");
+            Ok(())
+        },
+        Err(e) => {
+            log::error!("Child stdin writer encountered IO error: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// The async counterpart of `Parser` for `tokio`, that uses the same 
+/// `ParserScript` and `ParserOptions`, but spawns the parser with
+/// `tokio::process::Command` and drives its IO without blocking the runtime.
+/// 
+/// Dropping a parsing future before it finishes kills the parser along with
+/// the subshells and commands it spawned.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncParser<S = ParserScript> {
+    /// The underlying parser that holds the script and options
+    pub parser: Parser<S>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncParser {
+    /// Create a new async parser with default settings
+    #[cfg(feature = "tempfile")]
+    pub fn new() -> Result<Self> {
+        Ok(Self { parser: Parser::new()? })
+    }
+
+    /// Create a new async parser with default settings, with parser script
+    /// created at the given path
+    #[cfg(not(feature = "tempfile"))]
+    pub fn new<P: AsRef<Path>>(script_path: P) -> Result<Self> {
+        Ok(Self { parser: Parser::new(script_path)? })
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S> From<Parser<S>> for AsyncParser<S> {
+    fn from(parser: Parser<S>) -> Self {
+        Self { parser }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S: AsRef<ParserScript>> AsyncParser<S> {
+    /// Create a new async parser with default options and the given script,
+    /// which could be shared, e.g. an `Arc<ParserScript>`
+    pub fn with_script(script: S) -> Self {
+        Self { parser: Parser::with_script(script) }
+    }
+
+    /// Set the `ParserOptions` instance used
+    pub fn set_options(&mut self, options: ParserOptions) -> &mut Self {
+        self.parser.set_options(options);
+        self
+    }
+
+    /// Spawn the child, feed it the input and wait for it to finish, the IO
+    /// is all driven in the current task
+    async fn run_child(&self, 
+        command: Command, input: Vec<u8>, progress: Option<ProgressTracker<'_>>
+    ) -> Result<RawParseOutput>
+    {
+        let mut command = tokio::process::Command::from(command);
+        // The guard below kills the whole group, this makes sure the child
+        // itself is also reaped after the future is dropped
+        command.kill_on_drop(true);
+        self.parser.prepare_spawn()?;
+        let mut child = command.spawn().map_err(
+            |e|self.parser.spawn_error(e))?;
+        let Some(id) = child.id() else {
+            log::error!("Spawned child has no process ID");
+            return Err(Error::ChildStdioIncomplete)
+        };
+        let mut guard = ProcessGroupGuard(Some(id));
+        let stdin = take_child_io(&mut child.stdin)?;
+        let stdout = take_child_io(&mut child.stdout)?;
+        let stderr = take_child_io(&mut child.stderr)?;
+        let limits = OutputLimits {
+            stdout: self.parser.options.max_output,
+            stderr: self.parser.options.max_stderr,
+        };
+        let (written, stdout, stderr) = tokio::join!(
+            write_stdin_async(stdin, input),
+            read_stdout_async(stdout, id, progress, limits),
+            read_stderr_async(stderr, limits));
+        // The stdout error is the cause if both failed, e.g. on overflow
+        let out_and_err = match (written, stdout, stderr) {
+            (_, Err(e), _) | (Err(e), _, _) | (_, _, Err(e)) => Err(e),
+            (Ok(()), Ok(stdout), Ok(stderr)) => Ok((stdout, stderr)),
+        };
+        let result = match out_and_err {
+            Ok((stdout, stderr)) => match child.wait().await {
+                Ok(status) => Ok(RawParseOutput { stdout, stderr, status }),
+                Err(e) => {
+                    log::error!("Failed to wait for child: {}", e);
+                    Err(e.into())
+                },
+            },
+            Err(e) => {
+                kill_child(id);
+                match child.wait().await {
+                    Ok(status) =>
+                        log::warn!("Killed child return: {}", status),
+                    Err(e) => {
+                        log::error!("Failed to wait for killed child: {}", e);
+                        return Err(e.into())
+                    }
+                }
+                Err(e)
+            },
+        };
+        // The child is reaped, its ID could be reused
+        guard.0 = None;
+        result
+    }
+
+    /// Run the parser script on multiple PKGBUILD files asynchronously, but
+    /// only return its raw output without parsing, like `Parser::parse_raw()`
+    pub async fn parse_raw<I, P>(&self, paths: I) -> Result<RawParseOutput>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let input = self.parser.input_from_paths(paths,
+            self.parser.options.dedup_paths)?;
+        let command = self.parser.get_command(&input);
+        self.run_child(command, input.bytes, None).await
+    }
+
+    /// Parse multiple PKGBUILD files asynchronously
+    /// 
+    /// The results are returned in the same order as the input paths, just
+    /// like `Parser::parse_multi()`
+    pub async fn parse_multi<I, P>(&self, paths: I) -> Result<Vec<Pkgbuild>>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>
+    {
+        let input = self.parser.input_from_paths(paths,
+            self.parser.options.dedup_paths)?;
+        let count = input.count;
+        if count == 0 {
+            return Ok(Vec::new())
+        }
+        let reporter = self.parser.options.progress_reporter();
+        let progress = reporter.as_ref().map(
+            |report|ProgressTracker::new(report, count));
+        let command = self.parser.get_command(&input);
+        let raw = self.run_child(command, input.bytes, progress).await?;
+        self.parser.entries_from_raw(&raw, &input.paths)
+    }
+
+    /// Parse only a single PKGBUILD file asynchronously
+    /// 
+    /// If `path` is `None`, defaults to `PKGBUILD`, i.e. parse the `PKGBUILD`
+    /// in the work directory for parser. 
+    pub async fn parse_one<P>(&self, path: Option<P>) -> Result<Pkgbuild>
+    where
+        P: AsRef<Path> 
+    {
+        let mut pkgbuilds = match path {
+            Some(path) => self.parse_multi(std::iter::once(path)).await,
+            None => self.parse_multi(std::iter::once("PKGBUILD")).await,
+        }?;
+        let count = pkgbuilds.len();
+        if count != 1 {
+            log::error!("Parser return PKGBUILD count is not 1, but {}", count);
+            return Err(Error::MismatchedResultCount { 
+                input: 1, output: count, result: pkgbuilds,
+                missing: Vec::new() })
+        }
+        match pkgbuilds.pop() {
+            Some(pkgbuild) => Ok(pkgbuild),
+            None => Err(Error::MismatchedResultCount { 
+                input: 1, output: 0, result: pkgbuilds, missing: Vec::new() }),
+        }
+    }
+}
+
+/// Push a value of an array dumped only if set in the package function, an
+/// empty value means it's overriden to empty
+fn push_overridden<'a>(array: &mut Option<Vec<&'a [u8]>>, value: &'a [u8]) {
+    let array = array.get_or_insert_with(Vec::new);
+    if ! value.is_empty() {
+        array.push(value)
+    }
+}
+
+#[derive(Default, Debug)]
+struct PackageArchitectureParsing<'a> {
+    arch: &'a [u8],
+    checkdepends: Option<Vec<&'a [u8]>>,
+    depends: Option<Vec<&'a [u8]>>,
+    optdepends: Option<Vec<&'a [u8]>>,
+    provides: Option<Vec<&'a [u8]>>,
+    conflicts: Option<Vec<&'a [u8]>>,
+    replaces: Option<Vec<&'a [u8]>>,
+    extra: Vec<(&'a [u8], &'a [u8])>,
+}
+
+/// A sub-package parsed from a split-package `PKGBUILD`, borrowed variant
+/// during parsing. Library users should not used this.
+#[derive(Default, Debug)]
+struct PackageParsing<'a> {
+    pkgname: &'a [u8],
+    pkgdesc: Option<&'a [u8]>,
+    url: Option<&'a [u8]>,
+    license: Option<Vec<&'a [u8]>>,
+    groups: Option<Vec<&'a [u8]>>,
+    backup: Option<Vec<&'a [u8]>>,
+    options: Option<Vec<&'a [u8]>>,
+    install: Option<&'a [u8]>,
+    changelog: Option<&'a [u8]>,
+    arches: Vec<PackageArchitectureParsing<'a>>,
+    /// `arch=(any)`, instead of no `arch` at all
+    any_declared: bool,
+    extra: Vec<(&'a [u8], &'a [u8])>,
+}
+
+#[derive(Default, Debug)]
+struct PkgbuildArchitectureParsing<'a> {
+    arch: &'a [u8],
+    sources: Vec<&'a [u8]>,
+    cksums: Vec<&'a [u8]>,
+    md5sums: Vec<&'a [u8]>,
+    sha1sums: Vec<&'a [u8]>,
+    sha224sums: Vec<&'a [u8]>,
+    sha256sums: Vec<&'a [u8]>,
+    sha384sums: Vec<&'a [u8]>,
+    sha512sums: Vec<&'a [u8]>,
+    b2sums: Vec<&'a [u8]>,
+    depends: Vec<&'a [u8]>,
+    makedepends: Vec<&'a [u8]>,
+    checkdepends: Vec<&'a [u8]>,
+    optdepends: Vec<&'a [u8]>,
+    conflicts: Vec<&'a [u8]>,
+    provides: Vec<&'a [u8]>,
+    replaces: Vec<&'a [u8]>,
+    extra: Vec<(&'a [u8], &'a [u8])>,
+}
+
+/// A `PKGBUILD` being parsed. Library users should
+/// not use this.
+#[derive(Default, Debug)]
+struct PkgbuildParsing<'a> {
+    pkgbase: &'a [u8],
+    pkgs: Vec<PackageParsing<'a>>,
+    pkgver: &'a [u8],
+    pkgrel: &'a [u8],
+    epoch: &'a [u8],
+    pkgdesc: &'a [u8],
+    url: &'a [u8],
+    license: Vec<&'a [u8]>,
+    install: &'a [u8],
+    changelog: &'a [u8],
+    validgpgkeys: Vec<&'a [u8]>,
+    noextract: Vec<&'a [u8]>,
+    groups: Vec<&'a [u8]>,
+    arches: Vec<PkgbuildArchitectureParsing<'a>>,
+    /// `arch=(any)`, instead of no `arch` at all
+    any_declared: bool,
+    backups: Vec<&'a [u8]>,
+    options: Vec<&'a [u8]>,
+    pkgver_func: bool,
+    functions: Vec<(&'a [u8], &'a [u8])>,
+    warnings: Vec<&'a [u8]>,
+    extra: Vec<(&'a [u8], &'a [u8])>,
+    /// Parsed with `Strictness::Lenient`, so duplicated architectures are
+    /// merged with warnings instead of failing the conversion
+    lenient: bool,
+}
+
+#[derive(Default, Debug)]
+struct PkgbuildsParsing<'a> {
+    entries: Vec<PkgbuildParsing<'a>>,
+    /// The index of the input each entry was dumped from
+    inputs: Vec<usize>,
+}
 
 #[derive(Debug)]
 enum ParsingState<'a> {
@@ -938,11 +3494,41 @@ enum ParsingState<'a> {
     PkgbuildArchSpecific (PkgbuildParsing<'a>, PkgbuildArchitectureParsing<'a>),
 }
 
+/// The error for illegal output found when dumping the `index`-th input
+fn illegal_output(line: &[u8], index: usize, pkgbuild: Option<&PkgbuildParsing>)
+    -> Error
+{
+    let pkgbase = pkgbuild.and_then(|pkgbuild|
+        (! pkgbuild.pkgbase.is_empty()).then(||
+            string_from_slice_u8!(pkgbuild.pkgbase)));
+    Error::ParserScriptIllegalOutput { line: line.into(), index, pkgbase, 
+        path: None }
+}
+
 impl<'a> PkgbuildsParsing<'a> {
-    fn from_parser_output(output: &'a Vec<u8>) -> Result<Self> {
+    /// Parse the output lines from `unescaped_lines()`, in 
+    /// `Strictness::Lenient` lines with unknown keys are kept in `extra` of 
+    /// the section they're in
+    fn from_parser_output(lines: &'a [Cow<'_, [u8]>], strictness: Strictness) 
+        -> Result<Self> 
+    {
+        let lenient = strictness == Strictness::Lenient;
+        let mut lines = lines.iter().map(|line|line.as_ref());
+        let found = lines.next().and_then(|line|
+            line.strip_prefix(b"PROTOCOL:")).and_then(|version|
+                str_from_slice_u8!(version).parse().ok());
+        if found != Some(PARSER_SCRIPT_PROTOCOL) {
+            log::error!("Parser script speaks protocol {:?}, expecting {}, \
+                it might be built by another version of this library", found,
+                PARSER_SCRIPT_PROTOCOL);
+            return Err(Error::ProtocolMismatch { 
+                expected: PARSER_SCRIPT_PROTOCOL, found })
+        }
         let mut pkgbuilds = Vec::new();
+        let mut inputs = Vec::new();
+        let mut input_count: usize = 0;
         let mut state = ParsingState::None;
-        for line in output.split(|byte| *byte == b'\n') {
+        for line in lines {
             macro_rules! key_value_from_slice_u8 {
                 ($slice:ident, $key:ident, $value: ident) => {
                     let mut it = $slice.splitn(2, |byte|*byte == b':');
@@ -950,16 +3536,28 @@ impl<'a> PkgbuildsParsing<'a> {
                     let $value = it.next().unwrap_or_default();
                 };
             }
-            if line.is_empty() { continue }
             match state {
                 ParsingState::None => 
                 match line {
-                    b"PKGBUILD" => state = ParsingState::Pkgbuild(
-                                        PkgbuildParsing::default()),
+                    b"INPUT" => input_count += 1,
+                    b"PKGBUILD" => match input_count.checked_sub(1) {
+                        Some(input) if inputs.last() != Some(&input) => {
+                            inputs.push(input);
+                            state = ParsingState::Pkgbuild(
+                                PkgbuildParsing { lenient, ..Default::default() })
+                        },
+                        _ => {
+                            log::error!("PKGBUILD dumped without a preceding \
+                                [INPUT]");
+                            return Err(illegal_output(line, 
+                                input_count.saturating_sub(1), None))
+                        }
+                    },
                     _ => {
                         log::error!("Line '{}' encountered when expecting \
-                            [PKGBUILD]", str_from_slice_u8!(line));
-                        return Err(Error::ParserScriptIllegalOutput(line.into()))
+                            [INPUT] or [PKGBUILD]", str_from_slice_u8!(line));
+                        return Err(illegal_output(line, 
+                            input_count.saturating_sub(1), None))
                     }
                 },
                 ParsingState::Pkgbuild(mut pkgbuild) => {
@@ -991,22 +3589,31 @@ impl<'a> PkgbuildsParsing<'a> {
                                 b"groups" => pkgbuild.groups.push(value),
                                 b"backup" => pkgbuild.backups.push(value),
                                 b"options" => pkgbuild.options.push(value),
+                                b"warning" => pkgbuild.warnings.push(value),
+                                b"arch" if value == b"any" =>
+                                    pkgbuild.any_declared = true,
+                                _ if key.starts_with(b"extra_") =>
+                                    pkgbuild.extra.push((&key[6..], value)),
+                                _ if key.starts_with(b"function_") => 
+                                    pkgbuild.functions.push((&key[9..], value)),
                                 b"pkgver_func" => match value {
                                     b"y" => pkgbuild.pkgver_func = true,
                                     b"n" => pkgbuild.pkgver_func = false,
                                     _ => {
                                         log::error!("Invalid pkgver_func value: {}", 
                                         str_from_slice_u8!(line));
-                                        return Err(Error::ParserScriptIllegalOutput(
-                                            line.into()))
+                                        return Err(illegal_output(line, 
+                                            input_count - 1, Some(&pkgbuild)))
                                     }
                                 }
+                                _ if lenient => 
+                                    pkgbuild.extra.push((key, value)),
                                 _ => {
                                     log::error!("Line '{}' does not contain valid \
                                     key or keyword when expecting pkgbuild info", 
                                     str_from_slice_u8!(line));
-                                    return Err(Error::ParserScriptIllegalOutput(
-                                        line.into()))
+                                    return Err(illegal_output(line, 
+                                        input_count - 1, Some(&pkgbuild)))
                                 }
                             }
                         }
@@ -1027,23 +3634,40 @@ impl<'a> PkgbuildsParsing<'a> {
                     },
                     _ => {
                         key_value_from_slice_u8!(line, key, value);
+                        // These are only dumped if set in the package 
+                        // function, and could be overriden to empty
+                        match key {
+                            b"pkgdesc" => package.pkgdesc = Some(value),
+                            b"url" => package.url = Some(value),
+                            b"install" => package.install = Some(value),
+                            b"changelog" => package.changelog = Some(value),
+                            b"license" => 
+                                push_overridden(&mut package.license, value),
+                            b"groups" => 
+                                push_overridden(&mut package.groups, value),
+                            b"backup" => 
+                                push_overridden(&mut package.backup, value),
+                            b"options" => 
+                                push_overridden(&mut package.options, value),
+                            _ => (),
+                        }
                         if ! value.is_empty() {
                             match key {
                                 b"pkgname" => package.pkgname = value,
-                                b"pkgdesc" => package.pkgdesc = value,
-                                b"url" => package.url = value,
-                                b"license" => package.license.push(value),
-                                b"groups" => package.groups.push(value),
-                                b"backup" => package.backup.push(value),
-                                b"options" => package.options.push(value),
-                                b"install" => package.install = value,
-                                b"changelog" => package.changelog = value,
+                                b"warning" => pkgbuild.warnings.push(value),
+                                b"arch" if value == b"any" =>
+                                    package.any_declared = true,
+                                b"pkgdesc" | b"url" | b"install" | 
+                                b"changelog" | b"license" | b"groups" | 
+                                b"backup" | b"options" => (),
+                                _ if lenient => 
+                                    package.extra.push((key, value)),
                                 _ => {
                                     log::error!("Line '{}' does not contain valid \
                                     key or keyword when expecting pkgbuild info", 
                                     str_from_slice_u8!(line));
-                                    return Err(Error::ParserScriptIllegalOutput(
-                                        line.into()))
+                                    return Err(illegal_output(line, 
+                                        input_count - 1, Some(&pkgbuild)))
                                 }
                             }
                         }
@@ -1062,21 +3686,36 @@ impl<'a> PkgbuildsParsing<'a> {
                     },
                     _ => {
                         key_value_from_slice_u8!(line, key, value);
+                        // Like those in the package, these could be 
+                        // overriden to empty
+                        match key {
+                            b"checkdepends" => 
+                                push_overridden(&mut arch.checkdepends, value),
+                            b"depends" => 
+                                push_overridden(&mut arch.depends, value),
+                            b"optdepends" => 
+                                push_overridden(&mut arch.optdepends, value),
+                            b"provides" => 
+                                push_overridden(&mut arch.provides, value),
+                            b"conflicts" => 
+                                push_overridden(&mut arch.conflicts, value),
+                            b"replaces" => 
+                                push_overridden(&mut arch.replaces, value),
+                            _ => (),
+                        }
                         if ! value.is_empty() {
                             match key {
                                 b"arch" => arch.arch = value,
-                                b"checkdepends" => arch.checkdepends.push(value),
-                                b"depends" => arch.depends.push(value),
-                                b"optdepends" => arch.optdepends.push(value),
-                                b"provides" => arch.provides.push(value),
-                                b"conflicts" => arch.conflicts.push(value),
-                                b"replaces" => arch.replaces.push(value),
+                                b"checkdepends" | b"depends" | b"optdepends" |
+                                b"provides" | b"conflicts" | b"replaces" => (),
+                                _ if lenient => 
+                                    arch.extra.push((key, value)),
                                 _ => {
                                     log::error!("Line '{}' does not contain valid \
                                     key or keyword when expecting package arch \
                                     info", str_from_slice_u8!(line));
-                                    return Err(Error::ParserScriptIllegalOutput(
-                                        line.into()))
+                                    return Err(illegal_output(line, 
+                                        input_count - 1, Some(&pkgbuild)))
                                 }
                             }
                         }
@@ -1114,12 +3753,14 @@ impl<'a> PkgbuildsParsing<'a> {
                                 b"conflicts" => arch.conflicts.push(value),
                                 b"provides" => arch.provides.push(value),
                                 b"replaces" => arch.replaces.push(value),
+                                _ if lenient => 
+                                    arch.extra.push((key, value)),
                                 _ => {
                                     log::error!("Line '{}' does not contain valid \
                                     key or keyword when expecting pkgbuild arch \
                                     info", str_from_slice_u8!(line));
-                                    return Err(Error::ParserScriptIllegalOutput(
-                                        line.into()))
+                                    return Err(illegal_output(line, 
+                                        input_count - 1, Some(&pkgbuild)))
                                 }
                             }
                         }
@@ -1133,173 +3774,302 @@ impl<'a> PkgbuildsParsing<'a> {
             ParsingState::None => (),
             ParsingState::Pkgbuild(pkgbuild) => 
                 pkgbuilds.push(pkgbuild),
-            _ => {
-                log::error!("Unexpected state before finishing PKGBUILDs: {:?}",
-                    state);
-                return Err(Error::ParserScriptIllegalOutput(Default::default()))
+            ParsingState::Package(pkgbuild, _) |
+            ParsingState::PackageArchSpecific(pkgbuild, _, _) |
+            ParsingState::PkgbuildArchSpecific(pkgbuild, _) => {
+                log::error!("Unexpected end of output when parsing PKGBUILD \
+                    '{}'", str_from_slice_u8!(pkgbuild.pkgbase));
+                return Err(illegal_output(b"", input_count - 1, Some(&pkgbuild)))
             },
         }
         Ok(Self {
             entries: pkgbuilds,
+            inputs,
         })
     }
 }
 
 /// A re-implementation of `rpmvercmp` funtion, which is used in pacman's 
 /// `alpm_pkg_vercmp()` routine. This is used when comparing `PlainVersion`.
+/// 
+/// Like pacman, any two strings are comparable, so this never returns `None`,
+/// the `Option` is only kept for compatibility.
 #[cfg(feature = "vercmp")]
 pub fn vercmp<S1, S2>(ver1: S1, ver2: S2) -> Option<Ordering>
 where
     S1: AsRef<str>,
     S2: AsRef<str>
 {
-    let spliter = |c: char|!c.is_ascii_alphanumeric();
-    let mut segs1 = ver1.as_ref().split(spliter);
-    let mut segs2 = ver2.as_ref().split(spliter);
-    loop {
-        let seg1 = segs1.next();
-        let seg2 = segs2.next();
-        if seg1.is_none() {
-            if seg2.is_none() {
-                return Some(Ordering::Equal)
-            } else {
-                return Some(Ordering::Less)
-            }
-        } else if seg2.is_none() {
-            return Some(Ordering::Greater)
-        }
-        // These both cannot be None, but we still need to fight the type system
-        let mut seg1 = seg1.unwrap_or("");
-        let mut seg2 = seg2.unwrap_or("");
-        // Compare each variant
-        while let Some(c) = seg1.chars().nth(0) {
-            let mut current1 = seg1;
-            let mut current2 = seg2;
-            let mut sub = false;
-            let is_digit = c.is_ascii_digit();
-            for (indic, c) in seg1.char_indices() {
-                if c.is_ascii_digit() != is_digit {
-                    current1 = &seg1[0..indic];
-                    seg1 = &seg1[indic..];
-                    sub = true;
-                    break
-                }
-            }
-            if sub {
-                sub = false
-            } else {
-                seg1 = ""
-            }
-            for (indic, c) in seg2.char_indices() {
-                if c.is_ascii_digit() != is_digit {
-                    current2 = &seg2[0..indic];
-                    seg2 = &seg2[indic..];
-                    sub = true;
-                    break
-                }
-            }
-            if ! sub {
-                seg2 = ""
-            }
-            if is_digit {
-                // Prefer digit one
-                if current2.is_empty() {
-                    return Some(Ordering::Greater)
-                }
-                current1 = current1.trim_start_matches(|c: char| c == '0');
-                current2 = current2.trim_start_matches(|c: char| c == '0'); 
-                // Shortcut: the longer one wins
-                if let Some(order) = 
-                    current1.len().partial_cmp(&current2.len()) 
-                {
-                    if order != Ordering::Equal {
-                        return Some(order)
-                    }
-                }
-            } else if current2.is_empty() {
-                // Prefer digit one
-                return Some(Ordering::Less)
-            }
-            if let Some(order) = current1.partial_cmp(current2) {
-                if order != Ordering::Equal {
-                    return Some(order)
-                }
-            }
+    Some(rpmvercmp(ver1.as_ref().as_bytes(), ver2.as_ref().as_bytes()))
+}
+
+/// A line-by-line port of pacman's `rpmvercmp()`, bytes other than ASCII 
+/// letters and digits are separators
+#[cfg(feature = "vercmp")]
+fn rpmvercmp(ver1: &[u8], ver2: &[u8]) -> Ordering {
+    fn skip_separators(ver: &[u8]) -> &[u8] {
+        let start = ver.iter().position(u8::is_ascii_alphanumeric)
+            .unwrap_or(ver.len());
+        &ver[start..]
+    }
+    fn split_segment(ver: &[u8], is_type: fn(&u8) -> bool) 
+        -> (&[u8], &[u8]) 
+    {
+        ver.split_at(ver.iter().position(|c|! is_type(c)).unwrap_or(ver.len()))
+    }
+    if ver1 == ver2 {
+        return Ordering::Equal
+    }
+    let mut one = ver1;
+    let mut two = ver2;
+    while ! one.is_empty() && ! two.is_empty() {
+        let next1 = skip_separators(one);
+        let next2 = skip_separators(two);
+        // The lengths of the separators skipped
+        let separators1 = one.len() - next1.len();
+        let separators2 = two.len() - next2.len();
+        one = next1;
+        two = next2;
+        if one.is_empty() || two.is_empty() {
+            break
+        }
+        if separators1 != separators2 {
+            return separators1.cmp(&separators2)
+        }
+        // Take a completely numeric or completely alpha segment of both, by
+        // the type of the first one
+        let is_num = one[0].is_ascii_digit();
+        let is_type = if is_num {
+            u8::is_ascii_digit
+        } else {
+            u8::is_ascii_alphabetic
+        };
+        let (mut segment1, remaining1) = split_segment(one, is_type);
+        let (mut segment2, remaining2) = split_segment(two, is_type);
+        // Numeric segments are always newer than alpha ones
+        if segment2.is_empty() {
+            return if is_num {Ordering::Greater} else {Ordering::Less}
         }
-        if ! seg1.is_empty() {
-            log::error!("Version segment '{}' non empty when should be", seg1);
-            return None
+        if is_num {
+            // Leading zeros are thrown away, then the longer number wins
+            let zeros = |segment: &[u8]|
+                segment.iter().take_while(|c|**c == b'0').count();
+            segment1 = &segment1[zeros(segment1)..];
+            segment2 = &segment2[zeros(segment2)..];
+            let order = segment1.len().cmp(&segment2.len());
+            if order != Ordering::Equal {
+                return order
+            }
         }
-        if ! seg2.is_empty() {
-            return Some(Ordering::Less)
+        let order = segment1.cmp(segment2);
+        if order != Ordering::Equal {
+            return order
         }
+        one = remaining1;
+        two = remaining2;
+    }
+    // All segments compared identically, but the separators could differ
+    if one.is_empty() && two.is_empty() {
+        return Ordering::Equal
+    }
+    // A remaining alpha segment never beats an empty string: two is newer if
+    // one is empty and two is not alpha, or if one is alpha
+    if (one.is_empty() && ! two.first().is_some_and(u8::is_ascii_alphabetic))
+        || one.first().is_some_and(u8::is_ascii_alphabetic)
+    {
+        Ordering::Less
+    } else {
+        Ordering::Greater
     }
 }
 
-/// The version without ordering, the one used for package itself, but not the
-/// one used when declaring dependency relationship.
-#[derive(Debug, PartialEq, Eq, Default, Clone)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct PlainVersion {
-    pub epoch: String,
-    pub pkgver: String,
-    pub pkgrel: String
+/// The policy used by `Pkgbuild::rebuild_decision()`
+#[cfg(feature = "vercmp")]
+#[derive(Debug, Clone)]
+pub struct RebuildPolicy {
+    /// Always rebuild devel packages regardless of the published version, as
+    /// their actual version is only known after running `pkgver()`
+    /// 
+    /// Default: `true`
+    pub always_rebuild_devel: bool,
+
+    /// Skip if the version equals to the published one, otherwise rebuild it
+    /// 
+    /// Default: `true`
+    pub skip_equal: bool,
+
+    /// Flag a version older than the published one as a `Downgrade`, 
+    /// otherwise silently skip it
+    /// 
+    /// Default: `true`
+    pub error_on_downgrade: bool,
 }
 
 #[cfg(feature = "vercmp")]
-impl PartialOrd for PlainVersion {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        // The ALPM parseEVR() always assume at least 0 epoch
-        let mut order = vercmp(
-            if self.epoch.is_empty() {"0"} else {&self.epoch},
-            if other.epoch.is_empty() {"0"} else {&other.epoch})?;
-        if order != Ordering::Equal {
-            return Some(order)
-        }
-        order = vercmp(&self.pkgver, &other.pkgver)?;
-        if order != Ordering::Equal {
-            return Some(order)
-        }
-        // Only compare pkgrel if they both exist
-        if self.pkgrel.is_empty() || other.pkgrel.is_empty() {
-            return Some(Ordering::Equal)
+impl Default for RebuildPolicy {
+    fn default() -> Self {
+        Self {
+            always_rebuild_devel: true,
+            skip_equal: true,
+            error_on_downgrade: true,
         }
-        vercmp(&self.pkgrel, &other.pkgrel)
     }
 }
 
 #[cfg(feature = "vercmp")]
-impl Ord for PlainVersion {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if let Some(order) = self.partial_cmp(other) {
-            order
-        } else {
-            // Imitate the pacman vercmp behaviour, return -1 (less) for 
-            // versions not comparable
-            Ordering::Less
-        }
+impl RebuildPolicy {
+    /// Get a `RebuildPolicy` instance with default settings: always rebuild
+    /// devel packages, skip equal versions, flag downgrades
+    pub fn new() -> Self {
+        Self::default()
     }
-}
 
-#[cfg(feature = "format")]
-impl Display for PlainVersion {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if ! self.epoch.is_empty() {
-            write!(f, "{}:", self.epoch)?;
-        }
-        write!(f, "{}", self.pkgver)?;
-        if ! self.pkgrel.is_empty() {
-            write!(f, "-{}", self.pkgrel)?
-        }
-        Ok(())
+    /// Set whether to always rebuild devel packages
+    pub fn set_always_rebuild_devel(&mut self, always_rebuild_devel: bool) 
+        -> &mut Self 
+    {
+        self.always_rebuild_devel = always_rebuild_devel;
+        self
     }
-}
 
-impl From<&str> for PlainVersion {
-    fn from(value: &str) -> Self {
-        let (epoch, value) = 
-            match value.split_once(':') 
-        {
+    /// Set whether to skip versions equal to the published ones
+    pub fn set_skip_equal(&mut self, skip_equal: bool) -> &mut Self {
+        self.skip_equal = skip_equal;
+        self
+    }
+
+    /// Set whether to flag versions older than the published ones
+    pub fn set_error_on_downgrade(&mut self, error_on_downgrade: bool) 
+        -> &mut Self 
+    {
+        self.error_on_downgrade = error_on_downgrade;
+        self
+    }
+}
+
+/// Why `Pkgbuild::rebuild_decision()` decided to build
+#[cfg(feature = "vercmp")]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RebuildReason {
+    /// Nothing was published yet
+    NotPublished,
+    /// The version is newer than the published one, including epoch and 
+    /// pkgrel bumps
+    Newer,
+    /// The version equals to the published one, but the policy does not 
+    /// skip equal versions
+    Equal,
+    /// This is a devel package and the policy always rebuilds them
+    Devel,
+}
+
+/// The result of `Pkgbuild::rebuild_decision()`, carrying the compared 
+/// versions for logging
+#[cfg(feature = "vercmp")]
+#[derive(Debug, PartialEq, Clone)]
+pub enum RebuildDecision {
+    /// This should be built
+    Build {
+        local: PlainVersion,
+        published: Option<PlainVersion>,
+        reason: RebuildReason,
+    },
+    /// This should not be built
+    Skip {
+        local: PlainVersion,
+        published: PlainVersion,
+    },
+    /// The version is older than the published one, and the policy flags that
+    Downgrade {
+        local: PlainVersion,
+        published: PlainVersion,
+    },
+}
+
+#[cfg(feature = "vercmp")]
+impl RebuildDecision {
+    /// Whether this should be built
+    pub fn should_build(&self) -> bool {
+        matches!(self, RebuildDecision::Build { .. })
+    }
+}
+
+#[cfg(all(feature = "vercmp", feature = "format"))]
+impl Display for RebuildDecision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RebuildDecision::Build { local, published: None, reason: _ } =>
+                write!(f, "build {} (not published)", local),
+            RebuildDecision::Build { local, published: Some(published), 
+                reason } => 
+                write!(f, "build {} over {} ({:?})", local, published, reason),
+            RebuildDecision::Skip { local, published } => 
+                write!(f, "skip {} (published {})", local, published),
+            RebuildDecision::Downgrade { local, published } => 
+                write!(f, "downgrade {} < published {}", local, published),
+        }
+    }
+}
+
+/// The version without ordering, the one used for package itself, but not the
+/// one used when declaring dependency relationship.
+#[derive(Debug, PartialEq, Eq, Hash, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlainVersion {
+    pub epoch: String,
+    pub pkgver: String,
+    pub pkgrel: String
+}
+
+#[cfg(feature = "vercmp")]
+impl PartialOrd for PlainVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "vercmp")]
+impl Ord for PlainVersion {
+    /// Compare like pacman's `alpm_pkg_vercmp()`
+    fn cmp(&self, other: &Self) -> Ordering {
+        // The ALPM parseEVR() always assume at least 0 epoch
+        fn epoch(version: &PlainVersion) -> &[u8] {
+            if version.epoch.is_empty() {b"0"} else {version.epoch.as_bytes()}
+        }
+        match (self.epoch_num(), other.epoch_num()) {
+            (Some(epoch), Some(other)) => epoch.cmp(&other),
+            _ => rpmvercmp(epoch(self), epoch(other)),
+        }.then_with(||rpmvercmp(self.pkgver.as_bytes(), 
+                other.pkgver.as_bytes()))
+            .then_with(||
+                // Only compare pkgrel if they both exist
+                if self.pkgrel.is_empty() || other.pkgrel.is_empty() {
+                    Ordering::Equal
+                } else {
+                    rpmvercmp(self.pkgrel.as_bytes(), other.pkgrel.as_bytes())
+                })
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for PlainVersion {
+    /// Render as `epoch:pkgver-pkgrel`, with `epoch:` and `-pkgrel` omitted if
+    /// they're empty, so any well-formed version string round-trips through
+    /// `PlainVersion::from()`. A version without `pkgver` is not a valid 
+    /// version and renders as empty, instead of a dangling `1:` or `-1`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_version_string())
+    }
+}
+
+impl From<&str> for PlainVersion {
+    /// Split a version string `epoch:pkgver-pkgrel` at the first `:` and the
+    /// last `-`, the pieces are kept verbatim, e.g. a decimal pkgrel `4.5`,
+    /// even if invalid, use `parse_strict()` to reject those
+    fn from(value: &str) -> Self {
+        let (epoch, value) = 
+            match value.split_once(':') 
+        {
             Some((epoch, remaining)) 
                 =>(epoch.into(), remaining),
             None => (Default::default(), value),
@@ -1314,6 +4084,51 @@ impl From<&str> for PlainVersion {
     }
 }
 
+impl FromStr for PlainVersion {
+    type Err = VersionParseError;
+
+    /// Parse strictly, see `parse_strict()`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::parse_strict(s)
+    }
+}
+
+/// A component of a version string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionComponent {
+    Epoch,
+    Pkgver,
+    Pkgrel,
+}
+
+impl Display for VersionComponent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionComponent::Epoch => write!(f, "epoch"),
+            VersionComponent::Pkgver => write!(f, "pkgver"),
+            VersionComponent::Pkgrel => write!(f, "pkgrel"),
+        }
+    }
+}
+
+/// A version string rejected by `PlainVersion::parse_strict()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionParseError {
+    pub input: String,
+    /// The offending component
+    pub component: VersionComponent,
+    pub reason: &'static str,
+}
+
+impl Display for VersionParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid version '{}': {} {}", 
+            self.input, self.component, self.reason)
+    }
+}
+
+impl std::error::Error for VersionParseError {}
+
 impl From<&[u8]> for PlainVersion {
     fn from(value: &[u8]) -> Self {
         Self::from(str_from_slice_u8!(value))
@@ -1328,10 +4143,116 @@ impl PlainVersion {
             pkgrel: string_from_slice_u8!(pkgrel),
         }
     }
+
+    /// Parse a version string `epoch:pkgver-pkgrel` like `from()`, but reject
+    /// what makepkg and pacman would not take: an empty or non-numeric 
+    /// `epoch` if `:` is there, an empty `pkgver` or one containing `-`, `:`,
+    /// `/` or whitespace, and a `pkgrel` that is not a positive decimal like
+    /// `1` or `1.1` if `-` is there. Both `epoch:` and `-pkgrel` are optional,
+    /// as in the versions of dependencies.
+    pub fn parse_strict(value: &str) 
+        -> std::result::Result<Self, VersionParseError> 
+    {
+        let check = |component, rule: ValidationRule, part: &str|
+            match rule(part) {
+                Some((_, reason)) => Err(VersionParseError { 
+                    input: value.into(), component, reason }),
+                None => Ok(()),
+            };
+        let (epoch, remaining) = match value.split_once(':') {
+            Some((epoch, remaining)) => (Some(epoch), remaining),
+            None => (None, value),
+        };
+        let (pkgver, pkgrel) = match remaining.rsplit_once('-') {
+            Some((pkgver, pkgrel)) => (pkgver, Some(pkgrel)),
+            None => (remaining, None),
+        };
+        if let Some(epoch) = epoch {
+            check(VersionComponent::Epoch, |epoch|epoch.is_empty().then_some(
+                (Severity::Error, "is empty")), epoch)?;
+            check(VersionComponent::Epoch, validate_epoch, epoch)?
+        }
+        check(VersionComponent::Pkgver, validate_pkgver, pkgver)?;
+        if let Some(pkgrel) = pkgrel {
+            check(VersionComponent::Pkgrel, validate_pkgrel, pkgrel)?
+        }
+        Ok(Self { 
+            epoch: epoch.unwrap_or_default().into(), 
+            pkgver: pkgver.into(), 
+            pkgrel: pkgrel.unwrap_or_default().into(),
+        })
+    }
+
+    /// Get the version as `epoch:pkgver-pkgrel`, with `epoch:` and `-pkgrel` 
+    /// omitted if they're empty, empty if `pkgver` is, the same as the 
+    /// `Display` of the `format` feature
+    pub fn to_version_string(&self) -> String {
+        if self.pkgver.is_empty() {
+            return String::new()
+        }
+        let mut version = String::new();
+        if ! self.epoch.is_empty() {
+            version.push_str(&self.epoch);
+            version.push(':')
+        }
+        version.push_str(&self.pkgver);
+        if ! self.pkgrel.is_empty() {
+            version.push('-');
+            version.push_str(&self.pkgrel)
+        }
+        version
+    }
+
+    /// Get the `epoch` as a number, `0` if empty like pacman assumes, `None` if
+    /// it is not a non-negative integer fitting in `u64`
+    pub fn epoch_num(&self) -> Option<u64> {
+        if self.epoch.is_empty() {
+            Some(0)
+        } else if self.epoch.bytes().all(|c|c.is_ascii_digit()) {
+            self.epoch.parse().ok()
+        } else {
+            None
+        }
+    }
+
+    /// Get this with the `epoch` set to a number, `0` clears it, as makepkg
+    /// omits a zero `epoch` from the full version
+    pub fn with_epoch(mut self, epoch: u64) -> Self {
+        self.epoch = if epoch == 0 {
+            String::new()
+        } else {
+            epoch.to_string()
+        };
+        self
+    }
+
+    /// Increment the `pkgrel` for a rebuild, `2` becomes `3` and the decimal
+    /// `2.1` becomes `2.2`, an empty or malformed one becomes `1`
+    pub fn bump_pkgrel(&mut self) {
+        let (major, minor) = match self.pkgrel.split_once('.') {
+            Some((major, minor)) => (major, Some(minor)),
+            None => (self.pkgrel.as_str(), None),
+        };
+        let Ok(major) = major.parse::<u64>() else {
+            self.pkgrel = "1".into();
+            return
+        };
+        self.pkgrel = match minor.map(str::parse::<u64>) {
+            Some(Ok(minor)) => format!("{}.{}", major, minor + 1),
+            Some(Err(_)) => "1".into(),
+            None => (major + 1).to_string(),
+        }
+    }
+
+    /// Set a new `pkgver` and reset the `pkgrel` to `1`, the `epoch` is kept
+    pub fn set_pkgver<S: Into<String>>(&mut self, pkgver: S) {
+        self.pkgver = pkgver.into();
+        self.pkgrel = "1".into()
+    }
 }
 
-/// The dependency order, comparision is not implemented yet
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// The dependency order
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DependencyOrder {
     Greater,
@@ -1341,6 +4262,22 @@ pub enum DependencyOrder {
     Less
 }
 
+impl DependencyOrder {
+    /// Split `name`, the order and `version` at the first of `<`, `>` and 
+    /// `=`, `>=` and `<=` taking precedence over `>` and `<` there
+    fn split(value: &str) -> Option<(&str, Self, &str)> {
+        let (name, remaining) = value.split_at(value.find(['<', '>', '='])?);
+        let (order, len) = match remaining.as_bytes() {
+            [b'>', b'=', ..] => (Self::GreaterOrEqual, 2),
+            [b'<', b'=', ..] => (Self::LessOrEqual, 2),
+            [b'>', ..] => (Self::Greater, 1),
+            [b'<', ..] => (Self::Less, 1),
+            _ => (Self::Equal, 1),
+        };
+        Some((name, order, &remaining[len..]))
+    }
+}
+
 #[cfg(feature = "format")]
 impl Display for DependencyOrder {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -1359,8 +4296,9 @@ impl Display for DependencyOrder {
     }
 }
 
-/// The dependency version, comparision is not implemented yet
-#[derive(Debug, PartialEq, Clone)]
+/// The dependency version, check a version against it with `accepts()` of the
+/// `vercmp` feature
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OrderedVersion {
     pub order: DependencyOrder,
@@ -1368,6 +4306,133 @@ pub struct OrderedVersion {
     pub plain: PlainVersion,
 }
 
+/// The strings of a version to sort by, so sorting works without the `vercmp`
+/// feature
+fn version_sort_key(version: &PlainVersion) -> (&str, &str, &str) {
+    (&version.epoch, &version.pkgver, &version.pkgrel)
+}
+
+impl PartialOrd for OrderedVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedVersion {
+    /// Sort by the order then the version strings, this is only for sorting
+    /// and deduplicating, use `accepts()` to check versions
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.order.cmp(&other.order).then_with(||
+            version_sort_key(&self.plain).cmp(&version_sort_key(&other.plain)))
+    }
+}
+
+#[cfg(feature = "vercmp")]
+impl OrderedVersion {
+    /// Whether `candidate` satisfies this, like libalpm's `dep_vercmp()`, 
+    /// i.e. `epoch` defaults to `0`, and `pkgrel` is only compared if both 
+    /// have it
+    pub fn accepts(&self, candidate: &PlainVersion) -> bool {
+        let order = candidate.cmp(&self.plain);
+        match self.order {
+            DependencyOrder::Greater => order == Ordering::Greater,
+            DependencyOrder::GreaterOrEqual => order != Ordering::Less,
+            DependencyOrder::Equal => order == Ordering::Equal,
+            DependencyOrder::LessOrEqual => order != Ordering::Greater,
+            DependencyOrder::Less => order == Ordering::Less,
+        }
+    }
+
+    /// Whether some version could satisfy both this and `other`, e.g. `>2` 
+    /// and `<1` could never be satisfied together. A version without `pkgrel`
+    /// covers all `pkgrel`s of it, so `=1.0` overlaps `>1.0-1`.
+    pub fn overlaps(&self, other: &OrderedVersion) -> bool {
+        let (lower, upper) = self.bounds();
+        let (other_lower, other_upper) = other.bounds();
+        [lower, other_lower].into_iter().flatten().all(|lower|
+            [upper, other_upper].into_iter().flatten().all(|upper|
+                bound_below(lower, upper)))
+    }
+
+    /// The lower and upper bounds of the versions accepted, each with whether
+    /// it is inclusive, `None` if unbounded
+    fn bounds(&self) -> (Option<VersionBound<'_>>, Option<VersionBound<'_>>) {
+        let plain = &self.plain;
+        match self.order {
+            DependencyOrder::Greater => (Some((plain, false)), None),
+            DependencyOrder::GreaterOrEqual => (Some((plain, true)), None),
+            DependencyOrder::Equal => 
+                (Some((plain, true)), Some((plain, true))),
+            DependencyOrder::LessOrEqual => (None, Some((plain, true))),
+            DependencyOrder::Less => (None, Some((plain, false))),
+        }
+    }
+}
+
+/// A bound of the versions accepted by an `OrderedVersion`, with whether it
+/// is inclusive
+#[cfg(feature = "vercmp")]
+type VersionBound<'a> = (&'a PlainVersion, bool);
+
+/// Whether some version is at or above the `lower` bound and at or below the
+/// `upper` bound
+#[cfg(feature = "vercmp")]
+fn bound_below(lower: VersionBound<'_>, upper: VersionBound<'_>) -> bool {
+    match lower.0.cmp(upper.0) {
+        Ordering::Less => true,
+        Ordering::Greater => false,
+        // A bound without pkgrel covers all pkgrels, so there is room beside
+        // the other bound if it is inclusive itself
+        Ordering::Equal => match (lower.0.pkgrel.is_empty(), 
+            upper.0.pkgrel.is_empty()) 
+        {
+            (true, false) => lower.1,
+            (false, true) => upper.1,
+            _ => lower.1 && upper.1,
+        },
+    }
+}
+
+/// A version equals an `OrderedVersion` if accepted by it, otherwise it is
+/// below or above the versions accepted
+#[cfg(feature = "vercmp")]
+impl PartialEq<OrderedVersion> for PlainVersion {
+    fn eq(&self, other: &OrderedVersion) -> bool {
+        other.accepts(self)
+    }
+}
+
+#[cfg(feature = "vercmp")]
+impl PartialOrd<OrderedVersion> for PlainVersion {
+    fn partial_cmp(&self, other: &OrderedVersion) -> Option<Ordering> {
+        if other.accepts(self) {
+            return Some(Ordering::Equal)
+        }
+        // Rejected by a lower bound means below, by an upper bound above
+        Some(match other.order {
+            DependencyOrder::Greater | DependencyOrder::GreaterOrEqual => 
+                Ordering::Less,
+            DependencyOrder::Less | DependencyOrder::LessOrEqual => 
+                Ordering::Greater,
+            DependencyOrder::Equal => self.cmp(&other.plain),
+        })
+    }
+}
+
+#[cfg(feature = "vercmp")]
+impl PartialEq<PlainVersion> for OrderedVersion {
+    fn eq(&self, other: &PlainVersion) -> bool {
+        self.accepts(other)
+    }
+}
+
+#[cfg(feature = "vercmp")]
+impl PartialOrd<PlainVersion> for OrderedVersion {
+    fn partial_cmp(&self, other: &PlainVersion) -> Option<Ordering> {
+        other.partial_cmp(self).map(Ordering::reverse)
+    }
+}
+
 #[cfg(feature = "format")]
 impl Display for OrderedVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -1375,8 +4440,90 @@ impl Display for OrderedVersion {
     }
 }
 
-/// A dependency
-#[derive(Debug, PartialEq, Clone)]
+/// Why a string is rejected by the `FromStr` of `Dependency`, `Provide`,
+/// `OptionalDependency` or `Source`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorReason {
+    /// The name is empty, e.g. `>=1.0` or `::https://example.com/foo`
+    EmptyName,
+    /// The version after an operator is empty, e.g. `foo>=`
+    EmptyVersion,
+    /// The URL of a source is empty, e.g. `foo::`
+    EmptyUrl,
+    /// A character not allowed there, e.g. `>` in a `provides`, or a space in
+    /// a name
+    IllegalCharacter(char),
+}
+
+impl Display for ParseErrorReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorReason::EmptyName => write!(f, "empty name"),
+            ParseErrorReason::EmptyVersion => 
+                write!(f, "empty version after operator"),
+            ParseErrorReason::EmptyUrl => write!(f, "empty URL"),
+            ParseErrorReason::IllegalCharacter(c) => 
+                write!(f, "illegal character '{}'", c),
+        }
+    }
+}
+
+/// A string rejected by the `FromStr` of `Dependency`, `Provide`, 
+/// `OptionalDependency` or `Source`, their `From` and `TryFrom` still take
+/// it on a best-effort basis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub input: String,
+    pub reason: ParseErrorReason,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid '{}': {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    fn new(input: &str, reason: ParseErrorReason) -> Self {
+        Self { input: input.into(), reason }
+    }
+}
+
+/// Check the name of a dependency or provide, with the characters makepkg
+/// allows in `pkgname`
+fn check_relation_name(name: &str) 
+    -> std::result::Result<(), ParseErrorReason> 
+{
+    if name.is_empty() {
+        return Err(ParseErrorReason::EmptyName)
+    }
+    match name.chars().find(|c|! (c.is_ascii_alphanumeric() 
+        || matches!(c, '@' | '.' | '_' | '+' | '-'))) 
+    {
+        Some(c) => Err(ParseErrorReason::IllegalCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+/// Check the version after the operator of a dependency or provide
+fn check_relation_version(version: &str) 
+    -> std::result::Result<(), ParseErrorReason> 
+{
+    if version.is_empty() {
+        return Err(ParseErrorReason::EmptyVersion)
+    }
+    match version.chars().find(|c|matches!(c, '<' | '>' | '=') 
+        || c.is_whitespace()) 
+    {
+        Some(c) => Err(ParseErrorReason::IllegalCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+/// A dependency, sorted by name, then order and version strings
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Dependency {
     pub name: String,
@@ -1395,112 +4542,297 @@ impl Display for Dependency {
 }
 
 impl From<&str> for Dependency {
+    /// Split at the first operator, the pieces are kept verbatim even if 
+    /// empty, e.g. `foo>=` has an empty version and `>=1.0` an empty name
     fn from(value: &str) -> Self {
-        if let Some((name, version)) = 
-            value.split_once("=") 
-        {
-            if let Some((name, version)) = 
-                value.split_once(">=") 
-            {
-                Self { name: name.into(), 
-                    version: Some(OrderedVersion { 
-                        order: DependencyOrder::GreaterOrEqual, 
-                        plain: version.into() }) }
-            } else if let Some((name, version)) = 
-                value.split_once("<=") 
-            {
-                Self { name: name.into(), 
-                    version: Some(OrderedVersion { 
-                        order: DependencyOrder::LessOrEqual, 
-                        plain: version.into() }) }
-            } else {
-                Self { name: name.into(), 
-                    version: Some(OrderedVersion { 
-                        order: DependencyOrder::Equal, 
-                        plain: version.into() }) }
-            }
-        } else if let Some((name, version)) = 
-            value.split_once('>') 
-        {
-            Self { name: name.into(), 
-                version: Some(OrderedVersion { 
-                    order: DependencyOrder::Greater, 
-                    plain: version.into() }) }
-
-        } else if let Some((name, version)) = 
-            value.split_once('<') 
-        {
-            Self { name: name.into(), 
+        match DependencyOrder::split(value) {
+            Some((name, order, version)) => Self { name: name.into(),
                 version: Some(OrderedVersion { 
-                    order: DependencyOrder::Less, 
-                    plain: version.into() }) }
-        } else {
-            Self {name: value.into(), version: None}
+                    order, plain: version.into() }) },
+            None => Self { name: value.into(), version: None },
         }
     }
 }
 
+impl FromStr for Dependency {
+    type Err = ParseError;
+
+    /// Parse like `from()`, but reject an empty name or version, and 
+    /// characters makepkg does not allow in them
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match DependencyOrder::split(s) {
+            Some((name, _, version)) => check_relation_name(name).and_then(
+                |_|check_relation_version(version)),
+            None => check_relation_name(s),
+        }.map_err(|reason|ParseError::new(s, reason))?;
+        Ok(Self::from(s))
+    }
+}
+
 impl From<&[u8]> for Dependency {
     fn from(value: &[u8]) -> Self {
         Self::from(str_from_slice_u8!(value))
     }
 }
 
-pub type MakeDependency = Dependency;
-pub type CheckDependency = Dependency;
-
-#[derive(Debug, PartialEq, Clone)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct OptionalDependency {
-    pub dep: Dependency,
-    pub reason: String,
+impl Dependency {
+    /// Parse each whitespace-separated word like `from()`, e.g. `foo bar>=2`
+    /// into `foo` and `bar>=2`, as the parser does for the elements of the
+    /// relation arrays, while `from()` keeps all of it in one
+    pub fn parse_many(value: &str) -> Vec<Self> {
+        value.split_ascii_whitespace().map(Self::from).collect()
+    }
 }
 
-impl From<&str> for OptionalDependency {
-    fn from(value: &str) -> Self {
-        if let Some((dep, reason)) = 
-            value.split_once(": ") 
-        {
-            Self {
-                dep: dep.into(),
-                reason: reason.into(),
-            }
-        } else {
-            Self {
-                dep: value.into(),
-                reason: Default::default(),
-            }
+#[cfg(feature = "vercmp")]
+impl Dependency {
+    /// Whether `version` satisfies the version constraint of this, regardless
+    /// of the name, always for an unversioned one. Like libalpm, `epoch` 
+    /// defaults to `0`, and `pkgrel` is only compared if both have it.
+    pub fn satisfied_by_version(&self, version: &PlainVersion) -> bool {
+        self.version.as_ref().is_none_or(|required|required.accepts(version))
+    }
+
+    /// Whether `provide` satisfies this, by name and version, like pacman an
+    /// unversioned provide only satisfies an unversioned dependency
+    pub fn satisfied_by_provide(&self, provide: &Provide) -> bool {
+        if self.name != provide.name {
+            return false
+        }
+        match (&self.version, &provide.version) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(_), Some(version)) => self.satisfied_by_version(version),
         }
     }
-}
 
-impl From<&[u8]> for OptionalDependency {
-    fn from(value: &[u8]) -> Self {
-        Self::from(str_from_slice_u8!(value))
+    /// Whether a package named `pkgname` with `version` satisfies this by 
+    /// itself, i.e. not counting its `provides`
+    pub fn satisfied_by_package(&self, pkgname: &str, version: &PlainVersion)
+        -> bool
+    {
+        self.name == pkgname && self.satisfied_by_version(version)
     }
-}
 
-#[cfg(feature = "format")]
-impl Display for OptionalDependency {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.dep)?;
-        if ! self.reason.is_empty() {
-            write!(f, ": {}", self.reason)?;
-        }
-        Ok(())
+    /// Whether the package `pkg` of `pkgbuild` satisfies this, either by its
+    /// `pkgname` and version, or by one of its effective `provides` for 
+    /// `arch`, or for all architectures if `None`
+    pub fn satisfied_by_package_of(
+        &self, pkgbuild: &Pkgbuild, pkg: &Package, arch: Option<&Architecture>
+    ) -> bool 
+    {
+        self.satisfied_by_package(&pkg.pkgname, &pkgbuild.version)
+            || pkg.effective_provides(pkgbuild, arch).into_iter().any(
+                |provide|self.satisfied_by_provide(provide))
     }
 }
 
-pub type Conflict = Dependency;
+/// Declare a newtype of `Dependency` for a relation array other than 
+/// `depends`, so they could not be mixed up, while still reading like a 
+/// `Dependency` through `Deref`
+macro_rules! dependency_newtype {
+    ($(#[$meta:meta])* $type:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+        #[cfg_attr(feature = "serde", 
+            derive(Serialize, Deserialize), serde(transparent))]
+        pub struct $type(pub Dependency);
+
+        impl std::ops::Deref for $type {
+            type Target = Dependency;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
 
-#[derive(Debug, PartialEq, Clone)]
+        impl std::ops::DerefMut for $type {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+
+        impl From<Dependency> for $type {
+            fn from(value: Dependency) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$type> for Dependency {
+            fn from(value: $type) -> Self {
+                value.0
+            }
+        }
+
+        impl From<&str> for $type {
+            fn from(value: &str) -> Self {
+                Self(value.into())
+            }
+        }
+
+        impl From<&[u8]> for $type {
+            fn from(value: &[u8]) -> Self {
+                Self(value.into())
+            }
+        }
+
+        impl FromStr for $type {
+            type Err = ParseError;
+
+            fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+
+        #[cfg(feature = "format")]
+        impl Display for $type {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+dependency_newtype!(
+    /// A dependency in `makedepends`
+    /// 
+    /// It could not be mixed up with the other dependency arrays:
+    /// 
+    /// ```compile_fail
+    /// let mut pkgbuild = pkgbuild::Pkgbuild::default();
+    /// let make: pkgbuild::MakeDependency = "meson".into();
+    /// pkgbuild.multiarch.any.checkdepends.push(make);
+    /// ```
+    MakeDependency);
+dependency_newtype!(
+    /// A dependency in `checkdepends`
+    /// 
+    /// ```compile_fail
+    /// let check: pkgbuild::CheckDependency = "python-pytest".into();
+    /// let makedepends: Vec<pkgbuild::MakeDependency> = vec![check];
+    /// ```
+    CheckDependency);
+dependency_newtype!(
+    /// A package in `conflicts`, matched like a dependency
+    /// 
+    /// A conflict could not be pushed into `depends` by mistake:
+    /// 
+    /// ```compile_fail
+    /// let mut pkgbuild = pkgbuild::Pkgbuild::default();
+    /// let conflict: pkgbuild::Conflict = "foo".into();
+    /// pkgbuild.multiarch.any.depends.push(conflict);
+    /// ```
+    /// 
+    /// But only when converted explicitly:
+    /// 
+    /// ```
+    /// let mut pkgbuild = pkgbuild::Pkgbuild::default();
+    /// let conflict: pkgbuild::Conflict = "foo".into();
+    /// pkgbuild.multiarch.any.depends.push(conflict.into());
+    /// ```
+    Conflict);
+dependency_newtype!(
+    /// A package in `replaces`, matched like a dependency
+    /// 
+    /// ```compile_fail
+    /// let mut pkgbuild = pkgbuild::Pkgbuild::default();
+    /// let replace: pkgbuild::Replace = "foo-legacy".into();
+    /// pkgbuild.multiarch.any.conflicts.push(replace);
+    /// ```
+    Replace);
+
+/// An optional dependency, `dep: reason` in `optdepends`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OptionalDependency {
+    /// The dependency, could be versioned, e.g. `java-environment>=17`
+    pub dep: Dependency,
+    /// The reason after the first `: `, empty if there's none
+    pub reason: String,
+}
+
+impl OptionalDependency {
+    /// Whether the dependency has a version constraint
+    pub fn is_versioned(&self) -> bool {
+        self.dep.version.is_some()
+    }
+}
+
+impl From<&str> for OptionalDependency {
+    /// Split at the first `: ` like libalpm, so the reason could contain `: `
+    /// itself, and the dependency before it keeps its version constraint, 
+    /// e.g. `java-environment>=17: for the GUI`. A `:` not followed by a 
+    /// space is part of the dependency.
+    fn from(value: &str) -> Self {
+        if let Some((dep, reason)) = 
+            value.split_once(": ") 
+        {
+            Self {
+                dep: dep.into(),
+                reason: reason.into(),
+            }
+        } else {
+            Self {
+                dep: value.into(),
+                reason: Default::default(),
+            }
+        }
+    }
+}
+
+impl FromStr for OptionalDependency {
+    type Err = ParseError;
+
+    /// Parse like `from()`, with the dependency part checked like 
+    /// `Dependency::from_str()`, the reason could be anything
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let dep = s.split_once(": ").map_or(s, |(dep, _)|dep);
+        Dependency::from_str(dep).map_err(
+            |e|ParseError::new(s, e.reason))?;
+        Ok(Self::from(s))
+    }
+}
+
+impl From<&[u8]> for OptionalDependency {
+    fn from(value: &[u8]) -> Self {
+        Self::from(str_from_slice_u8!(value))
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for OptionalDependency {
+    /// Render as `dep: reason`, or `dep` alone if the reason is empty, so any
+    /// string with a non-empty reason round-trips through `from()`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.dep)?;
+        if ! self.reason.is_empty() {
+            write!(f, ": {}", self.reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Provide {
     pub name: String,
     pub version: Option<PlainVersion>
 }
 
-pub type Replace = Dependency;
+impl PartialOrd for Provide {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Provide {
+    /// Sort by name then the version strings, unversioned first
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name).then_with(||
+            self.version.as_ref().map(version_sort_key)
+                .cmp(&other.version.as_ref().map(version_sort_key)))
+    }
+}
 
 #[cfg(feature = "format")]
 impl Display for Provide {
@@ -1521,7 +4853,8 @@ impl TryFrom<&str> for Provide {
             value.contains('<') 
         {
             log::error!("Version string '{}' contains illegal > or <", value);
-            return Err(Error::BrokenPKGBUILDs(Vec::new()))
+            return Err(BrokenPkgbuild::error(format!(
+                "provides '{}' contains illegal > or <", value)))
         }
         if let Some((name, version)) = 
             value.split_once("=") 
@@ -1534,6 +4867,35 @@ impl TryFrom<&str> for Provide {
     }
 }
 
+#[cfg(feature = "vercmp")]
+impl Provide {
+    /// Whether this satisfies `dep` by pacman's rules, see 
+    /// `Dependency::satisfied_by_provide()`, e.g. `foo=1.2` satisfies `foo`, 
+    /// `foo=1.2` and `foo>=1.0`, while `foo` only satisfies `foo`, and a 
+    /// `pkgrel` is only compared if both have it
+    pub fn satisfies(&self, dep: &Dependency) -> bool {
+        dep.satisfied_by_provide(self)
+    }
+}
+
+impl FromStr for Provide {
+    type Err = ParseError;
+
+    /// Parse like `try_from()`, but also reject an empty name or version 
+    /// after `=`, and characters makepkg does not allow in them
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, version) = match s.split_once('=') {
+            Some((name, version)) => (name, Some(version)),
+            None => (s, None),
+        };
+        check_relation_name(name).and_then(|_|match version {
+            Some(version) => check_relation_version(version),
+            None => Ok(()),
+        }).map_err(|reason|ParseError::new(s, reason))?;
+        Ok(Self { name: name.into(), version: version.map(Into::into) })
+    }
+}
+
 impl TryFrom<&[u8]> for Provide {
     type Error = Error;
 
@@ -1542,78 +4904,411 @@ impl TryFrom<&[u8]> for Provide {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+impl From<&Dependency> for Provide {
+    /// Provide what `dep` names, with its version only if it requires exactly
+    /// that version, i.e. `foo=1.2`, as a provide can only carry `=`
+    fn from(dep: &Dependency) -> Self {
+        Self { 
+            name: dep.name.clone(), 
+            version: dep.version.as_ref().filter(
+                |version|version.order == DependencyOrder::Equal)
+                .map(|version|version.plain.clone()),
+        }
+    }
+}
+
+impl TryFrom<Provide> for Dependency {
+    type Error = ParseError;
+
+    /// Depend on exactly what `provide` provides, i.e. `foo=1.2` for a 
+    /// versioned one, checked like `Dependency::from_str()`
+    fn try_from(provide: Provide) -> std::result::Result<Self, Self::Error> {
+        let version = provide.version.as_ref()
+            .map(PlainVersion::to_version_string);
+        let mut input = provide.name.clone();
+        if let Some(version) = &version {
+            input.push('=');
+            input.push_str(version)
+        }
+        check_relation_name(&provide.name).and_then(|_|
+            version.as_deref().map_or(Ok(()), check_relation_version))
+            .map_err(|reason|ParseError::new(&input, reason))?;
+        Ok(Self { 
+            name: provide.name, 
+            version: provide.version.map(|plain|OrderedVersion { 
+                order: DependencyOrder::Equal, plain })
+        })
+    }
+}
+
+/// The arch-specific variables of a split package
+/// 
+/// Each array is `None` if not set in the package function so the one of the
+/// `PKGBUILD` is inherited, `Some` with an empty `Vec` if explicitly set to 
+/// empty there
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PackageArchSpecific {
-    pub checkdepends: Vec<CheckDependency>,
+    pub checkdepends: Option<Vec<CheckDependency>>,
     /// The dependencies of the split package
-    pub depends: Vec<Dependency>,
-    pub optdepends: Vec<OptionalDependency>,
+    pub depends: Option<Vec<Dependency>>,
+    pub optdepends: Option<Vec<OptionalDependency>>,
     /// What virtual packages does this package provide
-    pub provides: Vec<Provide>,
-    pub conflicts: Vec<Conflict>,
-    pub replaces: Vec<Replace>,
+    pub provides: Option<Vec<Provide>>,
+    pub conflicts: Option<Vec<Conflict>>,
+    pub replaces: Option<Vec<Replace>>,
+    /// Lines with keys unknown to this library, only collected with 
+    /// `Strictness::Lenient`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra: BTreeMap<String, Vec<String>>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MultiArch<T> {
     pub any: T,
     pub arches: BTreeMap<Architecture, T>,
+    /// The order the architectures were declared in `arch`, as `arches` is
+    /// sorted by key, use `iter_arches()` to iterate in this order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub arch_order: Vec<Architecture>,
+}
+
+impl<T> MultiArch<T> {
+    /// Whether the architectures were declared as `arch=(any)`, instead of
+    /// not declared at all, both have no architecture-specific sections
+    pub fn is_any_declared(&self) -> bool {
+        self.arches.is_empty() && self.arch_order.contains(&Architecture::Any)
+    }
+
+    /// Get the declared architectures in declared order, `[Architecture::Any]`
+    /// for `arch=(any)`, empty if not declared at all
+    pub fn declared_arches(&self) -> Vec<Architecture> {
+        if self.is_any_declared() {
+            vec![Architecture::Any]
+        } else {
+            self.iter_arches().map(|(arch, _)|arch.clone()).collect()
+        }
+    }
+
+    /// Get the variables specific to the architecture
+    pub fn get(&self, arch: &Architecture) -> Option<&T> {
+        self.arches.get(arch)
+    }
+
+    /// Iterate over the arch-independent variables, with `None` as the
+    /// architecture, followed by the arch-specific ones like `iter_arches()`
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&Architecture>, &T)> {
+        [(None, &self.any)].into_iter().chain(
+            self.iter_arches().map(|(arch, value)|(Some(arch), value)))
+    }
+
+    /// Whether both have the same set of architectures, regardless of order
+    pub fn same_arches<T2>(&self, other: &MultiArch<T2>) -> bool {
+        self.arches.len() == other.arches.len()
+            && self.arches.keys().all(|arch|other.arches.contains_key(arch))
+    }
+
+    /// Whether both have the same architectures and `eq` holds for the 
+    /// arch-independent and each arch-specific variables, regardless of the
+    /// declared order
+    fn semantically_eq_by<F>(&self, other: &Self, eq: F) -> bool
+    where
+        F: Fn(&T, &T) -> bool
+    {
+        self.is_any_declared() == other.is_any_declared()
+            && self.same_arches(other)
+            && eq(&self.any, &other.any)
+            && self.arches.iter().all(|(arch, value)|
+                other.arches.get(arch).is_some_and(|other|eq(value, other)))
+    }
+
+    /// Iterate over the arch-specific variables in their declared order,
+    /// arches missing from `arch_order` come last in sorted order
+    pub fn iter_arches(&self) -> impl Iterator<Item = (&Architecture, &T)> {
+        // Only look for the unordered ones if there are, as that is quadratic
+        let unordered = self.arches.len() > self.arch_order.iter()
+            .filter(|arch|self.arches.contains_key(*arch)).count();
+        self.arch_order.iter()
+            .filter_map(|arch|self.arches.get_key_value(arch))
+            .chain(self.arches.iter().filter(move |(arch, _)|
+                unordered && ! self.arch_order.contains(arch)))
+    }
 }
 
+#[deprecated(note = "use `MultiArch::same_arches()` instead")]
 pub fn multiarch_have_same_arches<T1, T2>(
     some: &MultiArch<T1>, other: &MultiArch<T2>
+) -> bool
+{
+    some.same_arches(other)
+}
+
+/// Concatenate the lines with keys unknown to this library
+fn extra_extend(
+    merged: &mut BTreeMap<String, Vec<String>>,
+    extra: &BTreeMap<String, Vec<String>>
+) {
+    for (key, values) in extra.iter() {
+        merged.entry(key.clone()).or_default().extend(values.iter().cloned())
+    }
+}
+
+/// Concatenate an array of a package, which stays `None` only if all parts
+/// are `None`
+fn option_vec_extend<T: Clone>(merged: &mut Option<Vec<T>>, part: &Option<Vec<T>>) {
+    if let Some(part) = part {
+        merged.get_or_insert_with(Vec::new).extend(part.iter().cloned())
+    }
+}
+
+/// Whether both have the same items regardless of order, duplicated items
+/// count as many times as they appear
+fn same_items<T: PartialEq>(some: &[T], other: &[T]) -> bool {
+    if some.len() != other.len() { return false }
+    let mut matched = vec![false; other.len()];
+    some.iter().all(|item|
+        match other.iter().enumerate().position(|(index, other)|
+            ! matched[index] && other == item) 
+        {
+            Some(index) => { matched[index] = true; true },
+            None => false,
+        })
+}
+
+/// Like `same_items()`, but for an array of a package, `None` and `Some` are 
+/// never the same
+fn same_option_items<T: PartialEq>(
+    some: &Option<Vec<T>>, other: &Option<Vec<T>>
 ) -> bool 
 {
-    let this = &some.arches;
-    let that = &other.arches;
-    if this.is_empty() {
-        that.is_empty()
-    } else if that.is_empty() {
-        false
-    } else {
-        let mut arches_this: Vec<&Architecture> = this.keys().collect();
-        let mut arches_that: Vec<&Architecture> = that.keys().collect();
-        arches_this.sort_unstable();
-        arches_that.sort_unstable();
-        arches_this == arches_that
+    match (some, other) {
+        (Some(some), Some(other)) => same_items(some, other),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+impl PkgbuildArchSpecific {
+    /// Like `==`, but ignoring the order of arrays except sources
+    fn semantically_eq(&self, other: &Self) -> bool {
+        self.sources_with_checksums == other.sources_with_checksums
+            && same_items(&self.depends, &other.depends)
+            && same_items(&self.makedepends, &other.makedepends)
+            && same_items(&self.checkdepends, &other.checkdepends)
+            && same_items(&self.optdepends, &other.optdepends)
+            && same_items(&self.conflicts, &other.conflicts)
+            && same_items(&self.provides, &other.provides)
+            && same_items(&self.replaces, &other.replaces)
+            && self.extra == other.extra
+    }
+
+    /// Move the values of another section of the same architecture after
+    /// those of this one
+    fn append(&mut self, mut other: Self) {
+        self.sources_with_checksums.append(&mut other.sources_with_checksums);
+        self.depends.append(&mut other.depends);
+        self.makedepends.append(&mut other.makedepends);
+        self.checkdepends.append(&mut other.checkdepends);
+        self.optdepends.append(&mut other.optdepends);
+        self.conflicts.append(&mut other.conflicts);
+        self.provides.append(&mut other.provides);
+        self.replaces.append(&mut other.replaces);
+        extra_extend(&mut self.extra, &other.extra);
+    }
+}
+
+impl PackageArchSpecific {
+    /// Like `==`, but ignoring the order of arrays
+    fn semantically_eq(&self, other: &Self) -> bool {
+        same_option_items(&self.checkdepends, &other.checkdepends)
+            && same_option_items(&self.depends, &other.depends)
+            && same_option_items(&self.optdepends, &other.optdepends)
+            && same_option_items(&self.provides, &other.provides)
+            && same_option_items(&self.conflicts, &other.conflicts)
+            && same_option_items(&self.replaces, &other.replaces)
+            && self.extra == other.extra
+    }
+
+    /// Move the values of another section of the same architecture after
+    /// those of this one
+    fn append(&mut self, other: Self) {
+        option_vec_extend(&mut self.checkdepends, &other.checkdepends);
+        option_vec_extend(&mut self.depends, &other.depends);
+        option_vec_extend(&mut self.optdepends, &other.optdepends);
+        option_vec_extend(&mut self.provides, &other.provides);
+        option_vec_extend(&mut self.conflicts, &other.conflicts);
+        option_vec_extend(&mut self.replaces, &other.replaces);
+        extra_extend(&mut self.extra, &other.extra);
+    }
+}
+
+impl MultiArch<PkgbuildArchSpecific> {
+    /// Clone the arch-independent variables and concatenate those of `arch`,
+    /// or all architectures if `None`, after them
+    pub fn merged(&self, arch: Option<&Architecture>) -> PkgbuildArchSpecific {
+        let mut merged = self.any.clone();
+        for (_, part) in self.iter().skip(1).filter(|(part_arch, _)|
+            arch.is_none_or(|arch|*part_arch == Some(arch)))
+        {
+            merged.sources_with_checksums.extend(
+                part.sources_with_checksums.iter().cloned());
+            merged.depends.extend(part.depends.iter().cloned());
+            merged.makedepends.extend(part.makedepends.iter().cloned());
+            merged.checkdepends.extend(part.checkdepends.iter().cloned());
+            merged.optdepends.extend(part.optdepends.iter().cloned());
+            merged.conflicts.extend(part.conflicts.iter().cloned());
+            merged.provides.extend(part.provides.iter().cloned());
+            merged.replaces.extend(part.replaces.iter().cloned());
+            extra_extend(&mut merged.extra, &part.extra);
+        }
+        merged
+    }
+}
+
+impl MultiArch<PackageArchSpecific> {
+    /// Clone the arch-independent variables and concatenate those of `arch`,
+    /// or all architectures if `None`, after them. An array stays `None` only
+    /// if it's not set in any of the merged parts.
+    pub fn merged(&self, arch: Option<&Architecture>) -> PackageArchSpecific {
+        let mut merged = self.any.clone();
+        for (_, part) in self.iter().skip(1).filter(|(part_arch, _)|
+            arch.is_none_or(|arch|*part_arch == Some(arch)))
+        {
+            option_vec_extend(&mut merged.checkdepends, &part.checkdepends);
+            option_vec_extend(&mut merged.depends, &part.depends);
+            option_vec_extend(&mut merged.optdepends, &part.optdepends);
+            option_vec_extend(&mut merged.provides, &part.provides);
+            option_vec_extend(&mut merged.conflicts, &part.conflicts);
+            option_vec_extend(&mut merged.replaces, &part.replaces);
+            extra_extend(&mut merged.extra, &part.extra);
+        }
+        merged
     }
 }
 
 /// A sub-package parsed from a split-package `PKGBUILD`
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Package {
     /// The name of the split pacakge
     pub pkgname: String,
-    pub pkgdesc: String,
-    pub url: String,
-    pub license: Vec<String>,
-    pub groups: Vec<String>,
-    pub backup: Vec<String>,
-    pub options: Options,
-    pub install: String,
-    pub changelog: String,
+    /// The `pkgdesc` set in the package function, `None` if not mentioned
+    /// there so the one of the `PKGBUILD` is inherited, `Some("")` if 
+    /// explicitly set to empty, see `effective_pkgdesc()`
+    pub pkgdesc: Option<String>,
+    /// The `url` set in the package function, like `pkgdesc`
+    pub url: Option<String>,
+    /// The `license` set in the package function, `None` if not mentioned
+    /// there so the one of the `PKGBUILD` is inherited, `Some` with an empty
+    /// `Vec` if explicitly set to empty
+    pub license: Option<Vec<String>>,
+    /// The `groups` set in the package function, like `license`
+    pub groups: Option<Vec<String>>,
+    /// The `backup` set in the package function, like `license`
+    pub backup: Option<Vec<String>>,
+    /// The `options` set in the package function, like `license`
+    pub options: Option<Options>,
+    /// The `install` set in the package function, `None` if not mentioned
+    /// there so the one of the `PKGBUILD` is inherited, `Some("")` if 
+    /// explicitly set to empty, see `effective_install()`
+    pub install: Option<String>,
+    /// The `changelog` set in the package function, like `install`
+    pub changelog: Option<String>,
     pub multiarch: MultiArch<PackageArchSpecific>,
+    /// Lines with keys unknown to this library, only collected with 
+    /// `Strictness::Lenient`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra: BTreeMap<String, Vec<String>>,
+}
+
+/// The effective value of a scalar field a split package could override from
+/// the `PKGBUILD`, e.g. `install` and `changelog`
+/// 
+/// A package function setting `install=''` is different from one not 
+/// mentioning it: makepkg then packs no install script for that package even
+/// if the `PKGBUILD` has one, and `.SRCINFO` has an empty `install = ` line for
+/// it instead of omitting the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageOverride<'a> {
+    /// Not mentioned in the package function, the value of the `PKGBUILD` is
+    /// used, which could be empty
+    Inherited(&'a str),
+    /// Set to empty in the package function, the package has none regardless
+    /// of the `PKGBUILD`
+    Cleared,
+    /// Set to a non-empty value in the package function
+    Overridden(&'a str),
+}
+
+impl<'a> PackageOverride<'a> {
+    fn new(package: Option<&'a str>, pkgbuild: &'a str) -> Self {
+        match package {
+            None => Self::Inherited(pkgbuild),
+            Some("") => Self::Cleared,
+            Some(value) => Self::Overridden(value),
+        }
+    }
+
+    /// The value the package ends up with, `None` if it has none
+    pub fn value(&self) -> Option<&'a str> {
+        match self {
+            Self::Inherited("") | Self::Cleared => None,
+            Self::Inherited(value) | Self::Overridden(value) => Some(value),
+        }
+    }
+}
+
+/// The path of a file referenced by a `PKGBUILD`, e.g. `install`, relative
+/// to the directory of the `PKGBUILD`, `None` if `name` is
+fn referenced_file_path(base_dir: &Path, name: Option<&str>) 
+    -> Option<PathBuf> 
+{
+    name.map(|name|base_dir.join(name))
 }
 
+/// Read a file referenced by a `PKGBUILD` in `base_dir`, `None` if `name` is
+fn read_referenced_file(
+    kind: &'static str, base_dir: &Path, name: Option<&str>
+) -> Result<Option<String>>
+{
+    let Some(path) = referenced_file_path(base_dir, name) else {
+        return Ok(None)
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::error!("The {} file '{}' does not exist", kind, 
+                path.display());
+            Err(Error::ReferencedFileMissing { 
+                kind, name: name.unwrap_or_default().into() })
+        },
+        Err(e) => {
+            log::error!("Failed to read {} file '{}': {}", kind, 
+                path.display(), e);
+            Err(e.into())
+        },
+    }
+}
+
+/// Define a method collecting an arch-specific array for an arch or all 
+/// arches, with `flatten` for the `Option<Vec<_>>` ones of packages
 macro_rules! pkg_iter_all_arch {
-    ($pkg:ident, $var:ident, $type: ident) => {
+    ($pkg:ident, $var:ident, $type: ident $(, $flatten: ident)?) => {
         pub fn $var(&self, arch: Option<&Architecture>) -> Vec<&$type> {
             let mut values = Vec::new();
-            for value in self.multiarch.any.$var.iter() {
+            for value in self.multiarch.any.$var.iter()$(.$flatten())? {
                 values.push(value)
             }
             if let Some(arch) = arch {
                 if let Some(arch_specific) = self.multiarch.arches.get(arch) {
-                    for value in arch_specific.$var.iter() {
+                    for value in arch_specific.$var.iter()$(.$flatten())? {
                         values.push(value)
                     }
                 }
             } else {
                 for arch_specific in self.multiarch.arches.values() {
-                    for value in arch_specific.$var.iter() {
+                    for value in arch_specific.$var.iter()$(.$flatten())? {
                         values.push(value)
                     }
                 }
@@ -1623,63 +5318,527 @@ macro_rules! pkg_iter_all_arch {
     }
 }
 
-impl Package {
-    pkg_iter_all_arch!(self, depends, Dependency);
-    pkg_iter_all_arch!(self, optdepends, OptionalDependency);
-    pkg_iter_all_arch!(self, provides, Provide);
-    pkg_iter_all_arch!(self, conflicts, Conflict);
-    pkg_iter_all_arch!(self, replaces, Replace);
+/// Clone the items sorted and deduplicated
+fn sorted_deduped<T: Ord + Clone>(items: Vec<&T>) -> Vec<T> {
+    let mut items: Vec<T> = items.into_iter().cloned().collect();
+    items.sort_unstable();
+    items.dedup();
+    items
 }
 
-#[cfg(feature = "format")]
-fn format_write_iter<I, D>(f: &mut Formatter<'_>, array: I) 
--> std::fmt::Result 
-where
-    I: IntoIterator<Item = D>,
-    D: Display
+/// Parse a non-empty `url` field
+#[cfg(feature = "url")]
+fn url_parsed(url: &str) 
+    -> Option<std::result::Result<url::Url, url::ParseError>> 
 {
-    let mut started = false;
-    for item in array.into_iter() {
-        if started {
-            write!(f, ", {}", item)?
-        } else {
-            started = true;
-            write!(f, "{}", item)?
-        }
+    if url.is_empty() {
+        None
+    } else {
+        Some(url::Url::parse(url))
     }
-    Ok(())
 }
 
-#[cfg(feature = "format")]
-impl Display for Package {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{{name: {}, depends: [", self.pkgname)?;
-        format_write_iter(f, self.depends(None))?;
-        write!(f, "], provides: [")?;
-        format_write_iter(f, self.provides(None))?;
-        write!(f, "]}}")
+/// Get the host of a non-empty, valid `url` field
+#[cfg(feature = "url")]
+fn url_host(url: &str) -> Option<String> {
+    url_parsed(url)?.ok()?.host_str().map(String::from)
+}
+
+/// Define a method collecting an arch-specific array of a package with the
+/// makepkg inheritance rules, for an arch or all arches, like the arrays of
+/// `Pkgbuild::resolve_package()` but borrowed
+macro_rules! pkg_effective_array {
+    ($name:ident, $var:ident, $type: ident) => {
+        #[doc = concat!("Get the effective `", stringify!($var), "` of this ",
+            "package, each arch-specific part inherited from the `PKGBUILD` ",
+            "if not set in the package function, for `arch` or all ",
+            "architectures if `None`")]
+        pub fn $name<'a>(
+            &'a self, pkgbuild: &'a Pkgbuild, arch: Option<&Architecture>
+        ) -> Vec<&'a $type> 
+        {
+            let section = |package: &'a PackageArchSpecific, 
+                base: Option<&'a PkgbuildArchSpecific>| -> &'a [$type] 
+            {
+                match &package.$var {
+                    Some(items) => items,
+                    None => base.map(|base|base.$var.as_slice())
+                        .unwrap_or_default(),
+                }
+            };
+            std::iter::once(section(&self.multiarch.any, 
+                Some(&pkgbuild.multiarch.any)))
+                .chain(self.multiarch.iter_arches().filter(
+                    |(pkg_arch, _)|arch.is_none_or(|arch|arch == *pkg_arch))
+                    .map(|(pkg_arch, values)|
+                        section(values, pkgbuild.multiarch.get(pkg_arch))))
+                .flatten().collect()
+        }
     }
 }
 
-/// A VSC source fragment, declared in source as `url#fragment`, usually to 
-/// declare which `fragment` of the VSC source to use, e.g. commit, tag, etc
-pub trait Fragment {
-    /// Get the type string for the fragment, e.g. `revision`, `commit`, etc;
-    /// 
-    /// And get the actual value for the fragment, e.g. `master`, `main`, etc
-    fn get_type_and_value(&self) -> (&str, &str);
+impl Package {
+    pkg_iter_all_arch!(self, depends, Dependency, flatten);
+    pkg_iter_all_arch!(self, optdepends, OptionalDependency, flatten);
+    pkg_iter_all_arch!(self, provides, Provide, flatten);
+    pkg_iter_all_arch!(self, conflicts, Conflict, flatten);
+    pkg_iter_all_arch!(self, replaces, Replace, flatten);
+
+    /// Get `depends()` sorted and deduplicated, e.g. a dependency declared in
+    /// both `depends` and `depends_x86_64` only once
+    pub fn depends_deduped(&self, arch: Option<&Architecture>) 
+        -> Vec<Dependency> 
+    {
+        sorted_deduped(self.depends(arch))
+    }
+    pkg_effective_array!(effective_provides, provides, Provide);
+    pkg_effective_array!(effective_conflicts, conflicts, Conflict);
+    pkg_effective_array!(effective_replaces, replaces, Replace);
+
+    /// The effective `conflicts` followed by `replaces` of this package
+    #[cfg(feature = "vercmp")]
+    fn conflict_constraints<'a>(
+        &'a self, pkgbuild: &'a Pkgbuild, arch: Option<&Architecture>
+    ) -> impl Iterator<Item = (ConflictKind, &'a Dependency)> 
+    {
+        self.effective_conflicts(pkgbuild, arch).into_iter().map(
+            |conflict|(ConflictKind::Conflict, &conflict.0))
+            .chain(self.effective_replaces(pkgbuild, arch).into_iter().map(
+                |replace|(ConflictKind::Replace, &replace.0)))
+    }
 
-    /// Get the type string for the fragment, e.g. `revision`, `commit`, etc;
-    fn get_type(&self) -> &str {
-        self.get_type_and_value().0
+    /// Get the effective `conflicts` and `replaces` of this package, a 
+    /// package of `pkgbuild`, satisfied by `other`, a package of 
+    /// `other_pkgbuild`, by its `pkgname` and version or its `provides`, for
+    /// `arch` or all architectures if `None`. As pacman refuses two packages
+    /// if either conflicts with the other, check it both ways.
+    #[cfg(feature = "vercmp")]
+    pub fn conflicts_with<'a>(
+        &'a self, pkgbuild: &'a Pkgbuild, 
+        other: &Package, other_pkgbuild: &Pkgbuild, 
+        arch: Option<&Architecture>
+    ) -> Vec<(ConflictKind, &'a Dependency)> 
+    {
+        self.conflict_constraints(pkgbuild, arch).filter(|(_, constraint)|
+            constraint.satisfied_by_package_of(other_pkgbuild, other, arch))
+            .collect()
     }
 
-    /// Get the actual value for the fragment, e.g. `master`, `main`, etc
-    fn get_value(&self) -> &str {
-        self.get_type_and_value().1
+    /// Like `==`, but ignoring the order of arrays, e.g. `license`, `groups`
+    /// and `provides`, and of architectures, see `Pkgbuild::semantically_eq()`
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        self.pkgname == other.pkgname
+            && self.pkgdesc == other.pkgdesc
+            && self.url == other.url
+            && same_option_items(&self.license, &other.license)
+            && same_option_items(&self.groups, &other.groups)
+            && same_option_items(&self.backup, &other.backup)
+            && match (&self.options, &other.options) {
+                (Some(some), Some(other)) => some.semantically_eq(other),
+                (None, None) => true,
+                _ => false,
+            }
+            && self.install == other.install
+            && self.changelog == other.changelog
+            && self.multiarch.semantically_eq_by(&other.multiarch,
+                PackageArchSpecific::semantically_eq)
+            && self.extra == other.extra
     }
 
-    fn from_url(url: &str) -> (&str, Option<Self>)
+    /// Get the architectures of this package in declared order, like
+    /// `MultiArch::declared_arches()`. The parser already resolves a package
+    /// not setting `arch` in its package function to the architectures of the
+    /// `PKGBUILD`.
+    pub fn arches(&self) -> Vec<Architecture> {
+        self.multiarch.declared_arches()
+    }
+
+    /// Whether this package is `any`, i.e. has no architecture-specific
+    /// sections, either declared as `arch=(any)` or not declared at all
+    pub fn is_arch_independent(&self) -> bool {
+        self.multiarch.arches.is_empty()
+    }
+
+    /// Get a view of this package for a single architecture
+    pub fn for_arch<'a>(&'a self, arch: &Architecture) -> PackageForArch<'a> {
+        PackageForArch { package: self, any: &self.multiarch.any, 
+            arch: self.multiarch.arches.get(arch) }
+    }
+
+    /// Whether this package could be built for the architecture, a package
+    /// without architecture-specific sections is `any`
+    pub fn supports_arch(&self, arch: &Architecture) -> bool {
+        self.is_arch_independent() 
+            || self.multiarch.arches.contains_key(arch)
+    }
+
+    /// Get the `url` of this package, falling back to the one of the parent 
+    /// `Pkgbuild` if not overriden in the package function
+    pub fn effective_url<'a>(&'a self, pkgbuild: &'a Pkgbuild) -> &'a str {
+        PackageOverride::new(self.url.as_deref(), &pkgbuild.url)
+            .value().unwrap_or_default()
+    }
+
+    /// Get the `pkgdesc` of this package, like `effective_url()`
+    pub fn effective_pkgdesc<'a>(&'a self, pkgbuild: &'a Pkgbuild) -> &'a str {
+        PackageOverride::new(self.pkgdesc.as_deref(), &pkgbuild.pkgdesc)
+            .value().unwrap_or_default()
+    }
+
+    /// Get the options applying to this package, i.e. those set in the 
+    /// package function merged over those of the parent `Pkgbuild`
+    pub fn effective_options(&self, pkgbuild: &Pkgbuild) -> Options {
+        match &self.options {
+            Some(options) => options.merged_over(&pkgbuild.options),
+            None => pkgbuild.options.clone(),
+        }
+    }
+
+    /// Get the identifier `pkgname-epoch:pkgver-pkgrel` of this package, e.g.
+    /// `foo-2:1.2.3-2`, with `epoch:` omitted if it's empty, see 
+    /// `Pkgbuild::full_version()`
+    pub fn full_name(&self, pkgbuild: &Pkgbuild) -> String {
+        format!("{}-{}", self.pkgname, pkgbuild.full_version())
+    }
+
+    /// Get the `install` of this package, telling whether it's inherited from
+    /// the parent `Pkgbuild`, cleared or overriden in the package function
+    pub fn effective_install<'a>(&'a self, pkgbuild: &'a Pkgbuild) 
+        -> PackageOverride<'a> 
+    {
+        PackageOverride::new(self.install.as_deref(), &pkgbuild.install)
+    }
+
+    /// Get the `changelog` of this package, like `effective_install()`
+    pub fn effective_changelog<'a>(&'a self, pkgbuild: &'a Pkgbuild) 
+        -> PackageOverride<'a> 
+    {
+        PackageOverride::new(self.changelog.as_deref(), &pkgbuild.changelog)
+    }
+
+    /// Parse the effective `url` of this package, `None` if it's empty
+    #[cfg(feature = "url")]
+    pub fn url_parsed(&self, pkgbuild: &Pkgbuild) 
+        -> Option<std::result::Result<url::Url, url::ParseError>> 
+    {
+        url_parsed(self.effective_url(pkgbuild))
+    }
+
+    /// Get the host of the effective `url` of this package, `None` if it's 
+    /// empty, invalid or has no host
+    #[cfg(feature = "url")]
+    pub fn upstream_host(&self, pkgbuild: &Pkgbuild) -> Option<String> {
+        url_host(self.effective_url(pkgbuild))
+    }
+
+    /// Check the rules of makepkg and pacman the parser does not, for the 
+    /// name and the fields set in the package function, those inherited are
+    /// checked by `Pkgbuild::validate()`
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut validation = Validation::new(Some(&self.pkgname));
+        validation.check("pkgname", &self.pkgname, validate_name);
+        validation.check_paths(self.backup.as_deref().unwrap_or_default(),
+            self.install.as_deref().unwrap_or_default(), 
+            self.changelog.as_deref().unwrap_or_default());
+        validation.issues
+    }
+
+    /// Get the path of the effective `install` of this package, relative to
+    /// `base_dir`, the directory of the `PKGBUILD`, `None` if it has none
+    pub fn install_path<P: AsRef<Path>>(&self, pkgbuild: &Pkgbuild, base_dir: P)
+        -> Option<PathBuf> 
+    {
+        referenced_file_path(base_dir.as_ref(), 
+            self.effective_install(pkgbuild).value())
+    }
+
+    /// Read the effective `install` of this package in `base_dir`, `None` if
+    /// it has none, `Error::ReferencedFileMissing` if the file does not exist
+    pub fn read_install<P: AsRef<Path>>(&self, pkgbuild: &Pkgbuild, base_dir: P)
+        -> Result<Option<String>> 
+    {
+        read_referenced_file("install", base_dir.as_ref(), 
+            self.effective_install(pkgbuild).value())
+    }
+
+    /// Get the path of the effective `changelog` of this package, like 
+    /// `install_path()`
+    pub fn changelog_path<P: AsRef<Path>>(
+        &self, pkgbuild: &Pkgbuild, base_dir: P
+    ) -> Option<PathBuf> 
+    {
+        referenced_file_path(base_dir.as_ref(), 
+            self.effective_changelog(pkgbuild).value())
+    }
+
+    /// Read the effective `changelog` of this package, like `read_install()`
+    pub fn read_changelog<P: AsRef<Path>>(
+        &self, pkgbuild: &Pkgbuild, base_dir: P
+    ) -> Result<Option<String>> 
+    {
+        read_referenced_file("changelog", base_dir.as_ref(), 
+            self.effective_changelog(pkgbuild).value())
+    }
+}
+
+/// A split package with everything inherited from the `PKGBUILD` resolved the
+/// way makepkg does, from `Pkgbuild::resolved_packages()`
+/// 
+/// Each field is the one of the package function if set there, even if set
+/// to empty, otherwise the one of the `PKGBUILD`. The arch-specific arrays
+/// are resolved separately for the arch-independent part and each arch, e.g.
+/// a package setting only `depends` still inherits `depends_x86_64`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResolvedPackage {
+    pub pkgname: String,
+    pub pkgdesc: String,
+    pub url: String,
+    pub license: Vec<String>,
+    pub groups: Vec<String>,
+    pub backup: Vec<String>,
+    /// The options of the `PKGBUILD`, with those set in the package function
+    /// taking precedence one by one
+    pub options: Options,
+    pub install: String,
+    pub changelog: String,
+    /// The architectures of the package in declared order,
+    /// `[Architecture::Any]` for `arch=(any)`, empty if not declared
+    pub arches: Vec<Architecture>,
+    pub checkdepends: Vec<CheckDependency>,
+    pub depends: Vec<Dependency>,
+    pub optdepends: Vec<OptionalDependency>,
+    pub provides: Vec<Provide>,
+    pub conflicts: Vec<Conflict>,
+    pub replaces: Vec<Replace>,
+}
+
+/// Define a method collecting an arch-specific array of a single-arch view,
+/// i.e. those of the arch-independent part followed by those of the arch
+macro_rules! for_arch_iter {
+    ($var:ident, $type: ident $(, $flatten: ident)?) => {
+        pub fn $var(&self) -> Vec<&'a $type> {
+            self.any.$var.iter()$(.$flatten())?.chain(self.arch.into_iter()
+                .flat_map(|arch_specific|arch_specific.$var.iter()$(.$flatten())?))
+                .collect()
+        }
+    }
+}
+
+/// A borrowed view of a `Pkgbuild` for a single architecture, from 
+/// `Pkgbuild::for_arch()`
+#[derive(Debug, Clone, Copy)]
+pub struct PkgbuildForArch<'a> {
+    pub pkgbuild: &'a Pkgbuild,
+    /// The arch-independent variables
+    pub any: &'a PkgbuildArchSpecific,
+    /// The variables specific to the architecture, `None` if the `PKGBUILD`
+    /// does not declare it
+    pub arch: Option<&'a PkgbuildArchSpecific>,
+}
+
+impl<'a> PkgbuildForArch<'a> {
+    for_arch_iter!(sources_with_checksums, SourceWithChecksum);
+    for_arch_iter!(depends, Dependency);
+    for_arch_iter!(makedepends, MakeDependency);
+    for_arch_iter!(checkdepends, CheckDependency);
+    for_arch_iter!(optdepends, OptionalDependency);
+    for_arch_iter!(conflicts, Conflict);
+    for_arch_iter!(provides, Provide);
+    for_arch_iter!(replaces, Replace);
+}
+
+/// A borrowed view of a `Package` for a single architecture, from
+/// `Package::for_arch()`, with only what is set in the package function
+#[derive(Debug, Clone, Copy)]
+pub struct PackageForArch<'a> {
+    pub package: &'a Package,
+    /// The arch-independent variables
+    pub any: &'a PackageArchSpecific,
+    /// The variables specific to the architecture, `None` if the package 
+    /// does not have it
+    pub arch: Option<&'a PackageArchSpecific>,
+}
+
+impl<'a> PackageForArch<'a> {
+    for_arch_iter!(checkdepends, CheckDependency, flatten);
+    for_arch_iter!(depends, Dependency, flatten);
+    for_arch_iter!(optdepends, OptionalDependency, flatten);
+    for_arch_iter!(provides, Provide, flatten);
+    for_arch_iter!(conflicts, Conflict, flatten);
+    for_arch_iter!(replaces, Replace, flatten);
+}
+
+#[cfg(feature = "format")]
+fn format_write_iter<I, D>(f: &mut Formatter<'_>, array: I) 
+-> std::fmt::Result 
+where
+    I: IntoIterator<Item = D>,
+    D: Display
+{
+    let mut started = false;
+    for item in array.into_iter() {
+        if started {
+            write!(f, ", {}", item)?
+        } else {
+            started = true;
+            write!(f, "{}", item)?
+        }
+    }
+    Ok(())
+}
+
+/// Write a line `name: [item, item]` for the full `Display` of `Pkgbuild` and
+/// `Package`
+#[cfg(feature = "format")]
+fn write_full_array<I, D>(
+    f: &mut Formatter<'_>, indent: &str, name: &str, array: I
+) -> std::fmt::Result 
+where
+    I: IntoIterator<Item = D>,
+    D: Display
+{
+    write!(f, "{}{}: [", indent, name)?;
+    format_write_iter(f, array)?;
+    writeln!(f, "]")
+}
+
+/// Write a line `name: value` for the full `Display` of `Pkgbuild` and 
+/// `Package`, without the trailing space for an empty value
+#[cfg(feature = "format")]
+fn write_full_scalar<D: Display>(
+    f: &mut Formatter<'_>, indent: &str, name: &str, value: D
+) -> std::fmt::Result 
+{
+    let value = value.to_string();
+    if value.is_empty() {
+        writeln!(f, "{}{}:", indent, name)
+    } else {
+        writeln!(f, "{}{}: {}", indent, name, value)
+    }
+}
+
+/// Like `write_full_array()`, but for an array a package function could
+/// override, `None` if inherited
+#[cfg(feature = "format")]
+fn write_full_overridden_array<D: Display>(
+    f: &mut Formatter<'_>, indent: &str, name: &str, array: Option<&[D]>
+) -> std::fmt::Result 
+{
+    match array {
+        Some(array) => write_full_array(f, indent, name, array),
+        None => writeln!(f, "{}{}: (inherited)", indent, name),
+    }
+}
+
+/// Write a comment marking the architectures truncated at `max` out of 
+/// `total`, if any, for the `Display` of `Pkgbuild`, `Package` and `Srcinfo`
+#[cfg(feature = "format")]
+fn writeln_arches_truncated(
+    f: &mut Formatter<'_>, indent: &str, total: usize, max: usize
+) -> std::fmt::Result 
+{
+    if total > max {
+        writeln!(f, "{}# {} more architectures truncated", indent, total - max)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for Package {
+    /// A summary in a single line, or every field set in the package function
+    /// line by line with the alternate flag, i.e. `{:#}`, those not set are
+    /// written as `(inherited)`. A precision, e.g. `{:#.4}`, caps the 
+    /// architectures and their sections like `Srcinfo::set_max_arches()`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            writeln!(f, "pkgname: {}", self.pkgname)?;
+            return self.write_full(f, "")
+        }
+        write!(f, "{{name: {}, depends: [", self.pkgname)?;
+        format_write_iter(f, self.depends(None))?;
+        write!(f, "], provides: [")?;
+        format_write_iter(f, self.provides(None))?;
+        write!(f, "]}}")
+    }
+}
+
+#[cfg(feature = "format")]
+impl Package {
+    /// Write every field but `pkgname` line by line for the full `Display`
+    fn write_full(&self, f: &mut Formatter<'_>, indent: &str) 
+        -> std::fmt::Result 
+    {
+        for (name, value) in [("pkgdesc", &self.pkgdesc), ("url", &self.url),
+            ("install", &self.install), ("changelog", &self.changelog)] 
+        {
+            match value {
+                Some(value) => write_full_scalar(f, indent, name, value)?,
+                None => writeln!(f, "{}{}: (inherited)", indent, name)?,
+            }
+        }
+        write_full_overridden_array(f, indent, "license", 
+            self.license.as_deref())?;
+        write_full_overridden_array(f, indent, "groups", 
+            self.groups.as_deref())?;
+        write_full_overridden_array(f, indent, "backup", 
+            self.backup.as_deref())?;
+        match &self.options {
+            Some(options) => write_full_scalar(f, indent, "options", options)?,
+            None => writeln!(f, "{}options: (inherited)", indent)?,
+        }
+        let max_arches = f.precision().unwrap_or(usize::MAX);
+        write_full_array(f, indent, "arch", 
+            self.arches().iter().take(max_arches))?;
+        for (arch, values) in 
+            self.multiarch.iter().take(max_arches.saturating_add(1)) 
+        {
+            let suffix = arch.map(|arch|format!("_{}", arch))
+                .unwrap_or_default();
+            write_full_overridden_array(f, indent, 
+                &format!("depends{}", suffix), values.depends.as_deref())?;
+            write_full_overridden_array(f, indent, 
+                &format!("checkdepends{}", suffix), 
+                values.checkdepends.as_deref())?;
+            write_full_overridden_array(f, indent, 
+                &format!("conflicts{}", suffix), values.conflicts.as_deref())?;
+            write_full_overridden_array(f, indent, 
+                &format!("replaces{}", suffix), values.replaces.as_deref())?;
+            write_full_overridden_array(f, indent, 
+                &format!("optdepends{}", suffix), 
+                values.optdepends.as_deref())?;
+            write_full_overridden_array(f, indent, 
+                &format!("provides{}", suffix), values.provides.as_deref())?;
+        }
+        writeln_arches_truncated(f, indent, self.multiarch.arches.len(), 
+            max_arches)?;
+        for (key, values) in self.extra.iter() {
+            write_full_array(f, indent, key, values)?
+        }
+        Ok(())
+    }
+}
+
+/// A VSC source fragment, declared in source as `url#fragment`, usually to 
+/// declare which `fragment` of the VSC source to use, e.g. commit, tag, etc
+pub trait Fragment {
+    /// Get the type string for the fragment, e.g. `revision`, `commit`, etc;
+    /// 
+    /// And get the actual value for the fragment, e.g. `master`, `main`, etc
+    fn get_type_and_value(&self) -> (&str, &str);
+
+    /// Get the type string for the fragment, e.g. `revision`, `commit`, etc;
+    fn get_type(&self) -> &str {
+        self.get_type_and_value().0
+    }
+
+    /// Get the actual value for the fragment, e.g. `master`, `main`, etc
+    fn get_value(&self) -> &str {
+        self.get_type_and_value().1
+    }
+
+    fn from_url(url: &str) -> (&str, Option<Self>)
         where Self: Sized;
 }
 
@@ -1723,7 +5882,7 @@ fn split_url_fragment_no_query(url: &str) -> Option<(&str, &str, &str)> {
     None
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BzrSourceFragment {
     Revision(String)
@@ -1758,7 +5917,7 @@ impl Display for BzrSourceFragment {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FossilSourceFragment {
     Branch(String),
@@ -1801,7 +5960,7 @@ impl Display for FossilSourceFragment {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum GitSourceFragment {
     Branch(String),
@@ -1844,7 +6003,7 @@ impl Display for GitSourceFragment {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum HgSourceFragment {
     Branch(String),
@@ -1887,7 +6046,7 @@ impl Display for HgSourceFragment {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SvnSourceFragment {
     Revision(String)
@@ -1922,11 +6081,15 @@ impl Display for SvnSourceFragment {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SourceProtocol {
-    #[default]
-    Unknown,
+    /// A protocol we do not know, e.g. one handled by a custom `DLAGENTS`
+    /// entry, with the scheme as declared, e.g. `s3` for `s3://bucket/file` 
+    /// or `foo` for `foo+https://example.com/file`
+    Unknown {
+        scheme: String,
+    },
     Local,
     File,
     Ftp,
@@ -1956,7 +6119,8 @@ pub enum SourceProtocol {
 impl Display for SourceProtocol {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            SourceProtocol::Unknown => write!(f, "unknown")?,
+            SourceProtocol::Unknown { scheme } => 
+                write!(f, "unknown({})", scheme)?,
             SourceProtocol::Local => write!(f, "local")?,
             SourceProtocol::File => write!(f, "file")?,
             SourceProtocol::Ftp => write!(f, "ftp")?,
@@ -2008,11 +6172,27 @@ impl Display for SourceProtocol {
     }
 }
 
+impl Default for SourceProtocol {
+    fn default() -> Self {
+        Self::Unknown { scheme: String::new() }
+    }
+}
+
+impl SourceProtocol {
+    /// Whether this is a version control system protocol, i.e. `bzr`, 
+    /// `fossil`, `git`, `hg` or `svn`
+    pub fn is_vcs(&self) -> bool {
+        matches!(self, SourceProtocol::Bzr { .. } | 
+            SourceProtocol::Fossil { .. } | SourceProtocol::Git { .. } | 
+            SourceProtocol::Hg { .. } | SourceProtocol::Svn { .. })
+    }
+}
+
 #[cfg(feature = "format")]
 impl SourceProtocol {
-    fn get_proto_str(&self) -> &'static str {
+    fn get_proto_str(&self) -> &str {
         match self {
-            SourceProtocol::Unknown => "unknown",
+            SourceProtocol::Unknown { scheme } => scheme,
             SourceProtocol::Local => "local",
             SourceProtocol::File => "file",
             SourceProtocol::Ftp => "ftp",
@@ -2029,11 +6209,16 @@ impl SourceProtocol {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Source {
-    /// The local file name
+    /// The local file name, either the explicit one or the one derived from 
+    /// the URL
     pub name: String,
+    /// The local file name explicitly declared in `source=()` with `name::`,
+    /// `None` if it was derived from the URL
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub explicit_name: Option<String>,
     /// The actual URL, i.e. the one used to initialize connections, could be
     /// different from the one defined in `source=()`
     pub url: String,
@@ -2054,7 +6239,10 @@ impl From<&str> for Source {
         let mut source = Self::default();
         let mut url = match definition.split_once("::") {
             Some((name, url)) => {
-                source.name = name.into();
+                if ! name.is_empty() {
+                    source.name = name.into();
+                    source.explicit_name = Some(name.into())
+                }
                 url
             },
             None => definition,
@@ -2092,11 +6280,18 @@ impl From<&str> for Source {
                         SourceProtocol::Fossil { fragment }
                     },
                     "git" => {
+                        // Like makepkg, `?signed` could come either before 
+                        // or after the fragment, so check the whole url
+                        // before the fragment and the query are split off
+                        let signed = url.contains("?signed");
                         let (urln, fragment) 
                             = GitSourceFragment::from_url(url);
-                        url = urln;
-                        SourceProtocol::Git { fragment, 
-                            signed: url.contains("?signed")}
+                        url = if signed {
+                            urln.strip_suffix("?signed").unwrap_or(urln)
+                        } else {
+                            urln
+                        };
+                        SourceProtocol::Git { fragment, signed }
                     },
                     "hg" => {
                         let (urln, fragment) 
@@ -2114,7 +6309,7 @@ impl From<&str> for Source {
                     },
                     _ => {
                         log::warn!("Unknown protocol '{}'", proto);
-                        SourceProtocol::Unknown
+                        SourceProtocol::Unknown { scheme: proto.into() }
                     }
                 }
             } else { // No scheme, local file
@@ -2128,6 +6323,30 @@ impl From<&str> for Source {
     }
 }
 
+impl FromStr for Source {
+    type Err = ParseError;
+
+    /// Parse like `from()`, but reject an empty explicit name or one 
+    /// containing `/`, and an empty URL
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, url) = match s.split_once("::") {
+            Some((name, url)) => (Some(name), url),
+            None => (None, s),
+        };
+        let reason = match name {
+            Some("") => Some(ParseErrorReason::EmptyName),
+            Some(name) if name.contains('/') => 
+                Some(ParseErrorReason::IllegalCharacter('/')),
+            _ if url.is_empty() => Some(ParseErrorReason::EmptyUrl),
+            _ => None,
+        };
+        match reason {
+            Some(reason) => Err(ParseError::new(s, reason)),
+            None => Ok(Self::from(s)),
+        }
+    }
+}
+
 impl From<&[u8]> for Source {
     fn from(value: &[u8]) -> Self {
         str_from_slice_u8!(value).into()
@@ -2139,6 +6358,13 @@ impl Source {
     pub fn new<S: AsRef<str>>(definition: S) -> Self {
         definition.as_ref().into()
     }
+
+    /// Get the local file name, the explicit one if declared, otherwise the 
+    /// one derived from the URL
+    pub fn name(&self) -> &str {
+        self.explicit_name.as_deref().unwrap_or(&self.name)
+    }
+
     /// Generate name from the url
     pub fn get_url_name(&self) -> String {
         let mut name: String = 
@@ -2166,12 +6392,12 @@ impl Source {
     }
 
     #[cfg(feature = "format")]
-    /// Convert to the format `PKGBUILD` uses in the `source` array
+    /// Convert to the format `PKGBUILD` uses in the `source` array, the 
+    /// `name::` prefix is only written if the name was explicitly declared
     pub fn get_pkgbuild_source(&self) -> String {
         let mut raw = String::new();
-        let auto_name = self.get_url_name();
-        if auto_name != self.name {
-            raw.push_str(&self.name);
+        if let Some(name) = &self.explicit_name {
+            raw.push_str(name);
             raw.push_str("::")
         }
         let proto_url = match self.url.split_once("://") {
@@ -2180,8 +6406,10 @@ impl Source {
         };
         let proto_actual = self.protocol.get_proto_str();
         match &self.protocol {
-            SourceProtocol::Unknown | SourceProtocol::Local => (),
+            SourceProtocol::Local => (),
             _ =>
+                // Unknown ones are also kept, as a custom DLAGENT gets the
+                // `scheme+` prefix as-is
                 if proto_actual != proto_url {
                     raw.push_str(proto_actual);
                     raw.push('+');
@@ -2201,10 +6429,11 @@ impl Source {
             SourceProtocol::Fossil { fragment } => 
                 push_fragment!(fragment),
             SourceProtocol::Git { fragment, signed } => {
-                push_fragment!(fragment);
+                // The query goes before the fragment, as in a normal url
                 if *signed {
                     raw.push_str("?signed")
                 }
+                push_fragment!(fragment);
             },
             SourceProtocol::Hg { fragment } => 
                 push_fragment!(fragment),
@@ -2214,6 +6443,19 @@ impl Source {
         };
         raw
     }
+
+    /// Like `get_pkgbuild_source()`, but fail with 
+    /// `Error::UnknownSourceProtocols` if the protocol is unknown, for callers
+    /// that need to fetch the source without custom `DLAGENTS`
+    #[cfg(feature = "format")]
+    pub fn get_pkgbuild_source_strict(&self) -> Result<String> {
+        if let SourceProtocol::Unknown { scheme } = &self.protocol {
+            log::error!("Source '{}' has unknown protocol '{}'", 
+                self.get_pkgbuild_source(), scheme);
+            return Err(Error::UnknownSourceProtocols(vec![scheme.clone()]))
+        }
+        Ok(self.get_pkgbuild_source())
+    }
 }
 
 pub type Cksum = u32;
@@ -2225,7 +6467,7 @@ pub type Sha384sum = [u8; 48];
 pub type Sha512sum = [u8; 64];
 pub type B2sum = [u8; 64];
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SourceWithChecksum {
     pub source: Source,
@@ -2276,7 +6518,7 @@ impl Display for SourceWithChecksum {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Options {
     pub strip: Option<bool>,
@@ -2285,22 +6527,56 @@ pub struct Options {
     pub staticlibs: Option<bool>,
     pub emptydirs: Option<bool>,
     pub zipman: Option<bool>,
+    pub purge: Option<bool>,
     pub ccache: Option<bool>,
     pub distcc: Option<bool>,
     pub buildflags: Option<bool>,
     pub makeflags: Option<bool>,
     pub debug: Option<bool>,
     pub lto: Option<bool>,
+    pub autodeps: Option<bool>,
+    /// Options unknown to this library as `(name, enabled)`, in the order
+    /// they were declared, with the later one kept for duplicated names
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub other: Vec<(String, bool)>,
 }
 
 impl<'a> From<&Vec<&'a [u8]>> for Options {
     fn from(value: &Vec<&'a [u8]>) -> Self {
+        Self::from_items(value.iter().copied())
+    }
+}
+
+impl From<&[&str]> for Options {
+    fn from(value: &[&str]) -> Self {
+        Self::from_items(value.iter().map(|item|item.as_bytes()))
+    }
+}
+
+impl FromStr for Options {
+    type Err = std::convert::Infallible;
+
+    /// Parse whitespace-separated options, e.g. `!strip docs`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::from_items(s.split_ascii_whitespace().map(str::as_bytes)))
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for Options {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_strings().join(" "))
+    }
+}
+
+impl Options {
+    /// Parse the items of an `options` array, `!` negates an option
+    fn from_items<'a, I: IntoIterator<Item = &'a [u8]>>(items: I) -> Self {
         let mut options = Self::default();
-        for item in value.iter() {
+        for mut item in items {
             if item.is_empty() { 
                 continue 
             }
-            let mut item = *item;
             let enable = item[0] != b'!';
             if ! enable { 
                 item = &item[1..]; 
@@ -2315,62 +6591,272 @@ impl<'a> From<&Vec<&'a [u8]>> for Options {
                 b"staticlibs" => options.staticlibs = Some(enable),
                 b"emptydirs" => options.emptydirs = Some(enable),
                 b"zipman" => options.zipman = Some(enable),
+                b"purge" => options.purge = Some(enable),
                 b"ccache" => options.ccache = Some(enable),
                 b"distcc" => options.distcc = Some(enable),
                 b"buildflags" => options.buildflags = Some(enable),
                 b"makeflags" => options.makeflags = Some(enable),
                 b"debug" => options.debug = Some(enable),
                 b"lto" => options.lto = Some(enable),
-                _ => log::warn!("Unknown option {}", str_from_slice_u8!(item)),
+                b"autodeps" => options.autodeps = Some(enable),
+                _ => {
+                    log::debug!("Unknown option {}", str_from_slice_u8!(item));
+                    options.set_other(string_from_slice_u8!(item), enable)
+                },
             }
         }
         options
     }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub enum Architecture {
-    #[default]
-    // Arch Linux specific
-    X86_64,
-    // Arch Linux ARM specific
-    Aarch64,
-    Armv7h,
-    // Arch Linux RSIC-V specific
-    Riscv64,
-    Other(String)
+/// A single option that differs between two `Options`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct OptionChange {
+    /// The option name, e.g. `strip`
+    pub name: &'static str,
+    /// The value in the base `Options`
+    pub base: Option<bool>,
+    /// The value in the compared `Options`
+    pub value: Option<bool>,
 }
 
-impl From<&[u8]> for Architecture {
-    fn from(value: &[u8]) -> Self {
-        Self::from(str_from_slice_u8!(value))
+#[cfg(feature = "format")]
+impl Display for OptionChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn write_value(f: &mut Formatter<'_>, name: &str, value: Option<bool>) 
+            -> std::fmt::Result 
+        {
+            match value {
+                Some(true) => write!(f, "{}", name),
+                Some(false) => write!(f, "!{}", name),
+                None => write!(f, "(unset)"),
+            }
+        }
+        write_value(f, self.name, self.base)?;
+        write!(f, " -> ")?;
+        write_value(f, self.name, self.value)
     }
 }
 
-impl From<&str> for Architecture {
-    fn from(value: &str) -> Self {
-        let arch = value.to_lowercase();
-        match arch.as_str() {
-            // "any" => Self::Any,
-            "x86_64" => Self::X86_64,
-            "aarch64" => Self::Aarch64,
-            "armv7h" => Self::Armv7h,
-            "riscv64" => Self::Riscv64,
-            _ => Self::Other(arch)
-        }
+/// The options that differ between two `Options`, in makepkg's canonical 
+/// order of options
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct OptionsDiff {
+    pub changes: Vec<OptionChange>
+}
+
+impl OptionsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
     }
 }
 
-impl AsRef<str> for Architecture {
-    fn as_ref(&self) -> &str {
-        match self {
-            // Architecture::Any => "any",
-            Architecture::X86_64 => "x86_64",
-            Architecture::Aarch64 => "aarch64",
-            Architecture::Armv7h => "armv7h",
+#[cfg(feature = "format")]
+impl Display for OptionsDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        format_write_iter(f, &self.changes)
+    }
+}
+
+impl Options {
+    /// All known options as `(name, value)` pairs, in makepkg's canonical
+    /// order
+    fn entries(&self) -> [(&'static str, Option<bool>); 14] {
+        [
+            ("strip", self.strip),
+            ("docs", self.docs),
+            ("libtool", self.libtool),
+            ("staticlibs", self.staticlibs),
+            ("emptydirs", self.emptydirs),
+            ("zipman", self.zipman),
+            ("purge", self.purge),
+            ("ccache", self.ccache),
+            ("distcc", self.distcc),
+            ("buildflags", self.buildflags),
+            ("makeflags", self.makeflags),
+            ("debug", self.debug),
+            ("lto", self.lto),
+            ("autodeps", self.autodeps),
+        ]
+    }
+
+    /// Like `entries()`, but for modifying the options in place
+    fn entries_mut(&mut self) -> [(&'static str, &mut Option<bool>); 14] {
+        [
+            ("strip", &mut self.strip),
+            ("docs", &mut self.docs),
+            ("libtool", &mut self.libtool),
+            ("staticlibs", &mut self.staticlibs),
+            ("emptydirs", &mut self.emptydirs),
+            ("zipman", &mut self.zipman),
+            ("purge", &mut self.purge),
+            ("ccache", &mut self.ccache),
+            ("distcc", &mut self.distcc),
+            ("buildflags", &mut self.buildflags),
+            ("makeflags", &mut self.makeflags),
+            ("debug", &mut self.debug),
+            ("lto", &mut self.lto),
+            ("autodeps", &mut self.autodeps),
+        ]
+    }
+
+    /// Get the options back as `name` or `!name` strings for the `options`
+    /// array, known ones in makepkg's canonical order followed by unknown ones
+    /// in declared order
+    pub fn to_strings(&self) -> Vec<String> {
+        fn to_string(name: &str, enable: bool) -> String {
+            if enable {
+                name.into()
+            } else {
+                format!("!{}", name)
+            }
+        }
+        self.entries().into_iter().filter_map(|(name, value)|
+            value.map(|enable|to_string(name, enable)))
+            .chain(self.other.iter().map(|(name, enable)|
+                to_string(name, *enable)))
+            .collect()
+    }
+
+    /// Set an option unknown to this library, replacing the earlier value
+    fn set_other(&mut self, name: String, enable: bool) {
+        match self.other.iter_mut().find(|(other, _)|*other == name) {
+            Some((_, value)) => *value = enable,
+            None => self.other.push((name, enable)),
+        }
+    }
+
+    /// Whether no option, known or not, is set
+    pub fn is_empty(&self) -> bool {
+        self.other.is_empty()
+            && self.entries().iter().all(|(_, value)|value.is_none())
+    }
+
+    /// Like `==`, but ignoring the order of options unknown to this library
+    fn semantically_eq(&self, other: &Self) -> bool {
+        self.entries() == other.entries() 
+            && same_items(&self.other, &other.other)
+    }
+
+    /// Take the options set in this one over those of `base`, one by one, 
+    /// e.g. a package's options over those of the `PKGBUILD`. An option set
+    /// here wins even if it's the opposite of the base, those not set here
+    /// fall through to the base.
+    pub fn merged_over(&self, base: &Options) -> Options {
+        let mut merged = base.clone();
+        for ((_, value), (_, merged)) in
+            self.entries().into_iter().zip(merged.entries_mut())
+        {
+            if value.is_some() {
+                *merged = value
+            }
+        }
+        for (name, enable) in self.other.iter() {
+            merged.set_other(name.clone(), *enable)
+        }
+        merged
+    }
+
+    /// Compare against a `base`, e.g. the `PKGBUILD`-level options when this
+    /// is a package's options, and list every option that differs
+    pub fn diff(&self, base: &Options) -> OptionsDiff {
+        OptionsDiff { 
+            changes: self.entries().into_iter().zip(base.entries()).filter_map(
+                |((name, value), (_, base))| if value == base {
+                    None
+                } else {
+                    Some(OptionChange { name, base, value })
+                }).collect()
+        }
+    }
+}
+
+/// An architecture in `arch`, serialized as its lowercase name.
+///
+/// The variants are ordered as declared and new ones are always added before
+/// `Other`, so the iteration order of maps keyed by it stays stable.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+pub enum Architecture {
+    /// Declared as `arch=(any)`, its variables are always in `MultiArch::any`
+    /// and it never has a section in `MultiArch::arches`
+    Any,
+    #[default]
+    // Arch Linux specific
+    X86_64,
+    // Arch Linux ARM specific
+    Aarch64,
+    Armv7h,
+    // Arch Linux RSIC-V specific
+    Riscv64,
+    // Arch Linux 32 specific
+    I686,
+    Pentium4,
+    // Arch Linux ARM specific, older ones
+    Arm,
+    Armv6h,
+    // Arch Linux LoongArch specific
+    Loong64,
+    Other(String)
+}
+
+impl From<&[u8]> for Architecture {
+    fn from(value: &[u8]) -> Self {
+        Self::from(str_from_slice_u8!(value))
+    }
+}
+
+impl From<&str> for Architecture {
+    fn from(value: &str) -> Self {
+        let arch = value.to_lowercase();
+        match arch.as_str() {
+            "any" => Self::Any,
+            "x86_64" => Self::X86_64,
+            "aarch64" => Self::Aarch64,
+            "armv7h" => Self::Armv7h,
+            "riscv64" => Self::Riscv64,
+            "i686" => Self::I686,
+            "pentium4" => Self::Pentium4,
+            "arm" => Self::Arm,
+            "armv6h" => Self::Armv6h,
+            "loong64" => Self::Loong64,
+            _ => Self::Other(arch)
+        }
+    }
+}
+
+impl From<String> for Architecture {
+    fn from(value: String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl From<Architecture> for String {
+    fn from(value: Architecture) -> Self {
+        match value {
+            Architecture::Other(arch) => arch,
+            _ => value.as_ref().into()
+        }
+    }
+}
+
+impl AsRef<str> for Architecture {
+    fn as_ref(&self) -> &str {
+        match self {
+            Architecture::Any => "any",
+            Architecture::X86_64 => "x86_64",
+            Architecture::Aarch64 => "aarch64",
+            Architecture::Armv7h => "armv7h",
             Architecture::Riscv64 => "riscv64",
-            Architecture::Other(arch) => &arch,
+            Architecture::I686 => "i686",
+            Architecture::Pentium4 => "pentium4",
+            Architecture::Arm => "arm",
+            Architecture::Armv6h => "armv6h",
+            Architecture::Loong64 => "loong64",
+            Architecture::Other(arch) => arch,
         }
     }
 }
@@ -2383,7 +6869,7 @@ impl Display for Architecture {
 
 
 /// A `PKGBUILD`'s arch-specific variables
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PkgbuildArchSpecific {
     pub sources_with_checksums: Vec<SourceWithChecksum>,
@@ -2394,10 +6880,354 @@ pub struct PkgbuildArchSpecific {
     pub conflicts: Vec<Conflict>,
     pub provides: Vec<Provide>,
     pub replaces: Vec<Replace>,
+    /// Lines with keys unknown to this library, only collected with 
+    /// `Strictness::Lenient`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra: BTreeMap<String, Vec<String>>,
+}
+
+/// How `Pkgbuild::merge()` merges an array field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayMerge {
+    /// Append the overlay's items after the base's
+    #[default]
+    Append,
+    /// Use the overlay's items if it declared any, otherwise keep the base's
+    Replace,
+}
+
+/// Which side wins for scalar fields in `Pkgbuild::merge()`, an empty value
+/// never wins over a non-empty one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScalarPrecedence {
+    #[default]
+    Overlay,
+    Base,
+}
+
+/// The policy used by `Pkgbuild::merge()`
+#[derive(Debug, Clone, Default)]
+pub struct MergePolicy {
+    /// Which side wins for scalar fields and options
+    /// 
+    /// Default: `ScalarPrecedence::Overlay`
+    pub scalars: ScalarPrecedence,
+
+    /// How array fields are merged, unless overriden in `array_fields`
+    /// 
+    /// Default: `ArrayMerge::Append`
+    pub arrays: ArrayMerge,
+
+    /// How specific array fields are merged, keyed by their names in 
+    /// `PKGBUILD`, e.g. `source`, `depends` or `arch`. For `arch`, `Append`
+    /// means the union of both sides.
+    /// 
+    /// Default: empty
+    pub array_fields: BTreeMap<String, ArrayMerge>,
+
+    /// Allow the overlay to declare a different `epoch` or `pkgver`, `pkgrel`
+    /// could always be overriden
+    /// 
+    /// Default: `false`
+    pub allow_version_change: bool,
+}
+
+impl MergePolicy {
+    /// Get a `MergePolicy` instance with default settings: overlay wins for
+    /// scalars, arrays are appended, versions could not change
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set which side wins for scalar fields and options
+    pub fn set_scalars(&mut self, scalars: ScalarPrecedence) -> &mut Self {
+        self.scalars = scalars;
+        self
+    }
+
+    /// Set how array fields are merged by default
+    pub fn set_arrays(&mut self, arrays: ArrayMerge) -> &mut Self {
+        self.arrays = arrays;
+        self
+    }
+
+    /// Set how a specific array field is merged
+    pub fn set_array_field<S: Into<String>>(
+        &mut self, field: S, merge: ArrayMerge
+    ) -> &mut Self 
+    {
+        self.array_fields.insert(field.into(), merge);
+        self
+    }
+
+    /// Set whether the overlay could declare a different `epoch` or `pkgver`
+    pub fn set_allow_version_change(&mut self, allow_version_change: bool) 
+        -> &mut Self 
+    {
+        self.allow_version_change = allow_version_change;
+        self
+    }
+
+    /// Get how an array field is merged
+    pub fn array_merge(&self, field: &str) -> ArrayMerge {
+        self.array_fields.get(field).copied().unwrap_or(self.arrays)
+    }
+
+    fn scalar(&self, base: &str, overlay: &str) -> String {
+        let (first, second) = match self.scalars {
+            ScalarPrecedence::Overlay => (overlay, base),
+            ScalarPrecedence::Base => (base, overlay),
+        };
+        if first.is_empty() { second } else { first }.into()
+    }
+
+    /// Like `scalar()`, but for package fields where explicitly empty ones 
+    /// also take precedence
+    fn scalar_override(&self, base: &Option<String>, overlay: &Option<String>) 
+        -> Option<String> 
+    {
+        match self.scalars {
+            ScalarPrecedence::Overlay => overlay.as_ref().or(base.as_ref()),
+            ScalarPrecedence::Base => base.as_ref().or(overlay.as_ref()),
+        }.cloned()
+    }
+
+    fn array<T: Clone>(&self, field: &str, base: &[T], overlay: &[T]) -> Vec<T> {
+        match self.array_merge(field) {
+            ArrayMerge::Append => base.iter().chain(overlay).cloned().collect(),
+            ArrayMerge::Replace => if overlay.is_empty() {
+                base.to_vec()
+            } else {
+                overlay.to_vec()
+            },
+        }
+    }
+
+    /// Like `array()`, but for package fields where explicitly empty ones 
+    /// also replace
+    fn array_override<T: Clone>(&self, 
+        field: &str, base: &Option<Vec<T>>, overlay: &Option<Vec<T>>
+    ) -> Option<Vec<T>> 
+    {
+        match (base, overlay) {
+            (Some(base), Some(overlay)) => Some(match self.array_merge(field) {
+                ArrayMerge::Append => 
+                    base.iter().chain(overlay).cloned().collect(),
+                ArrayMerge::Replace => overlay.clone(),
+            }),
+            (base, overlay) => overlay.as_ref().or(base.as_ref()).cloned(),
+        }
+    }
+
+    /// Merge the unknown keys, each like an array field of the same name
+    fn extra(&self, 
+        base: &BTreeMap<String, Vec<String>>, 
+        overlay: &BTreeMap<String, Vec<String>>
+    ) -> BTreeMap<String, Vec<String>> 
+    {
+        let mut extra = base.clone();
+        for (key, values) in overlay.iter() {
+            let merged = self.array(key, 
+                base.get(key).map(Vec::as_slice).unwrap_or_default(), values);
+            extra.insert(key.clone(), merged);
+        }
+        extra
+    }
+
+    fn functions(&self, 
+        base: &BTreeMap<String, String>, 
+        overlay: &BTreeMap<String, String>
+    ) -> BTreeMap<String, String> 
+    {
+        let (first, second) = match self.scalars {
+            ScalarPrecedence::Overlay => (overlay, base),
+            ScalarPrecedence::Base => (base, overlay),
+        };
+        let mut functions = second.clone();
+        functions.extend(first.iter().map(|(name, body)|
+            (name.clone(), body.clone())));
+        functions
+    }
+
+    fn options(&self, base: &Options, overlay: &Options) -> Options {
+        let (first, second) = match self.scalars {
+            ScalarPrecedence::Overlay => (overlay, base),
+            ScalarPrecedence::Base => (base, overlay),
+        };
+        first.merged_over(second)
+    }
+
+    /// Like `options()`, but for package options which could be not set
+    fn options_override(&self, base: &Option<Options>, overlay: &Option<Options>)
+        -> Option<Options> 
+    {
+        match (base, overlay) {
+            (Some(base), Some(overlay)) => Some(self.options(base, overlay)),
+            (base, overlay) => overlay.as_ref().or(base.as_ref()).cloned(),
+        }
+    }
+}
+
+/// A field that could not be merged by `Pkgbuild::merge()`
+#[derive(Debug, Clone)]
+pub struct FieldConflict {
+    /// The path to the field, e.g. `pkgbase` or `version.pkgver`
+    pub path: String,
+    pub base: String,
+    pub overlay: String,
+}
+
+/// The fields that could not be merged by `Pkgbuild::merge()`, or the 
+/// errors of the merged result by `Pkgbuild::validate()`
+#[derive(Debug, Clone)]
+pub struct MergeConflict {
+    pub fields: Vec<FieldConflict>,
+    /// The issues of `Severity::Error` in the merged result, only checked if
+    /// all fields could be merged
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Merge Conflict: [")?;
+        let mut started = false;
+        for field in self.fields.iter() {
+            if started {
+                write!(f, ", ")?
+            } else {
+                started = true
+            }
+            write!(f, "{}: '{}' != '{}'", 
+                field.path, field.base, field.overlay)?
+        }
+        for issue in self.issues.iter() {
+            if started {
+                write!(f, ", ")?
+            } else {
+                started = true
+            }
+            write!(f, "{} '{}' ", issue.field, issue.value)?;
+            if let Some(pkgname) = &issue.pkgname {
+                write!(f, "of package '{}' ", pkgname)?
+            }
+            write!(f, "{}", issue.reason)?
+        }
+        write!(f, "]")
+    }
+}
+
+impl std::error::Error for MergeConflict {}
+
+/// Arch-specific variables that could be merged by `Pkgbuild::merge()`
+trait MergeArchSpecific: Clone {
+    fn merge(&self, overlay: &Self, policy: &MergePolicy) -> Self;
+}
+
+impl MergeArchSpecific for PkgbuildArchSpecific {
+    fn merge(&self, overlay: &Self, policy: &MergePolicy) -> Self {
+        Self {
+            sources_with_checksums: policy.array("source", 
+                &self.sources_with_checksums, &overlay.sources_with_checksums),
+            depends: policy.array(
+                "depends", &self.depends, &overlay.depends),
+            makedepends: policy.array(
+                "makedepends", &self.makedepends, &overlay.makedepends),
+            checkdepends: policy.array(
+                "checkdepends", &self.checkdepends, &overlay.checkdepends),
+            optdepends: policy.array(
+                "optdepends", &self.optdepends, &overlay.optdepends),
+            conflicts: policy.array(
+                "conflicts", &self.conflicts, &overlay.conflicts),
+            provides: policy.array(
+                "provides", &self.provides, &overlay.provides),
+            replaces: policy.array(
+                "replaces", &self.replaces, &overlay.replaces),
+            extra: policy.extra(&self.extra, &overlay.extra),
+        }
+    }
+}
+
+impl MergeArchSpecific for PackageArchSpecific {
+    fn merge(&self, overlay: &Self, policy: &MergePolicy) -> Self {
+        Self {
+            checkdepends: policy.array_override(
+                "checkdepends", &self.checkdepends, &overlay.checkdepends),
+            depends: policy.array_override(
+                "depends", &self.depends, &overlay.depends),
+            optdepends: policy.array_override(
+                "optdepends", &self.optdepends, &overlay.optdepends),
+            provides: policy.array_override(
+                "provides", &self.provides, &overlay.provides),
+            conflicts: policy.array_override(
+                "conflicts", &self.conflicts, &overlay.conflicts),
+            replaces: policy.array_override(
+                "replaces", &self.replaces, &overlay.replaces),
+            extra: policy.extra(&self.extra, &overlay.extra),
+        }
+    }
+}
+
+/// Merge arch-specific variables, the arches are merged like an array field
+fn merge_multiarch<T: MergeArchSpecific>(
+    base: &MultiArch<T>, overlay: &MultiArch<T>, policy: &MergePolicy
+) -> MultiArch<T> 
+{
+    let mut arches = BTreeMap::new();
+    if policy.array_merge("arch") == ArrayMerge::Append || 
+        overlay.arches.is_empty() 
+    {
+        for (arch, base) in base.arches.iter() {
+            arches.insert(arch.clone(), match overlay.arches.get(arch) {
+                Some(overlay) => base.merge(overlay, policy),
+                None => base.clone(),
+            });
+        }
+    }
+    for (arch, overlay) in overlay.arches.iter() {
+        if arches.contains_key(arch) { continue }
+        arches.insert(arch.clone(), match base.arches.get(arch) {
+            Some(base) => base.merge(overlay, policy),
+            None => overlay.clone(),
+        });
+    }
+    let mut arch_order = base.iter_arches().chain(overlay.iter_arches())
+        .filter_map(|(arch, _)|arches.contains_key(arch).then_some(arch))
+        .fold(Vec::new(), |mut order, arch| {
+            if ! order.contains(arch) { order.push(arch.clone()) }
+            order
+        });
+    if arches.is_empty() && (base.is_any_declared() || overlay.is_any_declared())
+    {
+        arch_order.push(Architecture::Any)
+    }
+    MultiArch { any: base.any.merge(&overlay.any, policy), arches, arch_order }
+}
+
+impl Package {
+    fn merge(&self, overlay: &Self, policy: &MergePolicy) -> Self {
+        Self {
+            pkgname: self.pkgname.clone(),
+            pkgdesc: policy.scalar_override(&self.pkgdesc, &overlay.pkgdesc),
+            url: policy.scalar_override(&self.url, &overlay.url),
+            license: policy.array_override(
+                "license", &self.license, &overlay.license),
+            groups: policy.array_override(
+                "groups", &self.groups, &overlay.groups),
+            backup: policy.array_override(
+                "backup", &self.backup, &overlay.backup),
+            options: policy.options_override(&self.options, &overlay.options),
+            install: policy.scalar_override(&self.install, &overlay.install),
+            changelog: policy.scalar_override(
+                &self.changelog, &overlay.changelog),
+            multiarch: merge_multiarch(
+                &self.multiarch, &overlay.multiarch, policy),
+            extra: policy.extra(&self.extra, &overlay.extra),
+        }
+    }
 }
 
 /// A `PKGBUILD` that could potentially have multiple split-packages
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pkgbuild {
     pub pkgbase: String,
@@ -2414,12 +7244,40 @@ pub struct Pkgbuild {
     pub multiarch: MultiArch<PkgbuildArchSpecific>,
     pub backup: Vec<String>,
     pub options: Options,
+    /// Whether a `pkgver()` function is defined after sourcing the `PKGBUILD`,
+    /// including one defined conditionally. Only the `PKGBUILD` itself counts,
+    /// functions exported from the parser's environment are ignored, and a 
+    /// `pkgver` variable alone does not set this.
     pub pkgver_func: bool,
+    /// The bodies of `pkgver()`, `prepare()`, `build()`, `check()`, 
+    /// `package()` and `package_*()` as `declare -f` prints them, keyed by the
+    /// function names, only collected with 
+    /// `ParserScriptBuilder::set_dump_function_bodies(true)`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub functions: BTreeMap<String, String>,
+    /// Anomalies tolerated when parsing, e.g. `any` mixed with other 
+    /// architectures with `ParserScriptBuilder::set_tolerate_any_with_arch()`,
+    /// or duplicated architectures merged with `Strictness::Lenient`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warnings: Vec<String>,
+    /// The variables added by `ParserScriptBuilder::add_extra_variable()`, 
+    /// and lines with keys unknown to this library, only collected with 
+    /// `Strictness::Lenient`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra: BTreeMap<String, Vec<String>>,
 }
 
 #[cfg(feature = "format")]
 impl Display for Pkgbuild {
+    /// A summary in a single line, or every field line by line with the 
+    /// alternate flag, i.e. `{:#}`, including the architecture-specific ones
+    /// and those of every package. A precision, e.g. `{:#.4}`, caps the 
+    /// architectures and their sections of the `PKGBUILD` and each package,
+    /// like `Srcinfo::set_max_arches()`
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return self.write_full(f)
+        }
         write!(f, "{{base: {}, pkgs: [", self.pkgbase)?;
         format_write_iter(f, &self.pkgs)?;
         write!(f, "], version: {}", self.version)?;
@@ -2438,285 +7296,2999 @@ impl Display for Pkgbuild {
     }
 }
 
-#[derive(Debug, Default)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct Pkgbuilds {
-    entries: Vec<Pkgbuild>
-}
-
 #[cfg(feature = "format")]
-impl Display for Pkgbuilds {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for pkgbuild in self.entries.iter() {
-            writeln!(f, "{}", pkgbuild)?
+impl Pkgbuild {
+    /// Write every field line by line for the full `Display`
+    fn write_full(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "pkgbase: {}", self.pkgbase)?;
+        write_full_array(f, "", "pkgname", self.pkgnames())?;
+        writeln!(f, "version: {}", self.version)?;
+        writeln!(f, "pkgver_func: {}", self.pkgver_func)?;
+        write_full_scalar(f, "", "pkgdesc", &self.pkgdesc)?;
+        write_full_scalar(f, "", "url", &self.url)?;
+        write_full_scalar(f, "", "install", &self.install)?;
+        write_full_scalar(f, "", "changelog", &self.changelog)?;
+        write_full_array(f, "", "license", &self.license)?;
+        write_full_array(f, "", "groups", &self.groups)?;
+        write_full_array(f, "", "backup", &self.backup)?;
+        write_full_array(f, "", "noextract", &self.noextract)?;
+        write_full_array(f, "", "validpgpkeys", &self.validpgpkeys)?;
+        write_full_scalar(f, "", "options", &self.options)?;
+        let max_arches = f.precision().unwrap_or(usize::MAX);
+        write_full_array(f, "", "arch", 
+            self.arches().iter().take(max_arches))?;
+        for (arch, values) in 
+            self.multiarch.iter().take(max_arches.saturating_add(1)) 
+        {
+            let suffix = arch.map(|arch|format!("_{}", arch))
+                .unwrap_or_default();
+            if values.sources_with_checksums.is_empty() {
+                writeln!(f, "source{}: []", suffix)?
+            } else {
+                writeln!(f, "source{}:", suffix)?;
+                for source in values.sources_with_checksums.iter() {
+                    write_full_source(f, source)?
+                }
+            }
+            write_full_array(f, "", &format!("depends{}", suffix), 
+                &values.depends)?;
+            write_full_array(f, "", &format!("makedepends{}", suffix), 
+                &values.makedepends)?;
+            write_full_array(f, "", &format!("checkdepends{}", suffix), 
+                &values.checkdepends)?;
+            write_full_array(f, "", &format!("conflicts{}", suffix), 
+                &values.conflicts)?;
+            write_full_array(f, "", &format!("replaces{}", suffix), 
+                &values.replaces)?;
+            write_full_array(f, "", &format!("optdepends{}", suffix), 
+                &values.optdepends)?;
+            write_full_array(f, "", &format!("provides{}", suffix), 
+                &values.provides)?;
+        }
+        writeln_arches_truncated(f, "", self.multiarch.arches.len(), 
+            max_arches)?;
+        write_full_array(f, "", "functions", self.functions.keys())?;
+        for (key, values) in self.extra.iter() {
+            write_full_array(f, "", key, values)?
+        }
+        for warning in self.warnings.iter() {
+            writeln!(f, "warning: {}", warning)?
+        }
+        for pkg in self.pkgs.iter() {
+            writeln!(f, "package {}:", pkg.pkgname)?;
+            pkg.write_full(f, "  ")?
         }
         Ok(())
     }
 }
 
-fn vec_items_from_vec_items<'a, I1, I2>(items: &'a Vec<&'a I2>) -> Vec<I1>
-where
-    I1: From<&'a I2>,
-    I2: ?Sized
+/// Write a source as written in the `PKGBUILD` with its checksums on its own
+/// line, for the full `Display` of `Pkgbuild`
+#[cfg(feature = "format")]
+fn write_full_source(f: &mut Formatter<'_>, source: &SourceWithChecksum) 
+    -> std::fmt::Result 
 {
-    items.iter().map(|item|I1::from(*item)).collect()
+    write!(f, "  {}", source.source.get_pkgbuild_source())?;
+    let mut checksums = Vec::new();
+    if let Some(cksum) = source.cksum {
+        checksums.push(format!("cksum: {}", cksum))
+    }
+    macro_rules! checksums {
+        ($($cksum: ident), +) => {$(
+            if let Some(sum) = &source.$cksum {
+                checksums.push(format!("{}: {}", stringify!($cksum), 
+                    hex::encode(sum)))
+            }
+        )+};
+    }
+    checksums!(md5sum, sha1sum, sha224sum, sha256sum, sha384sum, sha512sum, 
+        b2sum);
+    if checksums.is_empty() {
+        writeln!(f, " (SKIP)")
+    } else {
+        writeln!(f, " ({})", checksums.join(", "))
+    }
 }
 
-fn vec_items_try_from_vec_items<'a, I1, I2>(items: &'a Vec<&'a I2>) 
--> Result<Vec<I1>>
-where
-    I1: TryFrom<&'a I2>, Error: From<<I1 as TryFrom<&'a I2>>::Error>,
-    I2: ?Sized
-{
-    let mut converted = Vec::new();
-    for item in items.iter() {
-        converted.push(I1::try_from(*item)?)
-    }
-    Ok(converted)
+/// What the parser script wrote to stderr, from `Parser::parse_multi_verbose()`
+#[derive(Debug, Clone, Default)]
+pub struct ParseDiagnostics {
+    /// The whole stderr
+    pub stderr: Vec<u8>,
+    /// The part of stderr written before any `PKGBUILD` was parsed, e.g. 
+    /// warnings from `makepkg.conf`
+    pub global: Vec<u8>,
+    /// The parts of stderr written when parsing each `PKGBUILD`, in input 
+    /// order, only for those that have written anything
+    pub pkgbuilds: Vec<(PathBuf, Vec<u8>)>,
 }
 
-impl TryFrom<&PackageArchitectureParsing<'_>> for PackageArchSpecific {
-    type Error = Error;
+impl ParseDiagnostics {
+    /// Split stderr written with `--stderr-markers`, in which each `PKGBUILD`
+    /// is preceded by its path wrapped in `\0`, and what it wrote has `\` and
+    /// `\0` escaped as `\\` and `\\0`
+    fn from_marked_stderr(marked: &[u8], paths: Vec<PathBuf>) -> Self {
+        let mut parts = marked.split(|byte|*byte == b'\0');
+        let global = parts.next().unwrap_or_default().to_vec();
+        let mut stderr = global.clone();
+        let mut pkgbuilds = Vec::new();
+        let mut paths = paths.into_iter();
+        // Markers are written in input order, so only their count matters
+        while let (Some(_), Some(path)) = (parts.next(), paths.next()) {
+            let part = unescape_stderr(parts.next().unwrap_or_default());
+            if ! part.is_empty() {
+                stderr.extend_from_slice(&part);
+                pkgbuilds.push((path, part))
+            }
+        }
+        Self { stderr, global, pkgbuilds }
+    }
 
-    fn try_from(value: &PackageArchitectureParsing<'_>) -> Result<Self> {
-        let provides = 
-            vec_items_try_from_vec_items(&value.provides)?;
-        Ok(Self {
-            checkdepends: vec_items_from_vec_items(&value.checkdepends),
-            depends: vec_items_from_vec_items(&value.depends),
-            optdepends: vec_items_from_vec_items(&value.optdepends),
-            provides,
-            conflicts: vec_items_from_vec_items(&value.conflicts),
-            replaces: vec_items_from_vec_items(&value.replaces),
-        })
-    }   
+    /// Get the stderr written when parsing the `PKGBUILD` at `path`
+    pub fn for_path<P: AsRef<Path>>(&self, path: P) -> Option<&[u8]> {
+        let path = path.as_ref();
+        self.pkgbuilds.iter().find_map(|(this, stderr)|
+            (this == path).then_some(stderr.as_slice()))
+    }
 }
 
-fn vec_string_from_vec_slice_u8<'a>(vec: &Vec<&'a [u8]>) -> Vec<String> {
-    vec.iter().map(|item|string_from_slice_u8!(*item)).collect()
+/// Revert the escaping of `_escape_stderr` in the parser script
+fn unescape_stderr(escaped: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(escaped.len());
+    let mut bytes = escaped.iter();
+    while let Some(byte) = bytes.next() {
+        if *byte == b'\\' {
+            match bytes.next() {
+                Some(b'0') => unescaped.push(b'\0'),
+                Some(byte) => unescaped.push(*byte),
+                None => unescaped.push(b'\\'),
+            }
+        } else {
+            unescaped.push(*byte)
+        }
+    }
+    unescaped
 }
 
-impl TryFrom<&PackageParsing<'_>> for Package {
-    type Error = Error;
+/// How long each stage of a parse took and what the parser used, from 
+/// `Parser::parse_multi_report()`. Generating the script is not included, as
+/// that's done once when creating the `ParserScript`.
+#[derive(Debug, Clone, Default)]
+pub struct ParseReport {
+    /// The wall time of the whole parse
+    pub total: Duration,
+    /// The time taken to spawn the parser
+    pub spawn: Duration,
+    /// The time from the parser spawned till it exited, i.e. feeding it the
+    /// paths and reading its output
+    pub io: Duration,
+    /// The time taken to convert the output into `Pkgbuild`s
+    pub convert: Duration,
+    /// The user CPU time of the parser, including the subshells and commands
+    /// it waited for
+    pub child_user: Duration,
+    /// The system CPU time of the parser, including the subshells and 
+    /// commands it waited for
+    pub child_sys: Duration,
+    /// The bytes the parser wrote to stdout
+    pub output_bytes: usize,
+    /// The pkgbase of each `PKGBUILD` and the time taken to dump it, in input
+    /// order. This is measured when the library reads the end of its dump, 
+    /// so the first one also includes the parser starting up and sourcing the
+    /// makepkg library and config. It could be incomplete if the progress 
+    /// callback panicked.
+    pub per_pkgbuild: Vec<(String, Duration)>,
+}
 
-    fn try_from(value: &PackageParsing) -> Result<Self> {
-        let mut multiarch 
-            = MultiArch::default();
-        for arch in value.arches.iter() {
-            let arch_value = 
-                PackageArchSpecific::try_from(arch)?;
-            if arch.arch == b"any" {
-                multiarch.any = arch_value;
+/// The raw, unparsed output of the parser script, from `Parser::parse_raw()`
+#[derive(Debug, Clone)]
+pub struct RawParseOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub status: std::process::ExitStatus,
+}
+
+/// A collection of parsed `Pkgbuild`s, derefs to `[Pkgbuild]` and converts
+/// from and into `Vec<Pkgbuild>`
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pkgbuilds {
+    entries: Vec<Pkgbuild>,
+    /// The input indices of the entries, only known right after parsing and
+    /// used to tell which inputs had no result
+    #[cfg_attr(feature = "serde", serde(skip))]
+    inputs: Vec<usize>,
+}
+
+impl PartialEq for Pkgbuilds {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl Eq for Pkgbuilds {}
+
+impl Pkgbuilds {
+    /// Parse the raw output of the parser script from `Parser::parse_raw()`,
+    /// this fails if the script did not exit successfully
+    pub fn from_raw(raw: &RawParseOutput) -> Result<Self> {
+        Self::from_raw_with_strictness(raw, Strictness::Strict)
+    }
+
+    /// Like `from_raw()`, but with unknown keys handled as `strictness`
+    pub fn from_raw_with_strictness(
+        raw: &RawParseOutput, strictness: Strictness
+    ) -> Result<Self> 
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        let signal = raw.status.signal().or_else(|| 
+            // bash exits with 128 + signal if the subshell was killed, e.g. 
+            // by the OOM killer
+            raw.status.code().and_then(|code|code.checked_sub(128)).filter(
+                |signal|nix::sys::signal::Signal::try_from(*signal).is_ok()));
+        if let Some(signal) = signal {
+            log::error!("Child was killed by signal {}", signal);
+            log::debug!("Current stderr: {}", 
+                log_excerpt(&raw.stderr, LOG_EXCERPT_LIMIT));
+            return Err(Error::ParserKilledBySignal(signal))
+        }
+        if ! raw.status.success() {
+            log::error!("Child did not execute successfully");
+            log::debug!("Current stdout: {}", 
+                log_excerpt(&raw.stdout, LOG_EXCERPT_LIMIT));
+            log::debug!("Current stderr: {}", 
+                log_excerpt(&raw.stderr, LOG_EXCERPT_LIMIT));
+            return Err(Error::ParserScriptError {
+                error: ParserScriptError::from(raw.status.code()),
+                stderr_tail: tail_string(&raw.stderr, STDERR_TAIL_LIMIT),
+                kept_script: None,
+            })
+        }
+        if ! raw.stderr.is_empty() {
+            log::warn!("Parser has written to stderr: \n{}", 
+                log_excerpt(&raw.stderr, LOG_EXCERPT_LIMIT));
+        }
+        log::debug!("Raw output from parser:\n{}", 
+            log_excerpt(&raw.stdout, LOG_EXCERPT_LIMIT));
+        let lines = unescaped_lines(&raw.stdout);
+        Self::try_from(&PkgbuildsParsing::from_parser_output(
+            &lines, strictness)?)
+    }
+
+    /// Get the parsed `Pkgbuild`s, in the same order as the input paths
+    pub fn entries(&self) -> &[Pkgbuild] {
+        &self.entries
+    }
+
+    /// Take the parsed `Pkgbuild`s, in the same order as the input paths
+    pub fn into_entries(self) -> Vec<Pkgbuild> {
+        self.entries
+    }
+
+    /// The count of `Pkgbuild`s
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there is no `Pkgbuild`
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate through the `Pkgbuild`s in order
+    pub fn iter(&self) -> std::slice::Iter<'_, Pkgbuild> {
+        self.entries.iter()
+    }
+
+    /// Append a `Pkgbuild` to the end
+    pub fn push(&mut self, pkgbuild: Pkgbuild) {
+        self.entries.push(pkgbuild)
+    }
+
+    /// Index the `Pkgbuild`s by their `pkgbase`, fails with an
+    /// `Error::DuplicatedNames` listing every `pkgbase` declared more than once
+    pub fn by_pkgbase(&self) -> Result<BTreeMap<&str, &Pkgbuild>> {
+        index_unique("pkgbase", self.entries.iter().map(
+            |pkgbuild|(pkgbuild.pkgbase.as_str(), pkgbuild)))
+    }
+
+    /// Index the packages by their `pkgname`, together with the `Pkgbuild`s
+    /// building them, a non-split `PKGBUILD` has its only package named after
+    /// `pkgbase` by the parser. Fails with an `Error::DuplicatedNames` listing
+    /// every `pkgname` built by more than one `PKGBUILD`.
+    pub fn by_pkgname(&self) -> Result<BTreeMap<&str, (&Pkgbuild, &Package)>>
+    {
+        index_unique("pkgname", self.entries.iter().flat_map(|pkgbuild|
+            pkgbuild.pkgs.iter().map(move |pkg|
+                (pkg.pkgname.as_str(), (pkgbuild, pkg)))))
+    }
+
+    /// Find every package satisfying `dep` and the `Pkgbuild` building it, by
+    /// `pkgname` or `provides`, see `Dependency::satisfied_by_package_of()`.
+    /// With `arch`, packages not built for it are skipped.
+    #[cfg(feature = "vercmp")]
+    pub fn providers_of(&self, dep: &Dependency, arch: Option<&Architecture>)
+        -> Vec<(&Pkgbuild, &Package)>
+    {
+        self.entries.iter().flat_map(|pkgbuild|
+            pkgbuild.pkgs.iter().filter(move |pkg|
+                arch.is_none_or(|arch|pkg.supports_arch(arch))
+                    && dep.satisfied_by_package_of(pkgbuild, pkg, arch))
+                .map(move |pkg|(pkgbuild, pkg)))
+            .collect()
+    }
+
+    /// Find every dependency of `kinds` on `name` in the `Pkgbuild`s, in both
+    /// the arch-independent and arch-specific arrays for `arch`, or for all
+    /// architectures if `None`. Those of the `PKGBUILD` come first, then those
+    /// set in package functions. With `arch`, `PKGBUILD`s and packages not 
+    /// built for it are skipped.
+    pub fn reverse_dependencies(
+        &self, name: &str, kinds: DepKinds, arch: Option<&Architecture>
+    ) -> Vec<ReverseDep> 
+    {
+        let mut reverse_deps = Vec::new();
+        for pkgbuild in self.entries.iter() {
+            if arch.is_some_and(|arch|! pkgbuild.supports_arch(arch)) {
                 continue
             }
-            if let Some(_) = multiarch.arches.insert(
-                Architecture::from(arch.arch), arch_value) 
-            {
-                log::error!("Duplicated architecture {}", 
-                    str_from_slice_u8!(arch.arch));
-                return Err(Error::BrokenPKGBUILDs(Default::default()))
+            let mut push = |pkgname: Option<&str>, kind, deps: Vec<&Dependency>|
+                reverse_deps.extend(deps.into_iter().filter(
+                    |dep|dep.name == name).map(|dep|ReverseDep { 
+                        pkgbase: pkgbuild.pkgbase.clone(), 
+                        pkgname: pkgname.map(String::from), 
+                        kind, dependency: dep.clone() }));
+            if kinds.depends {
+                push(None, DepKind::Depends, pkgbuild.depends(arch))
+            }
+            if kinds.makedepends {
+                push(None, DepKind::Makedepends, pkgbuild.makedepends(arch)
+                    .into_iter().map(|dep|&dep.0).collect())
+            }
+            if kinds.checkdepends {
+                push(None, DepKind::Checkdepends, pkgbuild.checkdepends(arch)
+                    .into_iter().map(|dep|&dep.0).collect())
+            }
+            if ! kinds.depends {
+                continue
+            }
+            for pkg in pkgbuild.pkgs.iter() {
+                if arch.is_none_or(|arch|pkg.supports_arch(arch)) {
+                    push(Some(&pkg.pkgname), DepKind::Depends, 
+                        pkg.depends(arch))
+                }
             }
         }
-        Ok(Self { 
-            pkgname: string_from_slice_u8!(value.pkgname),
-            pkgdesc: string_from_slice_u8!(value.pkgdesc), 
-            url: string_from_slice_u8!(value.url),
-            license: vec_string_from_vec_slice_u8(&value.license),
-            groups: vec_string_from_vec_slice_u8(&value.groups),
-            backup: vec_string_from_vec_slice_u8(&value.backup),
-            options: (&value.options).into(),
-            install: string_from_slice_u8!(value.install),
-            changelog: string_from_slice_u8!(value.changelog),
-            multiarch
-         })
+        reverse_deps
     }
-}
-
 
+    /// Get the dependency graph for `arch`, or for all architectures if 
+    /// `None`, as the indices of the `Pkgbuild`s each one depends on, sorted
+    /// 
+    /// A `PKGBUILD` depends on another one in the set if the latter builds a
+    /// package satisfying one of the former's `depends`, `makedepends` or 
+    /// `checkdepends`, by `pkgname` or `provides`. If a dependency is 
+    /// satisfied by a `pkgname`, only those building that are depended on, 
+    /// otherwise all providing it. Dependencies not satisfied in the set are
+    /// external and ignored, and so are those on itself. With `arch`, 
+    /// `PKGBUILD`s and packages not built for it have no edges.
+    #[cfg(feature = "vercmp")]
+    pub fn dependency_graph(&self, arch: Option<&Architecture>) 
+        -> Vec<Vec<usize>> 
+    {
+        let included = |pkgbuild: &Pkgbuild|
+            arch.is_none_or(|arch|pkgbuild.supports_arch(arch));
+        let providers = self.provider_index(arch);
+        self.entries.iter().enumerate().map(|(index, pkgbuild)| {
+            let mut depended = Vec::new();
+            if ! included(pkgbuild) {
+                return depended
+            }
+            for dep in pkgbuild.depends(arch).into_iter()
+                .chain(pkgbuild.makedepends(arch).into_iter().map(|dep|&dep.0))
+                .chain(pkgbuild.checkdepends(arch).into_iter().map(|dep|&dep.0))
+            {
+                let Some(candidates) = providers.get(dep.name.as_str()) 
+                    else { continue };
+                let satisfying: Vec<&(usize, &Package)> = candidates.iter()
+                    .filter(|(other, pkg)|*other != index && 
+                        dep.satisfied_by_package_of(
+                            &self.entries[*other], pkg, arch))
+                    .collect();
+                let by_pkgname = satisfying.iter().any(
+                    |(_, pkg)|pkg.pkgname == dep.name);
+                depended.extend(satisfying.into_iter().filter(
+                    |(_, pkg)|! by_pkgname || pkg.pkgname == dep.name)
+                    .map(|(other, _)|*other))
+            }
+            depended.sort_unstable();
+            depended.dedup();
+            depended
+        }).collect()
+    }
 
-impl TryFrom<&PkgbuildArchitectureParsing<'_>> for PkgbuildArchSpecific {
-    type Error = Error;
-
-    fn try_from(value: &PkgbuildArchitectureParsing) -> Result<Self> {
-        let mut sources_with_checksums = Vec::new();
-        if ! value.sources.is_empty() {
-            let len = value.sources.len();
-            macro_rules! len_mismatch {
-                ($value:ident, $sums:ident, $len:ident) => {
-                    ! $value.$sums.is_empty() && $value.$sums.len() != $len
-                };
+    /// Index the packages built for `arch` by their `pkgname` and the names in
+    /// their effective `provides`, with the indices of their `Pkgbuild`s, a
+    /// package is only indexed once under each name
+    #[cfg(feature = "vercmp")]
+    fn provider_index(&self, arch: Option<&Architecture>) 
+        -> BTreeMap<&str, Vec<(usize, &Package)>> 
+    {
+        let mut providers: BTreeMap<&str, Vec<(usize, &Package)>> = 
+            BTreeMap::new();
+        for (index, pkgbuild) in self.entries.iter().enumerate() {
+            if arch.is_some_and(|arch|! pkgbuild.supports_arch(arch)) {
+                continue
             }
-            if len_mismatch!(value, cksums, len) ||
-                len_mismatch!(value, md5sums, len) ||
-                len_mismatch!(value, sha1sums, len) ||
-                len_mismatch!(value, sha224sums, len) ||
-                len_mismatch!(value, sha256sums, len) ||
-                len_mismatch!(value, sha384sums, len) ||
-                len_mismatch!(value, sha512sums, len) ||
-                len_mismatch!(value, b2sums, len)
+            for pkg in pkgbuild.pkgs.iter().filter(
+                |pkg|arch.is_none_or(|arch|pkg.supports_arch(arch))) 
             {
-                log::error!("Lengths of sources and checksums mismatch, \
-                    sources: {}, cksums: {}, md5sums: {}, sha1sums: {} \
-                    sha224sums: {}, sha256sums: {}, sha384sums: {} \
-                    sha512sums: {}, b2sums: {}",
-                    value.sources.len(), value.cksums.len(), value.md5sums.len(),
-                    value.sha1sums.len(), value.sha224sums.len(), 
-                    value.sha256sums.len(), value.sha384sums.len(),
-                    value.sha512sums.len(), value.b2sums.len());
-                return Err(Error::BrokenPKGBUILDs(Default::default()))
+                let names = std::iter::once(pkg.pkgname.as_str()).chain(
+                    pkg.effective_provides(pkgbuild, arch).into_iter().map(
+                        |provide|provide.name.as_str()));
+                for name in names {
+                    let providers = providers.entry(name).or_default();
+                    if providers.last().is_none_or(|(last, last_pkg)|
+                        *last != index || last_pkg.pkgname != pkg.pkgname)
+                    {
+                        providers.push((index, pkg))
+                    }
+                }
             }
-            for (id, source) in value.sources.iter().enumerate(){
-                let mut source_with_checksum = 
-                    SourceWithChecksum::default();
-                source_with_checksum.source = (*source).into();
-                if let Some(cksum) = value.cksums.get(id) {
-                    source_with_checksum.cksum = if cksum == b"SKIP" {
-                        None
-                    } else {
-                        String::from_utf8_lossy(cksum).parse().ok()
+        }
+        providers
+    }
+
+    /// Find every package whose effective `conflicts` or `replaces` is 
+    /// satisfied by another package in the set, by its `pkgname` or 
+    /// `provides`, see `Package::conflicts_with()`. With `arch`, packages not
+    /// built for it are left out.
+    #[cfg(feature = "vercmp")]
+    pub fn find_conflicts(&self, arch: Option<&Architecture>) 
+        -> Vec<ConflictReport> 
+    {
+        let providers = self.provider_index(arch);
+        let mut reports = Vec::new();
+        for (index, pkgbuild) in self.entries.iter().enumerate() {
+            if arch.is_some_and(|arch|! pkgbuild.supports_arch(arch)) {
+                continue
+            }
+            for pkg in pkgbuild.pkgs.iter().filter(
+                |pkg|arch.is_none_or(|arch|pkg.supports_arch(arch))) 
+            {
+                for (kind, constraint) in pkg.conflict_constraints(
+                    pkgbuild, arch) 
+                {
+                    let Some(candidates) = providers.get(
+                        constraint.name.as_str()) else { continue };
+                    for (other, other_pkg) in candidates.iter() {
+                        let other_pkgbuild = &self.entries[*other];
+                        if (*other == index 
+                                && other_pkg.pkgname == pkg.pkgname)
+                            || ! constraint.satisfied_by_package_of(
+                                other_pkgbuild, other_pkg, arch)
+                        {
+                            continue
+                        }
+                        reports.push(ConflictReport {
+                            pkgbase: pkgbuild.pkgbase.clone(),
+                            pkgname: pkg.pkgname.clone(),
+                            other_pkgbase: other_pkgbuild.pkgbase.clone(),
+                            other_pkgname: other_pkg.pkgname.clone(),
+                            kind,
+                            constraint: constraint.clone(),
+                        })
                     }
                 }
-                macro_rules! hash_sum_from_hex {
-                    ($sum:ident, $sums:ident) => {
-                        if let Some($sum) = value.$sums.get(id) {
-                            source_with_checksum.$sum = if $sum == b"SKIP" {
-                                None
-                            } else {
-                                FromHex::from_hex($sum).ok()
-                            }
-                        }                        
-                    };
+            }
+        }
+        reports
+    }
+
+    /// Order the `Pkgbuild`s so each one comes after those it depends on, 
+    /// see `dependency_graph()`, otherwise in the same order as in the set.
+    /// With `arch`, `PKGBUILD`s not built for it are left out.
+    /// 
+    /// Fails with a `CycleError` if some `PKGBUILD`s depend on each other, 
+    /// e.g. the bootstrapping of `rust` and `llvm`.
+    #[cfg(feature = "vercmp")]
+    pub fn build_order(&self, arch: Option<&Architecture>) 
+        -> std::result::Result<Vec<&Pkgbuild>, CycleError> 
+    {
+        let graph = self.dependency_graph(arch);
+        let mut dependents = vec![Vec::new(); graph.len()];
+        for (index, depended) in graph.iter().enumerate() {
+            for other in depended.iter() {
+                dependents[*other].push(index)
+            }
+        }
+        // Kahn's algorithm, the lowest index ready goes first
+        let mut pending: Vec<usize> = graph.iter().map(Vec::len).collect();
+        let mut ready: std::collections::BTreeSet<usize> = 
+            (0..graph.len()).filter(|index|pending[*index] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+            for dependent in dependents[index].iter() {
+                pending[*dependent] -= 1;
+                if pending[*dependent] == 0 {
+                    ready.insert(*dependent);
                 }
-                hash_sum_from_hex!(md5sum, md5sums);
-                hash_sum_from_hex!(sha1sum, sha1sums);
-                hash_sum_from_hex!(sha224sum, sha224sums);
-                hash_sum_from_hex!(sha256sum, sha256sums);
-                hash_sum_from_hex!(sha384sum, sha384sums);
-                hash_sum_from_hex!(sha512sum, sha512sums);
-                hash_sum_from_hex!(b2sum, b2sums);
-                sources_with_checksums.push(source_with_checksum)
             }
         }
-        let provides = 
-            vec_items_try_from_vec_items(&value.provides)?;
-        Ok (Self {
-            sources_with_checksums,
-            depends: vec_items_from_vec_items(&value.depends),
-            makedepends: vec_items_from_vec_items(&value.makedepends),
-            checkdepends: vec_items_from_vec_items(&value.checkdepends),
-            optdepends: vec_items_from_vec_items(&value.optdepends),
-            conflicts: vec_items_from_vec_items(&value.conflicts),
-            provides,
-            replaces: vec_items_from_vec_items(&value.replaces),
-        })
+        if order.len() == graph.len() {
+            return Ok(order.into_iter().map(|index|&self.entries[index])
+                .filter(|pkgbuild|
+                    arch.is_none_or(|arch|pkgbuild.supports_arch(arch)))
+                .collect())
+        }
+        // Those left either are in a cycle or depend on one, strip the latter
+        // by peeling those no one left depends on
+        let mut left: Vec<bool> = pending.iter().map(|count|*count > 0)
+            .collect();
+        let mut depended_by: Vec<usize> = dependents.iter().map(|dependents|
+            dependents.iter().filter(|dependent|left[**dependent]).count())
+            .collect();
+        let mut peeling: Vec<usize> = (0..graph.len()).filter(
+            |index|left[*index] && depended_by[*index] == 0).collect();
+        while let Some(index) = peeling.pop() {
+            left[index] = false;
+            for other in graph[index].iter() {
+                if left[*other] {
+                    depended_by[*other] -= 1;
+                    if depended_by[*other] == 0 {
+                        peeling.push(*other)
+                    }
+                }
+            }
+        }
+        Err(CycleError { pkgbases: self.entries.iter().zip(left).filter(
+            |(_, left)|*left).map(|(pkgbuild, _)|pkgbuild.pkgbase.clone())
+            .collect() })
+    }
+
+    /// Get the members of every group in `groups`, a package without `groups`
+    /// in its package function inherits those of the `PKGBUILD`. A member is 
+    /// a `Pkgbuild` with `None` if all of its packages are in the group, or 
+    /// with each package in the group otherwise. With `arch`, packages not 
+    /// built for it are left out, also when telling whether all are in.
+    pub fn groups(&self, arch: Option<&Architecture>) 
+        -> BTreeMap<String, Vec<(&Pkgbuild, Option<&Package>)>> 
+    {
+        let mut groups: BTreeMap<String, Vec<_>> = BTreeMap::new();
+        for pkgbuild in self.entries.iter() {
+            for (group, pkg) in group_members(pkgbuild, arch) {
+                groups.entry(group.into()).or_default().push((pkgbuild, pkg))
+            }
+        }
+        groups
+    }
+
+    /// Get the members of `group`, like a single group of `groups()`
+    pub fn in_group(&self, group: &str, arch: Option<&Architecture>) 
+        -> Vec<(&Pkgbuild, Option<&Package>)> 
+    {
+        self.entries.iter().flat_map(|pkgbuild|
+            group_members(pkgbuild, arch).into_iter().filter(
+                |(name, _)|*name == group).map(move |(_, pkg)|(pkgbuild, pkg)))
+            .collect()
+    }
+
+    /// Find the package named `name` and the `Pkgbuild` building it, the
+    /// first one in order if multiple `PKGBUILD`s build it
+    pub fn find_package(&self, name: &str) -> Option<(&Pkgbuild, &Package)> {
+        self.entries.iter().find_map(|pkgbuild|
+            pkgbuild.pkgs.iter().find(|pkg|pkg.pkgname == name)
+                .map(|pkg|(pkgbuild, pkg)))
     }
 }
 
+/// The kind of a dependency array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DepKind {
+    Depends,
+    Makedepends,
+    Checkdepends,
+}
 
+#[cfg(feature = "format")]
+impl Display for DepKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DepKind::Depends => write!(f, "depends"),
+            DepKind::Makedepends => write!(f, "makedepends"),
+            DepKind::Checkdepends => write!(f, "checkdepends"),
+        }
+    }
+}
 
-impl TryFrom<&PkgbuildParsing<'_>> for Pkgbuild {
-    type Error = Error;
+/// The kinds of dependency arrays to look into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DepKinds {
+    pub depends: bool,
+    pub makedepends: bool,
+    pub checkdepends: bool,
+}
 
-    fn try_from(value: &PkgbuildParsing) -> Result<Self> {
-        let mut pkgs = Vec::new();
-        for pkg in value.pkgs.iter() {
-            pkgs.push(pkg.try_into()?)
+impl Default for DepKinds {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl DepKinds {
+    /// All of `depends`, `makedepends` and `checkdepends`
+    pub const ALL: Self = Self { 
+        depends: true, makedepends: true, checkdepends: true };
+    /// Only `depends`, those needed at runtime
+    pub const RUNTIME: Self = Self { 
+        depends: true, makedepends: false, checkdepends: false };
+    /// `depends` and `makedepends`, those needed to build without checking
+    pub const BUILD: Self = Self { 
+        depends: true, makedepends: true, checkdepends: false };
+
+    /// Whether `kind` is included
+    pub fn contains(&self, kind: DepKind) -> bool {
+        match kind {
+            DepKind::Depends => self.depends,
+            DepKind::Makedepends => self.makedepends,
+            DepKind::Checkdepends => self.checkdepends,
         }
-        let mut multiarch = MultiArch::default();
-        for arch in value.arches.iter() {
-            let arch_value = 
-                PkgbuildArchSpecific::try_from(arch)?;
-            if arch.arch == b"any" {
-                multiarch.any = arch_value;
-                continue
-            }
-            if let Some(_) = 
-                multiarch.arches.insert(Architecture::from(arch.arch), arch_value) 
-            {
-                log::error!("Duplicated architecture {}", 
-                    str_from_slice_u8!(arch.arch));
-                return Err(Error::BrokenPKGBUILDs(Default::default()))
+    }
+}
+
+/// A dependency on a package, from `Pkgbuilds::reverse_dependencies()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ReverseDep {
+    /// The `pkgbase` of the depending `PKGBUILD`
+    pub pkgbase: String,
+    /// The depending split package, if this is from the `depends` set in its
+    /// package function, `None` if from the `PKGBUILD`
+    pub pkgname: Option<String>,
+    pub kind: DepKind,
+    /// The dependency as declared, with its version constraint
+    pub dependency: Dependency,
+}
+
+#[cfg(feature = "format")]
+impl Display for ReverseDep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pkgbase)?;
+        if let Some(pkgname) = &self.pkgname {
+            write!(f, "/{}", pkgname)?
+        }
+        write!(f, " {} {}", self.kind, self.dependency)
+    }
+}
+
+/// The kind of a relation refusing another package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ConflictKind {
+    /// From `conflicts`
+    Conflict,
+    /// From `replaces`
+    Replace,
+}
+
+#[cfg(feature = "format")]
+impl Display for ConflictKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictKind::Conflict => write!(f, "conflicts"),
+            ConflictKind::Replace => write!(f, "replaces"),
+        }
+    }
+}
+
+/// A package conflicting with or replacing another one, from 
+/// `Pkgbuilds::find_conflicts()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ConflictReport {
+    pub pkgbase: String,
+    /// The package declaring the `conflicts` or `replaces`
+    pub pkgname: String,
+    pub other_pkgbase: String,
+    /// The package satisfying the constraint
+    pub other_pkgname: String,
+    pub kind: ConflictKind,
+    /// The `conflicts` or `replaces` as declared, with its version constraint
+    pub constraint: Dependency,
+}
+
+#[cfg(feature = "format")]
+impl Display for ConflictReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} {} {} by {}/{}", self.pkgbase, self.pkgname, 
+            self.kind, self.constraint, self.other_pkgbase, self.other_pkgname)
+    }
+}
+
+/// Some `PKGBUILD`s depend on each other, from `Pkgbuilds::build_order()`
+#[cfg(feature = "vercmp")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// The `pkgbase`s of those in cycles, in the same order as in the set
+    pub pkgbases: Vec<String>,
+}
+
+#[cfg(feature = "vercmp")]
+impl Display for CycleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Dependency Cycle: {:?}", self.pkgbases)
+    }
+}
+
+#[cfg(feature = "vercmp")]
+impl std::error::Error for CycleError {}
+
+/// Get the groups of the packages in a `Pkgbuild` built for `arch`, with 
+/// `None` for a group all of them are in, see `Pkgbuilds::groups()`
+fn group_members<'a>(pkgbuild: &'a Pkgbuild, arch: Option<&Architecture>)
+    -> Vec<(&'a str, Option<&'a Package>)>
+{
+    let pkgs: Vec<&Package> = pkgbuild.pkgs.iter().filter(
+        |pkg|arch.is_none_or(|arch|pkg.supports_arch(arch))).collect();
+    let mut members: BTreeMap<&str, Vec<&Package>> = BTreeMap::new();
+    for pkg in pkgs.iter() {
+        for group in pkg.groups.as_ref().unwrap_or(&pkgbuild.groups) {
+            let members = members.entry(group.as_str()).or_default();
+            // A group could be declared twice
+            if ! members.iter().any(|member|member.pkgname == pkg.pkgname) {
+                members.push(pkg)
             }
         }
-        Ok(Self {
-            pkgbase: string_from_slice_u8!(value.pkgbase),
-            pkgs,
-            version: PlainVersion::from_raw(
-                value.epoch, value.pkgver, value.pkgrel),
-            pkgdesc: string_from_slice_u8!(value.pkgdesc),
-            url: string_from_slice_u8!(value.url),
-            license: vec_string_from_vec_slice_u8(&value.license),
-            install: string_from_slice_u8!(value.install),
-            changelog: string_from_slice_u8!(value.changelog),
-            validpgpkeys: vec_string_from_vec_slice_u8(&value.validgpgkeys),
-            noextract: vec_string_from_vec_slice_u8(&value.noextract),
-            groups: vec_string_from_vec_slice_u8(&value.groups),
-            multiarch,
-            backup: vec_string_from_vec_slice_u8(&value.backups),
-            options: (&value.options).into(),
-            pkgver_func: value.pkgver_func
+    }
+    members.into_iter().flat_map(|(group, members)|
+        if members.len() == pkgs.len() {
+            vec![(group, None)]
+        } else {
+            members.into_iter().map(|pkg|(group, Some(pkg))).collect()
         })
+        .collect()
+}
+
+/// Collect the `(name, value)` pairs into a map, failing with an
+/// `Error::DuplicatedNames` of `kind` if any name comes more than once
+fn index_unique<'a, T, I>(kind: &'static str, items: I)
+    -> Result<BTreeMap<&'a str, T>>
+where
+    I: IntoIterator<Item = (&'a str, T)>
+{
+    let mut map = BTreeMap::new();
+    let mut duplicated = Vec::new();
+    for (name, value) in items {
+        if map.insert(name, value).is_some() {
+            duplicated.push(name.to_string())
+        }
+    }
+    if duplicated.is_empty() {
+        Ok(map)
+    } else {
+        duplicated.sort_unstable();
+        duplicated.dedup();
+        Err(Error::DuplicatedNames { kind, names: duplicated })
+    }
+}
+
+impl std::ops::Deref for Pkgbuilds {
+    type Target = [Pkgbuild];
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+impl From<Vec<Pkgbuild>> for Pkgbuilds {
+    fn from(value: Vec<Pkgbuild>) -> Self {
+        Self { entries: value, inputs: Vec::new() }
+    }
+}
+
+impl From<Pkgbuilds> for Vec<Pkgbuild> {
+    fn from(value: Pkgbuilds) -> Self {
+        value.entries
+    }
+}
+
+impl FromIterator<Pkgbuild> for Pkgbuilds {
+    fn from_iter<I: IntoIterator<Item = Pkgbuild>>(iter: I) -> Self {
+        Self::from(Vec::from_iter(iter))
+    }
+}
+
+impl Extend<Pkgbuild> for Pkgbuilds {
+    fn extend<I: IntoIterator<Item = Pkgbuild>>(&mut self, iter: I) {
+        self.entries.extend(iter)
+    }
+}
+
+impl IntoIterator for Pkgbuilds {
+    type Item = Pkgbuild;
+    type IntoIter = std::vec::IntoIter<Pkgbuild>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Pkgbuilds {
+    type Item = &'a Pkgbuild;
+    type IntoIter = std::slice::Iter<'a, Pkgbuild>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for Pkgbuilds {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for pkgbuild in self.entries.iter() {
+            writeln!(f, "{}", pkgbuild)?
+        }
+        Ok(())
+    }
+}
+
+fn vec_items_from_vec_items<'a, I1, I2>(items: &'a Vec<&'a I2>) -> Vec<I1>
+where
+    I1: From<&'a I2>,
+    I2: ?Sized
+{
+    items.iter().map(|item|I1::from(*item)).collect()
+}
+
+/// Convert each whitespace-separated word of the items, as makepkg word-splits
+/// an element like `'foo bar>=2'` of a relation array
+fn vec_items_from_vec_words<'a, I>(items: &'a [&'a [u8]]) -> Vec<I>
+where
+    I: From<&'a [u8]>
+{
+    items.iter().flat_map(|item|split_words(item)).map(I::from).collect()
+}
+
+/// Like `vec_items_from_vec_words()`, but with a fallible conversion
+fn vec_items_try_from_vec_words<'a, I>(items: &'a [&'a [u8]]) 
+-> Result<Vec<I>>
+where
+    I: TryFrom<&'a [u8]>, Error: From<<I as TryFrom<&'a [u8]>>::Error>,
+{
+    let mut converted = Vec::new();
+    for word in items.iter().flat_map(|item|split_words(item)) {
+        converted.push(I::try_from(word)?)
+    }
+    Ok(converted)
+}
+
+/// Split at ASCII whitespace, without empty words
+fn split_words(item: &[u8]) -> impl Iterator<Item = &[u8]> {
+    item.split(u8::is_ascii_whitespace).filter(|word|! word.is_empty())
+}
+
+impl TryFrom<&PackageArchitectureParsing<'_>> for PackageArchSpecific {
+    type Error = Error;
+
+    fn try_from(value: &PackageArchitectureParsing<'_>) -> Result<Self> {
+        let provides = match &value.provides {
+            Some(provides) => Some(vec_items_try_from_vec_words(provides)?),
+            None => None,
+        };
+        Ok(Self {
+            checkdepends: value.checkdepends.as_deref().map(
+                vec_items_from_vec_words),
+            depends: value.depends.as_deref().map(vec_items_from_vec_words),
+            optdepends: value.optdepends.as_ref().map(
+                vec_items_from_vec_items),
+            provides,
+            conflicts: value.conflicts.as_deref().map(
+                vec_items_from_vec_words),
+            replaces: value.replaces.as_deref().map(vec_items_from_vec_words),
+            extra: extra_from_pairs(&value.extra),
+        })
+    }   
+}
+
+fn vec_string_from_vec_slice_u8(vec: &[&[u8]]) -> Vec<String> {
+    vec.iter().map(|item|string_from_slice_u8!(*item)).collect()
+}
+
+/// Reverse the escaping of values by the script, which turns `\` into `\\` 
+/// and newlines into `\n` to keep every value in one line
+fn unescape_value(value: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(value.len());
+    let mut escaping = false;
+    for byte in value.iter() {
+        match (escaping, byte) {
+            (false, b'\\') => escaping = true,
+            (true, b'n') => {
+                unescaped.push(b'\n');
+                escaping = false
+            },
+            (true, _) => {
+                unescaped.push(*byte);
+                escaping = false
+            },
+            (false, _) => unescaped.push(*byte),
+        }
+    }
+    unescaped
+}
+
+/// Split the output of the parser script into non-empty lines with values
+/// unescaped, only those with escapes are copied
+fn unescaped_lines(output: &[u8]) -> Vec<Cow<'_, [u8]>> {
+    output.split(|byte| *byte == b'\n').filter(|line|! line.is_empty())
+        .map(|line| if line.contains(&b'\\') {
+            Cow::Owned(unescape_value(line))
+        } else {
+            Cow::Borrowed(line)
+        }).collect()
+}
+
+fn extra_from_pairs(pairs: &[(&[u8], &[u8])]) -> BTreeMap<String, Vec<String>> {
+    let mut extra: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, value) in pairs.iter() {
+        extra.entry(string_from_slice_u8!(*key)).or_default().push(
+            string_from_slice_u8!(*value))
+    }
+    extra
+}
+
+impl TryFrom<&PackageParsing<'_>> for Package {
+    type Error = Error;
+
+    fn try_from(value: &PackageParsing) -> Result<Self> {
+        Self::from_parsing(value, false, &mut Vec::new())
+    }
+}
+
+impl Package {
+    /// Convert from the parsed values, with `lenient` duplicated architectures
+    /// are merged in first-seen order and warned about in `warnings`
+    fn from_parsing(
+        value: &PackageParsing, lenient: bool, warnings: &mut Vec<String>
+    ) -> Result<Self>
+    {
+        let mut multiarch: MultiArch<PackageArchSpecific>
+            = MultiArch::default();
+        let pkgname = string_from_slice_u8!(value.pkgname);
+        for arch in value.arches.iter() {
+            let arch_value = 
+                PackageArchSpecific::try_from(arch).map_err(|e|
+                    e.broken_context(format_args!("package '{}' arch '{}'",
+                        pkgname, str_from_slice_u8!(arch.arch))))?;
+            if arch.arch == b"any" {
+                multiarch.any = arch_value;
+                continue
+            }
+            let architecture = Architecture::from(arch.arch);
+            if let Some(existing) = multiarch.arches.get_mut(&architecture) {
+                if ! lenient {
+                    log::error!("Duplicated architecture {}",
+                        str_from_slice_u8!(arch.arch));
+                    return Err(BrokenPkgbuild::error(format!(
+                        "package '{}' has duplicated architecture '{}'",
+                        pkgname, str_from_slice_u8!(arch.arch))))
+                }
+                log::warn!("Merged duplicated architecture {} of package {}",
+                    architecture, pkgname);
+                warnings.push(format!("Package '{}' has duplicated \
+                    architecture '{}', merged", pkgname, architecture));
+                existing.append(arch_value);
+                continue
+            }
+            multiarch.arch_order.push(architecture.clone());
+            multiarch.arches.insert(architecture, arch_value);
+        }
+        if value.any_declared {
+            multiarch.arch_order.push(Architecture::Any)
+        }
+        Ok(Self { 
+            pkgname,
+            pkgdesc: value.pkgdesc.map(|pkgdesc|string_from_slice_u8!(pkgdesc)),
+            url: value.url.map(|url|string_from_slice_u8!(url)),
+            license: value.license.as_deref().map(vec_string_from_vec_slice_u8),
+            groups: value.groups.as_deref().map(vec_string_from_vec_slice_u8),
+            backup: value.backup.as_deref().map(vec_string_from_vec_slice_u8),
+            options: value.options.as_ref().map(Options::from),
+            install: value.install.map(|install|string_from_slice_u8!(install)),
+            changelog: value.changelog.map(
+                |changelog|string_from_slice_u8!(changelog)),
+            multiarch,
+            extra: extra_from_pairs(&value.extra),
+         })
+    }
+}
+
+
+
+impl TryFrom<&PkgbuildArchitectureParsing<'_>> for PkgbuildArchSpecific {
+    type Error = Error;
+
+    fn try_from(value: &PkgbuildArchitectureParsing) -> Result<Self> {
+        let mut sources_with_checksums = Vec::new();
+        if ! value.sources.is_empty() {
+            let len = value.sources.len();
+            macro_rules! len_mismatch {
+                ($value:ident, $sums:ident, $len:ident) => {
+                    ! $value.$sums.is_empty() && $value.$sums.len() != $len
+                };
+            }
+            if len_mismatch!(value, cksums, len) ||
+                len_mismatch!(value, md5sums, len) ||
+                len_mismatch!(value, sha1sums, len) ||
+                len_mismatch!(value, sha224sums, len) ||
+                len_mismatch!(value, sha256sums, len) ||
+                len_mismatch!(value, sha384sums, len) ||
+                len_mismatch!(value, sha512sums, len) ||
+                len_mismatch!(value, b2sums, len)
+            {
+                log::error!("Lengths of sources and checksums mismatch, \
+                    sources: {}, cksums: {}, md5sums: {}, sha1sums: {} \
+                    sha224sums: {}, sha256sums: {}, sha384sums: {} \
+                    sha512sums: {}, b2sums: {}",
+                    value.sources.len(), value.cksums.len(), value.md5sums.len(),
+                    value.sha1sums.len(), value.sha224sums.len(), 
+                    value.sha256sums.len(), value.sha384sums.len(),
+                    value.sha512sums.len(), value.b2sums.len());
+                return Err(BrokenPkgbuild::error(
+                    "lengths of sources and checksums mismatch"))
+            }
+            for (id, source) in value.sources.iter().enumerate(){
+                let mut source_with_checksum = SourceWithChecksum {
+                    source: (*source).into(),
+                    ..Default::default()
+                };
+                if let Some(cksum) = value.cksums.get(id) {
+                    source_with_checksum.cksum = if cksum == b"SKIP" {
+                        None
+                    } else {
+                        String::from_utf8_lossy(cksum).parse().ok()
+                    }
+                }
+                macro_rules! hash_sum_from_hex {
+                    ($sum:ident, $sums:ident) => {
+                        if let Some($sum) = value.$sums.get(id) {
+                            source_with_checksum.$sum = if $sum == b"SKIP" {
+                                None
+                            } else {
+                                FromHex::from_hex($sum).ok()
+                            }
+                        }                        
+                    };
+                }
+                hash_sum_from_hex!(md5sum, md5sums);
+                hash_sum_from_hex!(sha1sum, sha1sums);
+                hash_sum_from_hex!(sha224sum, sha224sums);
+                hash_sum_from_hex!(sha256sum, sha256sums);
+                hash_sum_from_hex!(sha384sum, sha384sums);
+                hash_sum_from_hex!(sha512sum, sha512sums);
+                hash_sum_from_hex!(b2sum, b2sums);
+                sources_with_checksums.push(source_with_checksum)
+            }
+        }
+        let provides = 
+            vec_items_try_from_vec_words(&value.provides)?;
+        Ok (Self {
+            sources_with_checksums,
+            depends: vec_items_from_vec_words(&value.depends),
+            makedepends: vec_items_from_vec_words(&value.makedepends),
+            checkdepends: vec_items_from_vec_words(&value.checkdepends),
+            optdepends: vec_items_from_vec_items(&value.optdepends),
+            conflicts: vec_items_from_vec_words(&value.conflicts),
+            provides,
+            replaces: vec_items_from_vec_words(&value.replaces),
+            extra: extra_from_pairs(&value.extra),
+        })
+    }
+}
+
+
+
+impl TryFrom<&PkgbuildParsing<'_>> for Pkgbuild {
+    type Error = Error;
+
+    fn try_from(value: &PkgbuildParsing) -> Result<Self> {
+        let pkgbase = string_from_slice_u8!(value.pkgbase);
+        let mut warnings = vec_string_from_vec_slice_u8(&value.warnings);
+        let mut pkgs = Vec::new();
+        for pkg in value.pkgs.iter() {
+            pkgs.push(Package::from_parsing(pkg, value.lenient, &mut warnings)
+                .map_err(|e|e.broken_pkgbase(&pkgbase))?)
+        }
+        let mut multiarch: MultiArch<PkgbuildArchSpecific>
+            = MultiArch::default();
+        for arch in value.arches.iter() {
+            let arch_value = 
+                PkgbuildArchSpecific::try_from(arch).map_err(|e|
+                    e.broken_context(format_args!("arch '{}'", 
+                        str_from_slice_u8!(arch.arch)))
+                    .broken_pkgbase(&pkgbase))?;
+            if arch.arch == b"any" {
+                multiarch.any = arch_value;
+                continue
+            }
+            let architecture = Architecture::from(arch.arch);
+            if let Some(existing) = multiarch.arches.get_mut(&architecture) {
+                if ! value.lenient {
+                    log::error!("Duplicated architecture {}",
+                        str_from_slice_u8!(arch.arch));
+                    return Err(BrokenPkgbuild::error(format!(
+                        "duplicated architecture '{}'",
+                        str_from_slice_u8!(arch.arch))).broken_pkgbase(&pkgbase))
+                }
+                log::warn!("Merged duplicated architecture {} of PKGBUILD {}",
+                    architecture, pkgbase);
+                warnings.push(format!("PKGBUILD '{}' has duplicated \
+                    architecture '{}', merged", pkgbase, architecture));
+                existing.append(arch_value);
+                continue
+            }
+            multiarch.arch_order.push(architecture.clone());
+            multiarch.arches.insert(architecture, arch_value);
+        }
+        if value.any_declared {
+            multiarch.arch_order.push(Architecture::Any)
+        }
+        Ok(Self {
+            pkgbase,
+            pkgs,
+            version: PlainVersion::from_raw(
+                value.epoch, value.pkgver, value.pkgrel),
+            pkgdesc: string_from_slice_u8!(value.pkgdesc),
+            url: string_from_slice_u8!(value.url),
+            license: vec_string_from_vec_slice_u8(&value.license),
+            install: string_from_slice_u8!(value.install),
+            changelog: string_from_slice_u8!(value.changelog),
+            validpgpkeys: vec_string_from_vec_slice_u8(&value.validgpgkeys),
+            noextract: vec_string_from_vec_slice_u8(&value.noextract),
+            groups: vec_string_from_vec_slice_u8(&value.groups),
+            multiarch,
+            backup: vec_string_from_vec_slice_u8(&value.backups),
+            options: (&value.options).into(),
+            pkgver_func: value.pkgver_func,
+            functions: value.functions.iter().map(|(name, body)|
+                (string_from_slice_u8!(*name), string_from_slice_u8!(*body)))
+                .collect(),
+            warnings,
+            extra: extra_from_pairs(&value.extra),
+        })
+    }
+}
+
+impl TryFrom<&PkgbuildsParsing<'_>> for Pkgbuilds {
+    type Error = Error;
+
+    fn try_from(value: &PkgbuildsParsing<'_>) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut brokens = Vec::new();
+        // Go through all entries so every broken PKGBUILD is reported
+        for entry in value.entries.iter() {
+            match entry.try_into() {
+                Ok(entry) => entries.push(entry),
+                Err(Error::BrokenPKGBUILDs(broken)) => brokens.extend(broken),
+                Err(e) => return Err(e),
+            }
+        }
+        if ! brokens.is_empty() {
+            return Err(Error::BrokenPKGBUILDs(brokens))
+        }
+        Ok(Self {entries, inputs: value.inputs.clone()})
+    }
+}
+
+/// The items added to and removed from an array, in their declared order, an
+/// item declared twice counts twice
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ArrayDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+impl<T> Default for ArrayDiff<T> {
+    fn default() -> Self {
+        Self { added: Vec::new(), removed: Vec::new() }
+    }
+}
+
+/// The items of `items` not in `from`, each item in `from` only matches once
+fn items_missing<T: PartialEq + Clone>(items: &[T], from: &[T]) -> Vec<T> {
+    let mut matched = vec![false; from.len()];
+    items.iter().filter(|item|
+        match (0..from.len()).find(|index|
+            ! matched[*index] && from[*index] == **item) 
+        {
+            Some(index) => { matched[index] = true; false },
+            None => true,
+        }).cloned().collect()
+}
+
+impl<T: PartialEq + Clone> ArrayDiff<T> {
+    /// Compare the items against those of a `base`
+    pub fn new(items: &[T], base: &[T]) -> Self {
+        Self { 
+            added: items_missing(items, base), 
+            removed: items_missing(base, items),
+        }
+    }
+}
+
+impl<T> ArrayDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A value that differs between two `Pkgbuild`s
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Change<T> {
+    /// The value in the base
+    pub base: T,
+    /// The value in the compared one
+    pub value: T,
+}
+
+/// A scalar field that differs between two `Pkgbuild`s or packages
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ScalarChange {
+    /// The field name, e.g. `pkgdesc`
+    pub name: &'static str,
+    pub change: Change<String>,
+}
+
+/// A checksum that differs for a source kept in both `Pkgbuild`s
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ChecksumChange {
+    /// The local file name of the source
+    pub source: String,
+    /// The checksum array, e.g. `sha256sums`
+    pub kind: &'static str,
+    /// The checksums in hex, or decimal for `cksums`, `None` for `SKIP`
+    pub change: Change<Option<String>>,
+}
+
+/// The changes of the arrays in an arch-independent or arch-specific section
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ArchSpecificDiff {
+    pub sources: ArrayDiff<Source>,
+    /// The checksum changes of sources in both, in the order of sources
+    pub checksums: Vec<ChecksumChange>,
+    pub depends: ArrayDiff<Dependency>,
+    pub makedepends: ArrayDiff<MakeDependency>,
+    pub checkdepends: ArrayDiff<CheckDependency>,
+    pub optdepends: ArrayDiff<OptionalDependency>,
+    pub conflicts: ArrayDiff<Conflict>,
+    pub provides: ArrayDiff<Provide>,
+    pub replaces: ArrayDiff<Replace>,
+}
+
+impl ArchSpecificDiff {
+    fn new(value: &PkgbuildArchSpecific, base: &PkgbuildArchSpecific) -> Self {
+        let sources: Vec<Source> = value.sources_with_checksums.iter()
+            .map(|source|source.source.clone()).collect();
+        let base_sources: Vec<Source> = base.sources_with_checksums.iter()
+            .map(|source|source.source.clone()).collect();
+        let mut matched = vec![false; base_sources.len()];
+        let mut checksums = Vec::new();
+        for source in value.sources_with_checksums.iter() {
+            let Some(index) = (0..base_sources.len()).find(|index|
+                ! matched[*index] && base_sources[*index] == source.source)
+            else {
+                continue
+            };
+            matched[index] = true;
+            let base = &base.sources_with_checksums[index];
+            macro_rules! compare_checksums {
+                ($($cksum: ident: $format: expr),+) => {$(
+                    if source.$cksum != base.$cksum {
+                        checksums.push(ChecksumChange {
+                            source: source.source.name.clone(),
+                            kind: concat!(stringify!($cksum), "s"),
+                            change: Change {
+                                base: base.$cksum.as_ref().map($format),
+                                value: source.$cksum.as_ref().map($format),
+                            }
+                        })
+                    }
+                )+};
+            }
+            compare_checksums!(cksum: |sum: &Cksum|sum.to_string(), 
+                md5sum: hex::encode, sha1sum: hex::encode, 
+                sha224sum: hex::encode, sha256sum: hex::encode, 
+                sha384sum: hex::encode, sha512sum: hex::encode, 
+                b2sum: hex::encode);
+        }
+        Self {
+            sources: ArrayDiff::new(&sources, &base_sources),
+            checksums,
+            depends: ArrayDiff::new(&value.depends, &base.depends),
+            makedepends: ArrayDiff::new(&value.makedepends, &base.makedepends),
+            checkdepends: 
+                ArrayDiff::new(&value.checkdepends, &base.checkdepends),
+            optdepends: ArrayDiff::new(&value.optdepends, &base.optdepends),
+            conflicts: ArrayDiff::new(&value.conflicts, &base.conflicts),
+            provides: ArrayDiff::new(&value.provides, &base.provides),
+            replaces: ArrayDiff::new(&value.replaces, &base.replaces),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty() && self.checksums.is_empty() 
+            && self.depends.is_empty() && self.makedepends.is_empty()
+            && self.checkdepends.is_empty() && self.optdepends.is_empty()
+            && self.conflicts.is_empty() && self.provides.is_empty()
+            && self.replaces.is_empty()
+    }
+}
+
+/// Compare the arch-specific sections of `arches`, with `value` and `base` 
+/// getting the sections to compare
+fn arch_specific_diffs<'a, I, V, B>(arches: I, value: V, base: B) 
+    -> BTreeMap<Architecture, ArchSpecificDiff>
+where
+    I: IntoIterator<Item = &'a Architecture>,
+    V: Fn(&Architecture) -> PkgbuildArchSpecific,
+    B: Fn(&Architecture) -> PkgbuildArchSpecific,
+{
+    arches.into_iter().filter_map(|arch| {
+        let diff = ArchSpecificDiff::new(&value(arch), &base(arch));
+        (! diff.is_empty()).then(||(arch.clone(), diff))
+    }).collect()
+}
+
+/// The changes of a split package kept in both `Pkgbuild`s, compared by their
+/// effective values, i.e. with those inherited from the `PKGBUILD`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PackageDiff {
+    pub pkgname: String,
+    pub scalars: Vec<ScalarChange>,
+    pub license: ArrayDiff<String>,
+    pub groups: ArrayDiff<String>,
+    pub backup: ArrayDiff<String>,
+    pub options: OptionsDiff,
+    pub arches: ArrayDiff<Architecture>,
+    /// The changes of the arch-independent arrays, `sources` and 
+    /// `makedepends` are always empty as packages do not have them
+    pub any: ArchSpecificDiff,
+    pub arch_specific: BTreeMap<Architecture, ArchSpecificDiff>,
+}
+
+impl PackageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.scalars.is_empty() && self.license.is_empty() 
+            && self.groups.is_empty() && self.backup.is_empty() 
+            && self.options.is_empty() && self.arches.is_empty() 
+            && self.any.is_empty() && self.arch_specific.is_empty()
+    }
+}
+
+/// The effective arrays of a package section, with those not overridden
+/// falling back to the same section of the `PKGBUILD`
+fn package_section(
+    package: &PackageArchSpecific, pkgbuild: &PkgbuildArchSpecific
+) -> PkgbuildArchSpecific
+{
+    fn array<T: Clone>(package: &Option<Vec<T>>, pkgbuild: &[T]) -> Vec<T> {
+        package.as_deref().unwrap_or(pkgbuild).to_vec()
+    }
+    PkgbuildArchSpecific {
+        depends: array(&package.depends, &pkgbuild.depends),
+        checkdepends: array(&package.checkdepends, &pkgbuild.checkdepends),
+        optdepends: array(&package.optdepends, &pkgbuild.optdepends),
+        conflicts: array(&package.conflicts, &pkgbuild.conflicts),
+        provides: array(&package.provides, &pkgbuild.provides),
+        replaces: array(&package.replaces, &pkgbuild.replaces),
+        ..Default::default()
+    }
+}
+
+/// The changes between two `Pkgbuild`s, see `Pkgbuild::diff()`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PkgbuildDiff {
+    /// The changed scalars other than the version, e.g. `pkgdesc`
+    pub scalars: Vec<ScalarChange>,
+    pub version: Option<Change<PlainVersion>>,
+    pub license: ArrayDiff<String>,
+    pub groups: ArrayDiff<String>,
+    pub backup: ArrayDiff<String>,
+    pub noextract: ArrayDiff<String>,
+    pub validpgpkeys: ArrayDiff<String>,
+    pub options: OptionsDiff,
+    pub arches: ArrayDiff<Architecture>,
+    pub any: ArchSpecificDiff,
+    pub arch_specific: BTreeMap<Architecture, ArchSpecificDiff>,
+    /// The names of the split packages added and removed
+    pub packages: ArrayDiff<String>,
+    /// The changes of the split packages in both, in the compared order
+    pub package_changes: Vec<PackageDiff>,
+}
+
+impl PkgbuildDiff {
+    pub fn is_empty(&self) -> bool {
+        self.scalars.is_empty() && self.version.is_none() 
+            && self.license.is_empty() && self.groups.is_empty() 
+            && self.backup.is_empty() && self.noextract.is_empty() 
+            && self.validpgpkeys.is_empty() && self.options.is_empty() 
+            && self.arches.is_empty() && self.any.is_empty() 
+            && self.arch_specific.is_empty() && self.packages.is_empty() 
+            && self.package_changes.is_empty()
+    }
+}
+
+/// The changed scalars of the `(name, value, base)` triples
+fn scalar_changes(scalars: &[(&'static str, &str, &str)]) -> Vec<ScalarChange> {
+    scalars.iter().filter(|(_, value, base)|value != base)
+        .map(|(name, value, base)|ScalarChange { 
+            name, 
+            change: Change { base: (*base).into(), value: (*value).into() }
+        }).collect()
+}
+
+#[cfg(feature = "format")]
+impl Display for ArchSpecificDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write_indented(f, "", None)
+    }
+}
+
+#[cfg(feature = "format")]
+impl ArchSpecificDiff {
+    /// Write a line for each changed array, keyed with the `_arch` suffix
+    fn write_indented(
+        &self, f: &mut Formatter<'_>, indent: &str, arch: Option<&Architecture>
+    ) -> std::fmt::Result 
+    {
+        let suffix = arch.map(|arch|format!("_{}", arch)).unwrap_or_default();
+        let sources = ArrayDiff {
+            added: self.sources.added.iter()
+                .map(Source::get_pkgbuild_source).collect(),
+            removed: self.sources.removed.iter()
+                .map(Source::get_pkgbuild_source).collect(),
+        };
+        sources.write_indented(f, indent, "source", &suffix)?;
+        for checksum in self.checksums.iter() {
+            writeln!(f, "{}{}{} of {}: {} -> {}", indent, checksum.kind, 
+                suffix, checksum.source, 
+                checksum.change.base.as_deref().unwrap_or("SKIP"),
+                checksum.change.value.as_deref().unwrap_or("SKIP"))?
+        }
+        self.depends.write_indented(f, indent, "depends", &suffix)?;
+        self.makedepends.write_indented(f, indent, "makedepends", &suffix)?;
+        self.checkdepends.write_indented(f, indent, "checkdepends", &suffix)?;
+        self.optdepends.write_indented(f, indent, "optdepends", &suffix)?;
+        self.conflicts.write_indented(f, indent, "conflicts", &suffix)?;
+        self.provides.write_indented(f, indent, "provides", &suffix)?;
+        self.replaces.write_indented(f, indent, "replaces", &suffix)
+    }
+}
+
+#[cfg(feature = "format")]
+impl<T: Display> ArrayDiff<T> {
+    /// Write a line each for the added and the removed items, if any
+    fn write_indented(
+        &self, f: &mut Formatter<'_>, indent: &str, name: &str, suffix: &str
+    ) -> std::fmt::Result 
+    {
+        for (items, action) in 
+            [(&self.added, "added"), (&self.removed, "removed")] 
+        {
+            if ! items.is_empty() {
+                write!(f, "{}{}{} {}: ", indent, name, suffix, action)?;
+                format_write_iter(f, items)?;
+                writeln!(f)?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for ScalarChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: '{}' -> '{}'", self.name, self.change.base, 
+            self.change.value)
+    }
+}
+
+#[cfg(feature = "format")]
+impl PackageDiff {
+    fn write_indented(&self, f: &mut Formatter<'_>, indent: &str) 
+        -> std::fmt::Result 
+    {
+        for scalar in self.scalars.iter() {
+            writeln!(f, "{}{}", indent, scalar)?
+        }
+        self.arches.write_indented(f, indent, "arch", "")?;
+        self.license.write_indented(f, indent, "license", "")?;
+        self.groups.write_indented(f, indent, "groups", "")?;
+        self.backup.write_indented(f, indent, "backup", "")?;
+        if ! self.options.is_empty() {
+            writeln!(f, "{}options: {}", indent, self.options)?
+        }
+        self.any.write_indented(f, indent, None)?;
+        for (arch, diff) in self.arch_specific.iter() {
+            diff.write_indented(f, indent, Some(arch))?
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for PackageDiff {
+    /// A line for each change, e.g. `depends added: foo, bar`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write_indented(f, "")
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for PkgbuildDiff {
+    /// A line for each change, e.g. `depends added: foo, bar`, followed by 
+    /// those of each changed package indented under `package [pkgname]:`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(version) = &self.version {
+            writeln!(f, "version: {} -> {}", version.base, version.value)?
+        }
+        for scalar in self.scalars.iter() {
+            writeln!(f, "{}", scalar)?
+        }
+        self.arches.write_indented(f, "", "arch", "")?;
+        self.license.write_indented(f, "", "license", "")?;
+        self.groups.write_indented(f, "", "groups", "")?;
+        self.backup.write_indented(f, "", "backup", "")?;
+        self.noextract.write_indented(f, "", "noextract", "")?;
+        self.validpgpkeys.write_indented(f, "", "validpgpkeys", "")?;
+        if ! self.options.is_empty() {
+            writeln!(f, "options: {}", self.options)?
+        }
+        self.any.write_indented(f, "", None)?;
+        for (arch, diff) in self.arch_specific.iter() {
+            diff.write_indented(f, "", Some(arch))?
+        }
+        self.packages.write_indented(f, "", "package", "")?;
+        for package in self.package_changes.iter() {
+            writeln!(f, "package {}:", package.pkgname)?;
+            package.write_indented(f, "  ")?
+        }
+        Ok(())
+    }
+}
+
+/// How serious a `ValidationIssue` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Severity {
+    /// Accepted by makepkg and pacman, but frowned upon, e.g. by the AUR
+    Warning,
+    /// Rejected by makepkg or pacman
+    Error,
+}
+
+#[cfg(feature = "format")]
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A rule of makepkg or pacman that a `PKGBUILD` or a package breaks, from
+/// `Pkgbuild::validate()` or `Package::validate()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// The package the field is set in, `None` for the `PKGBUILD` itself
+    pub pkgname: Option<String>,
+    /// The field, e.g. `pkgver`
+    pub field: &'static str,
+    /// The offending value, for an array the offending item
+    pub value: String,
+    /// What's wrong with the value, e.g. `is empty`
+    pub reason: &'static str,
+}
+
+#[cfg(feature = "format")]
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} '{}' ", self.severity, self.field, self.value)?;
+        if let Some(pkgname) = &self.pkgname {
+            write!(f, "of package '{}' ", pkgname)?
+        }
+        write!(f, "{}", self.reason)
+    }
+}
+
+/// A rule checking a value, returning the severity and the reason if broken
+type ValidationRule = fn(&str) -> Option<(Severity, &'static str)>;
+
+/// Check `pkgname` and `pkgbase` like pacman, and the AUR for uppercase 
+/// letters
+fn validate_name(name: &str) -> Option<(Severity, &'static str)> {
+    if name.is_empty() {
+        Some((Severity::Error, "is empty"))
+    } else if name.starts_with(['-', '.']) {
+        Some((Severity::Error, "starts with a hyphen or a dot"))
+    } else if ! name.chars().all(|c|c.is_ascii_alphanumeric() 
+        || matches!(c, '@' | '.' | '_' | '+' | '-')) 
+    {
+        Some((Severity::Error, 
+            "contains characters other than a-z, 0-9, @, ., _, + and -"))
+    } else if name.chars().any(|c|c.is_ascii_uppercase()) {
+        Some((Severity::Warning, "contains uppercase letters"))
+    } else {
+        None
+    }
+}
+
+fn validate_pkgver(pkgver: &str) -> Option<(Severity, &'static str)> {
+    if pkgver.is_empty() {
+        Some((Severity::Error, "is empty"))
+    } else if pkgver.contains(|c: char|matches!(c, '-' | ':' | '/') 
+        || c.is_whitespace()) 
+    {
+        Some((Severity::Error, "contains -, :, / or whitespace"))
+    } else if ! pkgver.is_ascii() {
+        Some((Severity::Error, "contains non-ASCII characters"))
+    } else {
+        None
+    }
+}
+
+fn validate_epoch(epoch: &str) -> Option<(Severity, &'static str)> {
+    (! epoch.chars().all(|c|c.is_ascii_digit())).then_some(
+        (Severity::Error, "is not a non-negative integer"))
+}
+
+fn validate_pkgrel(pkgrel: &str) -> Option<(Severity, &'static str)> {
+    let is_digits = |part: &str|
+        ! part.is_empty() && part.chars().all(|c|c.is_ascii_digit());
+    let valid = match pkgrel.split_once('.') {
+        Some((major, minor)) => is_digits(major) && is_digits(minor),
+        None => is_digits(pkgrel),
+    };
+    (! valid || pkgrel.chars().all(|c|matches!(c, '0' | '.'))).then_some(
+        (Severity::Error, "is not a positive decimal, e.g. 1 or 1.1"))
+}
+
+/// Check `backup`, `install` and `changelog`, which are all relative, the
+/// former to the package root, the latter to the `PKGBUILD`
+fn validate_relative_path(path: &str) -> Option<(Severity, &'static str)> {
+    path.starts_with('/').then_some((Severity::Error, "is an absolute path"))
+}
+
+/// The `ValidationIssue`s found so far in a `PKGBUILD` or a package
+struct Validation<'a> {
+    pkgname: Option<&'a str>,
+    issues: Vec<ValidationIssue>,
+}
+
+impl<'a> Validation<'a> {
+    fn new(pkgname: Option<&'a str>) -> Self {
+        Self { pkgname, issues: Vec::new() }
+    }
+
+    fn check(
+        &mut self, field: &'static str, value: &str, rule: ValidationRule
+    ) {
+        if let Some((severity, reason)) = rule(value) {
+            self.issues.push(ValidationIssue { 
+                severity, 
+                pkgname: self.pkgname.map(Into::into), 
+                field, 
+                value: value.into(), 
+                reason,
+            })
+        }
+    }
+
+    /// Check `backup`, `install` and `changelog`, empty `install` and 
+    /// `changelog` mean none
+    fn check_paths(
+        &mut self, backup: &[String], install: &str, changelog: &str
+    ) {
+        for backup in backup.iter() {
+            self.check("backup", backup, validate_relative_path)
+        }
+        self.check("install", install, validate_relative_path);
+        self.check("changelog", changelog, validate_relative_path)
+    }
+}
+
+/// What's wrong with an entry of `license`, from `spdx::check_expression()`
+#[cfg(feature = "spdx")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum LicenseProblem {
+    /// Not a valid SPDX license expression, e.g. with unbalanced parentheses,
+    /// this contains the reason
+    Malformed(&'static str),
+    /// An identifier on neither the SPDX license list nor the exception list
+    Unknown(String),
+    /// A deprecated SPDX identifier, or a license name Arch Linux used before 
+    /// migrating to SPDX, e.g. `GPL3` or `custom:name`, with the replacement 
+    /// if there's an unambiguous one
+    Deprecated {
+        identifier: String,
+        replacement: Option<String>,
+    },
+}
+
+#[cfg(all(feature = "spdx", feature = "format"))]
+impl Display for LicenseProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LicenseProblem::Malformed(reason) => write!(f, "{}", reason),
+            LicenseProblem::Unknown(identifier) => 
+                write!(f, "has unknown identifier '{}'", identifier),
+            LicenseProblem::Deprecated { identifier, replacement: None } => 
+                write!(f, "has deprecated identifier '{}'", identifier),
+            LicenseProblem::Deprecated { 
+                identifier, replacement: Some(replacement) 
+            } => write!(f, "has deprecated identifier '{}', use '{}' instead", 
+                identifier, replacement),
+        }
+    }
+}
+
+/// A problem of an entry of `license` of a `PKGBUILD` or a package, from
+/// `Pkgbuild::check_licenses()`
+#[cfg(feature = "spdx")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct LicenseIssue {
+    /// The package the `license` is set in, `None` for the `PKGBUILD` itself
+    pub pkgname: Option<String>,
+    /// The entry of `license`
+    pub license: String,
+    pub problem: LicenseProblem,
+}
+
+#[cfg(all(feature = "spdx", feature = "format"))]
+impl Display for LicenseIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "license '{}' ", self.license)?;
+        if let Some(pkgname) = &self.pkgname {
+            write!(f, "of package '{}' ", pkgname)?
+        }
+        write!(f, "{}", self.problem)
+    }
+}
+
+/// A `PKGBUILD` being rendered by `Pkgbuild::to_pkgbuild_string()`, values are
+/// all single-quoted so they need no escaping other than for single quotes
+#[cfg(feature = "format")]
+#[derive(Default)]
+struct PkgbuildText {
+    text: String,
+    /// Prepended to each line, non-empty inside package functions
+    indent: &'static str,
+}
+
+#[cfg(feature = "format")]
+impl PkgbuildText {
+    /// The key of an arch-specific array, e.g. `depends_x86_64`
+    fn key(key: &str, arch: Option<&Architecture>) -> String {
+        match arch {
+            Some(arch) => format!("{}_{}", key, arch),
+            None => key.into(),
+        }
+    }
+
+    fn quoted(value: &str) -> String {
+        let mut quoted = String::from("'");
+        quoted.push_str(&value.replace('\'', "'\\''"));
+        quoted.push('\'');
+        quoted
+    }
+
+    /// Write `key='value'`, skipped if the value is empty
+    fn scalar(&mut self, key: &str, value: &str) {
+        if ! value.is_empty() {
+            self.overridden_scalar(key, Some(value))
+        }
+    }
+
+    /// Write `key=('item' ...)`, skipped if there is no item
+    fn array<I, D>(&mut self, key: &str, items: I) 
+    where
+        I: IntoIterator<Item = D>,
+        D: Display
+    {
+        let items: Vec<String> = items.into_iter().map(|item|
+            Self::quoted(&item.to_string())).collect();
+        if ! items.is_empty() {
+            self.line(format_args!("{}=({})", key, items.join(" ")))
+        }
+    }
+
+    /// Write a scalar a package function sets, even if empty
+    fn overridden_scalar(&mut self, key: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.line(format_args!("{}={}", key, Self::quoted(value)))
+        }
+    }
+
+    /// Write an array a package function sets, `key=()` if empty
+    fn overridden_array<D: Display>(&mut self, key: &str, items: Option<&[D]>) {
+        match items {
+            Some([]) => self.line(format_args!("{}=()", key)),
+            Some(items) => self.array(key, items),
+            None => (),
+        }
+    }
+
+    fn line<D: Display>(&mut self, line: D) {
+        self.text.push_str(self.indent);
+        self.text.push_str(&line.to_string());
+        self.text.push('\n')
+    }
+
+    /// Write the sources, the checksums, and the other arrays of a section,
+    /// a checksum array is written if any source has such checksum, with 
+    /// `SKIP` for those without
+    fn pkgbuild_arch_specific(
+        &mut self, arch: Option<&Architecture>, values: &PkgbuildArchSpecific
+    ) {
+        let sources = &values.sources_with_checksums;
+        self.array(&Self::key("source", arch), sources.iter().map(|source|
+            source.source.get_pkgbuild_source()));
+        macro_rules! checksums {
+            ($($cksum: ident: $format: expr),+) => {$(
+                if sources.iter().any(|source|source.$cksum.is_some()) {
+                    self.array(&Self::key(concat!(stringify!($cksum), "s"), arch),
+                        sources.iter().map(|source|match &source.$cksum {
+                            Some(sum) => $format(sum),
+                            None => "SKIP".into(),
+                        }))
+                }
+            )+};
+        }
+        checksums!(cksum: |sum: &Cksum|sum.to_string(), 
+            md5sum: hex::encode, sha1sum: hex::encode, sha224sum: hex::encode,
+            sha256sum: hex::encode, sha384sum: hex::encode, 
+            sha512sum: hex::encode, b2sum: hex::encode);
+        self.array(&Self::key("depends", arch), &values.depends);
+        self.array(&Self::key("makedepends", arch), &values.makedepends);
+        self.array(&Self::key("checkdepends", arch), &values.checkdepends);
+        self.array(&Self::key("optdepends", arch), &values.optdepends);
+        self.array(&Self::key("provides", arch), &values.provides);
+        self.array(&Self::key("conflicts", arch), &values.conflicts);
+        self.array(&Self::key("replaces", arch), &values.replaces);
+    }
+
+    /// Write the arrays of a section a package function overrides
+    fn package_arch_specific(
+        &mut self, arch: Option<&Architecture>, values: &PackageArchSpecific
+    ) {
+        self.overridden_array(&Self::key("depends", arch), 
+            values.depends.as_deref());
+        self.overridden_array(&Self::key("checkdepends", arch), 
+            values.checkdepends.as_deref());
+        self.overridden_array(&Self::key("optdepends", arch), 
+            values.optdepends.as_deref());
+        self.overridden_array(&Self::key("provides", arch), 
+            values.provides.as_deref());
+        self.overridden_array(&Self::key("conflicts", arch), 
+            values.conflicts.as_deref());
+        self.overridden_array(&Self::key("replaces", arch), 
+            values.replaces.as_deref());
+    }
+
+    /// Write a function with its body from `Pkgbuild::functions` if dumped,
+    /// otherwise with a placeholder comment and the `lines` known to be in it
+    fn function(&mut self, name: &str, body: Option<&String>, lines: &str) {
+        match body {
+            Some(body) => self.text.push_str(body),
+            None => {
+                self.text.push_str(name);
+                self.text.push_str(
+                    "() {\n  # The original body could not be reconstructed\n");
+                if lines.is_empty() {
+                    self.text.push_str("  :\n")
+                } else {
+                    self.text.push_str(lines)
+                }
+                self.text.push('}')
+            }
+        }
+        self.text.push_str("\n\n")
+    }
+}
+
+impl Pkgbuild {
+    pkg_iter_all_arch!(self, sources_with_checksums, SourceWithChecksum);
+    pkg_iter_all_arch!(self, depends, Dependency);
+    pkg_iter_all_arch!(self, makedepends, MakeDependency);
+    pkg_iter_all_arch!(self, checkdepends, CheckDependency);
+    pkg_iter_all_arch!(self, optdepends, OptionalDependency);
+    pkg_iter_all_arch!(self, conflicts, Conflict);
+    pkg_iter_all_arch!(self, provides, Provide);
+    pkg_iter_all_arch!(self, replaces, Replace);
+
+    /// Get `depends()` sorted and deduplicated, e.g. a dependency declared in
+    /// both `depends` and `depends_x86_64` only once
+    pub fn depends_deduped(&self, arch: Option<&Architecture>) 
+        -> Vec<Dependency> 
+    {
+        sorted_deduped(self.depends(arch))
+    }
+
+    /// Like `==`, but ignoring the order of arrays whose order does not matter
+    /// to makepkg, e.g. `license`, `groups`, `depends` and `provides`, and of
+    /// architectures. Sources with their checksums and the split packages 
+    /// still have to be in the same order, while `warnings` are ignored as
+    /// they are about the parsing rather than the `PKGBUILD`.
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        self.pkgbase == other.pkgbase
+            && self.pkgs.len() == other.pkgs.len()
+            && self.pkgs.iter().zip(other.pkgs.iter()).all(|(some, other)|
+                some.semantically_eq(other))
+            && self.version == other.version
+            && self.pkgdesc == other.pkgdesc
+            && self.url == other.url
+            && same_items(&self.license, &other.license)
+            && self.install == other.install
+            && self.changelog == other.changelog
+            && same_items(&self.validpgpkeys, &other.validpgpkeys)
+            && same_items(&self.noextract, &other.noextract)
+            && same_items(&self.groups, &other.groups)
+            && self.multiarch.semantically_eq_by(&other.multiarch,
+                PkgbuildArchSpecific::semantically_eq)
+            && same_items(&self.backup, &other.backup)
+            && self.options.semantically_eq(&other.options)
+            && self.pkgver_func == other.pkgver_func
+            && self.functions == other.functions
+            && self.extra == other.extra
+    }
+
+    /// Bump the version for an update to `new_pkgver` with the `pkgrel` reset,
+    /// or for a rebuild with the `pkgrel` incremented if `None`, the `epoch` 
+    /// is always kept, see `PlainVersion::set_pkgver()` and `bump_pkgrel()`
+    pub fn bump(&mut self, new_pkgver: Option<&str>) {
+        match new_pkgver {
+            Some(pkgver) => self.version.set_pkgver(pkgver),
+            None => self.version.bump_pkgrel(),
+        }
+    }
+
+    /// Get the sorted, deduplicated schemes of sources with unknown protocols
+    /// across all architectures, e.g. `s3` for `s3://bucket/file`
+    pub fn unknown_source_schemes(&self) -> Vec<&str> {
+        let mut schemes: Vec<&str> = self.multiarch.arches.values()
+            .chain([&self.multiarch.any])
+            .flat_map(|arch_specific|arch_specific.sources_with_checksums.iter())
+            .filter_map(|source|match &source.source.protocol {
+                SourceProtocol::Unknown { scheme } => Some(scheme.as_str()),
+                _ => None,
+            }).collect();
+        schemes.sort_unstable();
+        schemes.dedup();
+        schemes
+    }
+
+    /// Get the split package with the exact, case-sensitive `pkgname`
+    pub fn package<S: AsRef<str>>(&self, name: S) -> Option<&Package> {
+        let name = name.as_ref();
+        self.pkgs.iter().find(|pkg|pkg.pkgname == name)
+    }
+
+    /// Get the split package with the exact, case-sensitive `pkgname` for
+    /// modification
+    pub fn package_mut<S: AsRef<str>>(&mut self, name: S)
+        -> Option<&mut Package>
+    {
+        let name = name.as_ref();
+        self.pkgs.iter_mut().find(|pkg|pkg.pkgname == name)
+    }
+
+    /// Iterate over the `pkgname`s of the split packages, in declared order
+    pub fn pkgnames(&self) -> impl Iterator<Item = &str> {
+        self.pkgs.iter().map(|pkg|pkg.pkgname.as_str())
+    }
+
+    /// Get the full version `epoch:pkgver-pkgrel` shared by all packages, see
+    /// `PlainVersion::to_version_string()`
+    pub fn full_version(&self) -> String {
+        self.version.to_version_string()
+    }
+
+    /// Get the names of the package files makepkg would create when building
+    /// on `arch`, e.g. `foo-2:1.2.3-2-x86_64.pkg.tar.zst` for `ext` 
+    /// `.pkg.tar.zst` (the leading `.` is optional), in declared order. 
+    /// 
+    /// Packages with `arch=(any)` get `any` instead of `arch`, and those whose
+    /// `arch`, either their own or inherited, does not include `arch` are 
+    /// skipped, as makepkg won't create them.
+    pub fn package_file_names(&self, arch: &Architecture, ext: &str) 
+        -> Vec<String> 
+    {
+        let ext = ext.strip_prefix('.').unwrap_or(ext);
+        self.pkgs.iter().filter(|pkg|pkg.supports_arch(arch))
+            .map(|pkg|format!("{}-{}.{}", pkg.full_name(self), 
+                if pkg.multiarch.is_any_declared() {
+                    &Architecture::Any
+                } else {
+                    arch
+                }.as_ref(), ext))
+            .collect()
+    }
+
+    /// Resolve all split packages with the makepkg inheritance rules, see
+    /// `resolve_package()`
+    pub fn resolved_packages(&self, arch: Option<&Architecture>) 
+        -> Vec<ResolvedPackage> 
+    {
+        self.pkgs.iter().map(|pkg|self.resolve(pkg, arch)).collect()
+    }
+
+    /// Resolve the split package with the exact `pkgname` with the makepkg
+    /// inheritance rules. The arch-specific arrays are folded for `arch`,
+    /// or for all architectures if `None`, like `depends()`.
+    pub fn resolve_package<S: AsRef<str>>(
+        &self, name: S, arch: Option<&Architecture>
+    ) -> Option<ResolvedPackage> 
+    {
+        self.package(name).map(|pkg|self.resolve(pkg, arch))
+    }
+
+    fn resolve(&self, pkg: &Package, arch: Option<&Architecture>) 
+        -> ResolvedPackage 
+    {
+        fn scalar(package: &Option<String>, pkgbuild: &str) -> String {
+            package.as_deref().unwrap_or(pkgbuild).into()
+        }
+        fn array<T: Clone>(package: &Option<Vec<T>>, pkgbuild: &[T]) -> Vec<T> {
+            package.as_deref().unwrap_or(pkgbuild).to_vec()
+        }
+        let empty = PkgbuildArchSpecific::default();
+        // Each arch-specific array falls back to the same one of the PKGBUILD
+        let arch_parts: Vec<(&PackageArchSpecific, &PkgbuildArchSpecific)> = 
+            [(&pkg.multiarch.any, &self.multiarch.any)].into_iter()
+            .chain(pkg.multiarch.iter_arches().filter(
+                |(pkg_arch, _)|arch.is_none_or(|arch|arch == *pkg_arch))
+                .map(|(pkg_arch, pkg_specific)|(pkg_specific, 
+                    self.multiarch.arches.get(pkg_arch).unwrap_or(&empty))))
+            .collect();
+        macro_rules! fold_arches {
+            ($var:ident) => {
+                arch_parts.iter().flat_map(|(pkg_specific, pkgbuild_specific)|
+                    array(&pkg_specific.$var, &pkgbuild_specific.$var))
+                    .collect()
+            };
+        }
+        ResolvedPackage {
+            pkgname: pkg.pkgname.clone(),
+            pkgdesc: scalar(&pkg.pkgdesc, &self.pkgdesc),
+            url: scalar(&pkg.url, &self.url),
+            license: array(&pkg.license, &self.license),
+            groups: array(&pkg.groups, &self.groups),
+            backup: array(&pkg.backup, &self.backup),
+            options: pkg.effective_options(self),
+            install: scalar(&pkg.install, &self.install),
+            changelog: scalar(&pkg.changelog, &self.changelog),
+            arches: pkg.arches(),
+            checkdepends: fold_arches!(checkdepends),
+            depends: fold_arches!(depends),
+            optdepends: fold_arches!(optdepends),
+            provides: fold_arches!(provides),
+            conflicts: fold_arches!(conflicts),
+            replaces: fold_arches!(replaces),
+        }
+    }
+
+    /// Compare against a `base`, e.g. the `PKGBUILD` before an update, and
+    /// list every changed scalar, added and removed array items, and the 
+    /// changes of each split package kept in both
+    pub fn diff(&self, base: &Pkgbuild) -> PkgbuildDiff {
+        let empty = PkgbuildArchSpecific::default();
+        let empty_package = PackageArchSpecific::default();
+        let package_changes = self.pkgs.iter().filter_map(|pkg| {
+            let base_pkg = base.package(&pkg.pkgname)?;
+            let value = self.resolve(pkg, None);
+            let resolved_base = base.resolve(base_pkg, None);
+            let diff = PackageDiff {
+                pkgname: pkg.pkgname.clone(),
+                scalars: scalar_changes(&[
+                    ("pkgdesc", &value.pkgdesc, &resolved_base.pkgdesc),
+                    ("url", &value.url, &resolved_base.url),
+                    ("install", &value.install, &resolved_base.install),
+                    ("changelog", &value.changelog, &resolved_base.changelog),
+                ]),
+                license: ArrayDiff::new(&value.license, &resolved_base.license),
+                groups: ArrayDiff::new(&value.groups, &resolved_base.groups),
+                backup: ArrayDiff::new(&value.backup, &resolved_base.backup),
+                options: value.options.diff(&resolved_base.options),
+                arches: ArrayDiff::new(&value.arches, &resolved_base.arches),
+                any: ArchSpecificDiff::new(
+                    &package_section(&pkg.multiarch.any, &self.multiarch.any),
+                    &package_section(&base_pkg.multiarch.any, 
+                        &base.multiarch.any)),
+                arch_specific: arch_specific_diffs(
+                    pkg.multiarch.arches.keys().chain(
+                        base_pkg.multiarch.arches.keys()),
+                    |arch|package_section(
+                        pkg.multiarch.get(arch).unwrap_or(&empty_package),
+                        self.multiarch.get(arch).unwrap_or(&empty)),
+                    |arch|package_section(
+                        base_pkg.multiarch.get(arch).unwrap_or(&empty_package),
+                        base.multiarch.get(arch).unwrap_or(&empty))),
+            };
+            (! diff.is_empty()).then_some(diff)
+        }).collect();
+        PkgbuildDiff {
+            scalars: scalar_changes(&[
+                ("pkgbase", &self.pkgbase, &base.pkgbase),
+                ("pkgdesc", &self.pkgdesc, &base.pkgdesc),
+                ("url", &self.url, &base.url),
+                ("install", &self.install, &base.install),
+                ("changelog", &self.changelog, &base.changelog),
+            ]),
+            version: (self.version != base.version).then(||Change {
+                base: base.version.clone(), value: self.version.clone() }),
+            license: ArrayDiff::new(&self.license, &base.license),
+            groups: ArrayDiff::new(&self.groups, &base.groups),
+            backup: ArrayDiff::new(&self.backup, &base.backup),
+            noextract: ArrayDiff::new(&self.noextract, &base.noextract),
+            validpgpkeys: 
+                ArrayDiff::new(&self.validpgpkeys, &base.validpgpkeys),
+            options: self.options.diff(&base.options),
+            arches: ArrayDiff::new(&self.arches(), &base.arches()),
+            any: 
+                ArchSpecificDiff::new(&self.multiarch.any, &base.multiarch.any),
+            arch_specific: arch_specific_diffs(
+                self.multiarch.arches.keys()
+                    .chain(base.multiarch.arches.keys()),
+                |arch|self.multiarch.get(arch).cloned().unwrap_or_default(),
+                |arch|base.multiarch.get(arch).cloned().unwrap_or_default()),
+            packages: ArrayDiff::new(
+                &self.pkgnames().map(String::from).collect::<Vec<_>>(), 
+                &base.pkgnames().map(String::from).collect::<Vec<_>>()),
+            package_changes,
+        }
+    }
+
+    /// Get the architectures declared in `arch` in declared order,
+    /// `[Architecture::Any]` for `arch=(any)`, empty if `arch` is missing
+    pub fn arches(&self) -> Vec<Architecture> {
+        self.multiarch.declared_arches()
+    }
+
+    /// Whether this is `any`, i.e. has no architecture-specific sections,
+    /// either declared as `arch=(any)` or not declared at all
+    pub fn is_arch_independent(&self) -> bool {
+        self.multiarch.arches.is_empty()
+    }
+
+    /// Get a view of this for a single architecture, in which the 
+    /// arch-specific arrays are the arch-independent ones plus those of the
+    /// architecture
+    pub fn for_arch<'a>(&'a self, arch: &Architecture) -> PkgbuildForArch<'a> {
+        PkgbuildForArch { pkgbuild: self, any: &self.multiarch.any, 
+            arch: self.multiarch.arches.get(arch) }
+    }
+
+    /// Whether this could be built for the architecture, a `PKGBUILD` without
+    /// architecture-specific sections is `any`
+    pub fn supports_arch(&self, arch: &Architecture) -> bool {
+        self.is_arch_independent() 
+            || self.multiarch.arches.contains_key(arch)
+    }
+
+    /// Check the rules of makepkg and pacman the parser does not, e.g. the
+    /// characters allowed in `pkgver`, for this and all of its packages. An 
+    /// empty result means this is valid.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut validation = Validation::new(None);
+        validation.check("pkgbase", &self.pkgbase, validate_name);
+        validation.check("epoch", &self.version.epoch, validate_epoch);
+        validation.check("pkgver", &self.version.pkgver, validate_pkgver);
+        validation.check("pkgrel", &self.version.pkgrel, validate_pkgrel);
+        validation.check_paths(&self.backup, &self.install, &self.changelog);
+        let mut issues = validation.issues;
+        for pkg in self.pkgs.iter() {
+            issues.append(&mut pkg.validate())
+        }
+        issues
+    }
+
+    /// Check the entries of `license` of this and those overridden by its 
+    /// packages as SPDX license expressions, see `spdx::check_expression()`.
+    /// An empty result means all of them are valid.
+    #[cfg(feature = "spdx")]
+    pub fn check_licenses(&self) -> Vec<LicenseIssue> {
+        let pkgs = self.pkgs.iter().filter_map(|pkg|pkg.license.as_ref()
+            .map(|license|(Some(&pkg.pkgname), license)));
+        std::iter::once((None, &self.license)).chain(pkgs)
+            .flat_map(|(pkgname, license)|license.iter().flat_map(
+                move |license|spdx::check_expression(license).into_iter()
+                    .map(move |problem|LicenseIssue { 
+                        pkgname: pkgname.cloned(), 
+                        license: license.clone(), 
+                        problem 
+                    })))
+            .collect()
+    }
+
+    /// Get the path of `install` relative to `base_dir`, the directory of 
+    /// this `PKGBUILD`, `None` if it's empty. Packages could override it, see
+    /// `Package::install_path()`.
+    pub fn install_path<P: AsRef<Path>>(&self, base_dir: P) -> Option<PathBuf> {
+        referenced_file_path(base_dir.as_ref(), 
+            Some(self.install.as_str()).filter(|install|! install.is_empty()))
+    }
+
+    /// Read `install` in `base_dir`, `None` if it's empty, 
+    /// `Error::ReferencedFileMissing` if the file does not exist
+    pub fn read_install<P: AsRef<Path>>(&self, base_dir: P) 
+        -> Result<Option<String>> 
+    {
+        read_referenced_file("install", base_dir.as_ref(), 
+            Some(self.install.as_str()).filter(|install|! install.is_empty()))
+    }
+
+    /// Get the path of `changelog`, like `install_path()`
+    pub fn changelog_path<P: AsRef<Path>>(&self, base_dir: P) 
+        -> Option<PathBuf> 
+    {
+        referenced_file_path(base_dir.as_ref(), Some(self.changelog.as_str())
+            .filter(|changelog|! changelog.is_empty()))
+    }
+
+    /// Read `changelog`, like `read_install()`
+    pub fn read_changelog<P: AsRef<Path>>(&self, base_dir: P) 
+        -> Result<Option<String>> 
+    {
+        read_referenced_file("changelog", base_dir.as_ref(), 
+            Some(self.changelog.as_str())
+                .filter(|changelog|! changelog.is_empty()))
+    }
+
+    /// Whether this declares more than one package in `pkgname`
+    pub fn is_split(&self) -> bool {
+        self.pkgs.len() > 1
+    }
+
+    /// Whether this is a devel package, i.e. it has a `pkgver()` function and
+    /// at least one VCS source, so its actual version is only known after 
+    /// the sources are fetched
+    pub fn is_devel(&self) -> bool {
+        self.pkgver_func && self.sources_with_checksums(None).iter().any(
+            |source|source.source.protocol.is_vcs())
+    }
+
+    /// Decide whether this should be built, given the version currently 
+    /// published, e.g. in a repo, `None` if it's not published yet
+    #[cfg(feature = "vercmp")]
+    pub fn rebuild_decision(
+        &self, published: Option<&PlainVersion>, policy: &RebuildPolicy
+    ) -> RebuildDecision 
+    {
+        let local = self.version.clone();
+        let Some(published) = published else {
+            return RebuildDecision::Build { local, published: None, 
+                reason: RebuildReason::NotPublished }
+        };
+        // The pkgver in PKGBUILD is stale for devel packages
+        if policy.always_rebuild_devel && self.is_devel() {
+            return RebuildDecision::Build { local, 
+                published: Some(published.clone()), reason: RebuildReason::Devel }
+        }
+        let published = published.clone();
+        match local.cmp(&published) {
+            Ordering::Greater => RebuildDecision::Build { local, 
+                published: Some(published), reason: RebuildReason::Newer },
+            Ordering::Equal => if policy.skip_equal {
+                RebuildDecision::Skip { local, published }
+            } else {
+                RebuildDecision::Build { local, published: Some(published), 
+                    reason: RebuildReason::Equal }
+            },
+            Ordering::Less => if policy.error_on_downgrade {
+                RebuildDecision::Downgrade { local, published }
+            } else {
+                RebuildDecision::Skip { local, published }
+            },
+        }
+    }
+
+    /// Merge an overlay `Pkgbuild` of the same `pkgbase` over this one, e.g.
+    /// a site-specific fragment over a base `PKGBUILD`. Scalars and arrays are
+    /// merged according to the policy, packages are matched by `pkgname`, and
+    /// those only in the overlay are appended.
+    /// 
+    /// A different `pkgbase`, or a different `epoch` or `pkgver` when the
+    /// policy does not allow that, is reported as a conflict. The merged 
+    /// result is then checked by `validate()`, and its issues of 
+    /// `Severity::Error` are reported in the conflict instead, e.g. an overlay
+    /// `pkgrel` that's not a number. Those of `Severity::Warning` are accepted
+    /// by makepkg and pacman, so they do not fail the merge.
+    pub fn merge(&self, overlay: &Pkgbuild, policy: &MergePolicy) 
+        -> std::result::Result<Pkgbuild, MergeConflict>
+    {
+        let mut fields = Vec::new();
+        let mut check = |path: &str, base: &str, overlay: &str| 
+            if ! base.is_empty() && ! overlay.is_empty() && base != overlay {
+                fields.push(FieldConflict { path: path.into(), 
+                    base: base.into(), overlay: overlay.into() })
+            };
+        check("pkgbase", &self.pkgbase, &overlay.pkgbase);
+        if ! policy.allow_version_change {
+            check("version.epoch", 
+                &self.version.epoch, &overlay.version.epoch);
+            check("version.pkgver", 
+                &self.version.pkgver, &overlay.version.pkgver);
+        }
+        if ! fields.is_empty() {
+            let conflict = MergeConflict { fields, issues: Vec::new() };
+            log::error!("Failed to merge overlay into PKGBUILD '{}': {}", 
+                self.pkgbase, conflict);
+            return Err(conflict)
+        }
+        let mut pkgs: Vec<Package> = self.pkgs.iter().map(|base|
+            match overlay.pkgs.iter().find(|pkg|pkg.pkgname == base.pkgname) {
+                Some(overlay) => base.merge(overlay, policy),
+                None => base.clone(),
+            }).collect();
+        for pkg in overlay.pkgs.iter() {
+            if ! self.pkgs.iter().any(|base|base.pkgname == pkg.pkgname) {
+                pkgs.push(pkg.clone())
+            }
+        }
+        let merged = Pkgbuild {
+            pkgbase: policy.scalar(&self.pkgbase, &overlay.pkgbase),
+            pkgs,
+            version: PlainVersion {
+                epoch: policy.scalar(
+                    &self.version.epoch, &overlay.version.epoch),
+                pkgver: policy.scalar(
+                    &self.version.pkgver, &overlay.version.pkgver),
+                pkgrel: policy.scalar(
+                    &self.version.pkgrel, &overlay.version.pkgrel),
+            },
+            pkgdesc: policy.scalar(&self.pkgdesc, &overlay.pkgdesc),
+            url: policy.scalar(&self.url, &overlay.url),
+            license: policy.array("license", &self.license, &overlay.license),
+            install: policy.scalar(&self.install, &overlay.install),
+            changelog: policy.scalar(&self.changelog, &overlay.changelog),
+            validpgpkeys: policy.array("validpgpkeys", 
+                &self.validpgpkeys, &overlay.validpgpkeys),
+            noextract: policy.array(
+                "noextract", &self.noextract, &overlay.noextract),
+            groups: policy.array("groups", &self.groups, &overlay.groups),
+            multiarch: merge_multiarch(
+                &self.multiarch, &overlay.multiarch, policy),
+            backup: policy.array("backup", &self.backup, &overlay.backup),
+            options: policy.options(&self.options, &overlay.options),
+            pkgver_func: self.pkgver_func || overlay.pkgver_func,
+            functions: policy.functions(&self.functions, &overlay.functions),
+            warnings: self.warnings.iter().chain(overlay.warnings.iter())
+                .cloned().collect(),
+            extra: policy.extra(&self.extra, &overlay.extra),
+        };
+        let issues: Vec<_> = merged.validate().into_iter().filter(
+            |issue|issue.severity == Severity::Error).collect();
+        if ! issues.is_empty() {
+            let conflict = MergeConflict { fields: Vec::new(), issues };
+            log::error!("Merged PKGBUILD '{}' is invalid: {}", 
+                self.pkgbase, conflict);
+            return Err(conflict)
+        }
+        Ok(merged)
+    }
+
+    /// Parse the `url` field, `None` if it's empty
+    #[cfg(feature = "url")]
+    pub fn url_parsed(&self) 
+        -> Option<std::result::Result<url::Url, url::ParseError>> 
+    {
+        url_parsed(&self.url)
+    }
+
+    /// Get the host of the `url` field, `None` if it's empty, invalid or has 
+    /// no host
+    #[cfg(feature = "url")]
+    pub fn upstream_host(&self) -> Option<String> {
+        url_host(&self.url)
+    }
+
+    /// Render the declarative parts back into a `PKGBUILD`, e.g. after bumping
+    /// the version in code. Functions are written from `functions` if their
+    /// bodies were dumped, otherwise `pkgver()` and the package functions are
+    /// written as placeholders only keeping the values they set, and other
+    /// functions are lost, so the result is only good for parsing.
+    #[cfg(feature = "format")]
+    pub fn to_pkgbuild_string(&self) -> String {
+        let mut text = PkgbuildText::default();
+        text.scalar("pkgbase", &self.pkgbase);
+        text.array("pkgname", self.pkgs.iter().map(|pkg|&pkg.pkgname));
+        text.scalar("pkgver", &self.version.pkgver);
+        text.scalar("pkgrel", &self.version.pkgrel);
+        text.scalar("epoch", &self.version.epoch);
+        text.scalar("pkgdesc", &self.pkgdesc);
+        text.array("arch", self.arches());
+        text.scalar("url", &self.url);
+        text.array("license", &self.license);
+        text.array("groups", &self.groups);
+        text.array("backup", &self.backup);
+        text.array("options", self.options.to_strings());
+        text.scalar("install", &self.install);
+        text.scalar("changelog", &self.changelog);
+        text.array("noextract", &self.noextract);
+        text.array("validpgpkeys", &self.validpgpkeys);
+        for (arch, values) in self.multiarch.iter() {
+            text.pkgbuild_arch_specific(arch, values)
+        }
+        for (key, values) in self.extra.iter() {
+            // Only those still valid as variable names
+            if key.starts_with(|c: char|c.is_ascii_digit()) || ! key.chars()
+                .all(|c|c.is_ascii_alphanumeric() || c == '_') 
+            {
+                continue
+            }
+            text.array(key, values)
+        }
+        text.text.push('\n');
+        for name in ["pkgver", "prepare", "build", "check"] {
+            let body = self.functions.get(name);
+            if body.is_some() || (name == "pkgver" && self.pkgver_func) {
+                let pkgver = format!("  echo {}\n", 
+                    PkgbuildText::quoted(&self.version.pkgver));
+                text.function(name, body, &pkgver)
+            }
+        }
+        for pkg in self.pkgs.iter() {
+            let split_name = format!("package_{}", pkg.pkgname);
+            let name = if self.pkgs.len() == 1 
+                && ! self.functions.contains_key(&split_name) 
+            {
+                "package"
+            } else {
+                &split_name
+            };
+            let mut lines = PkgbuildText { text: String::new(), indent: "  " };
+            lines.overridden_scalar("pkgdesc", pkg.pkgdesc.as_deref());
+            lines.overridden_scalar("url", pkg.url.as_deref());
+            if pkg.arches() != self.arches() {
+                lines.array("arch", pkg.arches())
+            }
+            lines.overridden_array("license", pkg.license.as_deref());
+            lines.overridden_array("groups", pkg.groups.as_deref());
+            lines.overridden_array("backup", pkg.backup.as_deref());
+            lines.overridden_array("options", 
+                pkg.options.as_ref().map(Options::to_strings).as_deref());
+            lines.overridden_scalar("install", pkg.install.as_deref());
+            lines.overridden_scalar("changelog", pkg.changelog.as_deref());
+            for (arch, values) in pkg.multiarch.iter() {
+                lines.package_arch_specific(arch, values)
+            }
+            text.function(name, self.functions.get(name), &lines.text)
+        }
+        text.text
+    }
+
+    /// Get a result similar to `makepkg --printsrcinfo`, useful for formatting
+    #[cfg(feature = "srcinfo")]
+    pub fn srcinfo<'a>(&'a self) -> Srcinfo<'a> {
+        Srcinfo { pkgbuild: self, max_arches: None }
+    }
+
+    // /// Get a flattened list of options, note it would be impossible to go back
+    // /// to the original order of options from only the result options.
+    // pub fn options(&self) -> Options {
+    //     (&self.options).into()
+    // }
+}
+
+/// The section of a builder's `MultiArch` for `arch`, the arch-independent one
+/// for `None` and `Some(Architecture::Any)`
+fn multiarch_section<T: Default>(
+    multiarch: &mut MultiArch<T>, arch: Option<Architecture>
+) -> &mut T 
+{
+    match arch {
+        None | Some(Architecture::Any) => &mut multiarch.any,
+        Some(arch) => multiarch.arches.entry(arch).or_default(),
+    }
+}
+
+/// Set the declared architectures of a built `MultiArch` and add empty 
+/// sections for those without values, like the parser does, failing if `any`
+/// is mixed with others, or a section has an architecture not declared
+fn multiarch_declare<T: Default>(
+    multiarch: &mut MultiArch<T>, arches: &[Architecture]
+) -> Result<()>
+{
+    if arches.len() > 1 && arches.contains(&Architecture::Any) {
+        return Err(BrokenPkgbuild::error(
+            "architecture 'any' mixed with other architectures"))
+    }
+    if let Some(arch) = multiarch.arches.keys().find(|arch|
+        ! arches.contains(arch))
+    {
+        return Err(BrokenPkgbuild::error(format!(
+            "values for architecture '{}' not declared in arch", arch)))
+    }
+    for arch in arches.iter() {
+        if *arch != Architecture::Any {
+            multiarch.arches.entry(arch.clone()).or_default();
+        }
+    }
+    multiarch.arch_order = arches.to_vec();
+    Ok(())
+}
+
+/// Generate `add_[field]()` and `add_[field]_for_arch()` for an array in the
+/// arch-specific sections of `PkgbuildBuilder`
+macro_rules! pkgbuild_builder_add {
+    ($field: ident, $item: ty, $add: ident, $add_for_arch: ident) => {
+        #[doc = concat!("Add an item to `", stringify!($field), "`")]
+        pub fn $add<I: Into<$item>>(&mut self, item: I) -> &mut Self {
+            self.$add_for_arch(None, item)
+        }
+
+        #[doc = concat!("Add an item to `", stringify!($field), "_[arch]`, ",
+            "or to the arch-independent one if `arch` is `None`")]
+        pub fn $add_for_arch<I: Into<$item>>(
+            &mut self, arch: Option<Architecture>, item: I
+        ) -> &mut Self 
+        {
+            multiarch_section(&mut self.pkgbuild.multiarch, arch)
+                .$field.push(item.into());
+            self
+        }
+    };
+}
+
+/// A builder to construct a `Pkgbuild` in code instead of parsing one, e.g. to
+/// write its `.SRCINFO`. Architectures must be declared with `add_arch()`
+/// before `build()`, a `PKGBUILD` without packages gets one named `pkgbase`.
+#[derive(Debug, Clone, Default)]
+pub struct PkgbuildBuilder {
+    pkgbuild: Pkgbuild,
+    arches: Vec<Architecture>,
+    packages: Vec<PackageBuilder>,
+}
+
+impl PkgbuildBuilder {
+    /// Create a new `PkgbuildBuilder` with everything empty
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the `pkgbase`, which must not be empty
+    pub fn pkgbase<S: Into<String>>(&mut self, pkgbase: S) -> &mut Self {
+        self.pkgbuild.pkgbase = pkgbase.into();
+        self
+    }
+
+    /// Set the version, e.g. from `"1:2.3-4"`, `pkgver` must not be empty nor
+    /// contain `-` or `:`, and `pkgrel` must not be empty
+    pub fn version<V: Into<PlainVersion>>(&mut self, version: V) -> &mut Self {
+        self.pkgbuild.version = version.into();
+        self
+    }
+
+    /// Set the `pkgdesc`
+    pub fn pkgdesc<S: Into<String>>(&mut self, pkgdesc: S) -> &mut Self {
+        self.pkgbuild.pkgdesc = pkgdesc.into();
+        self
+    }
+
+    /// Set the `url`
+    pub fn url<S: Into<String>>(&mut self, url: S) -> &mut Self {
+        self.pkgbuild.url = url.into();
+        self
+    }
+
+    /// Set the `install` script
+    pub fn install<S: Into<String>>(&mut self, install: S) -> &mut Self {
+        self.pkgbuild.install = install.into();
+        self
+    }
+
+    /// Set the `changelog`
+    pub fn changelog<S: Into<String>>(&mut self, changelog: S) -> &mut Self {
+        self.pkgbuild.changelog = changelog.into();
+        self
+    }
+
+    /// Set the `options`
+    pub fn options(&mut self, options: Options) -> &mut Self {
+        self.pkgbuild.options = options;
+        self
+    }
+
+    /// Add a license to `license`
+    pub fn add_license<S: Into<String>>(&mut self, license: S) -> &mut Self {
+        self.pkgbuild.license.push(license.into());
+        self
+    }
+
+    /// Add a group to `groups`
+    pub fn add_group<S: Into<String>>(&mut self, group: S) -> &mut Self {
+        self.pkgbuild.groups.push(group.into());
+        self
+    }
+
+    /// Add a file to `backup`
+    pub fn add_backup<S: Into<String>>(&mut self, backup: S) -> &mut Self {
+        self.pkgbuild.backup.push(backup.into());
+        self
+    }
+
+    /// Add a key fingerprint to `validpgpkeys`
+    pub fn add_validpgpkey<S: Into<String>>(&mut self, key: S) -> &mut Self {
+        self.pkgbuild.validpgpkeys.push(key.into());
+        self
+    }
+
+    /// Add a file name to `noextract`
+    pub fn add_noextract<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.pkgbuild.noextract.push(name.into());
+        self
+    }
+
+    /// Declare an architecture in `arch`, in order, `Architecture::Any` could
+    /// not be mixed with others
+    pub fn add_arch(&mut self, arch: Architecture) -> &mut Self {
+        if ! self.arches.contains(&arch) {
+            self.arches.push(arch)
+        }
+        self
+    }
+
+    /// Add a source without checksums to the arch-independent `source`
+    pub fn add_source<S: Into<Source>>(&mut self, source: S) -> &mut Self {
+        self.add_source_for_arch(None, source)
+    }
+
+    /// Add a source without checksums to `source_[arch]`, or to the 
+    /// arch-independent `source` if `arch` is `None`
+    pub fn add_source_for_arch<S: Into<Source>>(
+        &mut self, arch: Option<Architecture>, source: S
+    ) -> &mut Self 
+    {
+        self.add_source_with_checksum_for_arch(arch, SourceWithChecksum {
+            source: source.into(), ..Default::default() })
+    }
+
+    /// Like `add_source_for_arch()`, but with checksums
+    pub fn add_source_with_checksum_for_arch(
+        &mut self, arch: Option<Architecture>, source: SourceWithChecksum
+    ) -> &mut Self 
+    {
+        multiarch_section(&mut self.pkgbuild.multiarch, arch)
+            .sources_with_checksums.push(source);
+        self
+    }
+
+    pkgbuild_builder_add!(depends, Dependency, add_depend, add_depend_for_arch);
+    pkgbuild_builder_add!(makedepends, MakeDependency, add_makedepend, 
+        add_makedepend_for_arch);
+    pkgbuild_builder_add!(checkdepends, CheckDependency, add_checkdepend, 
+        add_checkdepend_for_arch);
+    pkgbuild_builder_add!(optdepends, OptionalDependency, add_optdepend, 
+        add_optdepend_for_arch);
+    pkgbuild_builder_add!(conflicts, Conflict, add_conflict, 
+        add_conflict_for_arch);
+    pkgbuild_builder_add!(provides, Provide, add_provide, add_provide_for_arch);
+    pkgbuild_builder_add!(replaces, Replace, add_replace, add_replace_for_arch);
+
+    /// Add a split package, packages are built in the order added and their
+    /// names must be unique
+    pub fn add_package(&mut self, package: &PackageBuilder) -> &mut Self {
+        self.packages.push(package.clone());
+        self
+    }
+
+    /// Validate and build the `Pkgbuild`, failing with 
+    /// `Error::BrokenPKGBUILDs` on empty `pkgbase`, malformed version, 
+    /// undeclared architectures, or duplicated package names
+    pub fn build(&self) -> Result<Pkgbuild> {
+        let mut pkgbuild = self.pkgbuild.clone();
+        let pkgbase = &pkgbuild.pkgbase;
+        if pkgbase.is_empty() {
+            return Err(BrokenPkgbuild::error("pkgbase is empty"))
+        }
+        self.validate().map_err(|e|e.broken_pkgbase(pkgbase))?;
+        multiarch_declare(&mut pkgbuild.multiarch, &self.arches)
+            .map_err(|e|e.broken_pkgbase(pkgbase))?;
+        let default_package;
+        let packages = if self.packages.is_empty() {
+            default_package = [PackageBuilder::new(pkgbase.as_str())];
+            &default_package[..]
+        } else {
+            &self.packages[..]
+        };
+        for package in packages.iter() {
+            let pkgname = &package.package.pkgname;
+            if pkgbuild.pkgs.iter().any(|pkg|pkg.pkgname == *pkgname) {
+                return Err(BrokenPkgbuild::error(format!(
+                    "duplicated package '{}'", pkgname))
+                    .broken_pkgbase(pkgbase))
+            }
+            pkgbuild.pkgs.push(package.build(&self.arches)
+                .map_err(|e|e.broken_pkgbase(pkgbase))?)
+        }
+        Ok(pkgbuild)
+    }
+
+    /// Check the version
+    fn validate(&self) -> Result<()> {
+        let version = &self.pkgbuild.version;
+        if version.pkgver.is_empty() {
+            return Err(BrokenPkgbuild::error("pkgver is empty"))
+        }
+        if version.pkgver.contains(['-', ':']) {
+            return Err(BrokenPkgbuild::error(format!(
+                "pkgver '{}' contains illegal - or :", version.pkgver)))
+        }
+        if version.pkgrel.is_empty() {
+            return Err(BrokenPkgbuild::error("pkgrel is empty"))
+        }
+        Ok(())
+    }
+}
+
+/// A builder to construct a split `Package` for `PkgbuildBuilder`, fields not
+/// set are inherited from the `PKGBUILD`, like a package function not 
+/// mentioning them
+#[derive(Debug, Clone)]
+pub struct PackageBuilder {
+    package: Package,
+    arches: Option<Vec<Architecture>>,
+}
+
+/// Generate `set_[field]()` and `add_[item]()` for an arch-independent array
+/// of `PackageBuilder`, overriding the one of the `PKGBUILD`
+macro_rules! package_builder_set {
+    ($field: ident, $set: ident, $add: ident, $item: ty) => {
+        #[doc = concat!("Override `", stringify!($field), 
+            "`, an empty iterator explicitly sets it to empty")]
+        pub fn $set<I, T>(&mut self, items: I) -> &mut Self 
+        where
+            I: IntoIterator<Item = T>,
+            T: Into<$item>
+        {
+            self.package.$field = 
+                Some(items.into_iter().map(Into::into).collect());
+            self
+        }
+
+        #[doc = concat!("Add an item to the overridden `", stringify!($field),
+            "`, starting from empty if not yet overridden")]
+        pub fn $add<T: Into<$item>>(&mut self, item: T) -> &mut Self {
+            self.package.$field.get_or_insert_with(Vec::new).push(item.into());
+            self
+        }
+    };
+}
+
+/// Generate the setters of an array in the arch-specific sections of 
+/// `PackageBuilder`, overriding the one of the `PKGBUILD`
+macro_rules! package_builder_arch {
+    ($field: ident, $item: ty, $set: ident, $set_for_arch: ident, $add: ident,
+        $add_for_arch: ident) => 
+    {
+        #[doc = concat!("Like `", stringify!($set_for_arch), 
+            "()`, for the arch-independent one")]
+        pub fn $set<I, T>(&mut self, items: I) -> &mut Self 
+        where
+            I: IntoIterator<Item = T>,
+            T: Into<$item>
+        {
+            self.$set_for_arch(None, items)
+        }
+
+        #[doc = concat!("Override `", stringify!($field), "_[arch]`, or the ",
+            "arch-independent one if `arch` is `None`, an empty iterator ",
+            "explicitly sets it to empty")]
+        pub fn $set_for_arch<I, T>(
+            &mut self, arch: Option<Architecture>, items: I
+        ) -> &mut Self 
+        where
+            I: IntoIterator<Item = T>,
+            T: Into<$item>
+        {
+            multiarch_section(&mut self.package.multiarch, arch).$field = 
+                Some(items.into_iter().map(Into::into).collect());
+            self
+        }
+
+        #[doc = concat!("Like `", stringify!($add_for_arch), 
+            "()`, for the arch-independent one")]
+        pub fn $add<T: Into<$item>>(&mut self, item: T) -> &mut Self {
+            self.$add_for_arch(None, item)
+        }
+
+        #[doc = concat!("Add an item to the overridden `", stringify!($field),
+            "_[arch]`, or the arch-independent one if `arch` is `None`, ",
+            "starting from empty if not yet overridden")]
+        pub fn $add_for_arch<T: Into<$item>>(
+            &mut self, arch: Option<Architecture>, item: T
+        ) -> &mut Self 
+        {
+            multiarch_section(&mut self.package.multiarch, arch).$field
+                .get_or_insert_with(Vec::new).push(item.into());
+            self
+        }
+    };
+}
+
+impl PackageBuilder {
+    /// Create a new `PackageBuilder` inheriting everything from the `PKGBUILD`
+    pub fn new<S: Into<String>>(pkgname: S) -> Self {
+        Self {
+            package: Package {
+                pkgname: pkgname.into(),
+                pkgdesc: None,
+                url: None,
+                license: None,
+                groups: None,
+                backup: None,
+                options: None,
+                install: None,
+                changelog: None,
+                multiarch: Default::default(),
+                extra: Default::default(),
+            },
+            arches: None,
+        }
+    }
+
+    /// Override the `pkgdesc`
+    pub fn pkgdesc<S: Into<String>>(&mut self, pkgdesc: S) -> &mut Self {
+        self.package.pkgdesc = Some(pkgdesc.into());
+        self
+    }
+
+    /// Override the `url`
+    pub fn url<S: Into<String>>(&mut self, url: S) -> &mut Self {
+        self.package.url = Some(url.into());
+        self
+    }
+
+    /// Override the `install` script, empty to pack none
+    pub fn install<S: Into<String>>(&mut self, install: S) -> &mut Self {
+        self.package.install = Some(install.into());
+        self
     }
-}
 
-impl TryFrom<&PkgbuildsParsing<'_>> for Pkgbuilds {
-    type Error = Error;
+    /// Override the `changelog`, empty to pack none
+    pub fn changelog<S: Into<String>>(&mut self, changelog: S) -> &mut Self {
+        self.package.changelog = Some(changelog.into());
+        self
+    }
 
-    fn try_from(value: &PkgbuildsParsing<'_>) -> Result<Self> {
-        let mut entries = Vec::new();
-        for entry in value.entries.iter() {
-            entries.push(entry.try_into()?)
-        }
-        Ok(Self {entries})
+    /// Override the `options`, those not set are still inherited one by one
+    pub fn options(&mut self, options: Options) -> &mut Self {
+        self.package.options = Some(options);
+        self
     }
-}
 
-impl Pkgbuild {
-    pkg_iter_all_arch!(self, sources_with_checksums, SourceWithChecksum);
-    pkg_iter_all_arch!(self, depends, Dependency);
-    pkg_iter_all_arch!(self, makedepends, MakeDependency);
-    pkg_iter_all_arch!(self, checkdepends, CheckDependency);
-    pkg_iter_all_arch!(self, optdepends, OptionalDependency);
-    pkg_iter_all_arch!(self, conflicts, Conflict);
-    pkg_iter_all_arch!(self, provides, Provide);
-    pkg_iter_all_arch!(self, replaces, Replace);
+    package_builder_set!(license, set_license, add_license, String);
+    package_builder_set!(groups, set_groups, add_group, String);
+    package_builder_set!(backup, set_backup, add_backup, String);
 
-    /// Get a result similar to `makepkg --printsrcinfo`, useful for formatting
-    #[cfg(feature = "srcinfo")]
-    pub fn srcinfo<'a>(&'a self) -> Srcinfo<'a> {
-        Srcinfo { pkgbuild: self }
+    /// Override the architectures by declaring one in `arch`, in order, 
+    /// without any the package has those of the `PKGBUILD`
+    pub fn add_arch(&mut self, arch: Architecture) -> &mut Self {
+        let arches = self.arches.get_or_insert_with(Vec::new);
+        if ! arches.contains(&arch) {
+            arches.push(arch)
+        }
+        self
     }
 
-    // /// Get a flattened list of options, note it would be impossible to go back
-    // /// to the original order of options from only the result options.
-    // pub fn options(&self) -> Options {
-    //     (&self.options).into()
-    // }
+    package_builder_arch!(checkdepends, CheckDependency, set_checkdepends, 
+        set_checkdepends_for_arch, add_checkdepend, add_checkdepend_for_arch);
+    package_builder_arch!(depends, Dependency, set_depends, 
+        set_depends_for_arch, add_depend, add_depend_for_arch);
+    package_builder_arch!(optdepends, OptionalDependency, set_optdepends, 
+        set_optdepends_for_arch, add_optdepend, add_optdepend_for_arch);
+    package_builder_arch!(provides, Provide, set_provides, 
+        set_provides_for_arch, add_provide, add_provide_for_arch);
+    package_builder_arch!(conflicts, Conflict, set_conflicts, 
+        set_conflicts_for_arch, add_conflict, add_conflict_for_arch);
+    package_builder_arch!(replaces, Replace, set_replaces, 
+        set_replaces_for_arch, add_replace, add_replace_for_arch);
+
+    /// Build the `Package`, with the architectures of the `PKGBUILD` if not
+    /// overridden
+    fn build(&self, pkgbuild_arches: &[Architecture]) -> Result<Package> {
+        let mut package = self.package.clone();
+        if package.pkgname.is_empty() {
+            return Err(BrokenPkgbuild::error("pkgname is empty"))
+        }
+        let arches = self.arches.as_deref().unwrap_or(pkgbuild_arches);
+        multiarch_declare(&mut package.multiarch, arches).map_err(|e|
+            e.broken_context(format_args!("package '{}'", package.pkgname)))?;
+        Ok(package)
+    }
 }
 
 #[cfg(feature = "srcinfo")]
 pub struct Srcinfo<'a> {
-    pub pkgbuild: &'a Pkgbuild
+    pub pkgbuild: &'a Pkgbuild,
+    /// Only render this many architecture-specific sections for the 
+    /// `PKGBUILD` and each package, with a comment marking the truncation.
+    /// This is for display only, the output would not be a valid `.SRCINFO`
+    /// once truncated.
+    pub max_arches: Option<usize>,
+}
+
+#[cfg(feature = "srcinfo")]
+impl<'a> Srcinfo<'a> {
+    /// Set the max count of architecture-specific sections to render
+    pub fn set_max_arches(&mut self, max_arches: Option<usize>) -> &mut Self {
+        self.max_arches = max_arches;
+        self
+    }
+}
+
+/// A key in `.SRCINFO`, with an optional `_arch` suffix, formatted without
+/// allocating
+#[cfg(feature = "srcinfo")]
+#[derive(Clone, Copy)]
+struct SrcinfoKey<'a>(&'a str, &'a str);
+
+#[cfg(feature = "srcinfo")]
+impl<'a> Display for SrcinfoKey<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.1.is_empty() {
+            f.write_str(self.0)
+        } else {
+            write!(f, "{}_{}", self.0, self.1)
+        }
+    }
 }
 
 #[cfg(feature = "srcinfo")]
 impl<'a> Display for Srcinfo<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        fn writeln_indented_str<S: AsRef<str>>(
-            f: &mut Formatter<'_>, title: &str, content: S
+        fn writeln_indented_str<T: Display, S: AsRef<str>>(
+            f: &mut Formatter<'_>, title: T, content: S
         ) -> std::fmt::Result 
         {
             let content = content.as_ref();
             if content.is_empty() { return Ok(()) }
-            writeln!(f, "\t{} = {}", title, content)
+            // Like makepkg, whitespaces are squeezed into single spaces and
+            // trimmed, so multi-line values stay in one line
+            if content.contains(|c: char|c.is_ascii_whitespace() && c != ' ')
+                || content.contains("  ") || content.starts_with(' ') 
+                || content.ends_with(' ') 
+            {
+                let content: Vec<&str> = 
+                    content.split_ascii_whitespace().collect();
+                writeln!(f, "\t{} = {}", title, content.join(" "))
+            } else {
+                writeln!(f, "\t{} = {}", title, content)
+            }
         }
-        fn writeln_indented_display<D: Display>(
-            f: &mut Formatter<'_>, title: &str, content: D
+        fn writeln_indented_display<T: Display, D: Display>(
+            f: &mut Formatter<'_>, title: T, content: D
         ) -> std::fmt::Result 
         {
-            writeln_indented_str(f, title, &content.to_string())
+            writeln_indented_str(f, title, content.to_string())
         }
         let pkgbuild = self.pkgbuild;
+        let max_arches = self.max_arches.unwrap_or(usize::MAX);
         writeln!(f, "pkgbase = {}", pkgbuild.pkgbase)?;
         writeln_indented_str(f, "pkgdesc", &pkgbuild.pkgdesc)?;
         writeln_indented_str(f, "pkgver", &pkgbuild.version.pkgver)?;
@@ -2728,14 +10300,17 @@ impl<'a> Display for Srcinfo<'a> {
         if pkgbuild.multiarch.arches.is_empty() {
             writeln_indented_str(f, "arch", "any")?;
         } else {
-            for (arch, _) in pkgbuild.multiarch.arches.iter() {
+            for (arch, _) in pkgbuild.multiarch.iter_arches().take(max_arches) {
                 writeln_indented_str(f, "arch", arch)?;
             }
+            writeln_arches_truncated(f, "\t", pkgbuild.multiarch.arches.len(), 
+                max_arches)?;
         }
-        fn writelns_indented_iter_str<I, S>(
-            f: &mut Formatter<'_>, title: &str, contents: I
+        fn writelns_indented_iter_str<T, I, S>(
+            f: &mut Formatter<'_>, title: T, contents: I
         ) -> std::fmt::Result
         where
+            T: Display + Copy,
             I: IntoIterator<Item = S>,
             S: AsRef<str>
         {
@@ -2744,10 +10319,11 @@ impl<'a> Display for Srcinfo<'a> {
             }
             Ok(())
         }
-        fn writelns_indented_iter_display<I, D>(
-            f: &mut Formatter<'_>, title: &str, contents: I
+        fn writelns_indented_iter_display<T, I, D>(
+            f: &mut Formatter<'_>, title: T, contents: I
         ) -> std::fmt::Result
         where
+            T: Display + Copy,
             I: IntoIterator<Item = D>,
             D: Display
         {
@@ -2756,6 +10332,29 @@ impl<'a> Display for Srcinfo<'a> {
             }
             Ok(())
         }
+        // Package fields overriden to empty are printed with empty values 
+        // just like makepkg does, those not overriden are omitted
+        fn writeln_indented_overridden<T: Display>(
+            f: &mut Formatter<'_>, title: T, content: Option<&str>
+        ) -> std::fmt::Result 
+        {
+            match content {
+                Some("") => writeln!(f, "\t{} = ", title),
+                Some(content) => writeln_indented_str(f, title, content),
+                None => Ok(()),
+            }
+        }
+        fn writelns_indented_overridden<T: Display + Copy, D: Display>(
+            f: &mut Formatter<'_>, title: T, contents: Option<&[D]>
+        ) -> std::fmt::Result 
+        {
+            match contents {
+                Some([]) => writeln!(f, "\t{} = ", title),
+                Some(contents) => 
+                    writelns_indented_iter_display(f, title, contents),
+                None => Ok(()),
+            }
+        }
         writelns_indented_iter_str(f, "groups", &pkgbuild.groups)?;
         writelns_indented_iter_str(f, "license", &pkgbuild.license)?;
         let arch_specific = &pkgbuild.multiarch.any;
@@ -2767,21 +10366,18 @@ impl<'a> Display for Srcinfo<'a> {
         writelns_indented_iter_display(f, "conflicts", &arch_specific.conflicts)?;
         writelns_indented_iter_display(f, "replaces", &arch_specific.replaces)?;
         writelns_indented_iter_str(f, "noextract", &pkgbuild.noextract)?;
-        macro_rules! write_option {
-            ($options: expr, $($option: ident),+) => {
-                $(
-                    if let Some(value) = $options.$option {
-                        writeln!(f, "\toptions = {}{}", 
-                            if value {""} else {"!"}, stringify!($option))?
-                    }
-                )+
-            };
-            ($options: expr) => {
-                write_option!($options, strip, docs, libtool, staticlibs, emptydirs, zipman, 
-                    ccache, distcc, buildflags, makeflags, debug, lto);
-            };
+        // A package's options only carry what its package function set, i.e.
+        // the whole overriding array, which is what makepkg prints for it
+        fn write_options(f: &mut Formatter<'_>, options: &Options) 
+            -> std::fmt::Result 
+        {
+            // Those unknown to this library could only come after known ones
+            for option in options.to_strings() {
+                writeln!(f, "\toptions = {}", option)?
+            }
+            Ok(())
         }
-        write_option!(pkgbuild.options);
+        write_options(f, &pkgbuild.options)?;
 
         writelns_indented_iter_str(f, "backup", &pkgbuild.backup)?;
         #[derive(Default)]
@@ -2807,13 +10403,7 @@ impl<'a> Display for Srcinfo<'a> {
         }
         fn write_sources_and_stat_sums(f: &mut Formatter<'_>, arch_name: &str, arch_specific: &PkgbuildArchSpecific) -> std::result::Result<StatChecksum, std::fmt::Error> {
             let mut stat = StatChecksum::default();
-            let title_temp;
-            let title = if arch_name.is_empty() {
-                "source"
-            } else {
-                title_temp = format!("source_{}", arch_name);
-                &title_temp
-            };
+            let title = SrcinfoKey("source", arch_name);
             for source_with_checksum in arch_specific.sources_with_checksums.iter() {
                 writeln_indented_str(f, title, source_with_checksum.source.get_pkgbuild_source())?;
                 macro_rules! update_flag {
@@ -2830,29 +10420,22 @@ impl<'a> Display for Srcinfo<'a> {
         }
         let mut stat_checksums = write_sources_and_stat_sums(f, "", arch_specific)?;
         writelns_indented_iter_str(f, "validpgpkeys", &pkgbuild.validpgpkeys)?;
-        fn suffix_from_arch_name(arch_name: &str) -> String {
-            if arch_name.is_empty() {
-                String::new()
-            } else {
-                format!("_{}", arch_name)
-            }
-        }
         fn write_all_checksums(f: &mut Formatter<'_>, stat_checksums: &StatChecksum, 
             arch_name: &str, arch_specific: &PkgbuildArchSpecific
         ) -> std::fmt::Result 
         {
-            let suffix = suffix_from_arch_name(arch_name);
             macro_rules! write_checksums {
                 ($($cksum: ident),+) => {$(
                     if stat_checksums.$cksum {
-                        let title = format!("{}s{}", stringify!($cksum), suffix);
+                        let title = SrcinfoKey(
+                            concat!(stringify!($cksum), "s"), arch_name);
                         for source_with_checksum in arch_specific.sources_with_checksums.iter() {
                             if let Some(bytes) = source_with_checksum.$cksum {
-                                write!(f, "\t{} = ", &title)?;
+                                write!(f, "\t{} = ", title)?;
                                 write_byte_iter(f, bytes)?;
                                 writeln!(f)?
                             } else {
-                                writeln_indented_str(f, &title, "SKIP")?
+                                writeln_indented_str(f, title, "SKIP")?
                             }
                         }
                     }
@@ -2861,55 +10444,692 @@ impl<'a> Display for Srcinfo<'a> {
             write_checksums!(md5sum, sha1sum, sha224sum, sha256sum, sha384sum, sha512sum, b2sum);
             Ok(())
         }
-        write_all_checksums(f, &stat_checksums, "", &arch_specific)?;
-        for (arch, arch_specific) in pkgbuild.multiarch.arches.iter() {
+        write_all_checksums(f, &stat_checksums, "", arch_specific)?;
+        for (arch, arch_specific) in 
+            pkgbuild.multiarch.iter_arches().take(max_arches) 
+        {
             let arch_name = arch.as_ref();
             stat_checksums = write_sources_and_stat_sums(f, arch_name, arch_specific)?;
-            writelns_indented_iter_display(f, &format!("provides_{}", arch_name), &arch_specific.provides)?;
-            writelns_indented_iter_display(f, &format!("conflicts_{}", arch_name), &arch_specific.conflicts)?;
-            writelns_indented_iter_display(f, &format!("depends_{}", arch_name), &arch_specific.depends)?;
-            writelns_indented_iter_display(f, &format!("replaces_{}", arch_name), &arch_specific.replaces)?;
-            writelns_indented_iter_display(f, &format!("optdepends_{}", arch_name), &arch_specific.optdepends)?;
-            writelns_indented_iter_display(f, &format!("makedepends_{}", arch_name), &arch_specific.makedepends)?;
-            writelns_indented_iter_display(f, &format!("checkdepends_{}", arch_name), &arch_specific.checkdepends)?;
+            writelns_indented_iter_display(f, SrcinfoKey("provides", arch_name), &arch_specific.provides)?;
+            writelns_indented_iter_display(f, SrcinfoKey("conflicts", arch_name), &arch_specific.conflicts)?;
+            writelns_indented_iter_display(f, SrcinfoKey("depends", arch_name), &arch_specific.depends)?;
+            writelns_indented_iter_display(f, SrcinfoKey("replaces", arch_name), &arch_specific.replaces)?;
+            writelns_indented_iter_display(f, SrcinfoKey("optdepends", arch_name), &arch_specific.optdepends)?;
+            writelns_indented_iter_display(f, SrcinfoKey("makedepends", arch_name), &arch_specific.makedepends)?;
+            writelns_indented_iter_display(f, SrcinfoKey("checkdepends", arch_name), &arch_specific.checkdepends)?;
             write_all_checksums(f, &stat_checksums, arch_name, arch_specific)?
         }
+        writeln_arches_truncated(f, "\t", pkgbuild.multiarch.arches.len(), 
+            max_arches)?;
         for pkg in pkgbuild.pkgs.iter() {
             writeln!(f, "\npkgname = {}", pkg.pkgname)?;
-            writeln_indented_str(f, "pkgdesc", &pkg.pkgdesc)?;
-            writeln_indented_str(f, "url", &pkg.url)?;
-            writeln_indented_str(f, "install", &pkg.install)?;
-            writeln_indented_str(f, "changelog", &pkg.changelog)?;
-            if ! multiarch_have_same_arches(&pkgbuild.multiarch, &pkg.multiarch) {
+            writeln_indented_overridden(f, "pkgdesc", pkg.pkgdesc.as_deref())?;
+            writeln_indented_overridden(f, "url", pkg.url.as_deref())?;
+            writeln_indented_overridden(f, "install", pkg.install.as_deref())?;
+            writeln_indented_overridden(f, "changelog", 
+                pkg.changelog.as_deref())?;
+            if ! pkgbuild.multiarch.same_arches(&pkg.multiarch) {
                 if pkg.multiarch.arches.is_empty() {
                     writeln_indented_str(f, "arch", "any")?;
                 } else {
-                    for (arch, _) in pkg.multiarch.arches.iter() {
+                    for (arch, _) in pkg.multiarch.iter_arches().take(max_arches) {
                         writeln_indented_str(f, "arch", arch)?;
                     }
+                    writeln_arches_truncated(f, "\t", 
+                        pkg.multiarch.arches.len(), max_arches)?;
                 }
             }
-            writelns_indented_iter_str(f, "groups", &pkg.groups)?;
-            writelns_indented_iter_str(f, "license", &pkg.license)?;
+            writelns_indented_overridden(f, "groups", pkg.groups.as_deref())?;
+            writelns_indented_overridden(f, "license", pkg.license.as_deref())?;
             let arch_specific = &pkg.multiarch.any;
-            writelns_indented_iter_display(f, "checkdepends", &arch_specific.checkdepends)?;
-            writelns_indented_iter_display(f, "depends", &arch_specific.depends)?;
-            writelns_indented_iter_display(f, "optdepends", &arch_specific.optdepends)?;
-            writelns_indented_iter_display(f, "provides", &arch_specific.provides)?;
-            writelns_indented_iter_display(f, "conflicts", &arch_specific.conflicts)?;
-            writelns_indented_iter_display(f, "replaces", &arch_specific.replaces)?;
-            write_option!(pkg.options);
-            writelns_indented_iter_str(f, "backup", &pkg.backup)?;
-            for (arch, arch_specific) in pkg.multiarch.arches.iter() {
+            writelns_indented_overridden(f, "checkdepends", arch_specific.checkdepends.as_deref())?;
+            writelns_indented_overridden(f, "depends", arch_specific.depends.as_deref())?;
+            writelns_indented_overridden(f, "optdepends", arch_specific.optdepends.as_deref())?;
+            writelns_indented_overridden(f, "provides", arch_specific.provides.as_deref())?;
+            writelns_indented_overridden(f, "conflicts", arch_specific.conflicts.as_deref())?;
+            writelns_indented_overridden(f, "replaces", arch_specific.replaces.as_deref())?;
+            match &pkg.options {
+                Some(options) if options.is_empty() => 
+                        writeln!(f, "\toptions = ")?,
+                Some(options) => write_options(f, options)?,
+                None => (),
+            }
+            writelns_indented_overridden(f, "backup", pkg.backup.as_deref())?;
+            for (arch, arch_specific) in 
+                pkg.multiarch.iter_arches().take(max_arches) 
+            {
                 let arch_name = arch.as_ref();
-                writelns_indented_iter_display(f, &format!("provides_{}", arch_name), &arch_specific.provides)?;
-                writelns_indented_iter_display(f, &format!("conflicts_{}", arch_name), &arch_specific.conflicts)?;
-                writelns_indented_iter_display(f, &format!("depends_{}", arch_name), &arch_specific.depends)?;
-                writelns_indented_iter_display(f, &format!("replaces_{}", arch_name), &arch_specific.replaces)?;
-                writelns_indented_iter_display(f, &format!("optdepends_{}", arch_name), &arch_specific.optdepends)?;
-                writelns_indented_iter_display(f, &format!("checkdepends_{}", arch_name), &arch_specific.checkdepends)?;
+                writelns_indented_overridden(f, SrcinfoKey("provides", arch_name), arch_specific.provides.as_deref())?;
+                writelns_indented_overridden(f, SrcinfoKey("conflicts", arch_name), arch_specific.conflicts.as_deref())?;
+                writelns_indented_overridden(f, SrcinfoKey("depends", arch_name), arch_specific.depends.as_deref())?;
+                writelns_indented_overridden(f, SrcinfoKey("replaces", arch_name), arch_specific.replaces.as_deref())?;
+                writelns_indented_overridden(f, SrcinfoKey("optdepends", arch_name), arch_specific.optdepends.as_deref())?;
+                writelns_indented_overridden(f, SrcinfoKey("checkdepends", arch_name), arch_specific.checkdepends.as_deref())?;
             }
+            writeln_arches_truncated(f, "\t", pkg.multiarch.arches.len(), 
+                max_arches)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options used when syncing `.SRCINFO` files with `sync_srcinfo()` and 
+/// `sync_srcinfo_tree()`
+#[cfg(feature = "srcinfo")]
+#[derive(Debug, Clone)]
+pub struct SrcinfoOptions {
+    /// Only compare and report, do not write any `.SRCINFO`
+    /// 
+    /// Default: `false`
+    pub dry_run: bool,
+
+    /// Skip hidden directories, e.g. `.git`, when looking for `PKGBUILD`s
+    /// 
+    /// Default: `true`
+    pub skip_hidden: bool,
+
+    /// Refuse to write `.SRCINFO` for `PKGBUILD`s that declared more than
+    /// this many architectures in itself or any of its packages
+    /// 
+    /// Default: `None`
+    pub max_arches: Option<usize>,
+
+    /// Refuse to write `.SRCINFO` for `PKGBUILD`s with sources using unknown
+    /// protocols, which could only be fetched with custom `DLAGENTS`. 
+    /// Otherwise they're written with their schemes as-is, like makepkg does.
+    /// 
+    /// Default: `false`
+    pub strict_protocols: bool,
+}
+
+#[cfg(feature = "srcinfo")]
+impl Default for SrcinfoOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            skip_hidden: true,
+            max_arches: None,
+            strict_protocols: false,
+        }
+    }
+}
+
+#[cfg(feature = "srcinfo")]
+impl SrcinfoOptions {
+    /// Get a `SrcinfoOptions` instance with default settings: write changes,
+    /// skip hidden directories
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether to only compare and report without writing
+    pub fn set_dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Set whether to skip hidden directories when looking for `PKGBUILD`s
+    pub fn set_skip_hidden(&mut self, skip_hidden: bool) -> &mut Self {
+        self.skip_hidden = skip_hidden;
+        self
+    }
+
+    /// Set the max count of architectures allowed when writing `.SRCINFO`
+    pub fn set_max_arches(&mut self, max_arches: Option<usize>) -> &mut Self {
+        self.max_arches = max_arches;
+        self
+    }
+
+    /// Set whether to refuse sources with unknown protocols
+    pub fn set_strict_protocols(&mut self, strict_protocols: bool) 
+        -> &mut Self 
+    {
+        self.strict_protocols = strict_protocols;
+        self
+    }
+}
+
+/// The result of syncing a single `.SRCINFO`
+#[cfg(feature = "srcinfo")]
+#[derive(Debug, Clone)]
+pub enum SrcinfoSyncStatus {
+    /// The `.SRCINFO` did not exist and was created
+    Created,
+    /// The `.SRCINFO` existed but was outdated and was updated
+    Updated,
+    /// The `.SRCINFO` was already up to date
+    Unchanged,
+    /// The `PKGBUILD` could not be parsed or the `.SRCINFO` could not be 
+    /// read or written
+    Failed(Error),
+}
+
+#[cfg(feature = "srcinfo")]
+impl Display for SrcinfoSyncStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SrcinfoSyncStatus::Created => write!(f, "created"),
+            SrcinfoSyncStatus::Updated => write!(f, "updated"),
+            SrcinfoSyncStatus::Unchanged => write!(f, "unchanged"),
+            SrcinfoSyncStatus::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+/// Write the `.SRCINFO` of `pkgbuild` to `path` if it does not exist or its
+/// content differs, with `dry_run` set in `options` nothing would be written
+#[cfg(feature = "srcinfo")]
+pub fn sync_srcinfo<P: AsRef<Path>>(
+    pkgbuild: &Pkgbuild, path: P, options: &SrcinfoOptions
+) -> Result<SrcinfoSyncStatus> 
+{
+    let path = path.as_ref();
+    if let Some(max) = options.max_arches {
+        let count = pkgbuild.pkgs.iter().map(|pkg|pkg.multiarch.arches.len())
+            .chain([pkgbuild.multiarch.arches.len()]).max().unwrap_or_default();
+        if count > max {
+            log::error!("PKGBUILD '{}' declared {} architectures, more than \
+                allowed {}", pkgbuild.pkgbase, count, max);
+            return Err(Error::TooManyArchitectures { count, max })
+        }
+    }
+    if options.strict_protocols {
+        let schemes = pkgbuild.unknown_source_schemes();
+        if ! schemes.is_empty() {
+            log::error!("PKGBUILD '{}' has sources with unknown protocols: {}",
+                pkgbuild.pkgbase, schemes.join(", "));
+            return Err(Error::UnknownSourceProtocols(
+                schemes.into_iter().map(String::from).collect()))
+        }
+    }
+    let content = pkgbuild.srcinfo().to_string();
+    let status = match std::fs::read(path) {
+        Ok(existing) => if existing == content.as_bytes() {
+            return Ok(SrcinfoSyncStatus::Unchanged)
+        } else {
+            SrcinfoSyncStatus::Updated
+        },
+        Err(e) => if e.kind() == std::io::ErrorKind::NotFound {
+            SrcinfoSyncStatus::Created
+        } else {
+            log::error!("Failed to read existing .SRCINFO '{}': {}", 
+                path.display(), e);
+            return Err(e.into())
+        },
+    };
+    if ! options.dry_run {
+        if let Err(e) = std::fs::write(path, content) {
+            log::error!("Failed to write .SRCINFO '{}': {}", 
+                path.display(), e);
+            return Err(e.into())
+        }
+    }
+    Ok(status)
+}
+
+/// The sync result of a single `PKGBUILD` in `TreeSyncReport`
+#[cfg(feature = "srcinfo")]
+#[derive(Debug, Clone)]
+pub struct TreeSyncEntry {
+    /// Path to the `PKGBUILD`
+    pub path: PathBuf,
+    pub status: SrcinfoSyncStatus,
+}
+
+/// The report of `sync_srcinfo_tree()`, with entries sorted by path
+#[cfg(feature = "srcinfo")]
+#[derive(Debug, Clone, Default)]
+pub struct TreeSyncReport {
+    pub entries: Vec<TreeSyncEntry>,
+    /// Paths under the root that could not be walked and were skipped, with
+    /// the errors, in walking order
+    pub walk_errors: Vec<(PathBuf, Error)>,
+}
+
+#[cfg(feature = "srcinfo")]
+impl TreeSyncReport {
+    /// Get the entries that failed to sync
+    pub fn failed(&self) -> impl Iterator<Item = &TreeSyncEntry> {
+        self.entries.iter().filter(|entry|
+            matches!(entry.status, SrcinfoSyncStatus::Failed(_)))
+    }
+
+    /// Get the entries whose `.SRCINFO` was created or updated
+    pub fn changed(&self) -> impl Iterator<Item = &TreeSyncEntry> {
+        self.entries.iter().filter(|entry| matches!(entry.status, 
+            SrcinfoSyncStatus::Created | SrcinfoSyncStatus::Updated))
+    }
+}
+
+#[cfg(feature = "srcinfo")]
+impl Display for TreeSyncReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "{}: {}", entry.path.display(), entry.status)?
+        }
+        for (path, e) in self.walk_errors.iter() {
+            writeln!(f, "{}: skipped: {}", path.display(), e)?
         }
         Ok(())
     }
 }
+
+/// Recursively find all `PKGBUILD`s under `dir`, without following symlinks.
+/// Only failing to read `dir` itself is returned as an error, a subtree that
+/// could not be walked is skipped, with the error pushed to `errors`
+#[cfg(feature = "srcinfo")]
+fn find_pkgbuilds(
+    dir: &Path, skip_hidden: bool, paths: &mut Vec<PathBuf>, 
+    errors: &mut Vec<(PathBuf, Error)>
+) -> Result<()> 
+{
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to read dir '{}': {}", dir.display(), e);
+            return Err(e.into())
+        },
+    };
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::error!("Failed to read entry in dir '{}', skipped: {}", 
+                    dir.display(), e);
+                errors.push((dir.into(), e.into()));
+                continue
+            },
+        };
+        let name = entry.file_name();
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(e) => {
+                log::error!("Failed to get file type of '{}', skipped: {}", 
+                    path.display(), e);
+                errors.push((path, e.into()));
+                continue
+            },
+        };
+        if file_type.is_dir() {
+            if skip_hidden && name.as_bytes().starts_with(b".") {
+                continue
+            }
+            if let Err(e) = find_pkgbuilds(&path, skip_hidden, paths, errors) {
+                log::warn!("Skipped dir '{}' that could not be walked", 
+                    path.display());
+                errors.push((path, e))
+            }
+        } else if name == "PKGBUILD" {
+            paths.push(path)
+        }
+    }
+    Ok(())
+}
+
+/// Find all `PKGBUILD`s under `root`, parse them, and sync the `.SRCINFO` 
+/// next to each of them.
+/// 
+/// All `PKGBUILD`s are parsed in one batch first, if that fails they would
+/// then be parsed one by one, so a broken `PKGBUILD` only fails its own entry
+/// in the report. Only failing to read `root` is returned as an error, the
+/// subtrees that could not be walked are skipped and recorded in the report.
+/// The entries in the report are sorted by path.
+#[cfg(feature = "srcinfo")]
+pub fn sync_srcinfo_tree<P: AsRef<Path>>(
+    parser: &Parser, root: P, options: &SrcinfoOptions
+) -> Result<TreeSyncReport> 
+{
+    let mut paths = Vec::new();
+    let mut report = TreeSyncReport::default();
+    find_pkgbuilds(root.as_ref(), options.skip_hidden, &mut paths, 
+        &mut report.walk_errors)?;
+    paths.sort_unstable();
+    let parsed: Vec<Result<Pkgbuild>> = match parser.parse_multi(&paths) {
+        Ok(pkgbuilds) => pkgbuilds.into_iter().map(Ok).collect(),
+        Err(e) => {
+            log::warn!("Failed to parse PKGBUILDs in batch, falling back to \
+                parse them one by one: {}", e);
+            paths.iter().map(|path|parser.parse_one(Some(path))).collect()
+        },
+    };
+    for (path, pkgbuild) in paths.into_iter().zip(parsed) {
+        let status = match pkgbuild.and_then(|pkgbuild|
+            sync_srcinfo(&pkgbuild, path.with_file_name(".SRCINFO"), options)) 
+        {
+            Ok(status) => status,
+            Err(e) => {
+                log::error!("Failed to sync .SRCINFO for '{}': {}", 
+                    path.display(), e);
+                SrcinfoSyncStatus::Failed(e)
+            },
+        };
+        report.entries.push(TreeSyncEntry { path, status })
+    }
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    mod build_info {
+        use crate::{build_info, BuildFeatures, PARSER_SCRIPT_PROTOCOL};
+
+        #[test]
+        fn features_match_cfg() {
+            let info = build_info();
+            assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+            assert_eq!(info.protocol, PARSER_SCRIPT_PROTOCOL);
+            assert_eq!(info.features, BuildFeatures {
+                alpm_interop: cfg!(feature = "alpm-interop"),
+                r#async: cfg!(feature = "async"),
+                format: cfg!(feature = "format"),
+                nothread: cfg!(feature = "nothread"),
+                sandbox: cfg!(feature = "sandbox"),
+                serde: cfg!(feature = "serde"),
+                spdx: cfg!(feature = "spdx"),
+                srcinfo: cfg!(feature = "srcinfo"),
+                tempfile: cfg!(feature = "tempfile"),
+                unsafe_str: cfg!(feature = "unsafe_str"),
+                url: cfg!(feature = "url"),
+                vendored_makepkg: cfg!(feature = "vendored-makepkg"),
+                vercmp: cfg!(feature = "vercmp"),
+            });
+        }
+
+        #[test]
+        fn display_lists_enabled_features() {
+            let display = build_info().to_string();
+            for (name, enabled) in [
+                ("alpm-interop", cfg!(feature = "alpm-interop")),
+                ("spdx", cfg!(feature = "spdx")),
+                ("vendored-makepkg", cfg!(feature = "vendored-makepkg")),
+                ("vercmp", cfg!(feature = "vercmp")),
+            ] {
+                assert_eq!(display.contains(name), enabled, 
+                    "'{}' in '{}'", name, display)
+            }
+        }
+    }
+
+    #[cfg(feature = "vercmp")]
+    mod vercmp {
+        use std::cmp::Ordering;
+
+        use crate::{vercmp, PlainVersion};
+
+        /// The cases of pacman's `test/util/vercmptest.sh`, as version pairs
+        /// and what `vercmp` prints, each also checked in reverse
+        const PACMAN_CASES: &[(&str, &str, i8)] = &[
+            // all similar length, no pkgrel
+            ("1.5.0", "1.5.0", 0),
+            ("1.5.1", "1.5.0", 1),
+            // mixed length
+            ("1.5.1", "1.5", 1),
+            // with pkgrel, simple
+            ("1.5.0-1", "1.5.0-1", 0),
+            ("1.5.0-1", "1.5.0-2", -1),
+            ("1.5.0-1", "1.5.1-1", -1),
+            ("1.5.0-2", "1.5.1-1", -1),
+            // with pkgrel, mixed lengths
+            ("1.5-1", "1.5.1-1", -1),
+            ("1.5-2", "1.5.1-1", -1),
+            ("1.5-2", "1.5.1-2", -1),
+            // mixed pkgrel inclusion
+            ("1.5", "1.5-1", 0),
+            ("1.5-1", "1.5", 0),
+            ("1.1-1", "1.1", 0),
+            ("1.0-1", "1.1", -1),
+            ("1.1-1", "1.0", 1),
+            // alphanumeric versions
+            ("1.5b-1", "1.5-1", -1),
+            ("1.5b", "1.5", -1),
+            ("1.5b-1", "1.5", -1),
+            ("1.5b", "1.5.1", -1),
+            // from the manpage
+            ("1.0a", "1.0alpha", -1),
+            ("1.0alpha", "1.0b", -1),
+            ("1.0b", "1.0beta", -1),
+            ("1.0beta", "1.0rc", -1),
+            ("1.0rc", "1.0", -1),
+            // going crazy? alpha-dotted versions
+            ("1.5.a", "1.5", 1),
+            ("1.5.b", "1.5.a", 1),
+            ("1.5.1", "1.5.b", 1),
+            // alpha dots and dashes
+            ("1.5.b-1", "1.5.b", 0),
+            ("1.5-1", "1.5.b", -1),
+            // same/similar content, differing separators
+            ("2.0", "2_0", 0),
+            ("2.0_a", "2_0.a", 0),
+            ("2.0a", "2.0.a", -1),
+            ("2___a", "2_a", 1),
+            // epoch included version comparisons
+            ("0:1.0", "0:1.0", 0),
+            ("0:1.0", "0:1.1", -1),
+            ("1:1.0", "0:1.0", 1),
+            ("1:1.0", "0:1.1", 1),
+            ("1:1.0", "2:1.1", -1),
+            // epoch + sometimes present pkgrel
+            ("1:1.0", "0:1.0-1", 1),
+            ("1:1.0-1", "0:1.1-1", 1),
+            // epoch included on one version
+            ("0:1.0", "1.0", 0),
+            ("0:1.0", "1.1", -1),
+            ("0:1.1", "1.0", 1),
+            ("1:1.0", "1.0", 1),
+            ("1:1.0", "1.1", 1),
+            ("1:1.1", "1.1", 1),
+        ];
+
+        /// The corner cases where this used to diverge from pacman, with 
+        /// what `vercmp` of pacman prints
+        const DIVERGED_CASES: &[(&str, &str, i8)] = &[
+            ("1.0.", "1.0", 1),
+            ("1.0a", "1.0.1", -1),
+            ("1.0a", "1.0", -1),
+            (".1", "1", 1),
+            ("1..0", "1.0", 1),
+            ("1.0", "1.0.0", -1),
+            ("a", "1", -1),
+            ("", "1", -1),
+            ("", "", 0),
+            ("0001", "1", 0),
+            ("1.01", "1.1", 0),
+        ];
+
+        fn order(expected: i8) -> Ordering {
+            expected.cmp(&0)
+        }
+
+        fn pkg_vercmp(ver1: &str, ver2: &str) -> Ordering {
+            PlainVersion::from(ver1).cmp(&PlainVersion::from(ver2))
+        }
+
+        #[test]
+        fn pacman_vercmptest() {
+            for (ver1, ver2, expected) in PACMAN_CASES {
+                assert_eq!(pkg_vercmp(ver1, ver2), order(*expected),
+                    "{} vs {}", ver1, ver2);
+                assert_eq!(pkg_vercmp(ver2, ver1), order(-expected),
+                    "{} vs {}", ver2, ver1);
+            }
+        }
+
+        #[test]
+        fn diverged_corner_cases() {
+            for (ver1, ver2, expected) in DIVERGED_CASES {
+                assert_eq!(vercmp(ver1, ver2), Some(order(*expected)),
+                    "{} vs {}", ver1, ver2);
+                assert_eq!(vercmp(ver2, ver1), Some(order(-expected)),
+                    "{} vs {}", ver2, ver1);
+            }
+        }
+
+        /// Versions mixing numbers, letters, separators of different 
+        /// lengths, epochs and pkgrels
+        fn corpus() -> Vec<String> {
+            let pkgvers = ["1", "1.0", "1.0.0", "1.0a", "1.0.a", "1.0rc1", 
+                "1.0_1", "1..0", "1.0.", ".1", "1.1", "1.10", "1.9", "01.1",
+                "a", "b1", "1b", "1.a.1", "2", "10", "1+2", "0", ""];
+            let mut versions = Vec::new();
+            for epoch in ["", "0:", "1:"] {
+                for pkgver in pkgvers {
+                    for pkgrel in ["", "-1", "-2", "-1.1"] {
+                        versions.push(format!("{}{}{}", epoch, pkgver, pkgrel))
+                    }
+                }
+            }
+            versions
+        }
+
+        #[test]
+        fn vercmp_never_fails_and_is_antisymmetric() {
+            let corpus = corpus();
+            for ver1 in corpus.iter() {
+                assert_eq!(vercmp(ver1, ver1), Some(Ordering::Equal));
+                for ver2 in corpus.iter() {
+                    let order = vercmp(ver1, ver2)
+                        .expect("vercmp should never fail");
+                    assert_eq!(vercmp(ver2, ver1), Some(order.reverse()),
+                        "{} vs {}", ver1, ver2);
+                    assert_eq!(pkg_vercmp(ver2, ver1), 
+                        pkg_vercmp(ver1, ver2).reverse(), 
+                        "{} vs {}", ver1, ver2);
+                }
+            }
+        }
+
+        #[test]
+        fn vercmp_is_transitive() {
+            let corpus = corpus();
+            let mut sorted = corpus.clone();
+            sorted.sort_by(|ver1, ver2|vercmp(ver1, ver2).unwrap());
+            // A total order sorts consistently, every pair is ordered the 
+            // same as their positions
+            for (index1, ver1) in sorted.iter().enumerate() {
+                for ver2 in sorted[index1 + 1..].iter() {
+                    assert_ne!(vercmp(ver1, ver2), Some(Ordering::Greater),
+                        "{} sorted before {}", ver1, ver2);
+                }
+            }
+            for ver1 in corpus.iter() {
+                for ver2 in corpus.iter() {
+                    for ver3 in corpus.iter().step_by(7) {
+                        if vercmp(ver1, ver2) != Some(Ordering::Greater) &&
+                            vercmp(ver2, ver3) != Some(Ordering::Greater)
+                        {
+                            assert_ne!(vercmp(ver1, ver3), 
+                                Some(Ordering::Greater),
+                                "{} <= {} <= {}", ver1, ver2, ver3);
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Compare against pacman's `vercmp` if it's installed
+        #[test]
+        fn matches_system_vercmp() {
+            let Ok(output) = std::process::Command::new("vercmp")
+                .args(["1", "1"]).output() else {
+                eprintln!("vercmp not found, skipped");
+                return
+            };
+            assert!(output.status.success());
+            let corpus = corpus();
+            for ver1 in corpus.iter().step_by(5) {
+                for ver2 in corpus.iter().step_by(3) {
+                    let output = std::process::Command::new("vercmp")
+                        .args([ver1, ver2]).output().unwrap();
+                    let expected: i8 = String::from_utf8_lossy(&output.stdout)
+                        .trim().parse().unwrap();
+                    assert_eq!(pkg_vercmp(ver1, ver2), order(expected),
+                        "{} vs {}", ver1, ver2);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "format")]
+    mod version_display {
+        use crate::PlainVersion;
+
+        /// A xorshift generator, to have reproducible arbitrary inputs 
+        /// without a dependency on a property testing crate
+        struct Rng(u64);
+
+        impl Rng {
+            fn below(&mut self, bound: usize) -> usize {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                (self.0 % bound as u64) as usize
+            }
+
+            fn string(&mut self, charset: &[u8], min: usize, max: usize) 
+                -> String 
+            {
+                (0..min + self.below(max - min + 1)).map(|_|
+                    charset[self.below(charset.len())] as char).collect()
+            }
+        }
+
+        const DIGITS: &[u8] = b"0123456789";
+        const PKGVER: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz\
+            ABCDEFGHIJKLMNOPQRSTUVWXYZ._+";
+
+        /// Well-formed versions, `epoch:` and `-pkgrel` each present or not,
+        /// the pkgrel decimal or not
+        fn versions() -> impl Iterator<Item = String> {
+            let mut rng = Rng(0x9e3779b97f4a7c15);
+            (0..5000).map(move |_| {
+                let mut version = String::new();
+                if rng.below(2) == 0 {
+                    version.push_str(&rng.string(DIGITS, 1, 3));
+                    version.push(':')
+                }
+                version.push_str(&rng.string(PKGVER, 1, 12));
+                if rng.below(2) == 0 {
+                    version.push('-');
+                    version.push(char::from(b'1' + rng.below(9) as u8));
+                    version.push_str(&rng.string(DIGITS, 0, 2));
+                    if rng.below(2) == 0 {
+                        version.push('.');
+                        version.push_str(&rng.string(DIGITS, 1, 2))
+                    }
+                }
+                version
+            })
+        }
+
+        #[test]
+        fn well_formed_versions_round_trip() {
+            for version in versions() {
+                assert_eq!(PlainVersion::from(version.as_str()).to_string(),
+                    version);
+                let parsed = PlainVersion::parse_strict(&version)
+                    .unwrap_or_else(|e|panic!("'{}' rejected: {}", version, e));
+                assert_eq!(parsed.to_string(), version);
+            }
+        }
+
+        #[test]
+        fn fixed_versions_round_trip() {
+            for version in ["1.0", "1:1.0", "1.0-1", "1:1.0-1", "1.0-1.1", 
+                "2:1.0-4.5", "0:1.0-1", "1.0+r12.gabcdef-10.2"] 
+            {
+                let plain = PlainVersion::from(version);
+                assert_eq!(plain.to_string(), version);
+                assert_eq!(plain, version.parse::<PlainVersion>().unwrap());
+            }
+        }
+
+        #[test]
+        fn versions_without_pkgver_render_empty() {
+            for version in ["", "1:", "-1", "1:-1"] {
+                assert_eq!(PlainVersion::from(version).to_string(), "")
+            }
+        }
+    }
+}
@@ -0,0 +1,452 @@
+//! Semantic checks over parsed `Pkgbuild`s, like a lite `namcap` for
+//! `PKGBUILD`s, catching smells that are syntactically fine, e.g. the same
+//! dependency listed twice
+
+#[cfg(feature = "format")]
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::{Architecture, CheckDependency, Dependency, MakeDependency,
+    OptionalDependency, Package, PackageArchSpecific, Pkgbuild,
+    PkgbuildArchSpecific, Provide, Severity, SourceWithChecksum};
+
+/// A rule checked by `lint_pkgbuild()`, each with a stable code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum LintRule {
+    /// `PB001`: a dependency listed more than once in the same array, or in
+    /// an arch-specific array and the arch-independent one
+    DuplicatedDependency,
+    /// `PB002`: a package depending on itself
+    SelfDependency,
+    /// `PB003`: a package providing its own `pkgname`
+    ProvidesPkgname,
+    /// `PB004`: a `makedepends` also in `depends`, which are always installed
+    /// for building anyway
+    MakedependInDepends,
+    /// `PB005`: an `optdepends` without a reason
+    OptdependWithoutReason,
+    /// `PB006`: a versioned `provides` with a `-pkgrel`, which makes it fail
+    /// dependencies requiring another `pkgrel` of the same version
+    ProvideWithPkgrel,
+    /// `PB007`: an empty `url`, or one that's not absolute, e.g. without a
+    /// scheme like `example.org`
+    InvalidUrl,
+    /// `PB008`: non-VCS sources while no source has any checksum, i.e. the
+    /// checksum arrays are missing or all `SKIP`
+    MissingChecksums,
+    /// `PB009`: a non-VCS source with only `SKIP` while other sources have
+    /// checksums, e.g. a plain https tarball
+    SkippedChecksum,
+}
+
+impl LintRule {
+    /// All rules, in the order of their codes
+    pub const ALL: [LintRule; 9] = [
+        LintRule::DuplicatedDependency,
+        LintRule::SelfDependency,
+        LintRule::ProvidesPkgname,
+        LintRule::MakedependInDepends,
+        LintRule::OptdependWithoutReason,
+        LintRule::ProvideWithPkgrel,
+        LintRule::InvalidUrl,
+        LintRule::MissingChecksums,
+        LintRule::SkippedChecksum,
+    ];
+
+    /// The stable code, e.g. `PB001`
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintRule::DuplicatedDependency => "PB001",
+            LintRule::SelfDependency => "PB002",
+            LintRule::ProvidesPkgname => "PB003",
+            LintRule::MakedependInDepends => "PB004",
+            LintRule::OptdependWithoutReason => "PB005",
+            LintRule::ProvideWithPkgrel => "PB006",
+            LintRule::InvalidUrl => "PB007",
+            LintRule::MissingChecksums => "PB008",
+            LintRule::SkippedChecksum => "PB009",
+        }
+    }
+
+    /// Look up a rule by its code, e.g. `PB001`
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule|rule.code() == code)
+    }
+
+    /// How serious a violation of this rule is
+    pub fn severity(&self) -> Severity {
+        match self {
+            LintRule::SelfDependency | LintRule::MissingChecksums =>
+                Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// Where a `Lint` was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct LintLocation {
+    /// The field, e.g. `depends`, without the architecture suffix
+    pub field: &'static str,
+    /// The package, `None` for the `PKGBUILD` itself
+    pub pkgname: Option<String>,
+    /// The architecture-specific section, `None` for the arch-independent one
+    pub arch: Option<Architecture>,
+}
+
+#[cfg(feature = "format")]
+impl Display for LintLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.field)?;
+        if let Some(arch) = &self.arch {
+            write!(f, "_{}", arch)?
+        }
+        if let Some(pkgname) = &self.pkgname {
+            write!(f, " of package '{}'", pkgname)?
+        }
+        Ok(())
+    }
+}
+
+/// A violation of a `LintRule`, from `lint_pkgbuild()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Lint {
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub message: String,
+    pub location: LintLocation,
+}
+
+impl Lint {
+    /// The stable code of the rule, e.g. `PB001`
+    pub fn code(&self) -> &'static str {
+        self.rule.code()
+    }
+}
+
+#[cfg(feature = "format")]
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: {}: {}", self.code(), self.severity, self.location,
+            self.message)
+    }
+}
+
+/// Which rules `lint_pkgbuild_with()` checks, all by default
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    disabled: Vec<LintRule>,
+}
+
+impl LintConfig {
+    /// Create a new `LintConfig` with all rules enabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Suppress a rule
+    pub fn disable(&mut self, rule: LintRule) -> &mut Self {
+        if ! self.disabled.contains(&rule) {
+            self.disabled.push(rule)
+        }
+        self
+    }
+
+    /// Re-enable a rule suppressed earlier
+    pub fn enable(&mut self, rule: LintRule) -> &mut Self {
+        self.disabled.retain(|disabled|*disabled != rule);
+        self
+    }
+
+    /// Whether a rule is checked
+    pub fn is_enabled(&self, rule: LintRule) -> bool {
+        ! self.disabled.contains(&rule)
+    }
+}
+
+/// An arch-specific array of a package in effect, the package's own if
+/// overridden in its package function, otherwise the `PKGBUILD`'s
+struct PackageSection<'a, T> {
+    arch: Option<&'a Architecture>,
+    items: &'a [T],
+    overridden: bool,
+}
+
+/// Get the sections of an arch-specific array of a package in effect, the
+/// arch-independent one first
+fn package_sections<'a, T>(
+    pkgbuild: &'a Pkgbuild, pkg: &'a Package,
+    package: fn(&PackageArchSpecific) -> &Option<Vec<T>>,
+    base: fn(&PkgbuildArchSpecific) -> &Vec<T>,
+) -> Vec<PackageSection<'a, T>>
+{
+    let section = |arch, package_section: Option<&'a PackageArchSpecific>,
+        base_section: Option<&'a PkgbuildArchSpecific>|
+    {
+        match package_section.and_then(|section|package(section).as_deref()) {
+            Some(items) => PackageSection { arch, items, overridden: true },
+            None => PackageSection {
+                arch,
+                items: base_section.map(|section|base(section).as_slice())
+                    .unwrap_or_default(),
+                overridden: false
+            },
+        }
+    };
+    std::iter::once(section(None, Some(&pkg.multiarch.any),
+        Some(&pkgbuild.multiarch.any)))
+        .chain(pkg.multiarch.iter_arches().map(|(arch, values)|
+            section(Some(arch), Some(values),
+                pkgbuild.multiarch.get(arch))))
+        .collect()
+}
+
+/// Whether `url` is absolute, i.e. a scheme followed by `://` and anything,
+/// without parsing it any further
+fn is_absolute_url(url: &str) -> bool {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return false
+    };
+    scheme.starts_with(|c: char|c.is_ascii_alphabetic())
+        && scheme.chars().all(
+            |c|c.is_ascii_alphanumeric() || "+-.".contains(c))
+        && ! rest.is_empty()
+}
+
+/// The lints found so far, skipping the disabled rules
+struct Linter<'a> {
+    config: &'a LintConfig,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn lint(
+        &mut self, rule: LintRule, field: &'static str, pkgname: Option<&str>,
+        arch: Option<&Architecture>, message: String
+    ) {
+        if self.config.is_enabled(rule) {
+            self.lints.push(Lint {
+                rule,
+                severity: rule.severity(),
+                message,
+                location: LintLocation {
+                    field,
+                    pkgname: pkgname.map(Into::into),
+                    arch: arch.cloned()
+                }
+            })
+        }
+    }
+
+    /// Check an arch-specific dependency array for `PB001`, `sections` in
+    /// the order of `MultiArch::iter()`, only those `checked` are reported,
+    /// as the others are checked elsewhere
+    fn duplicated_dependencies(
+        &mut self, field: &'static str, pkgname: Option<&str>,
+        sections: &[(Option<&Architecture>, Vec<&str>, bool)]
+    ) {
+        let Some(((_, any, any_checked), arches)) = sections.split_first()
+        else {
+            return
+        };
+        let mut seen: Vec<&str> = Vec::new();
+        for name in any.iter() {
+            if seen.contains(name) {
+                if *any_checked {
+                    self.lint(LintRule::DuplicatedDependency, field, pkgname,
+                        None, format!("'{}' is listed more than once", name))
+                }
+            } else {
+                seen.push(name)
+            }
+        }
+        for (arch, names, checked) in arches.iter() {
+            let mut seen = seen.clone();
+            for name in names.iter() {
+                if seen.contains(name) {
+                    if *checked {
+                        self.lint(LintRule::DuplicatedDependency, field,
+                            pkgname, *arch, format!(
+                                "'{}' is listed more than once", name))
+                    }
+                } else {
+                    seen.push(name)
+                }
+            }
+        }
+    }
+
+    fn pkgbuild(&mut self, pkgbuild: &Pkgbuild) {
+        macro_rules! duplicated {
+            ($($field: ident: $name: expr),+) => {$(
+                let sections: Vec<_> = pkgbuild.multiarch.iter().map(
+                    |(arch, values)|(arch, values.$field.iter()
+                        .map($name).collect(), true)).collect();
+                self.duplicated_dependencies(stringify!($field), None,
+                    &sections);
+            )+};
+        }
+        duplicated!(depends: |dep: &Dependency|dep.name.as_str(),
+            makedepends: |dep: &MakeDependency|dep.name.as_str(),
+            checkdepends: |dep: &CheckDependency|dep.name.as_str(),
+            optdepends: |dep: &OptionalDependency|dep.dep.name.as_str());
+        self.url(None, &pkgbuild.url);
+        let any = &pkgbuild.multiarch.any;
+        for (arch, values) in pkgbuild.multiarch.iter() {
+            // Those of an arch are checked against both of the arch and any
+            let mut depends: Vec<_> = any.depends.iter().collect();
+            if arch.is_some() {
+                depends.extend(values.depends.iter())
+            }
+            for makedepend in values.makedepends.iter() {
+                if depends.iter().any(|depend|depend.name == makedepend.name) {
+                    self.lint(LintRule::MakedependInDepends, "makedepends",
+                        None, arch, format!("'{}' is also in depends",
+                            makedepend.name))
+                }
+            }
+            self.optdepends_without_reason(None, arch, &values.optdepends);
+            self.provides_with_pkgrel(None, arch, &values.provides);
+        }
+    }
+
+    fn provides_with_pkgrel(
+        &mut self, pkgname: Option<&str>, arch: Option<&Architecture>,
+        provides: &[Provide]
+    ) {
+        for provide in provides.iter() {
+            if provide.version.as_ref().is_some_and(
+                |version|! version.pkgrel.is_empty())
+            {
+                self.lint(LintRule::ProvideWithPkgrel, "provides", pkgname,
+                    arch, format!("'{}' has a pkgrel", provide.name))
+            }
+        }
+    }
+
+    fn optdepends_without_reason(
+        &mut self, pkgname: Option<&str>, arch: Option<&Architecture>,
+        optdepends: &[OptionalDependency]
+    ) {
+        for optdepend in optdepends.iter() {
+            if optdepend.reason.is_empty() {
+                self.lint(LintRule::OptdependWithoutReason, "optdepends",
+                    pkgname, arch, format!("'{}' has no reason",
+                        optdepend.dep.name))
+            }
+        }
+    }
+
+    /// Check a `url` for `PB007`, `pkgname` is `None` for the `PKGBUILD`
+    fn url(&mut self, pkgname: Option<&str>, url: &str) {
+        if url.is_empty() {
+            self.lint(LintRule::InvalidUrl, "url", pkgname, None,
+                "url is empty".into())
+        } else if ! is_absolute_url(url) {
+            self.lint(LintRule::InvalidUrl, "url", pkgname, None,
+                format!("'{}' is not an absolute URL", url))
+        }
+    }
+
+    fn package(&mut self, pkgbuild: &Pkgbuild, pkg: &Package) {
+        let pkgname = Some(pkg.pkgname.as_str());
+        if let Some(url) = &pkg.url {
+            self.url(pkgname, url)
+        }
+        let depends = package_sections(pkgbuild, pkg,
+            |section|&section.depends, |section|&section.depends);
+        for section in depends.iter() {
+            if section.items.iter().any(|depend|depend.name == pkg.pkgname) {
+                self.lint(LintRule::SelfDependency, "depends", pkgname,
+                    section.arch, format!("'{}' depends on itself",
+                        pkg.pkgname))
+            }
+        }
+        let provides = package_sections(pkgbuild, pkg,
+            |section|&section.provides, |section|&section.provides);
+        for section in provides.iter() {
+            if section.items.iter().any(|provide|provide.name == pkg.pkgname) {
+                self.lint(LintRule::ProvidesPkgname, "provides", pkgname,
+                    section.arch, format!("'{}' provides itself",
+                        pkg.pkgname))
+            }
+            if section.overridden {
+                self.provides_with_pkgrel(pkgname, section.arch, section.items)
+            }
+        }
+        macro_rules! duplicated {
+            ($($field: ident: $name: expr),+) => {$(
+                let sections: Vec<_> = package_sections(pkgbuild, pkg,
+                    |section|&section.$field, |section|&section.$field)
+                    .into_iter().map(|section|(section.arch, section.items
+                        .iter().map($name).collect(), section.overridden))
+                    .collect();
+                self.duplicated_dependencies(stringify!($field), pkgname,
+                    &sections);
+            )+};
+        }
+        duplicated!(depends: |dep: &Dependency|dep.name.as_str(),
+            checkdepends: |dep: &CheckDependency|dep.name.as_str(),
+            optdepends: |dep: &OptionalDependency|dep.dep.name.as_str());
+        let optdepends = package_sections(pkgbuild, pkg,
+            |section|&section.optdepends, |section|&section.optdepends);
+        for section in optdepends.iter().filter(|section|section.overridden) {
+            self.optdepends_without_reason(pkgname, section.arch,
+                section.items)
+        }
+    }
+
+    fn sources(&mut self, pkgbuild: &Pkgbuild) {
+        fn has_checksum(source: &SourceWithChecksum) -> bool {
+            source.cksum.is_some() || source.md5sum.is_some()
+                || source.sha1sum.is_some() || source.sha224sum.is_some()
+                || source.sha256sum.is_some() || source.sha384sum.is_some()
+                || source.sha512sum.is_some() || source.b2sum.is_some()
+        }
+        let any_checksum = pkgbuild.multiarch.iter().any(|(_, values)|
+            values.sources_with_checksums.iter().any(has_checksum));
+        for (arch, values) in pkgbuild.multiarch.iter() {
+            for source in values.sources_with_checksums.iter() {
+                if source.source.protocol.is_vcs() || has_checksum(source) {
+                    continue
+                }
+                if any_checksum {
+                    self.lint(LintRule::SkippedChecksum, "source", None, arch,
+                        format!("'{}' is not VCS but its checksums are SKIP",
+                            source.source.name))
+                } else {
+                    self.lint(LintRule::MissingChecksums, "source", None, arch,
+                        format!("'{}' is not VCS but has no checksum",
+                            source.source.name))
+                }
+            }
+        }
+    }
+}
+
+/// Check a `Pkgbuild` against all `LintRule`s
+pub fn lint_pkgbuild(pkgbuild: &Pkgbuild) -> Vec<Lint> {
+    lint_pkgbuild_with(pkgbuild, &LintConfig::default())
+}
+
+/// Check a `Pkgbuild` against the `LintRule`s enabled in `config`, the lints
+/// are sorted by their rules, and by their locations in the `PKGBUILD` for
+/// the same rule
+pub fn lint_pkgbuild_with(pkgbuild: &Pkgbuild, config: &LintConfig)
+    -> Vec<Lint>
+{
+    let mut linter = Linter { config, lints: Vec::new() };
+    linter.pkgbuild(pkgbuild);
+    for pkg in pkgbuild.pkgs.iter() {
+        linter.package(pkgbuild, pkg)
+    }
+    linter.sources(pkgbuild);
+    let mut lints = linter.lints;
+    lints.sort_by_key(|lint|lint.rule);
+    lints
+}
@@ -0,0 +1,467 @@
+//! Surgical in-place edits of existing `PKGBUILD` files, keeping everything
+//! not edited byte-for-byte as it was
+
+use std::{ffi::{OsStr, OsString}, ops::Range, path::Path};
+
+use crate::{Error, Pkgbuild, PlainVersion, Result, SourceWithChecksum};
+
+/// A word in a `PKGBUILD`, either the value of a scalar or an element of an
+/// array
+struct Word {
+    /// The byte range, including the quotes
+    range: Range<usize>,
+    /// The quote, `None` if unquoted
+    quote: Option<u8>,
+}
+
+/// A top-level assignment found in a `PKGBUILD` by 
+/// `find_top_level_assignments()`
+struct Assignment<'a> {
+    name: &'a str,
+    /// 1-based
+    line: usize,
+    /// The byte offset of the start of the line
+    line_start: usize,
+    /// The value, for an array the range covers the parentheses and the quote
+    /// is always `None`
+    value: Word,
+    /// The elements, `None` if this is a scalar
+    elements: Option<Vec<Word>>,
+    /// Whether the value is computed, e.g. by arithmetic or command 
+    /// substitution, or a mix of quoting styles that could not be rewritten
+    computed: bool,
+}
+
+/// Whether the byte ends an unquoted word
+fn is_word_end(byte: Option<&u8>) -> bool {
+    byte.is_none_or(|byte|matches!(byte, 
+        b' ' | b'\t' | b'\n' | b';' | b'&' | b'|' | b')'))
+}
+
+/// Parse a word starting at `start`, return it and whether it's computed
+fn parse_word(content: &[u8], start: usize) -> (Word, bool) {
+    let (end, quote, mut computed) = match content.get(start) {
+        Some(quote @ b'\'') => match content[start + 1..].iter()
+            .position(|byte|byte == quote) 
+        {
+            Some(len) => (start + len + 2, Some(*quote), false),
+            None => (content.len(), Some(*quote), true),
+        },
+        Some(quote @ b'"') => {
+            let mut end = start + 1;
+            let mut computed = true;
+            while let Some(byte) = content.get(end) {
+                match byte {
+                    b'\\' => end += 1,
+                    b'"' => { computed = false; break },
+                    b'$' | b'`' => computed = true,
+                    _ => (),
+                }
+                end += 1
+            }
+            (end + 1, Some(*quote), computed)
+        },
+        _ => {
+            let mut end = start;
+            let mut computed = false;
+            while ! is_word_end(content.get(end)) {
+                if matches!(content[end], 
+                    b'$' | b'`' | b'(' | b'\'' | b'"' | b'\\' | b'{') 
+                {
+                    computed = true
+                }
+                end += 1
+            }
+            (end, None, computed)
+        },
+    };
+    // A quoted value glued with anything else, e.g. `'1.0'.$minor`
+    let end = end.min(content.len());
+    if ! is_word_end(content.get(end)) {
+        computed = true
+    }
+    (Word { range: start..end, quote }, computed)
+}
+
+/// Parse an array starting at the `(` at `start`, return its range, its
+/// elements and whether any element is computed
+fn parse_array(content: &[u8], start: usize) -> (Word, Vec<Word>, bool) {
+    let mut elements = Vec::new();
+    let mut computed = false;
+    let mut index = start + 1;
+    while let Some(byte) = content.get(index) {
+        match byte {
+            b' ' | b'\t' | b'\n' => index += 1,
+            b'#' => while content.get(index).is_some_and(|byte|*byte != b'\n') {
+                index += 1
+            },
+            b')' => return (Word { range: start..index + 1, quote: None }, 
+                elements, computed),
+            _ => {
+                let (element, element_computed) = parse_word(content, index);
+                computed |= element_computed;
+                // A lone `\` or the like, never moves forward
+                index = element.range.end.max(index + 1);
+                elements.push(element)
+            },
+        }
+    }
+    (Word { range: start..content.len(), quote: None }, elements, true)
+}
+
+/// Find the assignments of `names` at top level, i.e. not inside functions,
+/// quotes or arrays, at the start of a command
+fn find_top_level_assignments<'a>(content: &[u8], names: &[&'a str]) 
+    -> Vec<Assignment<'a>> 
+{
+    let mut assignments = Vec::new();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut braces = 0_usize;
+    let mut parens = 0_usize;
+    let mut quote = None;
+    let mut command_start = true;
+    let mut index = 0;
+    while let Some(byte) = content.get(index) {
+        let byte = *byte;
+        if byte == b'\n' {
+            line += 1;
+            line_start = index + 1;
+        }
+        match quote {
+            Some(b'\'') => if byte == b'\'' { quote = None },
+            Some(_) => match byte {
+                b'\\' => index += 1,
+                b'"' => quote = None,
+                _ => (),
+            },
+            None => match byte {
+                b'\n' | b';' | b'&' | b'|' => command_start = true,
+                b' ' | b'\t' => (),
+                b'#' if index == 0 || matches!(content[index - 1], 
+                    b' ' | b'\t' | b'\n' | b';') => 
+                {
+                    while content.get(index + 1).is_some_and(|byte|*byte != b'\n') {
+                        index += 1
+                    }
+                },
+                b'\\' => {
+                    index += 1;
+                    command_start = false
+                },
+                b'\'' | b'"' => {
+                    quote = Some(byte);
+                    command_start = false
+                },
+                b'{' => { braces += 1; command_start = true },
+                b'}' => braces = braces.saturating_sub(1),
+                b'(' => { parens += 1; command_start = true },
+                b')' => parens = parens.saturating_sub(1),
+                _ => {
+                    let name = (command_start && braces == 0 && parens == 0)
+                        .then(||names.iter().find(|name|
+                            content[index..].starts_with(name.as_bytes()) 
+                            && content.get(index + name.len()) == Some(&b'=')))
+                        .flatten();
+                    if let Some(name) = name {
+                        let start = index + name.len() + 1;
+                        let (value, elements, computed) = 
+                            if content.get(start) == Some(&b'(') {
+                                let (value, elements, computed) = 
+                                    parse_array(content, start);
+                                (value, Some(elements), computed)
+                            } else {
+                                let (value, computed) = 
+                                    parse_word(content, start);
+                                (value, None, computed)
+                            };
+                        // Further assignments could follow on the same line
+                        index = value.range.end;
+                        assignments.push(Assignment { 
+                            name, line, line_start, value, elements, computed 
+                        });
+                        // A value could span multiple lines
+                        for (offset, byte) in content[start..index].iter()
+                            .enumerate()
+                        {
+                            if *byte == b'\n' {
+                                line += 1;
+                                line_start = start + offset + 1
+                            }
+                        }
+                        continue
+                    }
+                    command_start = false
+                },
+            },
+        }
+        index += 1
+    }
+    assignments
+}
+
+/// Quote a value like the one it replaces
+fn quoted_like(value: &str, quote: Option<u8>) -> String {
+    match quote {
+        Some(b'"') => {
+            let mut quoted = String::from("\"");
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | '$' | '`') {
+                    quoted.push('\\')
+                }
+                quoted.push(c)
+            }
+            quoted.push('"');
+            quoted
+        },
+        None if value.chars().all(|c|c.is_ascii_alphanumeric() 
+            || matches!(c, '.' | '_' | '+' | '~' | '-' | '@' | '%' | ',' | ':'))
+            => value.into(),
+        _ => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+/// Read a `PKGBUILD` to be edited
+fn read_pkgbuild(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| {
+        log::error!("Failed to read PKGBUILD '{}': {}", path.display(), e);
+        e.into()
+    })
+}
+
+fn unrewritable(path: &Path, variable: &str, line: Option<usize>) -> Error {
+    log::error!("Could not rewrite {} of PKGBUILD '{}'", variable, 
+        path.display());
+    Error::UnrewritableAssignment { 
+        path: path.into(), variable: variable.into(), line }
+}
+
+/// Apply `edits`, each replacing a byte range, to `content` and write it back
+/// to `path` if anything changed. The result is written to a temporary file 
+/// next to it first, then renamed over it, so it's never left half-written.
+fn write_edited(
+    path: &Path, content: &[u8], mut edits: Vec<(Range<usize>, String)>
+) -> Result<()>
+{
+    edits.sort_by_key(|(range, _)|range.start);
+    let mut edited = Vec::with_capacity(content.len());
+    let mut last = 0;
+    for (range, text) in edits {
+        edited.extend_from_slice(&content[last..range.start]);
+        edited.extend_from_slice(text.as_bytes());
+        last = range.end
+    }
+    edited.extend_from_slice(&content[last..]);
+    if edited == content {
+        return Ok(())
+    }
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or(OsStr::new("PKGBUILD")));
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    let result = std::fs::write(&temp_path, &edited)
+        .and_then(|_|std::fs::metadata(path))
+        .and_then(|metadata|
+            std::fs::set_permissions(&temp_path, metadata.permissions()))
+        .and_then(|_|std::fs::rename(&temp_path, path));
+    if let Err(e) = result {
+        log::error!("Failed to write PKGBUILD '{}': {}", path.display(), e);
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e.into())
+    }
+    Ok(())
+}
+
+/// Update the `pkgver`, `pkgrel` and `epoch` of an existing `PKGBUILD` in 
+/// place, only the values of their assignments at top level are replaced, so
+/// the quoting style, comments and everything else are kept.
+/// 
+/// An `epoch` missing from the `PKGBUILD` is added before `pkgver` if needed.
+/// Assignments whose values are computed, e.g. `pkgver=$(date +%Y)`, are never
+/// clobbered, the update then fails with `Error::UnrewritableAssignment`, as
+/// it also does if `pkgver` or `pkgrel` is missing. The file is written to a 
+/// temporary file next to it first, then renamed over it, so it's never left
+/// half-written.
+pub fn update_version_in_file<P: AsRef<Path>>(path: P, version: &PlainVersion)
+    -> Result<()>
+{
+    let path = path.as_ref();
+    let content = read_pkgbuild(path)?;
+    let assignments = find_top_level_assignments(
+        &content, &["pkgver", "pkgrel", "epoch"]);
+    if let Some(assignment) = assignments.iter().find(|assignment|
+        assignment.computed || assignment.elements.is_some()) 
+    {
+        return Err(unrewritable(path, assignment.name, Some(assignment.line)))
+    }
+    let mut edits = Vec::new();
+    for name in ["pkgver", "pkgrel"] {
+        if ! assignments.iter().any(|assignment|assignment.name == name) {
+            return Err(unrewritable(path, name, None))
+        }
+    }
+    if ! version.epoch.is_empty() 
+        && ! assignments.iter().any(|assignment|assignment.name == "epoch")
+    {
+        if let Some(pkgver) = assignments.iter().find(|assignment|
+            assignment.name == "pkgver") 
+        {
+            edits.push((pkgver.line_start..pkgver.line_start, format!(
+                "epoch={}\n", quoted_like(&version.epoch, None))))
+        }
+    }
+    for assignment in assignments.iter() {
+        let value = match assignment.name {
+            "pkgver" => &version.pkgver,
+            "pkgrel" => &version.pkgrel,
+            _ => &version.epoch,
+        };
+        edits.push((assignment.value.range.clone(), 
+            quoted_like(value, assignment.value.quote)))
+    }
+    write_edited(path, &content, edits)
+}
+
+/// The checksum arrays makepkg supports, in makepkg's order
+const CHECKSUM_ARRAYS: [&str; 8] = ["cksums", "md5sums", "sha1sums", 
+    "sha224sums", "sha256sums", "sha384sums", "sha512sums", "b2sums"];
+
+/// The checksums of `sources` for the array `kind`, e.g. `sha256sums`, as 
+/// written in a `PKGBUILD`, `SKIP` for VCS sources and those without such 
+/// checksum
+fn checksum_values(sources: &[&SourceWithChecksum], kind: &str) 
+    -> Vec<String> 
+{
+    sources.iter().map(|source| {
+        if source.source.protocol.is_vcs() {
+            return "SKIP".into()
+        }
+        match kind {
+            "cksums" => source.cksum.map(|sum|sum.to_string()),
+            "md5sums" => source.md5sum.map(hex::encode),
+            "sha1sums" => source.sha1sum.map(hex::encode),
+            "sha224sums" => source.sha224sum.map(hex::encode),
+            "sha256sums" => source.sha256sum.map(hex::encode),
+            "sha384sums" => source.sha384sum.map(hex::encode),
+            "sha512sums" => source.sha512sum.map(hex::encode),
+            "b2sums" => source.b2sum.map(hex::encode),
+            _ => None,
+        }.unwrap_or_else(||"SKIP".into())
+    }).collect()
+}
+
+/// Format `values` as an array laid out like the array of `template`, i.e.
+/// with the same text before the first element and after the last element, 
+/// and the same quoting, one element per line if it was written that way
+fn array_like(content: &[u8], template: &Assignment, values: &[String]) 
+    -> String 
+{
+    let text = |range: Range<usize>|
+        String::from_utf8_lossy(&content[range]).into_owned();
+    // The line break and the indentation of the last line in `text`
+    let line_break = |text: &str|text.rfind('\n').map(|index|
+        text[index..].to_string());
+    let range = &template.value.range;
+    let elements = template.elements.as_deref().unwrap_or_default();
+    let (prefix, suffix) = match (elements.first(), elements.last()) {
+        (Some(first), Some(last)) => (text(range.start..first.range.start), 
+            text(last.range.end..range.end)),
+        _ => ("(".into(), ")".into()),
+    };
+    let separator = match (elements.first(), elements.get(1)) {
+        (Some(first), Some(second)) => 
+            line_break(&text(first.range.end..second.range.start)),
+        _ => line_break(&prefix),
+    }.unwrap_or_else(||" ".into());
+    let quote = elements.first().map_or(Some(b'\''), |first|first.quote);
+    let values: Vec<_> = values.iter().map(|value|quoted_like(value, quote))
+        .collect();
+    format!("{}{}{}", prefix, values.join(&separator), suffix)
+}
+
+/// Update the checksum arrays of an existing `PKGBUILD` in place, like 
+/// `updpkgsums`, with `pkgbuild` being the parsed result of it and `computed`
+/// the sources with freshly computed checksums.
+/// 
+/// Every checksum array of the `PKGBUILD` and its architecture-specific 
+/// variants, e.g. `sha256sums` and `b2sums_x86_64`, is updated. A source not
+/// in `computed` keeps its parsed checksums, VCS sources always get `SKIP`, 
+/// as do those without a checksum of the array's kind, so `computed` should 
+/// carry every kind the `PKGBUILD` uses. A section with sources but no 
+/// checksum array gets a `sha256sums` right after its `source`, like 
+/// makepkg's default `INTEGRITY_CHECK`.
+/// 
+/// Elements are replaced one by one in their own quoting, keeping comments
+/// and the layout. If the number of sources changed, the array is rewritten
+/// following its original layout instead. Computed arrays fail the update
+/// with `Error::UnrewritableAssignment`. The file is written to a temporary
+/// file next to it first, then renamed over it.
+pub fn update_checksums_in_file<P: AsRef<Path>>(
+    path: P, pkgbuild: &Pkgbuild, computed: &[SourceWithChecksum]
+) -> Result<()>
+{
+    let path = path.as_ref();
+    let content = read_pkgbuild(path)?;
+    let sections: Vec<_> = pkgbuild.multiarch.iter().map(|(arch, values)|
+        (arch.map(|arch|format!("_{}", arch.as_ref())).unwrap_or_default(),
+            &values.sources_with_checksums)).collect();
+    let names: Vec<_> = sections.iter().flat_map(|(suffix, _)|
+        std::iter::once("source").chain(CHECKSUM_ARRAYS).map(move |name|
+            format!("{}{}", name, suffix))).collect();
+    let names: Vec<_> = names.iter().map(String::as_str).collect();
+    let assignments = find_top_level_assignments(&content, &names);
+    // Only the last assignment takes effect
+    let find = |name: &str|assignments.iter().rev().find(|assignment|
+        assignment.name == name);
+    let mut edits = Vec::new();
+    for (suffix, sources) in sections.iter() {
+        let sources: Vec<_> = sources.iter().map(|source|computed.iter()
+            .find(|computed|computed.source == source.source)
+            .unwrap_or(source)).collect();
+        let mut declared = false;
+        for kind in CHECKSUM_ARRAYS {
+            let name = format!("{}{}", kind, suffix);
+            let Some(assignment) = find(&name) else {
+                continue
+            };
+            declared = true;
+            let elements = match &assignment.elements {
+                Some(elements) if ! assignment.computed => elements,
+                _ => return Err(unrewritable(path, &name, 
+                    Some(assignment.line))),
+            };
+            let values = checksum_values(&sources, kind);
+            if elements.len() == values.len() {
+                for (element, value) in elements.iter().zip(values) {
+                    edits.push((element.range.clone(), 
+                        quoted_like(&value, element.quote)))
+                }
+            } else {
+                edits.push((assignment.value.range.clone(), 
+                    array_like(&content, assignment, &values)))
+            }
+        }
+        if declared || sources.is_empty() {
+            continue
+        }
+        let name = format!("source{}", suffix);
+        let source = match find(&name) {
+            Some(source) if source.elements.is_some() => source,
+            _ => return Err(unrewritable(path, &name, None)),
+        };
+        // On its own line after the line `source` ends on
+        let array = array_like(&content, source, 
+            &checksum_values(&sources, "sha256sums"));
+        let end = source.value.range.end;
+        let (start, text) = match content[end..].iter()
+            .position(|byte|*byte == b'\n') 
+        {
+            Some(offset) => (end + offset + 1, 
+                format!("sha256sums{}={}\n", suffix, array)),
+            None => (content.len(), 
+                format!("\nsha256sums{}={}", suffix, array)),
+        };
+        edits.push((start..start, text))
+    }
+    write_edited(path, &content, edits)
+}
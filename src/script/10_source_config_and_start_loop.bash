@@ -1,7 +1,65 @@
-source_makepkg_config
-_ifs_stored="${IFS}"
-while read -r _line; do
+case "${_use_makepkg_config}" in
+  file)
+    if [[ ! -r "${MAKEPKG_CONF}" ]]; then
+      echo "Failed to read makepkg config '${MAKEPKG_CONF}'" >&2
+      exit 252
+    fi
+    source_makepkg_config
+    ;;
+  inline)
+    eval "${_makepkg_config_inline}"
+    ;;
+esac
+# Dump values as key:value lines, with backslashes and newlines escaped so
+# every value stays in one line
+_dump() {
+  local _key="$1"
+  shift
+  (( $# )) || set -- ''
+  set -- "${@//\\/\\\\}"
+  set -- "${@//$'\n'/\\n}"
+  printf '%s\n' "${@/#/${_key}:}"
+}
+# Escape backslashes and NULs written to stderr by a PKGBUILD, so they could
+# not be taken as the NUL-wrapped markers
+_escape_stderr() {
+  local _chunk
+  while IFS= read -r -d '' _chunk; do
+    printf '%s\\0' "${_chunk//\\/\\\\}"
+  done
+  printf '%s' "${_chunk//\\/\\\\}"
+}
+_delimiter=$'\n'
+_stderr_markers=''
+for _arg in "$@"; do
+  case "${_arg}" in
+    --nul)
+      _delimiter=''
+      ;;
+    --stderr-markers)
+      _stderr_markers='y'
+      ;;
+    --carch=*)
+      CARCH="${_arg#--carch=}"
+      ;;
+  esac
+done
+# The markers go to the original stderr, while the stderr of each PKGBUILD
+# goes through _escape_stderr, which is waited for before the next marker
+[[ "${_stderr_markers}" ]] && exec {_stderr_fd}>&2
+while IFS= read -r -d "${_delimiter}" _line; do
+# Marks the start of every input, so PKGBUILDs that quit without dumping
+# anything could still be told apart
+echo INPUT
+if [[ "${_stderr_markers}" ]]; then
+  printf '\0%s\0' "${_line}" >&"${_stderr_fd}"
+  exec 2> >(_escape_stderr >&"${_stderr_fd}")
+  _stderr_pid=$!
+fi
 (
-  source "${_line}"
-  echo PKGBUILD
-  pkgbase="${pkgbase:-${pkgname}}"
+  [[ "${_stderr_markers}" ]] && exec {_stderr_fd}>&-
+  # Functions exported by the caller are inherited, only count the ones
+  # defined by the PKGBUILD itself
+  mapfile -t _funcs < <(compgen -A function package_)
+  unset -f pkgver prepare build check package "${_funcs[@]}"
+  # Likewise for variables, so all of those dumped are set even with set -u
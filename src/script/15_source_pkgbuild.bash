@@ -0,0 +1,14 @@
+  # Like makepkg, a PKGBUILD failing to be sourced is an error
+  # shellcheck source=/dev/null
+  if ! source "${_line}"; then
+    echo "Failed to source '${_line}'" >&2
+    exit 1
+  fi
+  # Like makepkg, a PKGBUILD without pkgname is not one, leave it out so it
+  # is reported as missing
+  if [[ -z "${pkgname[0]-}" ]]; then
+    echo "No pkgname defined in '${_line}'" >&2
+    exit
+  fi
+  echo PKGBUILD
+  pkgbase="${pkgbase:-${pkgname[0]}}"
@@ -1,7 +1,19 @@
-  if [[ $(type -t pkgver) == function ]]; then
+  if declare -F pkgver > /dev/null; then
     echo pkgver_func:y
   else
     echo pkgver_func:n
   fi
+  if [[ "${_dump_function_bodies}" ]]; then
+    mapfile -t _funcs < <(compgen -A function package_)
+    for _func in pkgver prepare build check package "${_funcs[@]}"; do
+      declare -F "${_func}" > /dev/null || continue
+      _dump function_"${_func}" "$(declare -f "${_func}")"
+    done
+  fi
+  # Scalars expand to themselves with [@], so these could be dumped the same
+  for _extra in "${_extra_variables[@]}"; do
+    _extra_array="${_extra}[@]"
+    _dump extra_"${_extra}" ${!_extra_array+"${!_extra_array}"}
+  done
   echo ARCH
   echo arch:any
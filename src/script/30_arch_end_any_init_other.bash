@@ -1,11 +1,21 @@
   echo END
   _arch_collapsed="${arch[*]}"
-  if [[ " ${_arch_collapsed} " == *any* ]]; then
-    if [[ "${#_arch_collapsed}" != 3 ]]; then
-      echo "ERROR: PKGBUILD Architecture 'any' found when multiple architecture defined"
-      exit -1
+  if [[ " ${_arch_collapsed} " == *any* && "${#_arch_collapsed}" != 3 ]]; then
+    if [[ -z "${_tolerate_any_with_arch}" ]]; then
+      echo "ERROR: PKGBUILD Architecture 'any' found when multiple architecture defined" >&2
+      exit 255
     fi
-  else
+    _dump warning "Architecture 'any' mixed with others in '${_arch_collapsed}', dropped 'any'"
+    _arches=()
+    for _arch in "${arch[@]}"; do
+      [[ "${_arch}" != any ]] && _arches+=("${_arch}")
+    done
+    arch=("${_arches[@]}")
+    _arch_collapsed="${arch[*]}"
+  fi
+  # Tell arch=(any) apart from a missing arch, both have no sections
+  [[ "${_arch_collapsed}" == any ]] && echo arch:any
+  if [[ " ${_arch_collapsed} " != *any* ]]; then
     for _arch in "${arch[@]}"; do
       echo ARCH
-      echo arch:"${_arch}"
+      _dump arch "${_arch}"
@@ -5,20 +5,19 @@
   for _pkgname in "${pkgname[@]}"; do
   (
     echo PACKAGE
-    echo pkgname:"${_pkgname}"
+    _dump pkgname "${_pkgname}"
     if [[ $(type -t package_"${_pkgname}") == function ]]; then
       _pkg_func=package_"${_pkgname}"
     elif [[ $(type -t package) == function ]]; then
       if [[ "${_pkg_used}" ]]; then
-        echo "Did not find package split function for ${_pkgname}"
-        exit -2
+        echo "Did not find package split function for ${_pkgname}" >&2
+        exit 254
       fi
       _pkg_func=package
-      _pkg_used=y
     elif [[ -z $(type -t build) ]]; then
       echo END
       exit
     else
-      echo "No package split function for ${_pkgname}"
-      exit -2
+      echo "No package split function for ${_pkgname}" >&2
+      exit 254
     fi
@@ -1,13 +1,13 @@
-    _arch_backup=("${arch[@]}")
-    IFS=$'\n'
-    _lines=($(declare -f "${_pkg_func}"))
-    IFS="${_ifs_stored}"
+    mapfile -t _lines < <(declare -f "${_pkg_func}")
     _buffer=
     for _line in "${_lines[@]:2:$((${#_lines[@]}-3))}"; do 
       if [[ "${_buffer}" ]]; then
-        _buffer+="
-        ${_line}"
-        if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
+        _buffer+=$'\n'"${_line}"
+        if [[ "${_buffer#*=}" != \(* ]]; then
+          # A plain value stays incomplete until its quotes are matched
+          eval "${_buffer}" 2>/dev/null || (( $? != 2 )) || continue
+          _buffer=
+        elif [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
           eval "${_buffer}"
           _buffer=
         fi
@@ -3,4 +3,7 @@
     fi
     echo END
   ) || exit $?
+  # Only one package could use the package function, this is recorded out of
+  # the subshell so later ones see it
+  [[ $(type -t package_"${_pkgname}") == function ]] || _pkg_used=y
   done
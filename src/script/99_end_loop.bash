@@ -0,0 +1,5 @@
+if [[ "${_stderr_markers}" ]]; then
+  exec 2>&"${_stderr_fd}"
+  wait "${_stderr_pid}"
+fi
+done
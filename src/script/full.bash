@@ -1,77 +1,173 @@
-source_makepkg_config
-_ifs_stored="${IFS}"
-while read -r _line; do
+case "${_use_makepkg_config}" in
+  file)
+    if [[ ! -r "${MAKEPKG_CONF}" ]]; then
+      echo "Failed to read makepkg config '${MAKEPKG_CONF}'" >&2
+      exit 252
+    fi
+    source_makepkg_config
+    ;;
+  inline)
+    eval "${_makepkg_config_inline}"
+    ;;
+esac
+# Dump values as key:value lines, with backslashes and newlines escaped so
+# every value stays in one line
+_dump() {
+  local _key="$1"
+  shift
+  (( $# )) || set -- ''
+  set -- "${@//\\/\\\\}"
+  set -- "${@//$'\n'/\\n}"
+  printf '%s\n' "${@/#/${_key}:}"
+}
+# Escape backslashes and NULs written to stderr by a PKGBUILD, so they could
+# not be taken as the NUL-wrapped markers
+_escape_stderr() {
+  local _chunk
+  while IFS= read -r -d '' _chunk; do
+    printf '%s\\0' "${_chunk//\\/\\\\}"
+  done
+  printf '%s' "${_chunk//\\/\\\\}"
+}
+_delimiter=$'\n'
+_stderr_markers=''
+for _arg in "$@"; do
+  case "${_arg}" in
+    --nul)
+      _delimiter=''
+      ;;
+    --stderr-markers)
+      _stderr_markers='y'
+      ;;
+    --carch=*)
+      CARCH="${_arg#--carch=}"
+      ;;
+  esac
+done
+# The markers go to the original stderr, while the stderr of each PKGBUILD
+# goes through _escape_stderr, which is waited for before the next marker
+[[ "${_stderr_markers}" ]] && exec {_stderr_fd}>&2
+while IFS= read -r -d "${_delimiter}" _line; do
+# Marks the start of every input, so PKGBUILDs that quit without dumping
+# anything could still be told apart
+echo INPUT
+if [[ "${_stderr_markers}" ]]; then
+  printf '\0%s\0' "${_line}" >&"${_stderr_fd}"
+  exec 2> >(_escape_stderr >&"${_stderr_fd}")
+  _stderr_pid=$!
+fi
 (
-  source "${_line}"
+  [[ "${_stderr_markers}" ]] && exec {_stderr_fd}>&-
+  # Functions exported by the caller are inherited, only count the ones
+  # defined by the PKGBUILD itself
+  mapfile -t _funcs < <(compgen -A function package_)
+  unset -f pkgver prepare build check package "${_funcs[@]}"
+  # Likewise for variables, so all of those dumped are set even with set -u
+  pkgbase='' pkgver='' pkgrel='' epoch='' pkgdesc='' url='' install='' changelog=''
+  pkgname=() arch=()
+  license=() validpgpkeys=() noextract=() groups=() backup=() options=()
+  source=() cksums=() md5sums=() sha1sums=() sha224sums=() sha256sums=() sha384sums=() sha512sums=() b2sums=() depends=() makedepends=() checkdepends=() optdepends=() conflicts=() provides=() replaces=()
+  # Like makepkg, a PKGBUILD failing to be sourced is an error
+  # shellcheck source=/dev/null
+  if ! source "${_line}"; then
+    echo "Failed to source '${_line}'" >&2
+    exit 1
+  fi
+  # Like makepkg, a PKGBUILD without pkgname is not one, leave it out so it
+  # is reported as missing
+  if [[ -z "${pkgname[0]-}" ]]; then
+    echo "No pkgname defined in '${_line}'" >&2
+    exit
+  fi
   echo PKGBUILD
-  pkgbase="${pkgbase:-${pkgname}}"
-  echo pkgbase:"${pkgbase}"
-  echo pkgver:"${pkgver}"
-  echo pkgrel:"${pkgrel}"
-  echo epoch:"${epoch}"
-  echo pkgdesc:"${pkgdesc}"
-  echo url:"${url}"
-  echo install:"${install}"
-  echo changelog:"${changelog}"
-  license=("${license[@]//
-/ }")
-  printf 'license:%s\n' "${license[@]}"
-  printf 'validpgpkeys:%s\n' "${validpgpkeys[@]}"
-  printf 'noextract:%s\n' "${noextract[@]}"
-  printf 'groups:%s\n' "${groups[@]}"
-  printf 'backup:%s\n' "${backup[@]}"
-  printf 'options:%s\n' "${options[@]}"
-  if [[ $(type -t pkgver) == function ]]; then
+  pkgbase="${pkgbase:-${pkgname[0]}}"
+  if [[ "${pkgbase}" == *[$'\\\n']* ]]; then _dump pkgbase "${pkgbase}"; else printf 'pkgbase:%s\n' "${pkgbase}"; fi
+  if [[ "${pkgver}" == *[$'\\\n']* ]]; then _dump pkgver "${pkgver}"; else printf 'pkgver:%s\n' "${pkgver}"; fi
+  if [[ "${pkgrel}" == *[$'\\\n']* ]]; then _dump pkgrel "${pkgrel}"; else printf 'pkgrel:%s\n' "${pkgrel}"; fi
+  if [[ "${epoch}" == *[$'\\\n']* ]]; then _dump epoch "${epoch}"; else printf 'epoch:%s\n' "${epoch}"; fi
+  if [[ "${pkgdesc}" == *[$'\\\n']* ]]; then _dump pkgdesc "${pkgdesc}"; else printf 'pkgdesc:%s\n' "${pkgdesc}"; fi
+  if [[ "${url}" == *[$'\\\n']* ]]; then _dump url "${url}"; else printf 'url:%s\n' "${url}"; fi
+  if [[ "${install}" == *[$'\\\n']* ]]; then _dump install "${install}"; else printf 'install:%s\n' "${install}"; fi
+  if [[ "${changelog}" == *[$'\\\n']* ]]; then _dump changelog "${changelog}"; else printf 'changelog:%s\n' "${changelog}"; fi
+  if [[ "${license[*]-}" == *[$'\\\n']* ]]; then _dump license ${license[@]+"${license[@]}"}; else printf 'license:%s\n' ${license[@]+"${license[@]}"}; fi
+  if [[ "${validpgpkeys[*]-}" == *[$'\\\n']* ]]; then _dump validpgpkeys ${validpgpkeys[@]+"${validpgpkeys[@]}"}; else printf 'validpgpkeys:%s\n' ${validpgpkeys[@]+"${validpgpkeys[@]}"}; fi
+  if [[ "${noextract[*]-}" == *[$'\\\n']* ]]; then _dump noextract ${noextract[@]+"${noextract[@]}"}; else printf 'noextract:%s\n' ${noextract[@]+"${noextract[@]}"}; fi
+  if [[ "${groups[*]-}" == *[$'\\\n']* ]]; then _dump groups ${groups[@]+"${groups[@]}"}; else printf 'groups:%s\n' ${groups[@]+"${groups[@]}"}; fi
+  if [[ "${backup[*]-}" == *[$'\\\n']* ]]; then _dump backup ${backup[@]+"${backup[@]}"}; else printf 'backup:%s\n' ${backup[@]+"${backup[@]}"}; fi
+  if [[ "${options[*]-}" == *[$'\\\n']* ]]; then _dump options ${options[@]+"${options[@]}"}; else printf 'options:%s\n' ${options[@]+"${options[@]}"}; fi
+  if declare -F pkgver > /dev/null; then
     echo pkgver_func:y
   else
     echo pkgver_func:n
   fi
+  if [[ "${_dump_function_bodies}" ]]; then
+    mapfile -t _funcs < <(compgen -A function package_)
+    for _func in pkgver prepare build check package "${_funcs[@]}"; do
+      declare -F "${_func}" > /dev/null || continue
+      _dump function_"${_func}" "$(declare -f "${_func}")"
+    done
+  fi
+  # Scalars expand to themselves with [@], so these could be dumped the same
+  for _extra in "${_extra_variables[@]}"; do
+    _extra_array="${_extra}[@]"
+    _dump extra_"${_extra}" ${!_extra_array+"${!_extra_array}"}
+  done
   echo ARCH
   echo arch:any
-  printf 'source:%s\n' "${source[@]}"
-  printf 'cksums:%s\n' "${cksums[@]}"
-  printf 'md5sums:%s\n' "${md5sums[@]}"
-  printf 'sha1sums:%s\n' "${sha1sums[@]}"
-  printf 'sha224sums:%s\n' "${sha224sums[@]}"
-  printf 'sha256sums:%s\n' "${sha256sums[@]}"
-  printf 'sha384sums:%s\n' "${sha384sums[@]}"
-  printf 'sha512sums:%s\n' "${sha512sums[@]}"
-  printf 'b2sums:%s\n' "${b2sums[@]}"
-  printf 'depends:%s\n' "${depends[@]}"
-  printf 'makedepends:%s\n' "${makedepends[@]}"
-  printf 'checkdepends:%s\n' "${checkdepends[@]}"
-  printf 'optdepends:%s\n' "${optdepends[@]}"
-  printf 'conflicts:%s\n' "${conflicts[@]}"
-  printf 'provides:%s\n' "${provides[@]}"
-  printf 'replaces:%s\n' "${replaces[@]}"
+  if [[ "${source[*]-}" == *[$'\\\n']* ]]; then _dump source ${source[@]+"${source[@]}"}; else printf 'source:%s\n' ${source[@]+"${source[@]}"}; fi
+  if [[ "${cksums[*]-}" == *[$'\\\n']* ]]; then _dump cksums ${cksums[@]+"${cksums[@]}"}; else printf 'cksums:%s\n' ${cksums[@]+"${cksums[@]}"}; fi
+  if [[ "${md5sums[*]-}" == *[$'\\\n']* ]]; then _dump md5sums ${md5sums[@]+"${md5sums[@]}"}; else printf 'md5sums:%s\n' ${md5sums[@]+"${md5sums[@]}"}; fi
+  if [[ "${sha1sums[*]-}" == *[$'\\\n']* ]]; then _dump sha1sums ${sha1sums[@]+"${sha1sums[@]}"}; else printf 'sha1sums:%s\n' ${sha1sums[@]+"${sha1sums[@]}"}; fi
+  if [[ "${sha224sums[*]-}" == *[$'\\\n']* ]]; then _dump sha224sums ${sha224sums[@]+"${sha224sums[@]}"}; else printf 'sha224sums:%s\n' ${sha224sums[@]+"${sha224sums[@]}"}; fi
+  if [[ "${sha256sums[*]-}" == *[$'\\\n']* ]]; then _dump sha256sums ${sha256sums[@]+"${sha256sums[@]}"}; else printf 'sha256sums:%s\n' ${sha256sums[@]+"${sha256sums[@]}"}; fi
+  if [[ "${sha384sums[*]-}" == *[$'\\\n']* ]]; then _dump sha384sums ${sha384sums[@]+"${sha384sums[@]}"}; else printf 'sha384sums:%s\n' ${sha384sums[@]+"${sha384sums[@]}"}; fi
+  if [[ "${sha512sums[*]-}" == *[$'\\\n']* ]]; then _dump sha512sums ${sha512sums[@]+"${sha512sums[@]}"}; else printf 'sha512sums:%s\n' ${sha512sums[@]+"${sha512sums[@]}"}; fi
+  if [[ "${b2sums[*]-}" == *[$'\\\n']* ]]; then _dump b2sums ${b2sums[@]+"${b2sums[@]}"}; else printf 'b2sums:%s\n' ${b2sums[@]+"${b2sums[@]}"}; fi
+  if [[ "${depends[*]-}" == *[$'\\\n']* ]]; then _dump depends ${depends[@]+"${depends[@]}"}; else printf 'depends:%s\n' ${depends[@]+"${depends[@]}"}; fi
+  if [[ "${makedepends[*]-}" == *[$'\\\n']* ]]; then _dump makedepends ${makedepends[@]+"${makedepends[@]}"}; else printf 'makedepends:%s\n' ${makedepends[@]+"${makedepends[@]}"}; fi
+  if [[ "${checkdepends[*]-}" == *[$'\\\n']* ]]; then _dump checkdepends ${checkdepends[@]+"${checkdepends[@]}"}; else printf 'checkdepends:%s\n' ${checkdepends[@]+"${checkdepends[@]}"}; fi
+  if [[ "${optdepends[*]-}" == *[$'\\\n']* ]]; then _dump optdepends ${optdepends[@]+"${optdepends[@]}"}; else printf 'optdepends:%s\n' ${optdepends[@]+"${optdepends[@]}"}; fi
+  if [[ "${conflicts[*]-}" == *[$'\\\n']* ]]; then _dump conflicts ${conflicts[@]+"${conflicts[@]}"}; else printf 'conflicts:%s\n' ${conflicts[@]+"${conflicts[@]}"}; fi
+  if [[ "${provides[*]-}" == *[$'\\\n']* ]]; then _dump provides ${provides[@]+"${provides[@]}"}; else printf 'provides:%s\n' ${provides[@]+"${provides[@]}"}; fi
+  if [[ "${replaces[*]-}" == *[$'\\\n']* ]]; then _dump replaces ${replaces[@]+"${replaces[@]}"}; else printf 'replaces:%s\n' ${replaces[@]+"${replaces[@]}"}; fi
   echo END
   _arch_collapsed="${arch[*]}"
-  if [[ " ${_arch_collapsed} " == *any* ]]; then
-    if [[ "${#_arch_collapsed}" != 3 ]]; then
-      echo "ERROR: PKGBUILD Architecture 'any' found when multiple architecture defined"
-      exit -1
+  if [[ " ${_arch_collapsed} " == *any* && "${#_arch_collapsed}" != 3 ]]; then
+    if [[ -z "${_tolerate_any_with_arch}" ]]; then
+      echo "ERROR: PKGBUILD Architecture 'any' found when multiple architecture defined" >&2
+      exit 255
     fi
-  else
+    _dump warning "Architecture 'any' mixed with others in '${_arch_collapsed}', dropped 'any'"
+    _arches=()
+    for _arch in "${arch[@]}"; do
+      [[ "${_arch}" != any ]] && _arches+=("${_arch}")
+    done
+    arch=("${_arches[@]}")
+    _arch_collapsed="${arch[*]}"
+  fi
+  # Tell arch=(any) apart from a missing arch, both have no sections
+  [[ "${_arch_collapsed}" == any ]] && echo arch:any
+  if [[ " ${_arch_collapsed} " != *any* ]]; then
     for _arch in "${arch[@]}"; do
       echo ARCH
-      echo arch:"${_arch}"
+      _dump arch "${_arch}"
       declare -n _arch_source=source_"${_arch}" _arch_cksums=cksums_"${_arch}" _arch_md5sums=md5sums_"${_arch}" _arch_sha1sums=sha1sums_"${_arch}" _arch_sha224sums=sha224sums_"${_arch}" _arch_sha256sums=sha256sums_"${_arch}" _arch_sha384sums=sha384sums_"${_arch}" _arch_sha512sums=sha512sums_"${_arch}" _arch_b2sums=b2sums_"${_arch}" _arch_depends=depends_"${_arch}" _arch_makedepends=makedepends_"${_arch}" _arch_checkdepends=checkdepends_"${_arch}" _arch_optdepends=optdepends_"${_arch}" _arch_conflicts=conflicts_"${_arch}" _arch_provides=provides_"${_arch}" _arch_replaces=replaces_"${_arch}"
-      printf 'source:%s\n' "${_arch_source[@]}"
-      printf 'cksums:%s\n' "${_arch_cksums[@]}"
-      printf 'md5sums:%s\n' "${_arch_md5sums[@]}"
-      printf 'sha1sums:%s\n' "${_arch_sha1sums[@]}"
-      printf 'sha224sums:%s\n' "${_arch_sha224sums[@]}"
-      printf 'sha256sums:%s\n' "${_arch_sha256sums[@]}"
-      printf 'sha384sums:%s\n' "${_arch_sha384sums[@]}"
-      printf 'sha512sums:%s\n' "${_arch_sha512sums[@]}"
-      printf 'b2sums:%s\n' "${_arch_b2sums[@]}"
-      printf 'depends:%s\n' "${_arch_depends[@]}"
-      printf 'makedepends:%s\n' "${_arch_makedepends[@]}"
-      printf 'checkdepends:%s\n' "${_arch_checkdepends[@]}"
-      printf 'optdepends:%s\n' "${_arch_optdepends[@]}"
-      printf 'conflicts:%s\n' "${_arch_conflicts[@]}"
-      printf 'provides:%s\n' "${_arch_provides[@]}"
-      printf 'replaces:%s\n' "${_arch_replaces[@]}"
+      if [[ "${_arch_source[*]-}" == *[$'\\\n']* ]]; then _dump source ${_arch_source[@]+"${_arch_source[@]}"}; else printf 'source:%s\n' ${_arch_source[@]+"${_arch_source[@]}"}; fi
+      if [[ "${_arch_cksums[*]-}" == *[$'\\\n']* ]]; then _dump cksums ${_arch_cksums[@]+"${_arch_cksums[@]}"}; else printf 'cksums:%s\n' ${_arch_cksums[@]+"${_arch_cksums[@]}"}; fi
+      if [[ "${_arch_md5sums[*]-}" == *[$'\\\n']* ]]; then _dump md5sums ${_arch_md5sums[@]+"${_arch_md5sums[@]}"}; else printf 'md5sums:%s\n' ${_arch_md5sums[@]+"${_arch_md5sums[@]}"}; fi
+      if [[ "${_arch_sha1sums[*]-}" == *[$'\\\n']* ]]; then _dump sha1sums ${_arch_sha1sums[@]+"${_arch_sha1sums[@]}"}; else printf 'sha1sums:%s\n' ${_arch_sha1sums[@]+"${_arch_sha1sums[@]}"}; fi
+      if [[ "${_arch_sha224sums[*]-}" == *[$'\\\n']* ]]; then _dump sha224sums ${_arch_sha224sums[@]+"${_arch_sha224sums[@]}"}; else printf 'sha224sums:%s\n' ${_arch_sha224sums[@]+"${_arch_sha224sums[@]}"}; fi
+      if [[ "${_arch_sha256sums[*]-}" == *[$'\\\n']* ]]; then _dump sha256sums ${_arch_sha256sums[@]+"${_arch_sha256sums[@]}"}; else printf 'sha256sums:%s\n' ${_arch_sha256sums[@]+"${_arch_sha256sums[@]}"}; fi
+      if [[ "${_arch_sha384sums[*]-}" == *[$'\\\n']* ]]; then _dump sha384sums ${_arch_sha384sums[@]+"${_arch_sha384sums[@]}"}; else printf 'sha384sums:%s\n' ${_arch_sha384sums[@]+"${_arch_sha384sums[@]}"}; fi
+      if [[ "${_arch_sha512sums[*]-}" == *[$'\\\n']* ]]; then _dump sha512sums ${_arch_sha512sums[@]+"${_arch_sha512sums[@]}"}; else printf 'sha512sums:%s\n' ${_arch_sha512sums[@]+"${_arch_sha512sums[@]}"}; fi
+      if [[ "${_arch_b2sums[*]-}" == *[$'\\\n']* ]]; then _dump b2sums ${_arch_b2sums[@]+"${_arch_b2sums[@]}"}; else printf 'b2sums:%s\n' ${_arch_b2sums[@]+"${_arch_b2sums[@]}"}; fi
+      if [[ "${_arch_depends[*]-}" == *[$'\\\n']* ]]; then _dump depends ${_arch_depends[@]+"${_arch_depends[@]}"}; else printf 'depends:%s\n' ${_arch_depends[@]+"${_arch_depends[@]}"}; fi
+      if [[ "${_arch_makedepends[*]-}" == *[$'\\\n']* ]]; then _dump makedepends ${_arch_makedepends[@]+"${_arch_makedepends[@]}"}; else printf 'makedepends:%s\n' ${_arch_makedepends[@]+"${_arch_makedepends[@]}"}; fi
+      if [[ "${_arch_checkdepends[*]-}" == *[$'\\\n']* ]]; then _dump checkdepends ${_arch_checkdepends[@]+"${_arch_checkdepends[@]}"}; else printf 'checkdepends:%s\n' ${_arch_checkdepends[@]+"${_arch_checkdepends[@]}"}; fi
+      if [[ "${_arch_optdepends[*]-}" == *[$'\\\n']* ]]; then _dump optdepends ${_arch_optdepends[@]+"${_arch_optdepends[@]}"}; else printf 'optdepends:%s\n' ${_arch_optdepends[@]+"${_arch_optdepends[@]}"}; fi
+      if [[ "${_arch_conflicts[*]-}" == *[$'\\\n']* ]]; then _dump conflicts ${_arch_conflicts[@]+"${_arch_conflicts[@]}"}; else printf 'conflicts:%s\n' ${_arch_conflicts[@]+"${_arch_conflicts[@]}"}; fi
+      if [[ "${_arch_provides[*]-}" == *[$'\\\n']* ]]; then _dump provides ${_arch_provides[@]+"${_arch_provides[@]}"}; else printf 'provides:%s\n' ${_arch_provides[@]+"${_arch_provides[@]}"}; fi
+      if [[ "${_arch_replaces[*]-}" == *[$'\\\n']* ]]; then _dump replaces ${_arch_replaces[@]+"${_arch_replaces[@]}"}; else printf 'replaces:%s\n' ${_arch_replaces[@]+"${_arch_replaces[@]}"}; fi
       unset -v checkdepends_"${_arch}" depends_"${_arch}" optdepends_"${_arch}" provides_"${_arch}" conflicts_"${_arch}" replaces_"${_arch}"
       echo END
     done
@@ -80,33 +176,36 @@ while read -r _line; do
   for _pkgname in "${pkgname[@]}"; do
   (
     echo PACKAGE
-    echo pkgname:"${_pkgname}"
+    _dump pkgname "${_pkgname}"
     if [[ $(type -t package_"${_pkgname}") == function ]]; then
       _pkg_func=package_"${_pkgname}"
     elif [[ $(type -t package) == function ]]; then
       if [[ "${_pkg_used}" ]]; then
-        echo "Did not find package split function for ${_pkgname}"
-        exit -2
+        echo "Did not find package split function for ${_pkgname}" >&2
+        exit 254
       fi
       _pkg_func=package
-      _pkg_used=y
     elif [[ -z $(type -t build) ]]; then
       echo END
       exit
     else
-      echo "No package split function for ${_pkgname}"
-      exit -2
+      echo "No package split function for ${_pkgname}" >&2
+      exit 254
     fi
-    _arch_backup=("${arch[@]}")
-    IFS=$'\n'
-    _lines=($(declare -f "${_pkg_func}"))
-    IFS="${_ifs_stored}"
+    _pkg_pkgdesc='' _pkg_url='' _pkg_install='' _pkg_changelog=''
+    _pkg_arch=''
+    _pkg_license='' _pkg_groups='' _pkg_backup='' _pkg_options=''
+    _pkg_checkdepends='' _pkg_depends='' _pkg_optdepends='' _pkg_provides='' _pkg_conflicts='' _pkg_replaces=''
+    mapfile -t _lines < <(declare -f "${_pkg_func}")
     _buffer=
     for _line in "${_lines[@]:2:$((${#_lines[@]}-3))}"; do 
       if [[ "${_buffer}" ]]; then
-        _buffer+="
-        ${_line}"
-        if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
+        _buffer+=$'\n'"${_line}"
+        if [[ "${_buffer#*=}" != \(* ]]; then
+          # A plain value stays incomplete until its quotes are matched
+          eval "${_buffer}" 2>/dev/null || (( $? != 2 )) || continue
+          _buffer=
+        elif [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
           eval "${_buffer}"
           _buffer=
         fi
@@ -117,20 +216,20 @@ while read -r _line; do
         _line="${_line_key##* }=${_line_value}"
         case "${_line}" in
           pkgdesc*)
-            eval "${_line}"
-            _pkg_pkgdesc='y'
+            eval "${_line}" 2>/dev/null || (( $? != 2 )) || _buffer="${_line}"
+            [[ "${_line%%[+=]*}" == pkgdesc ]] && _pkg_pkgdesc='y'
             ;;
           url*)
-            eval "${_line}"
-            _pkg_url='y'
+            eval "${_line}" 2>/dev/null || (( $? != 2 )) || _buffer="${_line}"
+            [[ "${_line%%[+=]*}" == url ]] && _pkg_url='y'
             ;;
           install*)
-            eval "${_line}"
-            _pkg_install='y'
+            eval "${_line}" 2>/dev/null || (( $? != 2 )) || _buffer="${_line}"
+            [[ "${_line%%[+=]*}" == install ]] && _pkg_install='y'
             ;;
           changelog*)
-            eval "${_line}"
-            _pkg_changelog='y'
+            eval "${_line}" 2>/dev/null || (( $? != 2 )) || _buffer="${_line}"
+            [[ "${_line%%[+=]*}" == changelog ]] && _pkg_changelog='y'
             ;;
           arch*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -138,7 +237,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_arch='y'
+            [[ "${_line%%[+=]*}" == arch ]] && _pkg_arch='y'
             ;;
           license*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -146,7 +245,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_license='y'
+            [[ "${_line%%[+=]*}" == license ]] && _pkg_license='y'
             ;;
           groups*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -154,7 +253,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_groups='y'
+            [[ "${_line%%[+=]*}" == groups ]] && _pkg_groups='y'
             ;;
           backup*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -162,7 +261,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_backup='y'
+            [[ "${_line%%[+=]*}" == backup ]] && _pkg_backup='y'
             ;;
           options*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -170,7 +269,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_options='y'
+            [[ "${_line%%[+=]*}" == options ]] && _pkg_options='y'
             ;;
           checkdepends*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -178,7 +277,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_checkdepends='y'
+            [[ "${_line%%[+=]*}" == checkdepends ]] && _pkg_checkdepends='y'
             ;;
           depends*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -186,7 +285,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_depends='y'
+            [[ "${_line%%[+=]*}" == depends ]] && _pkg_depends='y'
             ;;
           optdepends*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -194,7 +293,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_optdepends='y'
+            [[ "${_line%%[+=]*}" == optdepends ]] && _pkg_optdepends='y'
             ;;
           provides*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -202,7 +301,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_provides='y'
+            [[ "${_line%%[+=]*}" == provides ]] && _pkg_provides='y'
             ;;
           conflicts*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -210,7 +309,7 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_conflicts='y'
+            [[ "${_line%%[+=]*}" == conflicts ]] && _pkg_conflicts='y'
             ;;
           replaces*)
             if [[ "${_line}" == *');' || "${_line}" == *')' ]]; then
@@ -218,53 +317,67 @@ while read -r _line; do
             else
               _buffer="${_line}"
             fi
-            _pkg_replaces='y'
+            [[ "${_line%%[+=]*}" == replaces ]] && _pkg_replaces='y'
             ;;
         esac
       fi
     done
-    license=("${license[@]//
-/ }")
-    [[ "${_pkg_pkgdesc}" ]] && echo pkgdesc:"${pkgdesc}"
-    [[ "${_pkg_url}" ]] && echo url:"${url}"
-    [[ "${_pkg_install}" ]] && echo install:"${install}"
-    [[ "${_pkg_changelog}" ]] && echo changelog:"${changelog}"
-    [[ "${_pkg_license}" ]] && printf 'license:%s\n' "${license[@]}"
-    [[ "${_pkg_groups}" ]] && printf 'groups:%s\n' "${groups[@]}"
-    [[ "${_pkg_backup}" ]] && printf 'backup:%s\n' "${backup[@]}"
-    [[ "${_pkg_options}" ]] && printf 'options:%s\n' "${options[@]}"
+    [[ "${_pkg_pkgdesc}" ]] && if [[ "${pkgdesc}" == *[$'\\\n']* ]]; then _dump pkgdesc "${pkgdesc}"; else printf 'pkgdesc:%s\n' "${pkgdesc}"; fi
+    [[ "${_pkg_url}" ]] && if [[ "${url}" == *[$'\\\n']* ]]; then _dump url "${url}"; else printf 'url:%s\n' "${url}"; fi
+    [[ "${_pkg_install}" ]] && if [[ "${install}" == *[$'\\\n']* ]]; then _dump install "${install}"; else printf 'install:%s\n' "${install}"; fi
+    [[ "${_pkg_changelog}" ]] && if [[ "${changelog}" == *[$'\\\n']* ]]; then _dump changelog "${changelog}"; else printf 'changelog:%s\n' "${changelog}"; fi
+    [[ "${_pkg_license}" ]] && if [[ "${license[*]-}" == *[$'\\\n']* ]]; then _dump license ${license[@]+"${license[@]}"}; else printf 'license:%s\n' ${license[@]+"${license[@]}"}; fi
+    [[ "${_pkg_groups}" ]] && if [[ "${groups[*]-}" == *[$'\\\n']* ]]; then _dump groups ${groups[@]+"${groups[@]}"}; else printf 'groups:%s\n' ${groups[@]+"${groups[@]}"}; fi
+    [[ "${_pkg_backup}" ]] && if [[ "${backup[*]-}" == *[$'\\\n']* ]]; then _dump backup ${backup[@]+"${backup[@]}"}; else printf 'backup:%s\n' ${backup[@]+"${backup[@]}"}; fi
+    [[ "${_pkg_options}" ]] && if [[ "${options[*]-}" == *[$'\\\n']* ]]; then _dump options ${options[@]+"${options[@]}"}; else printf 'options:%s\n' ${options[@]+"${options[@]}"}; fi
     echo PACKAGEARCH
     echo arch:any
-    [[ "${_pkg_checkdepends}" ]] && printf 'checkdepends:%s\n' "${checkdepends[@]}"
-    [[ "${_pkg_depends}" ]] && printf 'depends:%s\n' "${depends[@]}"
-    [[ "${_pkg_optdepends}" ]] && printf 'optdepends:%s\n' "${optdepends[@]}"
-    [[ "${_pkg_provides}" ]] && printf 'provides:%s\n' "${provides[@]}"
-    [[ "${_pkg_conflicts}" ]] && printf 'conflicts:%s\n' "${conflicts[@]}"
-    [[ "${_pkg_replaces}" ]] && printf 'replaces:%s\n' "${replaces[@]}"
+    [[ "${_pkg_checkdepends}" ]] && if [[ "${checkdepends[*]-}" == *[$'\\\n']* ]]; then _dump checkdepends ${checkdepends[@]+"${checkdepends[@]}"}; else printf 'checkdepends:%s\n' ${checkdepends[@]+"${checkdepends[@]}"}; fi
+    [[ "${_pkg_depends}" ]] && if [[ "${depends[*]-}" == *[$'\\\n']* ]]; then _dump depends ${depends[@]+"${depends[@]}"}; else printf 'depends:%s\n' ${depends[@]+"${depends[@]}"}; fi
+    [[ "${_pkg_optdepends}" ]] && if [[ "${optdepends[*]-}" == *[$'\\\n']* ]]; then _dump optdepends ${optdepends[@]+"${optdepends[@]}"}; else printf 'optdepends:%s\n' ${optdepends[@]+"${optdepends[@]}"}; fi
+    [[ "${_pkg_provides}" ]] && if [[ "${provides[*]-}" == *[$'\\\n']* ]]; then _dump provides ${provides[@]+"${provides[@]}"}; else printf 'provides:%s\n' ${provides[@]+"${provides[@]}"}; fi
+    [[ "${_pkg_conflicts}" ]] && if [[ "${conflicts[*]-}" == *[$'\\\n']* ]]; then _dump conflicts ${conflicts[@]+"${conflicts[@]}"}; else printf 'conflicts:%s\n' ${conflicts[@]+"${conflicts[@]}"}; fi
+    [[ "${_pkg_replaces}" ]] && if [[ "${replaces[*]-}" == *[$'\\\n']* ]]; then _dump replaces ${replaces[@]+"${replaces[@]}"}; else printf 'replaces:%s\n' ${replaces[@]+"${replaces[@]}"}; fi
     echo END
     _arch_collapsed="${arch[*]}"
-    if [[ " ${_arch_collapsed} " == *any* ]]; then
-      if [[ "${#_arch_collapsed}" != 3 ]]; then
-        echo "ERROR: Package architecture 'any' found when multiple architecture defined"
-        exit -3
+    if [[ " ${_arch_collapsed} " == *any* && "${#_arch_collapsed}" != 3 ]]; then
+      if [[ -z "${_tolerate_any_with_arch}" ]]; then
+        echo "ERROR: Package architecture 'any' found when multiple architecture defined" >&2
+        exit 253
       fi
-    else
+      _dump warning "Package '${_pkgname}' architecture 'any' mixed with others in '${_arch_collapsed}', dropped 'any'"
+      _arches=()
+      for _arch in "${arch[@]}"; do
+        [[ "${_arch}" != any ]] && _arches+=("${_arch}")
+      done
+      arch=("${_arches[@]}")
+      _arch_collapsed="${arch[*]}"
+    fi
+    [[ "${_arch_collapsed}" == any ]] && echo arch:any
+    if [[ " ${_arch_collapsed} " != *any* ]]; then
       for _arch in "${arch[@]}"; do
         echo PACKAGEARCH
-        echo arch:"${_arch}"
+        _dump arch "${_arch}"
         declare -n _arch_checkdepends=checkdepends_"${_arch}" _arch_depends=depends_"${_arch}" _arch_optdepends=optdepends_"${_arch}" _arch_provides=provides_"${_arch}" _arch_conflicts=conflicts_"${_arch}" _arch_replaces=replaces_"${_arch}"
-        printf 'checkdepends:%s\n' "${_arch_checkdepends[@]}"
-        printf 'depends:%s\n' "${_arch_depends[@]}"
-        printf 'optdepends:%s\n' "${_arch_optdepends[@]}"
-        printf 'provides:%s\n' "${_arch_provides[@]}"
-        printf 'conflicts:%s\n' "${_arch_conflicts[@]}"
-        printf 'replaces:%s\n' "${_arch_replaces[@]}"
+        declare -p checkdepends_"${_arch}" &>/dev/null && if [[ "${_arch_checkdepends[*]-}" == *[$'\\\n']* ]]; then _dump checkdepends ${_arch_checkdepends[@]+"${_arch_checkdepends[@]}"}; else printf 'checkdepends:%s\n' ${_arch_checkdepends[@]+"${_arch_checkdepends[@]}"}; fi
+        declare -p depends_"${_arch}" &>/dev/null && if [[ "${_arch_depends[*]-}" == *[$'\\\n']* ]]; then _dump depends ${_arch_depends[@]+"${_arch_depends[@]}"}; else printf 'depends:%s\n' ${_arch_depends[@]+"${_arch_depends[@]}"}; fi
+        declare -p optdepends_"${_arch}" &>/dev/null && if [[ "${_arch_optdepends[*]-}" == *[$'\\\n']* ]]; then _dump optdepends ${_arch_optdepends[@]+"${_arch_optdepends[@]}"}; else printf 'optdepends:%s\n' ${_arch_optdepends[@]+"${_arch_optdepends[@]}"}; fi
+        declare -p provides_"${_arch}" &>/dev/null && if [[ "${_arch_provides[*]-}" == *[$'\\\n']* ]]; then _dump provides ${_arch_provides[@]+"${_arch_provides[@]}"}; else printf 'provides:%s\n' ${_arch_provides[@]+"${_arch_provides[@]}"}; fi
+        declare -p conflicts_"${_arch}" &>/dev/null && if [[ "${_arch_conflicts[*]-}" == *[$'\\\n']* ]]; then _dump conflicts ${_arch_conflicts[@]+"${_arch_conflicts[@]}"}; else printf 'conflicts:%s\n' ${_arch_conflicts[@]+"${_arch_conflicts[@]}"}; fi
+        declare -p replaces_"${_arch}" &>/dev/null && if [[ "${_arch_replaces[*]-}" == *[$'\\\n']* ]]; then _dump replaces ${_arch_replaces[@]+"${_arch_replaces[@]}"}; else printf 'replaces:%s\n' ${_arch_replaces[@]+"${_arch_replaces[@]}"}; fi
         echo END
       done
     fi
     echo END
   ) || exit $?
+  # Only one package could use the package function, this is recorded out of
+  # the subshell so later ones see it
+  [[ $(type -t package_"${_pkgname}") == function ]] || _pkg_used=y
   done
   echo END
 ) || exit $?
+if [[ "${_stderr_markers}" ]]; then
+  exec 2>&"${_stderr_fd}"
+  wait "${_stderr_pid}"
+fi
 done
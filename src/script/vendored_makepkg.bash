@@ -0,0 +1,35 @@
+# The few routines of the makepkg library the parser needs, for hosts without
+# makepkg installed, these behave the same as their makepkg counterparts
+source_safe() {
+  local _shellopts
+  _shellopts=$(shopt -p extglob)
+  shopt -u extglob
+  if ! source "$@"; then
+    echo "ERROR: Failed to source $1" >&2
+    exit 1
+  fi
+  eval "${_shellopts}"
+}
+source_makepkg_config() {
+  local MAKEPKG_CONF="${MAKEPKG_CONF:-/etc/makepkg.conf}"
+  local _conf
+  if [[ -r "${MAKEPKG_CONF}" ]]; then
+    source_safe "${MAKEPKG_CONF}"
+  else
+    echo "ERROR: ${MAKEPKG_CONF} not found." >&2
+    exit 1
+  fi
+  if [[ -d "${MAKEPKG_CONF}.d" ]]; then
+    for _conf in "${MAKEPKG_CONF}.d/"*.conf; do
+      [[ -f "${_conf}" ]] && source_safe "${_conf}"
+    done
+  fi
+  # User-specific overrides, only if no other config was specified
+  if [[ "${MAKEPKG_CONF}" == /etc/makepkg.conf ]]; then
+    if [[ -r "${XDG_CONFIG_HOME:-${HOME}/.config}/pacman/makepkg.conf" ]]; then
+      source_safe "${XDG_CONFIG_HOME:-${HOME}/.config}/pacman/makepkg.conf"
+    elif [[ -r "${HOME}/.makepkg.conf" ]]; then
+      source_safe "${HOME}/.makepkg.conf"
+    fi
+  fi
+}
@@ -0,0 +1,61 @@
+//! Parsing inside a chroot with `ParserOptions::set_chroot()`, which needs
+//! root, so this is skipped when not running as root
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use pkgbuild::{Parser, ParserScriptBuilder};
+
+/// Copy the host file at the absolute `path` to the same path under `root`
+fn copy_into(root: &Path, path: &Path) {
+    let target = root.join(path.strip_prefix("/").unwrap());
+    std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+    std::fs::copy(path, target).expect("Failed to copy file into chroot");
+}
+
+/// A minimal root with only bash and the libraries it links to
+fn minimal_root(root: &Path) {
+    copy_into(root, Path::new("/bin/bash"));
+    let output = Command::new("ldd").arg("/bin/bash").output()
+        .expect("Failed to run ldd");
+    assert!(output.status.success());
+    for line in String::from_utf8(output.stdout).unwrap().lines() {
+        // Either 'name => /path (address)' or '/path (address)'
+        let library = line.rsplit("=> ").next().unwrap().trim_start();
+        if let Some((library, _)) = library.split_once(' ') {
+            if library.starts_with('/') {
+                copy_into(root, Path::new(library))
+            }
+        }
+    }
+    std::fs::create_dir(root.join("tmp")).unwrap();
+}
+
+#[test]
+fn makepkg_config_from_chroot_consulted() {
+    if ! nix::unistd::geteuid().is_root() {
+        eprintln!("Not running as root, skipped");
+        return
+    }
+    let root = tempfile::tempdir().unwrap();
+    minimal_root(root.path());
+    // The host has either no makepkg.conf or one without the marker
+    std::fs::create_dir(root.path().join("etc")).unwrap();
+    std::fs::write(root.path().join("etc/makepkg.conf"),
+        "CARCH=x86_64\n_config_marker=chroot\n").unwrap();
+    common::write_pkgbuild(root.path(), "build", &format!(
+        "{}pkgdesc=\"config from ${{_config_marker:-host}}\"\n",
+        common::minimal("chroot")));
+    let mut builder = ParserScriptBuilder::new();
+    builder.set_vendored_makepkg_library(true)
+        .set_makepkg_config("/etc/makepkg.conf");
+    let mut parser = Parser::with_script(builder.build_in_chroot(root.path())
+        .expect("Failed to build parser script in chroot"));
+    parser.options.set_chroot(Some(root.path()));
+    let pkgbuilds = parser.parse_multi(["/build/PKGBUILD"]).unwrap();
+    assert_eq!(pkgbuilds.len(), 1);
+    assert_eq!(pkgbuilds[0].pkgbase, "chroot");
+    assert_eq!(pkgbuilds[0].pkgdesc, "config from chroot");
+}
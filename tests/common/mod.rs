@@ -0,0 +1,50 @@
+//! Helpers shared by the integration tests, which parse with the vendored
+//! makepkg library so they do not depend on the makepkg of the host
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use pkgbuild::{Parser, ParserScriptBuilder};
+
+/// A parser using the vendored makepkg library and no makepkg.conf
+pub fn parser() -> Parser {
+    let mut builder = ParserScriptBuilder::new();
+    builder.set_vendored_makepkg_library(true).set_use_makepkg_config(false);
+    Parser::with_script(builder.build(None::<&str>)
+        .expect("Failed to build parser script"))
+}
+
+/// The content of a minimal valid PKGBUILD with `pkgbase` as its pkgname
+pub fn minimal(pkgbase: &str) -> String {
+    format!("pkgname={pkgbase}\npkgver=1.0\npkgrel=1\narch=(any)\n\
+        package() {{ :; }}\n")
+}
+
+/// Write `content` as `dir/name/PKGBUILD` and return the path to it
+pub fn write_pkgbuild(dir: &Path, name: &str, content: &str) -> PathBuf {
+    let dir = dir.join(name);
+    std::fs::create_dir_all(&dir).expect("Failed to create PKGBUILD dir");
+    let path = dir.join("PKGBUILD");
+    std::fs::write(&path, content).expect("Failed to write PKGBUILD");
+    path
+}
+
+/// The path to a file under `tests/fixtures`
+pub fn fixture<P: AsRef<Path>>(path: P) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path)
+}
+
+/// Copy the tree `from` recursively into `to`, which must not exist
+pub fn copy_tree(from: &Path, to: &Path) {
+    std::fs::create_dir(to).expect("Failed to create dir");
+    for entry in std::fs::read_dir(from).expect("Failed to read dir") {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_tree(&entry.path(), &target)
+        } else {
+            std::fs::copy(entry.path(), target).expect("Failed to copy file");
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! The ordering contract of all the parse entry points: the results come back
+//! in the same order as the input paths, however the paths were ordered and
+//! however the parsing was split
+
+mod common;
+
+use pkgbuild::{Parser, Pkgbuild, Pkgbuilds, Strictness};
+
+/// A xorshift generator, to shuffle the paths reproducibly
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for id in (1..items.len()).rev() {
+            items.swap(id, self.below(id + 1))
+        }
+    }
+}
+
+fn pkgbases(pkgbuilds: &[Pkgbuild]) -> Vec<&str> {
+    pkgbuilds.iter().map(|pkgbuild|pkgbuild.pkgbase.as_str()).collect()
+}
+
+fn lenient() -> Parser {
+    let mut parser = common::parser();
+    parser.options.set_strictness(Strictness::Lenient);
+    parser
+}
+
+#[test]
+fn all_variants_keep_input_order() {
+    let dir = tempfile::tempdir().unwrap();
+    // Distinctive pkgbases that neither sort nor hash like their ids
+    let mut names: Vec<_> = (0..100).map(|id: u32|
+        format!("conform-{:08x}-{id}", id.wrapping_mul(2654435761)))
+        .collect();
+    Rng(0x5eed).shuffle(&mut names);
+    let paths: Vec<_> = names.iter().map(|name|
+        common::write_pkgbuild(dir.path(), name, &common::minimal(name)))
+        .collect();
+    let expected: Vec<_> = names.iter().map(String::as_str).collect();
+    for (name, parser) in [("strict", common::parser()), ("lenient", lenient())]
+    {
+        let check = |variant: &str, pkgbuilds: &[Pkgbuild]| assert_eq!(
+            pkgbases(pkgbuilds), expected, "{variant} ({name}) reordered");
+        check("parse_multi", &parser.parse_multi(&paths).unwrap());
+        check("parse_multi_collected",
+            &parser.parse_multi_collected(&paths).unwrap());
+        check("parse_multi_report", &parser.parse_multi_report(&paths)
+            .unwrap().0);
+        check("parse_multi_verbose", &parser.parse_multi_verbose(&paths)
+            .unwrap().0);
+        let raw = parser.parse_raw(&paths).unwrap();
+        check("parse_raw", &Pkgbuilds::from_raw_with_strictness(
+            &raw, parser.options.strictness).unwrap());
+        #[cfg(not(feature = "nothread"))]
+        for jobs in [0, 4, 7, 100] {
+            check(&format!("parse_multi_par with {jobs} jobs"),
+                &parser.parse_multi_par(&paths, jobs).unwrap())
+        }
+        // Chunked by the caller, each chunk parsed on its own
+        let chunked: Vec<_> = paths.chunks(9).flat_map(|chunk|
+            parser.parse_multi(chunk).unwrap()).collect();
+        check("chunked parse_multi", &chunked);
+    }
+}
@@ -0,0 +1,63 @@
+//! Elements of relation arrays split on whitespace like makepkg does, e.g.
+//! `depends=('foo bar>=2')` after sloppy quoting
+
+mod common;
+
+use pkgbuild::{Dependency, DependencyOrder};
+
+const PKGBUILD: &str = "pkgname=split-deps
+pkgver=1.0
+pkgrel=1
+arch=(any)
+depends=('foo bar>=2' '  ')
+makedepends=('meson	ninja')
+provides=('libsplit.so=1 split-compat=1.0')
+conflicts=(' split-old ')
+optdepends=('python: for the scripts' 'foo bar: two words')
+package() {
+  depends=('baz  qux<3')
+}
+";
+
+fn names<'a, I, D>(deps: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a D>,
+    D: std::ops::Deref<Target = Dependency> + 'a,
+{
+    deps.into_iter().map(|dep|dep.name.as_str()).collect()
+}
+
+#[test]
+fn relation_arrays_split_on_whitespace() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::write_pkgbuild(dir.path(), "split-deps", PKGBUILD);
+    let pkgbuild = common::parser().parse_one(Some(&path)).unwrap();
+    let any = &pkgbuild.multiarch.any;
+    assert_eq!(any.depends.len(), 2, "{:?}", any.depends);
+    assert_eq!(any.depends[0], Dependency::from("foo"));
+    assert_eq!(any.depends[1].name, "bar");
+    let version = any.depends[1].version.as_ref().unwrap();
+    assert_eq!(version.order, DependencyOrder::GreaterOrEqual);
+    assert_eq!(version.plain.pkgver, "2");
+    assert_eq!(names(&any.makedepends), ["meson", "ninja"]);
+    assert_eq!(any.provides.iter().map(|provide|provide.name.as_str())
+        .collect::<Vec<_>>(), ["libsplit.so", "split-compat"]);
+    assert_eq!(names(&any.conflicts), ["split-old"]);
+    // optdepends have free-form reasons, so they are never split
+    assert_eq!(any.optdepends.len(), 2);
+    assert_eq!(any.optdepends[0].dep.name, "python");
+    assert_eq!(any.optdepends[0].reason, "for the scripts");
+    assert_eq!(any.optdepends[1].dep.name, "foo bar");
+    assert_eq!(any.optdepends[1].reason, "two words");
+    // Those set in the package function are split too
+    let depends = pkgbuild.pkgs[0].multiarch.any.depends.as_ref().unwrap();
+    assert_eq!(depends.iter().map(|dep|dep.name.as_str())
+        .collect::<Vec<_>>(), ["baz", "qux"]);
+}
+
+#[test]
+fn parse_many_skips_empty_words() {
+    assert_eq!(Dependency::parse_many("  \t "), []);
+    assert_eq!(Dependency::parse_many(" foo  bar>=2 "),
+        [Dependency::from("foo"), Dependency::from("bar>=2")]);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+/// A PKGBUILD writing `stderr` verbatim to stderr when sourced
+fn noisy(pkgbase: &str, stderr: &str) -> String {
+    format!("printf '{stderr}' >&2\n{}", common::minimal(pkgbase))
+}
+
+/// NULs and backslashes written by a PKGBUILD do not desync the markers
+#[test]
+fn parse_multi_verbose_splits_stderr_with_nuls() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = [
+        common::write_pkgbuild(dir.path(), "nul", 
+            &noisy("nul", r"before\0/fake/PKGBUILD\0after\n")),
+        common::write_pkgbuild(dir.path(), "quiet", &common::minimal("quiet")),
+        common::write_pkgbuild(dir.path(), "backslash", 
+            &noisy("backslash", r"a \\0 b \\\\ c\0\n")),
+        common::write_pkgbuild(dir.path(), "plain", 
+            &noisy("plain", r"plain\n")),
+    ];
+    let (pkgbuilds, diagnostics) = common::parser()
+        .parse_multi_verbose(&paths).unwrap();
+    let pkgbases: Vec<_> = pkgbuilds.iter()
+        .map(|pkgbuild|pkgbuild.pkgbase.as_str()).collect();
+    assert_eq!(pkgbases, ["nul", "quiet", "backslash", "plain"]);
+    assert!(diagnostics.global.is_empty());
+    assert_eq!(diagnostics.for_path(&paths[0]), 
+        Some(&b"before\0/fake/PKGBUILD\0after\n"[..]));
+    assert_eq!(diagnostics.for_path(&paths[1]), None);
+    assert_eq!(diagnostics.for_path(&paths[2]), 
+        Some(&b"a \\0 b \\\\ c\0\n"[..]));
+    assert_eq!(diagnostics.for_path(&paths[3]), Some(&b"plain\n"[..]));
+    assert_eq!(diagnostics.stderr, 
+        b"before\0/fake/PKGBUILD\0after\na \\0 b \\\\ c\0\nplain\n");
+}
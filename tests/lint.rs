@@ -0,0 +1,75 @@
+//! The `url` checks, `PB007` of the linter and the typed access to the field
+
+use pkgbuild::lint::{lint_pkgbuild, lint_pkgbuild_with, LintConfig, LintRule};
+use pkgbuild::{PackageBuilder, Pkgbuild, PkgbuildBuilder};
+
+fn with_url(url: &str, pkg_url: Option<&str>) -> Pkgbuild {
+    let mut pkg = PackageBuilder::new("url-example");
+    if let Some(pkg_url) = pkg_url {
+        pkg.url(pkg_url);
+    }
+    PkgbuildBuilder::new()
+        .pkgbase("url-example")
+        .version("1.0-1")
+        .add_arch("any".into())
+        .url(url)
+        .add_package(&pkg)
+        .build().unwrap()
+}
+
+/// The `(pkgname, message)` of the `PB007` lints
+fn url_lints(pkgbuild: &Pkgbuild) -> Vec<(Option<String>, String)> {
+    lint_pkgbuild(pkgbuild).into_iter()
+        .filter(|lint|lint.rule == LintRule::InvalidUrl)
+        .map(|lint|(lint.location.pkgname, lint.message)).collect()
+}
+
+#[test]
+fn invalid_url_rule_registered() {
+    assert!(LintRule::ALL.contains(&LintRule::InvalidUrl));
+    assert_eq!(LintRule::InvalidUrl.code(), "PB007");
+    assert_eq!(LintRule::from_code("PB007"), Some(LintRule::InvalidUrl));
+    let mut config = LintConfig::new();
+    config.disable(LintRule::InvalidUrl);
+    assert!(! config.is_enabled(LintRule::InvalidUrl));
+    assert!(lint_pkgbuild_with(&with_url("", None), &config).iter()
+        .all(|lint|lint.rule != LintRule::InvalidUrl));
+}
+
+#[test]
+fn invalid_url_lints() {
+    assert_eq!(url_lints(&with_url("https://example.org", None)), []);
+    assert_eq!(url_lints(&with_url("ftp://ftp.example.org/pub", None)), []);
+    assert_eq!(url_lints(&with_url("example.org/project", None)),
+        [(None, "'example.org/project' is not an absolute URL".into())]);
+    assert_eq!(url_lints(&with_url("", None)),
+        [(None, "url is empty".into())]);
+    // Only those overridden in the package function are checked for it
+    assert_eq!(url_lints(&with_url("https://example.org", Some(""))),
+        [(Some("url-example".into()), "url is empty".into())]);
+    assert_eq!(url_lints(&with_url("", Some("https://example.org/pkg"))),
+        [(None, "url is empty".into())]);
+}
+
+#[cfg(feature = "url")]
+#[test]
+fn url_parsed_and_upstream_host() {
+    let pkgbuild = with_url("ftp://ftp.example.org/pub", None);
+    assert_eq!(pkgbuild.url_parsed().unwrap().unwrap().scheme(), "ftp");
+    assert_eq!(pkgbuild.upstream_host().as_deref(), Some("ftp.example.org"));
+    let pkgbuild = with_url("example.org/project", None);
+    assert_eq!(pkgbuild.url_parsed(),
+        Some(Err(url::ParseError::RelativeUrlWithoutBase)));
+    assert_eq!(pkgbuild.upstream_host(), None);
+    let pkgbuild = with_url("", Some("https://pkg.example.org"));
+    assert_eq!(pkgbuild.url_parsed(), None);
+    assert_eq!(pkgbuild.upstream_host(), None);
+    // The package inherits the url only if not overridden
+    let pkg = &pkgbuild.pkgs[0];
+    assert_eq!(pkg.upstream_host(&pkgbuild).as_deref(),
+        Some("pkg.example.org"));
+    let pkgbuild = with_url("ftp://ftp.example.org", None);
+    let pkg = &pkgbuild.pkgs[0];
+    assert_eq!(pkg.upstream_host(&pkgbuild).as_deref(),
+        Some("ftp.example.org"));
+}
@@ -0,0 +1,81 @@
+//! The vendored makepkg library compared with the one of the host, and the
+//! golden `.SRCINFO`s with those `makepkg --printsrcinfo` writes, both only
+//! when makepkg is installed, as on Arch, otherwise skipped
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use pkgbuild::{Parser, ParserScriptBuilder};
+
+/// The makepkg library of the host, same as the default of the builder
+fn system_library() -> PathBuf {
+    std::env::var_os("LIBRARY").unwrap_or("/usr/share/makepkg".into()).into()
+}
+
+fn fixture_pkgbuilds() -> Vec<PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(common::fixture("srcinfo"))
+        .unwrap().map(|entry|entry.unwrap().path().join("PKGBUILD"))
+        .collect();
+    for name in ["created", "unchanged", "updated"] {
+        paths.push(common::fixture("tree").join(name).join("PKGBUILD"))
+    }
+    paths.sort_unstable();
+    paths
+}
+
+#[test]
+fn vendored_library_matches_system() {
+    let library = system_library();
+    if ! library.join("util.sh").is_file() {
+        eprintln!("makepkg library not found at {}, skipped",
+            library.display());
+        return
+    }
+    let mut builder = ParserScriptBuilder::new();
+    builder.set_makepkg_library(&library).set_use_makepkg_config(false);
+    let system = Parser::with_script(builder.build(None::<&str>)
+        .expect("Failed to build parser script"));
+    let paths = fixture_pkgbuilds();
+    let expected = system.parse_multi(&paths)
+        .expect("Failed to parse with the system makepkg library");
+    let pkgbuilds = common::parser().parse_multi(&paths)
+        .expect("Failed to parse with the vendored makepkg library");
+    assert_eq!(pkgbuilds.len(), paths.len());
+    for ((path, pkgbuild), expected) in
+        paths.iter().zip(pkgbuilds.iter()).zip(expected.iter())
+    {
+        assert_eq!(pkgbuild, expected, "Mismatched parse of {}",
+            path.display())
+    }
+}
+
+fn printsrcinfo(dir: &Path) -> Option<String> {
+    let output = std::process::Command::new("makepkg")
+        .arg("--printsrcinfo").current_dir(dir).output().ok()?;
+    assert!(output.status.success(), "makepkg failed in {}: {}",
+        dir.display(), String::from_utf8_lossy(&output.stderr));
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn golden_srcinfo_matches_makepkg() {
+    if nix::unistd::geteuid().is_root() {
+        eprintln!("makepkg refuses to run as root, skipped");
+        return
+    }
+    let paths = fixture_pkgbuilds();
+    for path in paths.iter() {
+        let dir = path.parent().unwrap();
+        let Ok(expected) = std::fs::read_to_string(dir.join(".SRCINFO"))
+        else {
+            continue
+        };
+        let Some(srcinfo) = printsrcinfo(dir) else {
+            eprintln!("makepkg not found, skipped");
+            return
+        };
+        assert_eq!(srcinfo, expected, "Mismatched .SRCINFO for {}",
+            dir.display());
+    }
+}
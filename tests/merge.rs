@@ -0,0 +1,76 @@
+//! Merging an overlay `PKGBUILD` over a base one with `Pkgbuild::merge()`
+
+mod common;
+
+use pkgbuild::{MergePolicy, Pkgbuild, Severity};
+
+const BASE: &str = "pkgname=merge-example
+pkgver=1.2
+pkgrel=1
+pkgdesc='A base PKGBUILD with an overlay'
+arch=(x86_64)
+url='https://example.org'
+license=(MIT)
+source=('https://example.org/merge-example-1.2.tar.gz')
+sha256sums=('2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae')
+package() { :; }
+";
+
+fn overlay(pkgrel: &str) -> String {
+    format!("pkgname=merge-example
+pkgver=1.2
+pkgrel={pkgrel}
+arch=(aarch64)
+source_aarch64=('https://example.org/aarch64.patch')
+sha256sums_aarch64=(
+    'fcde2b2edba56bf408601fb721fe9b5c338d10ee429ea04fae5511b68fbf8fb9'
+)
+package() {{ :; }}
+")
+}
+
+fn parse(base: &str, overlay: &str) -> (Pkgbuild, Pkgbuild) {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = [common::write_pkgbuild(dir.path(), "base", base),
+        common::write_pkgbuild(dir.path(), "overlay", overlay)];
+    let mut pkgbuilds = common::parser().parse_multi(&paths).unwrap();
+    let overlay = pkgbuilds.pop().unwrap();
+    (pkgbuilds.pop().unwrap(), overlay)
+}
+
+#[test]
+fn merge_overlay_source_and_pkgrel() {
+    let (base, overlay) = parse(BASE, &overlay("2"));
+    let merged = base.merge(&overlay, &MergePolicy::new()).unwrap();
+    assert!(merged.validate().is_empty());
+    assert_eq!(merged.srcinfo().to_string(), "\
+pkgbase = merge-example
+\tpkgdesc = A base PKGBUILD with an overlay
+\tpkgver = 1.2
+\tpkgrel = 2
+\turl = https://example.org
+\tarch = x86_64
+\tarch = aarch64
+\tlicense = MIT
+\tsource = https://example.org/merge-example-1.2.tar.gz
+\tsha256sums = 2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae
+\tsource_aarch64 = https://example.org/aarch64.patch
+\tsha256sums_aarch64 = \
+fcde2b2edba56bf408601fb721fe9b5c338d10ee429ea04fae5511b68fbf8fb9
+
+pkgname = merge-example
+");
+}
+
+#[test]
+fn merge_result_validated() {
+    let (base, overlay) = parse(BASE, &overlay("0"));
+    let conflict = base.merge(&overlay, &MergePolicy::new()).unwrap_err();
+    assert!(conflict.fields.is_empty());
+    assert_eq!(conflict.issues.len(), 1);
+    let issue = &conflict.issues[0];
+    assert_eq!((issue.severity, issue.field, issue.value.as_str()),
+        (Severity::Error, "pkgrel", "0"));
+    assert_eq!(conflict.to_string(), "Merge Conflict: [pkgrel '0' is not a \
+        positive decimal, e.g. 1 or 1.1]");
+}
@@ -0,0 +1,51 @@
+#![cfg(not(feature = "nothread"))]
+
+mod common;
+
+use pkgbuild::Error;
+
+/// 12 paths in 4 chunks of 3, with the second chunk broken by its middle one
+#[test]
+fn parse_multi_par_keeps_slots_across_failed_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<_> = (0..12).map(|id| {
+        let name = format!("order-{id:02}");
+        let content = if id == 4 {
+            "pkgname=broken\npkgver=1\npkgrel=1\narch=(any)\nexit 1\n".into()
+        } else {
+            common::minimal(&name)
+        };
+        common::write_pkgbuild(dir.path(), &name, &content)
+    }).collect();
+    let Err(Error::ChunksFailed { errors, result }) = 
+        common::parser().parse_multi_par(&paths, 4) 
+    else {
+        panic!("Parsing should have failed for a chunk")
+    };
+    assert_eq!(errors.len(), 1);
+    assert_eq!(result.len(), paths.len());
+    for (id, pkgbuild) in result.iter().enumerate() {
+        if (3..6).contains(&id) {
+            assert!(pkgbuild.is_none(), "Slot {id} should be empty")
+        } else {
+            assert_eq!(pkgbuild.as_ref().expect("Slot should be filled")
+                .pkgbase, format!("order-{id:02}"))
+        }
+    }
+}
+
+#[test]
+fn parse_multi_par_keeps_input_order() {
+    let dir = tempfile::tempdir().unwrap();
+    // Deliberately not sorted, so any reordering would show
+    let names: Vec<_> = (0..20).map(|id| format!("order-{:02}", (id * 7) % 20))
+        .collect();
+    let paths: Vec<_> = names.iter().map(|name|
+        common::write_pkgbuild(dir.path(), name, &common::minimal(name)))
+        .collect();
+    for jobs in [1, 3, 8] {
+        let pkgbases: Vec<_> = common::parser().parse_multi_par(&paths, jobs)
+            .unwrap().into_iter().map(|pkgbuild|pkgbuild.pkgbase).collect();
+        assert_eq!(pkgbases, names, "Order differs with {jobs} jobs")
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use std::path::PathBuf;
+
+use pkgbuild::{Error, PathDelimiter};
+
+/// Directory names that would be mangled with a newline delimiter, or by a
+/// `read` without `-r` and an emptied `IFS`
+const NAMES: &[&str] = &["with\nnewline", " leading", "trailing ", 
+    "  both  ", "\n", " \n "];
+
+#[test]
+fn nul_delimited_paths_are_kept_verbatim() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<_> = NAMES.iter().enumerate().map(|(id, name)|
+        common::write_pkgbuild(dir.path(), name, 
+            &common::minimal(&format!("path-{id}"))))
+        .collect();
+    let mut parser = common::parser();
+    parser.options.set_path_delimiter(PathDelimiter::Nul);
+    let pkgbuilds = parser.parse_multi(&paths).unwrap();
+    let pkgbases: Vec<_> = pkgbuilds.into_iter()
+        .map(|pkgbuild|pkgbuild.pkgbase).collect();
+    let expected: Vec<_> = (0..NAMES.len()).map(|id|format!("path-{id}"))
+        .collect();
+    assert_eq!(pkgbases, expected)
+}
+
+/// Paths relative to the work dir, without a `/` they're fed as `./name`
+#[test]
+fn nul_delimited_relative_paths_are_kept_verbatim() {
+    let dir = tempfile::tempdir().unwrap();
+    let names = [" PKGBUILD", "PKGBUILD ", "PKG\nBUILD"];
+    for (id, name) in names.iter().enumerate() {
+        std::fs::write(dir.path().join(name), 
+            common::minimal(&format!("relative-{id}"))).unwrap()
+    }
+    let mut parser = common::parser();
+    parser.options.set_path_delimiter(PathDelimiter::Nul)
+        .set_work_dir(Some(dir.path()));
+    let pkgbuilds = parser.parse_multi(names).unwrap();
+    assert_eq!(pkgbuilds.len(), names.len());
+    for (id, pkgbuild) in pkgbuilds.iter().enumerate() {
+        assert_eq!(pkgbuild.pkgbase, format!("relative-{id}"))
+    }
+}
+
+#[test]
+fn newline_delimiter_rejects_paths_with_newline() {
+    let path = PathBuf::from("/nonexistent/with\nnewline/PKGBUILD");
+    match common::parser().parse_multi([&path]) {
+        Err(Error::InvalidInputPath(rejected)) => assert_eq!(rejected, path),
+        other => panic!("Path should be rejected, got {other:?}"),
+    }
+}
@@ -0,0 +1,44 @@
+//! The parser and everything the `PKGBUILD`s spawned, which are all in their
+//! own process group, killed together when parsing is interrupted
+
+mod common;
+
+use pkgbuild::Error;
+
+use std::time::{Duration, Instant};
+
+/// Whether the process is gone, or a zombie left to an init not reaping it
+fn is_dead(pid: &str) -> bool {
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        // The state follows the command in parentheses
+        Ok(stat) => stat.rsplit_once(") ").is_some_and(
+            |(_, rest)|rest.starts_with('Z')),
+        Err(_) => true,
+    }
+}
+
+#[test]
+fn group_killed_on_output_overflow() {
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("pid");
+    let first = common::write_pkgbuild(dir.path(), "spawner", &format!(
+        "{}sleep 1000 </dev/null >/dev/null 2>&1 &\necho $! > '{}'\n",
+        common::minimal("spawner"), pid_file.display()));
+    let second = common::write_pkgbuild(dir.path(), "second",
+        &common::minimal("second"));
+    let mut parser = common::parser();
+    parser.options.set_max_output(Some(64));
+    let Err(Error::OutputLimitExceeded { limit: 64 }) = 
+        parser.parse_multi([&first, &second]) 
+    else {
+        panic!("Parsing should have failed on the output limit")
+    };
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let pid = pid.trim();
+    let start = Instant::now();
+    while ! is_dead(pid) {
+        assert!(start.elapsed() < Duration::from_secs(5),
+            "Process {} spawned by the PKGBUILD is still alive", pid);
+        std::thread::sleep(Duration::from_millis(10))
+    }
+}
@@ -0,0 +1,41 @@
+//! Progress callbacks with state that's not `Sync`, as most progress bars
+//! keep theirs
+
+mod common;
+
+use std::cell::RefCell;
+use std::sync::mpsc::channel;
+
+use pkgbuild::Parser;
+
+fn paths(dir: &std::path::Path, count: usize) -> Vec<std::path::PathBuf> {
+    (0..count).map(|id| {
+        let name = format!("progress-{id}");
+        common::write_pkgbuild(dir, &name, &common::minimal(&name))
+    }).collect()
+}
+
+#[test]
+fn parser_with_refcell_progress_is_sync() {
+    fn assert_sync<T: Sync>(_: &T) {}
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths(dir.path(), 6);
+    let (sender, receiver) = channel();
+    let reported = RefCell::new(Vec::new());
+    let mut parser = common::parser();
+    parser.options.set_progress(Some(Box::new(move |done, total| {
+        reported.borrow_mut().push((done, total));
+        sender.send(reported.borrow().clone()).unwrap()
+    })));
+    assert_sync::<Parser>(&parser);
+    parser.parse_multi(&paths).unwrap();
+    let expected: Vec<_> = (1..=6).map(|done|(done, 6)).collect();
+    assert_eq!(receiver.try_iter().last().unwrap(), expected);
+    #[cfg(not(feature = "nothread"))]
+    {
+        parser.parse_multi_par(&paths, 3).unwrap();
+        let reported = receiver.try_iter().last().unwrap();
+        // The same callback, with the earlier reports kept in its state
+        assert_eq!(reported[6..], expected);
+    }
+}
@@ -0,0 +1,58 @@
+//! The parser scripts written in all flavours checked by shellcheck at its
+//! default severity, only when shellcheck is installed, otherwise skipped
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use pkgbuild::ParserScriptBuilder;
+
+/// Run shellcheck over the script, return its findings if any
+fn shellcheck(script: &[u8]) -> Option<String> {
+    let mut child = Command::new("shellcheck")
+        .args(["--shell=bash", "-"])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn().ok()?;
+    child.stdin.take().unwrap().write_all(script).unwrap();
+    let output = child.wait_with_output().unwrap();
+    if output.status.success() {
+        Some(String::new())
+    } else {
+        Some(format!("{}{}", String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+fn builders() -> Vec<(&'static str, ParserScriptBuilder)> {
+    let mut builders = Vec::new();
+    builders.push(("default", ParserScriptBuilder::new()));
+    let mut builder = ParserScriptBuilder::new();
+    builder.set_vendored_makepkg_library(true);
+    builders.push(("vendored", builder));
+    let mut builder = ParserScriptBuilder::new();
+    builder.set_makepkg_library("/opt/make'pkg")
+        .set_makepkg_config_inline("CARCH='x86_64'\nOPTIONS=(strip)\n");
+    builders.push(("inline config", builder));
+    let mut builder = ParserScriptBuilder::new();
+    builder.set_vendored_makepkg_library(true)
+        .set_use_makepkg_config(false)
+        .set_dump_function_bodies(true)
+        .set_tolerate_any_with_arch(true)
+        .add_extra_variable("_pkgname")
+        .add_extra_variable("_commit");
+    builders.push(("everything", builder));
+    builders
+}
+
+#[test]
+fn scripts_are_shellcheck_clean() {
+    for (name, builder) in builders() {
+        let mut script = Vec::new();
+        builder.write_to(&mut script).expect("Failed to write script");
+        let Some(findings) = shellcheck(&script) else {
+            eprintln!("shellcheck not found, skipped");
+            return
+        };
+        assert!(findings.is_empty(), "shellcheck findings in the {} script:\n\
+            {}", name, findings);
+    }
+}
@@ -0,0 +1,46 @@
+//! Git sources with and without fragments and `?signed`, parsed and written
+//! back by `Source::get_pkgbuild_source()`
+
+use pkgbuild::{GitSourceFragment, Source, SourceProtocol};
+
+const URL: &str = "https://example.org/repo.git";
+
+fn git(source: &Source) -> (Option<&GitSourceFragment>, bool) {
+    match &source.protocol {
+        SourceProtocol::Git { fragment, signed } =>
+            (fragment.as_ref(), *signed),
+        protocol => panic!("Not a git source: {:?}", protocol),
+    }
+}
+
+#[test]
+fn git_fragment_and_signed_round_trip() {
+    let tag = GitSourceFragment::Tag("v1".into());
+    for (definition, fragment, signed) in [
+        ("git+https://example.org/repo.git", None, false),
+        ("git+https://example.org/repo.git?signed", None, true),
+        ("git+https://example.org/repo.git#tag=v1", Some(&tag), false),
+        ("git+https://example.org/repo.git?signed#tag=v1", Some(&tag), true),
+    ] {
+        let source = Source::from(definition);
+        assert_eq!(source.url, URL, "Url of '{}'", definition);
+        assert_eq!(git(&source), (fragment, signed), "Git of '{}'",
+            definition);
+        assert_eq!(source.get_pkgbuild_source(), definition);
+        assert_eq!(Source::from(source.get_pkgbuild_source().as_str()),
+            source);
+    }
+}
+
+/// makepkg also accepts `?signed` after the fragment, which is written back
+/// before it, as in a normal url
+#[test]
+fn git_signed_after_fragment() {
+    let source = Source::from("git+https://example.org/repo.git#tag=v1?signed");
+    assert_eq!(source.url, URL);
+    assert_eq!(git(&source), (Some(&GitSourceFragment::Tag("v1".into())),
+        true));
+    let written = source.get_pkgbuild_source();
+    assert_eq!(written, "git+https://example.org/repo.git?signed#tag=v1");
+    assert_eq!(Source::from(written.as_str()), source);
+}
@@ -0,0 +1,101 @@
+//! The `.SRCINFO` written for the `PKGBUILD`s under `tests/fixtures/srcinfo`
+//! compared byte-for-byte with the `.SRCINFO` next to each of them, as 
+//! `makepkg --printsrcinfo` writes it
+//! 
+//! Some of them are the examples in the ALPM specifications. The `PKGBUILD`s 
+//! declare the known options in makepkg's canonical order, and use 
+//! `sha256sums` when all checksums are `SKIP`, as `Pkgbuild` keeps neither 
+//! the declared order of those nor the kind of such checksums.
+
+mod common;
+
+use std::path::PathBuf;
+
+use pkgbuild::{Architecture, Dependency, Options, PackageBuilder,
+    PkgbuildBuilder, Provide};
+
+fn fixture_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<_> = std::fs::read_dir(common::fixture("srcinfo"))
+        .unwrap().map(|entry|entry.unwrap().path()).collect();
+    dirs.sort_unstable();
+    dirs
+}
+
+#[test]
+fn srcinfo_matches_golden_files() {
+    let dirs = fixture_dirs();
+    assert!(dirs.len() >= 6);
+    let paths: Vec<_> = dirs.iter().map(|dir|dir.join("PKGBUILD")).collect();
+    let pkgbuilds = common::parser().parse_multi(&paths).unwrap();
+    for (dir, pkgbuild) in dirs.iter().zip(pkgbuilds.iter()) {
+        let expected = std::fs::read_to_string(dir.join(".SRCINFO")).unwrap();
+        assert_eq!(pkgbuild.srcinfo().to_string(), expected, 
+            "Mismatched .SRCINFO for {}", dir.display());
+    }
+}
+
+/// `split-override-options` rebuilt in code, which should be written the same
+#[test]
+fn builder_split_package_matches_makepkg() {
+    let mut docs = PackageBuilder::new("example-options-docs");
+    docs.pkgdesc("Documentation for example-options")
+        .add_arch(Architecture::Any)
+        .set_depends(Vec::<Dependency>::new())
+        .options("!strip docs".parse().unwrap());
+    let mut debug = PackageBuilder::new("example-options-debug");
+    debug.pkgdesc("Debug helpers for example-options")
+        .add_depend("example-options=1:2.1-3")
+        .options(Options::default());
+    let mut main = PackageBuilder::new("example-options");
+    main.add_backup("etc/example.conf");
+    let pkgbuild = PkgbuildBuilder::new()
+        .pkgbase("example-options")
+        .version("1:2.1-3")
+        .pkgdesc("A split package example overriding options")
+        .add_arch("x86_64".into())
+        .url("https://example.org")
+        .add_license("GPL-3.0-or-later")
+        .add_makedepend("meson")
+        .add_depend("glibc")
+        .options("debug !lto".parse().unwrap())
+        .add_source("https://download.example.org/example-options-2.1.tar.gz")
+        .add_package(&main)
+        .add_package(&docs)
+        .add_package(&debug)
+        .build().unwrap();
+    let expected = std::fs::read_to_string(common::fixture(
+        "srcinfo/split-override-options/.SRCINFO")).unwrap();
+    assert_eq!(pkgbuild.srcinfo().to_string(), expected);
+}
+
+#[test]
+fn builder_split_package_multiarch() {
+    let mut core = PackageBuilder::new("example-builder-core");
+    core.add_provide("example-builder=0.3".parse::<Provide>().unwrap());
+    let mut tools = PackageBuilder::new("example-builder-tools");
+    tools.pkgdesc("Tools of example-builder")
+        .add_arch("x86_64".into())
+        .add_depend("example-builder-core")
+        .add_optdepend("python: for the scripts")
+        .add_depend_for_arch(Some("x86_64".into()), "x86-helper");
+    let pkgbuild = PkgbuildBuilder::new()
+        .pkgbase("example-builder")
+        .version("0.3-2")
+        .pkgdesc("A split package built in code")
+        .add_arch("x86_64".into())
+        .add_arch("aarch64".into())
+        .url("https://example.org")
+        .add_license("MIT")
+        .add_makedepend("cmake")
+        .add_depend("glibc")
+        .add_depend_for_arch(Some("aarch64".into()), "libatomic_ops")
+        .add_source("https://download.example.org/example-builder-0.3.tar.gz")
+        .add_source_for_arch(Some("x86_64".into()), "x86_64.patch")
+        .add_source_for_arch(Some("aarch64".into()), "aarch64.patch")
+        .add_package(&core)
+        .add_package(&tools)
+        .build().unwrap();
+    let expected = std::fs::read_to_string(common::fixture(
+        "builder/split-multiarch.SRCINFO")).unwrap();
+    assert_eq!(pkgbuild.srcinfo().to_string(), expected);
+}
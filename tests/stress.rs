@@ -0,0 +1,114 @@
+//! Stress tests, the slow ones are ignored by default, run them with
+//! `cargo test --test stress -- --ignored`
+
+mod common;
+
+use std::{fmt::Write, time::{Duration, Instant}};
+
+use nix::sys::resource::{getrusage, UsageWho};
+
+fn cpu_time() -> Duration {
+    let usage = getrusage(UsageWho::RUSAGE_SELF).unwrap();
+    let user = usage.user_time();
+    let system = usage.system_time();
+    Duration::new((user.tv_sec() + system.tv_sec()) as u64, 
+        ((user.tv_usec() + system.tv_usec()) * 1000) as u32)
+}
+
+/// Feed 50k paths, far more than a pipe buffer holds, while the child keeps
+/// writing its output, the parent should neither deadlock with the child, 
+/// nor spin while waiting on the pipes
+#[test]
+#[ignore]
+fn parse_50k_paths() {
+    const COUNT: usize = 50_000;
+    let dir = tempfile::tempdir().unwrap();
+    // A long path, so the input is several MiBs
+    let path = common::write_pkgbuild(dir.path(), &"long-name-".repeat(20),
+        &common::minimal("stress"));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let wall = Instant::now();
+    let cpu = cpu_time();
+    std::thread::spawn(move|| {
+        let mut parser = common::parser();
+        parser.options.set_dedup_paths(false);
+        let result = parser.parse_multi(std::iter::repeat_n(&path, COUNT));
+        sender.send((result, cpu_time())).unwrap();
+    });
+    let (result, cpu_end) = receiver.recv_timeout(Duration::from_secs(1800))
+        .expect("Parsing did not finish, possibly deadlocked");
+    let wall = wall.elapsed();
+    let pkgbuilds = result.unwrap();
+    assert_eq!(pkgbuilds.len(), COUNT);
+    assert!(pkgbuilds.iter().all(|pkgbuild|pkgbuild.pkgbase == "stress"));
+    // The children's time is not counted in RUSAGE_SELF, and the parent 
+    // mostly waits for them, a busy wait would burn as much as the wall time
+    let cpu = cpu_end - cpu;
+    assert!(cpu < wall / 2, "Parent used {cpu:?} CPU in {wall:?}");
+}
+
+/// A PKGBUILD declaring `count` architectures, each with its own depends
+fn many_arches(count: usize) -> String {
+    let mut content = format!("pkgname=arches-{count}\npkgver=1\npkgrel=1\n\
+        arch=(");
+    for arch in 0..count {
+        write!(content, " a{arch}").unwrap();
+    }
+    content.push_str(")\n");
+    for arch in 0..count {
+        writeln!(content, "depends_a{arch}=(dep-a{arch})").unwrap();
+    }
+    content.push_str("package() { :; }\n");
+    content
+}
+
+/// Count the lines starting with `prefix` after the indentation
+fn count_lines(text: &str, prefix: &str) -> usize {
+    text.lines().filter(|line|line.trim_start().starts_with(prefix)).count()
+}
+
+/// Up to 500 architectures, the output grows linearly without the cap, and
+/// stays the same size with it
+#[test]
+fn render_500_arches() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<_> = [100, 300, 500].into_iter().map(|count| 
+        common::write_pkgbuild(dir.path(), &count.to_string(), 
+            &many_arches(count)))
+        .collect();
+    let pkgbuilds = common::parser().parse_multi(&paths).unwrap();
+    let mut srcinfo_lines = Vec::new();
+    let mut display_lines = Vec::new();
+    let mut capped_lines = Vec::new();
+    for (pkgbuild, count) in pkgbuilds.iter().zip([100, 300, 500]) {
+        assert_eq!(pkgbuild.multiarch.arches.len(), count);
+        let srcinfo = pkgbuild.srcinfo().to_string();
+        assert_eq!(count_lines(&srcinfo, "depends_a"), count);
+        srcinfo_lines.push(srcinfo.lines().count());
+        let display = format!("{:#}", pkgbuild);
+        assert_eq!(count_lines(&display, "depends_a"), count * 2);
+        display_lines.push(display.lines().count());
+
+        let mut capped = pkgbuild.srcinfo();
+        capped.set_max_arches(Some(8));
+        let capped = capped.to_string();
+        assert_eq!(count_lines(&capped, "depends_a"), 8);
+        // After the arch lines, the sections of the base and the package
+        assert_eq!(count_lines(&capped, &format!("# {} more architectures \
+            truncated", count - 8)), 3);
+        capped_lines.push(capped.lines().count());
+        let capped = format!("{:#.8}", pkgbuild);
+        assert_eq!(count_lines(&capped, "depends_a"), 16);
+        assert_eq!(count_lines(&capped, &format!("# {} more architectures \
+            truncated", count - 8)), 2);
+        capped_lines.push(capped.lines().count());
+    }
+    // The same growth from 100 to 300 arches as from 300 to 500
+    assert_eq!(srcinfo_lines[2] - srcinfo_lines[1], 
+        srcinfo_lines[1] - srcinfo_lines[0]);
+    assert_eq!(display_lines[2] - display_lines[1], 
+        display_lines[1] - display_lines[0]);
+    // While the capped ones, srcinfo and display in turn, do not grow at all
+    assert_eq!(capped_lines[0..2], capped_lines[2..4]);
+    assert_eq!(capped_lines[0..2], capped_lines[4..6]);
+}
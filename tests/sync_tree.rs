@@ -0,0 +1,96 @@
+mod common;
+
+use std::path::Path;
+
+use pkgbuild::{sync_srcinfo_tree, SrcinfoOptions, SrcinfoSyncStatus};
+
+/// Statuses of the report, by the dirs of the PKGBUILDs relative to `root`
+fn statuses(root: &Path, options: &SrcinfoOptions) -> Vec<(String, String)> {
+    let report = sync_srcinfo_tree(&common::parser(), root, options).unwrap();
+    assert!(report.walk_errors.is_empty(), "{:?}", report.walk_errors);
+    report.entries.iter().map(|entry| (
+        entry.path.parent().unwrap().strip_prefix(root).unwrap()
+            .display().to_string(),
+        match &entry.status {
+            SrcinfoSyncStatus::Failed(_) => "failed".into(),
+            status => status.to_string(),
+        }
+    )).collect()
+}
+
+fn expected(statuses: &[(&str, &str)]) -> Vec<(String, String)> {
+    statuses.iter().map(|(dir, status)|(dir.to_string(), status.to_string()))
+        .collect()
+}
+
+#[test]
+fn sync_tree_reports_each_pkgbuild() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("tree");
+    common::copy_tree(&common::fixture("tree"), &root);
+    let options = SrcinfoOptions::default();
+    assert_eq!(statuses(&root, &options), expected(&[
+        ("created", "created"),
+        ("group/broken", "failed"),
+        ("unchanged", "unchanged"),
+        ("updated", "updated"),
+    ]));
+    assert!(root.join("created/.SRCINFO").exists());
+    assert!(! root.join("group/broken/.SRCINFO").exists());
+    assert!(! root.join(".hidden/.SRCINFO").exists());
+    assert_eq!(std::fs::read(root.join("unchanged/.SRCINFO")).unwrap(),
+        std::fs::read(common::fixture("tree/unchanged/.SRCINFO")).unwrap());
+    // Everything written is now up to date
+    assert_eq!(statuses(&root, &options), expected(&[
+        ("created", "unchanged"),
+        ("group/broken", "failed"),
+        ("unchanged", "unchanged"),
+        ("updated", "unchanged"),
+    ]));
+}
+
+#[test]
+fn sync_tree_dry_run_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("tree");
+    common::copy_tree(&common::fixture("tree"), &root);
+    let options = SrcinfoOptions { 
+        dry_run: true, skip_hidden: false, ..Default::default() 
+    };
+    assert_eq!(statuses(&root, &options), expected(&[
+        (".hidden", "created"),
+        ("created", "created"),
+        ("group/broken", "failed"),
+        ("unchanged", "unchanged"),
+        ("updated", "updated"),
+    ]));
+    assert!(! root.join("created/.SRCINFO").exists());
+    assert_eq!(std::fs::read(root.join("updated/.SRCINFO")).unwrap(),
+        std::fs::read(common::fixture("tree/updated/.SRCINFO")).unwrap());
+}
+
+/// An unreadable subtree is skipped and recorded, instead of failing the 
+/// whole walk, root could read anything so this is skipped for it
+#[test]
+fn sync_tree_skips_unreadable_subtree() {
+    use std::os::unix::fs::PermissionsExt;
+
+    if nix::unistd::geteuid().is_root() {
+        eprintln!("Running as root, skipped");
+        return
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("tree");
+    common::copy_tree(&common::fixture("tree"), &root);
+    let locked = root.join("group");
+    std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000))
+        .unwrap();
+    let report = sync_srcinfo_tree(&common::parser(), &root, 
+        &SrcinfoOptions::default());
+    std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755))
+        .unwrap();
+    let report = report.unwrap();
+    assert_eq!(report.entries.len(), 3);
+    assert_eq!(report.walk_errors.len(), 1);
+    assert_eq!(report.walk_errors[0].0, locked);
+}
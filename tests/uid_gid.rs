@@ -0,0 +1,36 @@
+//! Dropping privileges for the parser with `ParserOptions::set_uid_gid()`,
+//! which needs root, so this is skipped when not running as root
+
+mod common;
+
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+/// The ids of `nobody` on most distros, not owning anything of the test
+const UID: u32 = 65534;
+const GID: u32 = 65534;
+
+#[test]
+fn files_created_by_pkgbuild_owned_by_target_uid() {
+    if ! nix::unistd::geteuid().is_root() {
+        eprintln!("Not running as root, skipped");
+        return
+    }
+    let dir = tempfile::tempdir().unwrap();
+    // Traversable and writable by the target user
+    std::fs::set_permissions(dir.path(), PermissionsExt::from_mode(0o777))
+        .unwrap();
+    let created = dir.path().join("created");
+    let path = common::write_pkgbuild(dir.path(), "uid-gid", &format!(
+        "{}: > '{}'\n", common::minimal("uid-gid"), created.display()));
+    let mut parser = common::parser();
+    parser.options.set_uid_gid(Some((UID, GID)));
+    let pkgbuilds = parser.parse_multi([&path]).unwrap();
+    assert_eq!(pkgbuilds.len(), 1);
+    let metadata = created.metadata()
+        .expect("The PKGBUILD should have created the file");
+    assert_eq!((metadata.uid(), metadata.gid()), (UID, GID));
+    // Without the option the file would be created as root
+    std::fs::remove_file(&created).unwrap();
+    common::parser().parse_multi([&path]).unwrap();
+    assert_eq!(created.metadata().unwrap().uid(), 0);
+}